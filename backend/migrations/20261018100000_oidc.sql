@@ -0,0 +1,51 @@
+-- Users that log in only through an identity provider don't have a password.
+-- SQLite can't drop NOT NULL constraint, so the table has to be rebuilt.
+-- Migrations run inside a transaction where foreign keys can't be disabled,
+-- so sessions are rebuilt as well to prevent cascade delete when dropping users.
+CREATE TABLE users_new (
+    id            INTEGER PRIMARY KEY NOT NULL,
+    username      TEXT NOT NULL UNIQUE,
+    password_hash TEXT,
+    created_at    TEXT NOT NULL,
+    updated_at    TEXT NOT NULL
+) STRICT;
+
+INSERT INTO users_new (id, username, password_hash, created_at, updated_at)
+SELECT id, username, password_hash, created_at, updated_at FROM users;
+
+CREATE TABLE user_sessions_new (
+    user_id      INTEGER NOT NULL REFERENCES users_new(id) ON DELETE CASCADE,
+    session_hash TEXT NOT NULL PRIMARY KEY,
+    expires_at   TEXT NOT NULL,
+    created_at   TEXT NOT NULL
+) STRICT;
+
+INSERT INTO user_sessions_new (user_id, session_hash, expires_at, created_at)
+SELECT user_id, session_hash, expires_at, created_at FROM user_sessions;
+
+DROP TABLE user_sessions;
+DROP TABLE users;
+
+-- Renaming also updates the reference in user_sessions_new.
+ALTER TABLE users_new RENAME TO users;
+ALTER TABLE user_sessions_new RENAME TO user_sessions;
+
+CREATE TABLE user_identities (
+    issuer     TEXT NOT NULL,
+    subject    TEXT NOT NULL,
+    user_id    INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+
+    PRIMARY KEY (issuer, subject)
+) STRICT;
+
+CREATE INDEX user_identities_user_id_idx ON user_identities(user_id);
+
+CREATE TABLE oidc_auth_requests (
+    state_hash    TEXT NOT NULL PRIMARY KEY,
+    code_verifier TEXT NOT NULL,
+    nonce         TEXT NOT NULL,
+    redirect_to   TEXT NOT NULL,
+    expires_at    TEXT NOT NULL,
+    created_at    TEXT NOT NULL
+) STRICT;
@@ -0,0 +1,22 @@
+CREATE TABLE webauthn_credentials (
+    id            INTEGER PRIMARY KEY NOT NULL,
+    user_id       INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    credential_id TEXT NOT NULL UNIQUE,
+    name          TEXT NOT NULL,
+    passkey       TEXT NOT NULL,
+    last_used_at  TEXT,
+    created_at    TEXT NOT NULL,
+    updated_at    TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX webauthn_credentials_user_id_idx ON webauthn_credentials(user_id);
+
+-- State of started registration and authentication ceremonies.
+CREATE TABLE webauthn_challenges (
+    challenge_hash TEXT NOT NULL PRIMARY KEY,
+    user_id        INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind           TEXT NOT NULL,
+    state          TEXT NOT NULL,
+    expires_at     TEXT NOT NULL,
+    created_at     TEXT NOT NULL
+) STRICT;
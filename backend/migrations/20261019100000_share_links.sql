@@ -0,0 +1,11 @@
+CREATE TABLE share_links (
+    id         INTEGER PRIMARY KEY NOT NULL,
+    store_id   INTEGER NOT NULL REFERENCES stores(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    permission TEXT NOT NULL,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX share_links_store_id_idx ON share_links(store_id);
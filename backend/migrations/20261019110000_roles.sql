@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'member';
+
+-- Existing users could do everything before roles were introduced.
+UPDATE users SET role = 'admin';
@@ -0,0 +1,32 @@
+-- Detach items whose section belongs to another store, so that the triggers
+-- below hold for all existing rows.
+UPDATE items
+SET section_id = NULL
+WHERE section_id IS NOT NULL
+  AND store_id IS NOT (SELECT store_id FROM sections WHERE sections.id = items.section_id);
+
+-- Section of an item must always belong to the item's store.
+CREATE TRIGGER items_section_store_insert
+BEFORE INSERT ON items
+WHEN NEW.section_id IS NOT NULL
+ AND NEW.store_id IS NOT (SELECT store_id FROM sections WHERE sections.id = NEW.section_id)
+BEGIN
+    SELECT RAISE(ABORT, 'section_store_mismatch');
+END;
+
+CREATE TRIGGER items_section_store_update
+BEFORE UPDATE OF store_id, section_id ON items
+WHEN NEW.section_id IS NOT NULL
+ AND NEW.store_id IS NOT (SELECT store_id FROM sections WHERE sections.id = NEW.section_id)
+BEGIN
+    SELECT RAISE(ABORT, 'section_store_mismatch');
+END;
+
+-- Sections can't be moved to another store while they contain items.
+CREATE TRIGGER sections_store_update
+BEFORE UPDATE OF store_id ON sections
+WHEN NEW.store_id IS NOT OLD.store_id
+ AND EXISTS (SELECT 1 FROM items WHERE items.section_id = OLD.id)
+BEGIN
+    SELECT RAISE(ABORT, 'section_store_mismatch');
+END;
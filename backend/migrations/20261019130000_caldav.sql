@@ -0,0 +1,19 @@
+-- Tokens for clients that can't log in interactively, ie. CalDAV apps.
+CREATE TABLE app_tokens (
+    id           INTEGER PRIMARY KEY NOT NULL,
+    user_id      INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name         TEXT NOT NULL,
+    token_hash   TEXT NOT NULL UNIQUE,
+    last_used_at TEXT,
+    created_at   TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX app_tokens_user_id_idx ON app_tokens(user_id);
+
+-- Resource names and UIDs chosen by CalDAV clients for the items they created.
+-- Other items are served as `lshop-{id}.ics`.
+CREATE TABLE caldav_objects (
+    item_id INTEGER PRIMARY KEY NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+    name    TEXT NOT NULL UNIQUE,
+    uid     TEXT NOT NULL UNIQUE
+) STRICT;
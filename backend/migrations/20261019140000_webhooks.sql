@@ -0,0 +1,28 @@
+CREATE TABLE webhooks (
+    id         INTEGER PRIMARY KEY NOT NULL,
+    url        TEXT NOT NULL,
+    -- Json array of event names, empty array subscribes to all events.
+    events     TEXT NOT NULL,
+    secret     TEXT NOT NULL,
+    enabled    INTEGER NOT NULL DEFAULT TRUE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+) STRICT;
+
+CREATE TABLE webhook_deliveries (
+    id               INTEGER PRIMARY KEY NOT NULL,
+    webhook_id       INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event            TEXT NOT NULL,
+    payload          TEXT NOT NULL,
+    status           TEXT NOT NULL,
+    attempts         INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at  TEXT NOT NULL,
+    last_status_code INTEGER,
+    last_error       TEXT,
+    created_at       TEXT NOT NULL,
+    delivered_at     TEXT
+) STRICT;
+
+CREATE INDEX webhook_deliveries_webhook_id_idx ON webhook_deliveries(webhook_id, id);
+CREATE INDEX webhook_deliveries_pending_idx ON webhook_deliveries(next_attempt_at)
+    WHERE status = 'pending';
@@ -0,0 +1,19 @@
+CREATE TABLE push_subscriptions (
+    id         INTEGER PRIMARY KEY NOT NULL,
+    user_id    INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    endpoint   TEXT NOT NULL UNIQUE,
+    p256dh     TEXT NOT NULL,
+    auth       TEXT NOT NULL,
+    created_at TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX push_subscriptions_user_id_idx ON push_subscriptions(user_id);
+
+-- Users without a row get the defaults.
+CREATE TABLE notification_preferences (
+    user_id      INTEGER PRIMARY KEY NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    item_created INTEGER NOT NULL DEFAULT TRUE,
+    -- Json array of store ids, empty array means all stores.
+    store_ids    TEXT NOT NULL DEFAULT '[]',
+    updated_at   TEXT NOT NULL
+) STRICT;
@@ -0,0 +1,26 @@
+CREATE TABLE notification_schedules (
+    id           INTEGER PRIMARY KEY NOT NULL,
+    user_id      INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- `list` sends the unchecked items, `digest` the items added since the last run.
+    kind         TEXT NOT NULL,
+    -- Only items of the store, all items if it's NULL.
+    store_id     INTEGER REFERENCES stores(id) ON DELETE CASCADE,
+    transport    TEXT NOT NULL,
+    -- Email address or ntfy topic.
+    target       TEXT NOT NULL,
+    -- Five field cron expression, evaluated in the local time of `timezone`.
+    cron         TEXT NOT NULL,
+    -- IANA time zone name, ie. `Europe/Ljubljana`.
+    timezone     TEXT NOT NULL DEFAULT 'UTC',
+    enabled      INTEGER NOT NULL DEFAULT TRUE,
+    next_run_at  TEXT NOT NULL,
+    -- Last run that succeeded, or found nothing to send.
+    last_sent_at TEXT,
+    last_error   TEXT,
+    created_at   TEXT NOT NULL,
+    updated_at   TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX notification_schedules_user_id_idx ON notification_schedules(user_id);
+CREATE INDEX notification_schedules_due_idx ON notification_schedules(next_run_at)
+    WHERE enabled = TRUE;
@@ -1,11 +1,17 @@
+use std::path::Path;
+
 use argon2::Argon2;
-use dialoguer::{Input, Password};
+use dialoguer::{Confirm, Input, Password};
 use password_hash::rand_core::OsRng;
 use password_hash::{PasswordHasher, SaltString};
 
-use crate::{state::AppState, store};
+use crate::config::Config;
+use crate::handler::validate::Validate;
+use crate::store::dataset::{Dataset, ImportMode};
+use crate::store::user::Role;
+use crate::{backup, db, state::AppState, store};
 
-pub async fn create_user(state: AppState) -> anyhow::Result<()> {
+pub async fn create_user(state: AppState, role: Role) -> anyhow::Result<()> {
     let username: String = Input::new().with_prompt("Username").interact()?;
 
     let password = Password::new()
@@ -17,7 +23,7 @@ pub async fn create_user(state: AppState) -> anyhow::Result<()> {
     let argon2 = Argon2::default();
     let pass_hash = argon2.hash_password(password.as_bytes(), &salt)?;
 
-    let res = store::user::create_user(&state.db, &username, &pass_hash).await;
+    let res = store::user::create_user(&state.db, &username, Some(&pass_hash), role).await;
     match res {
         Ok(_) => (),
         Err(sqlx::Error::Database(db)) => {
@@ -33,3 +39,96 @@ pub async fn create_user(state: AppState) -> anyhow::Result<()> {
     println!("User '{username}' created successfully");
     Ok(())
 }
+
+pub async fn set_role(state: AppState, username: &str, role: Role) -> anyhow::Result<()> {
+    let updated = store::user::set_role(&state.db, username, role).await?;
+    if !updated {
+        anyhow::bail!("User with username '{username}' doesn't exist");
+    }
+
+    println!("User '{username}' now has role '{role}'");
+    Ok(())
+}
+
+pub async fn backup(state: AppState, output: &Path, gzip: bool) -> anyhow::Result<()> {
+    backup::backup(&state.db, output, gzip).await?;
+    println!("Database backed up to '{}'", output.display());
+    Ok(())
+}
+
+pub async fn restore(conf: &Config, input: &Path, yes: bool) -> anyhow::Result<()> {
+    let target = db::file_path(&conf.db_path)?;
+    if !yes {
+        let confirmed = Confirm::new()
+            .with_prompt(format!(
+                "Replace '{}' with '{}'? The server must be stopped",
+                target.display(),
+                input.display()
+            ))
+            .default(false)
+            .interact()?;
+        if !confirmed {
+            anyhow::bail!("Restore cancelled");
+        }
+    }
+
+    backup::restore(&conf.db_path, input).await?;
+    println!("Database restored from '{}'", input.display());
+    Ok(())
+}
+
+pub async fn export(
+    state: AppState,
+    output: Option<&Path>,
+    password_hashes: bool,
+) -> anyhow::Result<()> {
+    let dataset = store::dataset::export(&state.db, password_hashes).await?;
+    let json = serde_json::to_string_pretty(&dataset)?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, json)?;
+            println!("Data exported to '{}'", path.display());
+        }
+        None => println!("{json}"),
+    }
+    Ok(())
+}
+
+pub async fn import(
+    state: AppState,
+    input: &Path,
+    replace: bool,
+    dry_run: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let mut dataset: Dataset = serde_json::from_slice(&std::fs::read(input)?)?;
+    if let Err(errors) = dataset.validate() {
+        for err in &errors {
+            eprintln!("{}: {}", err.field, err.message);
+        }
+        anyhow::bail!("'{}' is not a valid export", input.display());
+    }
+
+    let mode = if replace {
+        ImportMode::Replace
+    } else {
+        ImportMode::Merge
+    };
+    if mode == ImportMode::Replace && !dry_run && !yes {
+        let confirmed = Confirm::new()
+            .with_prompt("Delete all stores, sections and items before the import?")
+            .default(false)
+            .interact()?;
+        if !confirmed {
+            anyhow::bail!("Import cancelled");
+        }
+    }
+
+    let summary = store::dataset::import(&state.db, &dataset, mode, dry_run).await?;
+    if dry_run {
+        println!("Dry run, nothing was changed");
+    }
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Versions of the HTTP api.
+//!
+//! Every version is nested under its own path, ie. `/api/v1`. A new version gets its own
+//! router in `app`, handlers that didn't change are shared between versions. Old versions
+//! are marked as deprecated here, responses of deprecated versions then carry
+//! `Deprecation`, `Sunset` and `Link` headers.
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, header};
+use axum::middleware::Next;
+use axum::response::Response;
+use time::OffsetDateTime;
+use time::macros::{datetime, format_description};
+
+pub const DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+pub const SUNSET_HEADER: HeaderName = HeaderName::from_static("sunset");
+
+pub struct Deprecation {
+    /// When the version was deprecated.
+    pub since: OffsetDateTime,
+    /// When the version will stop responding.
+    pub sunset: Option<OffsetDateTime>,
+    /// Path of the version that replaces it.
+    pub successor: &'static str,
+}
+
+pub struct ApiVersion {
+    pub name: &'static str,
+    pub path: &'static str,
+    pub deprecation: Option<Deprecation>,
+}
+
+/// Version used by the current frontend.
+pub const CURRENT: &str = "v1";
+
+/// All versions served, newest first.
+pub const VERSIONS: &[ApiVersion] = &[V1, LEGACY];
+
+pub const V1: ApiVersion = ApiVersion {
+    name: "v1",
+    path: "/api/v1",
+    deprecation: None,
+};
+
+/// Unversioned routes, kept for frontend builds that are still cached on clients.
+/// Same handlers as `v1`.
+pub const LEGACY: ApiVersion = ApiVersion {
+    name: "legacy",
+    path: "/api",
+    deprecation: Some(Deprecation {
+        since: datetime!(2026-10-19 0:00 UTC),
+        sunset: Some(datetime!(2027-04-19 0:00 UTC)),
+        successor: V1.path,
+    }),
+};
+
+/// Middleware that adds deprecation headers to responses of a deprecated version.
+pub async fn deprecation_headers(
+    State(version): State<&'static ApiVersion>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut res = next.run(req).await;
+    let Some(deprecation) = &version.deprecation else {
+        return res;
+    };
+    let headers = res.headers_mut();
+
+    // RFC 9745, structured field date.
+    let since = format!("@{}", deprecation.since.unix_timestamp());
+    headers.insert(DEPRECATION_HEADER, HeaderValue::from_str(&since).unwrap());
+
+    // RFC 8594, HTTP date.
+    if let Some(sunset) = deprecation.sunset {
+        headers.insert(
+            SUNSET_HEADER,
+            HeaderValue::from_str(&http_date(sunset)).unwrap(),
+        );
+    }
+
+    let link = format!("<{}>; rel=\"successor-version\"", deprecation.successor);
+    headers.append(header::LINK, HeaderValue::from_str(&link).unwrap());
+
+    res
+}
+
+fn http_date(date: OffsetDateTime) -> String {
+    let format = format_description!(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+    );
+    date.to_offset(time::UtcOffset::UTC)
+        .format(&format)
+        .expect("date should be formattable")
+}
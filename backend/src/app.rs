@@ -1,67 +1,295 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
 use axum::Router;
-use axum::routing::{get, post, put};
+use axum::extract::{DefaultBodyLimit, Request};
+use axum::middleware;
+use axum::routing::{any, delete, get, post, put};
 use tokio::net::TcpListener;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::trace::TraceLayer;
+use utoipa::OpenApi;
+use utoipa_scalar::{Scalar, Servable};
 
-use crate::handler::{auth, item, organize, section, store};
+use crate::api_version::{self, LEGACY};
+use crate::backup;
+use crate::caldav;
+use crate::frontend;
+use crate::handler::{
+    app_token, auth, dataset, health, item, layout, notification, oidc, organize, print, push,
+    section, share, store, text, version, voice, webauthn, webhook,
+};
+use crate::metrics;
+use crate::notify;
+use crate::openapi::{self, ApiDoc};
+use crate::request_id::{self, REQUEST_ID_HEADER};
+use crate::shutdown::Shutdown;
 use crate::state::AppState;
+use crate::tls;
+use crate::webhook as webhook_delivery;
 
-fn create_app(state: AppState) -> Router {
-    Router::new()
+pub(crate) fn create_app(state: AppState) -> Router {
+    let mut router = Router::new();
+
+    // Metrics on a separate address are served by `metrics_app`.
+    if state
+        .config
+        .metrics
+        .as_ref()
+        .is_some_and(|conf| conf.address.is_none())
+    {
+        router = router.route("/metrics", get(metrics::serve));
+    }
+
+    // Interactive api documentation is served only in development.
+    if state.config.environment.is_dev() {
+        router = router.merge(Scalar::with_url("/api/docs", ApiDoc::openapi()));
+    }
+
+    router
         .nest(
             "/api",
             Router::new()
-                // Auth
-                .nest(
-                    "/auth",
-                    Router::new()
-                        .route("/login", post(auth::login))
-                        .route("/logout", post(auth::logout))
-                        .route("/me", get(auth::me)),
-                )
-                // Stores
-                .nest(
-                    "/stores",
-                    Router::new()
-                        .route("/", post(store::create).get(store::list))
-                        .route("/{store_id}", put(store::update).delete(store::delete)),
-                )
-                // Sections
-                .nest(
-                    "/stores/{store_id}/sections",
-                    Router::new()
-                        .route("/", get(section::list).post(section::create))
-                        .route("/reorder", put(section::reorder)),
-                )
-                .nest(
-                    "/sections",
-                    Router::new().route("/{id}", put(section::update).delete(section::delete)),
-                )
-                // Items
+                .route("/versions", get(version::versions))
+                .route("/health", get(health::health))
+                .route("/ready", get(health::ready))
+                .nest("/v1", api_v1())
+                // Unversioned routes of old frontend builds.
+                .merge(api_v1().layer(middleware::from_fn_with_state(
+                    &LEGACY,
+                    api_version::deprecation_headers,
+                ))),
+        )
+        // CalDAV for reminder apps, outside of the api because clients expect it there.
+        .route("/.well-known/caldav", any(caldav::well_known))
+        .route("/dav", any(caldav::serve))
+        .route("/dav/", any(caldav::serve))
+        .route("/dav/{*path}", any(caldav::serve))
+        .fallback(frontend::serve)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track,
+        ))
+        // Layers are applied from bottom to top
+        .layer(middleware::from_fn(request_id::scope))
+        .layer(TraceLayer::new_for_http().make_span_with(|req: &Request| {
+            let request_id = req
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|id| id.to_str().ok())
+                .unwrap_or_default();
+
+            tracing::debug_span!(
+                "request",
+                method = %req.method(),
+                uri = %req.uri(),
+                version = ?req.version(),
+                request_id,
+            )
+        }))
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid))
+        .with_state(state)
+}
+
+/// Routes of api version 1.
+fn api_v1() -> Router<AppState> {
+    Router::new()
+        .route("/openapi.json", get(openapi::openapi_json))
+        // Auth
+        .nest(
+            "/auth",
+            Router::new()
+                .route("/login", post(auth::login))
+                .route("/logout", post(auth::logout))
+                .route("/me", get(auth::me))
+                .route("/oidc/login", get(oidc::login))
+                .route("/oidc/callback", get(oidc::callback))
+                .route("/tokens", get(app_token::list).post(app_token::create))
+                .route("/tokens/{id}", delete(app_token::delete))
                 .nest(
-                    "/items",
+                    "/webauthn",
                     Router::new()
-                        .route("/", get(item::list).post(item::create))
-                        .route("/{item_id}/rename", put(item::rename))
-                        .route("/{item_id}/checked", put(item::set_checked))
-                        .route("/{item_id}/move", put(item::move_item)),
+                        .route("/register/start", post(webauthn::register_start))
+                        .route("/register/finish", post(webauthn::register_finish))
+                        .route("/login/start", post(webauthn::login_start))
+                        .route("/credentials", get(webauthn::list_credentials))
+                        .route("/credentials/{id}", delete(webauthn::delete_credential)),
+                ),
+        )
+        // Stores
+        .nest(
+            "/stores",
+            Router::new()
+                .route("/", post(store::create).get(store::list))
+                .route("/import", post(layout::import))
+                .route("/{store_id}", put(store::update).delete(store::delete))
+                .route("/{store_id}/layout", get(layout::export).put(layout::apply))
+                .route("/{store_id}/print", get(print::print)),
+        )
+        // Sections
+        .nest(
+            "/stores/{store_id}/sections",
+            Router::new()
+                .route("/", get(section::list).post(section::create))
+                .route("/reorder", put(section::reorder)),
+        )
+        .nest(
+            "/sections",
+            Router::new().route("/{id}", put(section::update).delete(section::delete)),
+        )
+        // Items
+        .nest(
+            "/items",
+            Router::new()
+                .route("/", get(item::list).post(item::create))
+                .route("/text", get(text::export).post(text::import))
+                .route("/voice", post(voice::add))
+                .route("/{item_id}/rename", put(item::rename))
+                .route("/{item_id}/checked", put(item::set_checked))
+                .route("/{item_id}/move", put(item::move_item)),
+        )
+        // Organize
+        .route("/stores/{store_id}/organize", post(organize::organize))
+        // Share links
+        .route(
+            "/stores/{store_id}/shares",
+            get(share::list).post(share::create),
+        )
+        .route("/shares/{id}", delete(share::delete))
+        // Webhooks
+        .nest(
+            "/webhooks",
+            Router::new()
+                .route("/", get(webhook::list).post(webhook::create))
+                .route("/{id}", put(webhook::update).delete(webhook::delete))
+                .route("/{id}/deliveries", get(webhook::deliveries)),
+        )
+        // Scheduled notifications
+        .nest(
+            "/notifications/schedules",
+            Router::new()
+                .route("/", get(notification::list).post(notification::create))
+                .route(
+                    "/{id}",
+                    put(notification::update).delete(notification::delete),
                 )
-                // Organize
-                .route("/stores/{store_id}/organize", post(organize::organize)),
+                .route("/{id}/send", post(notification::send)),
+        )
+        // Push notifications
+        .nest(
+            "/push",
+            Router::new()
+                .route("/key", get(push::key))
+                .route("/subscriptions", get(push::list).post(push::subscribe))
+                .route("/subscriptions/{id}", delete(push::unsubscribe))
+                .route(
+                    "/preferences",
+                    get(push::preferences).put(push::set_preferences),
+                ),
+        )
+        // Export and import
+        .route("/export", get(dataset::export))
+        .route(
+            "/import",
+            post(dataset::import).layer(DefaultBodyLimit::max(dataset::MAX_IMPORT_SIZE)),
         )
-        .layer(TraceLayer::new_for_http())
+        .nest(
+            "/share/{token}",
+            Router::new()
+                .route("/", get(share::guest_list))
+                .route("/items/{item_id}/checked", put(share::guest_set_checked)),
+        )
+}
+
+fn metrics_app(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics::serve))
         .with_state(state)
 }
 
-pub async fn start_server(state: AppState) -> anyhow::Result<()> {
+pub async fn start_server(mut state: AppState) -> anyhow::Result<()> {
+    state.load_frontend()?;
     let app = create_app(state.clone());
-    let listener = TcpListener::bind((state.config.address.as_str(), state.config.port)).await?;
-    tracing::info!(
-        "listening on {}:{}",
-        state.config.address,
-        state.config.port
-    );
-    axum::serve(listener, app).await?;
+    let addr = (state.config.address.as_str(), state.config.port);
+    let shutdown = Shutdown::listen();
+    let drain_timeout = Duration::from_secs(state.config.shutdown_timeout);
+
+    if let Some(backup_conf) = &state.config.backup {
+        tokio::spawn(backup::schedule(
+            state.db.clone(),
+            backup_conf.clone(),
+            shutdown.clone(),
+        ));
+    }
+
+    tokio::spawn(webhook_delivery::run(state.db.clone(), shutdown.clone()));
+
+    if state.notifier.is_configured() {
+        tokio::spawn(notify::run(
+            state.db.clone(),
+            state.notifier.clone(),
+            shutdown.clone(),
+        ));
+    }
+
+    if let Some(metrics_addr) = state
+        .config
+        .metrics
+        .as_ref()
+        .and_then(|m| m.address.as_ref())
+    {
+        let listener = TcpListener::bind(metrics_addr).await?;
+        tracing::info!("serving metrics on {metrics_addr}");
+        let server = axum::serve(listener, metrics_app(state.clone()))
+            .with_graceful_shutdown(shutdown.clone().requested());
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                tracing::error!(error = err.to_string(), "metrics server error: {err}");
+            }
+        });
+    }
+
+    if let Some(tls_conf) = &state.config.tls {
+        let listener = std::net::TcpListener::bind(addr)?;
+        tracing::info!(
+            "listening on https://{}:{}",
+            state.config.address,
+            state.config.port
+        );
+
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                shutdown.requested().await;
+                handle.graceful_shutdown(Some(drain_timeout));
+            }
+        });
+        tls::serve(listener, app, tls_conf, handle).await?;
+    } else {
+        let listener = TcpListener::bind(addr).await?;
+        tracing::info!(
+            "listening on {}:{}",
+            state.config.address,
+            state.config.port
+        );
+        // Peer address is needed to check whether proxy auth header can be trusted.
+        let server = axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown.clone().requested());
+
+        tokio::select! {
+            res = server => res?,
+            _ = shutdown.drain_timeout(drain_timeout) => {},
+        }
+    }
+
+    // Checkpoints the WAL, requests that were cut off are rolled back.
+    state.db.close().await;
+    tracing::info!("server stopped");
 
     Ok(())
 }
@@ -1,24 +1,33 @@
-use axum::extract::{FromRequestParts, OptionalFromRequestParts};
-use axum::http::StatusCode;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, FromRequestParts, OptionalFromRequestParts, Path};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, header};
 use axum::response::IntoResponse;
 use axum_extra::extract::CookieJar;
 use base64::prelude::*;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
 
 use crate::db::Db;
-use crate::handler::Problem;
+use crate::handler::{Problem, ProblemCode};
 use crate::state::AppState;
 use crate::store;
+use crate::store::user::Role;
 use crate::util::to_hex;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct User {
     pub id: i64,
     pub username: String,
+    pub role: Role,
 
+    /// Hash of the session cookie. Users authenticated by a proxy don't have a session.
     #[serde(skip_serializing)]
-    pub session_hash: String,
+    pub session_hash: Option<String>,
 
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: time::OffsetDateTime,
@@ -33,6 +42,7 @@ impl std::fmt::Debug for User {
         f.debug_struct("User")
             .field("id", &self.id)
             .field("username", &self.username)
+            .field("role", &self.role)
             .field("created_at", &self.created_at)
             .field("updated_at", &self.updated_at)
             .finish()
@@ -43,17 +53,32 @@ impl From<(store::user::User, String)> for User {
     fn from(value: (store::user::User, String)) -> Self {
         Self {
             id: value.0.id,
-            session_hash: value.1,
+            session_hash: Some(value.1),
             username: value.0.username,
+            role: value.0.role,
             created_at: value.0.created_at,
             updated_at: value.0.updated_at,
         }
     }
 }
 
+impl From<store::user::User> for User {
+    fn from(value: store::user::User) -> Self {
+        Self {
+            id: value.id,
+            session_hash: None,
+            username: value.username,
+            role: value.role,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
 pub enum AuthError {
     InvalidCredentials,
     MissingCredentials,
+    Forbidden,
     Internal,
 }
 
@@ -61,9 +86,8 @@ impl IntoResponse for AuthError {
     fn into_response(self) -> axum::response::Response {
         let problem = match self {
             AuthError::InvalidCredentials => Problem::invalid_credentials(),
-            AuthError::MissingCredentials => {
-                Problem::new(StatusCode::UNAUTHORIZED, "Missing credentials".to_string())
-            }
+            AuthError::MissingCredentials => Problem::from_code(ProblemCode::MissingCredentials),
+            AuthError::Forbidden => Problem::forbidden(),
             AuthError::Internal => Problem::internal(),
         };
 
@@ -71,13 +95,106 @@ impl IntoResponse for AuthError {
     }
 }
 
+/// Marker types for roles required by [`RequireRole`].
+pub mod role {
+    use crate::store::user::Role;
+
+    pub trait RoleMarker {
+        const ROLE: Role;
+    }
+
+    pub struct Admin;
+    pub struct Member;
+
+    impl RoleMarker for Admin {
+        const ROLE: Role = Role::Admin;
+    }
+
+    impl RoleMarker for Member {
+        const ROLE: Role = Role::Member;
+    }
+}
+
+/// Logged in user that has at least role `R`, ie. `RequireRole<role::Admin>`.
+/// Requests of users with lower role are rejected with 403.
+pub struct RequireRole<R>(pub User, pub PhantomData<R>);
+
+impl<R: role::RoleMarker> FromRequestParts<AppState> for RequireRole<R> {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = <User as FromRequestParts<_>>::from_request_parts(parts, state).await?;
+        if user.role < R::ROLE {
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(RequireRole(user, PhantomData))
+    }
+}
+
+/// Guest that accesses a store's list through a share link.
+/// The token is read from the `{token}` path parameter.
+#[derive(Debug)]
+pub struct Guest {
+    pub link_id: i64,
+    pub store_id: i64,
+    pub permission: store::share::Permission,
+}
+
+impl FromRequestParts<AppState> for Guest {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Ok(Path(params)) =
+            <Path<HashMap<String, String>> as FromRequestParts<_>>::from_request_parts(
+                parts, state,
+            )
+            .await
+        else {
+            return Err(AuthError::MissingCredentials);
+        };
+        let Some(token) = params.get("token") else {
+            return Err(AuthError::MissingCredentials);
+        };
+
+        let Ok(token_bytes) = BASE64_URL_SAFE_NO_PAD.decode(token) else {
+            return Err(AuthError::InvalidCredentials);
+        };
+        let token_hash = to_hex(&Sha256::digest(token_bytes)[..]);
+
+        let link = store::share::get_valid(&state.db, &token_hash).await;
+        match link {
+            Ok(Some(link)) => Ok(Guest {
+                link_id: link.id,
+                store_id: link.store_id,
+                permission: link.permission,
+            }),
+            Ok(None) => Err(AuthError::InvalidCredentials),
+            Err(err) => {
+                tracing::error!(error = err.to_string(), "database error: {err}");
+                Err(AuthError::Internal)
+            }
+        }
+    }
+}
+
 impl FromRequestParts<AppState> for User {
     type Rejection = AuthError;
 
     async fn from_request_parts(
-        parts: &mut axum::http::request::Parts,
+        parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
+        if let Some(user) = get_user_from_proxy(parts, state).await? {
+            return Ok(user);
+        }
+
         let jar = CookieJar::from_headers(&parts.headers);
         let Some(cookie) = jar.get("session") else {
             return Err(AuthError::MissingCredentials);
@@ -91,9 +208,13 @@ impl OptionalFromRequestParts<AppState> for User {
     type Rejection = AuthError;
 
     async fn from_request_parts(
-        parts: &mut axum::http::request::Parts,
+        parts: &mut Parts,
         state: &AppState,
     ) -> Result<Option<Self>, Self::Rejection> {
+        if let Some(user) = get_user_from_proxy(parts, state).await? {
+            return Ok(Some(user));
+        }
+
         let jar = CookieJar::from_headers(&parts.headers);
         let Some(cookie) = jar.get("session") else {
             return Ok(None);
@@ -120,3 +241,105 @@ async fn get_user_from_session(db: &Db, sess: &str) -> Result<User, AuthError> {
         }
     }
 }
+
+/// Returns the user of HTTP basic credentials with an app token as the password.
+/// Used by clients that can't log in interactively.
+pub async fn get_user_from_app_token(db: &Db, headers: &HeaderMap) -> Result<User, AuthError> {
+    let Some(encoded) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+    else {
+        return Err(AuthError::MissingCredentials);
+    };
+
+    let credentials = BASE64_STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok());
+    let Some((username, token)) = credentials.as_deref().and_then(|c| c.split_once(':')) else {
+        return Err(AuthError::InvalidCredentials);
+    };
+    let Ok(token_bytes) = BASE64_URL_SAFE_NO_PAD.decode(token) else {
+        return Err(AuthError::InvalidCredentials);
+    };
+    let token_hash = to_hex(&Sha256::digest(token_bytes)[..]);
+
+    let user = store::app_token::get_user(db, username, &token_hash).await;
+    match user {
+        Ok(Some(u)) => Ok(u.into()),
+        Ok(None) => Err(AuthError::InvalidCredentials),
+        Err(err) => {
+            tracing::error!(error = err.to_string(), "database error: {err}");
+            Err(AuthError::Internal)
+        }
+    }
+}
+
+/// Returns the user authenticated by a trusted reverse proxy.
+/// Returns `None` if proxy auth is disabled, the request doesn't come from
+/// a trusted proxy or the header is missing.
+async fn get_user_from_proxy(parts: &Parts, state: &AppState) -> Result<Option<User>, AuthError> {
+    let Some(conf) = &state.config.proxy_auth else {
+        return Ok(None);
+    };
+
+    let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() else {
+        return Ok(None);
+    };
+    if !conf.is_trusted(addr.ip()) {
+        return Ok(None);
+    }
+
+    let Some(header) = parts.headers.get(&conf.header) else {
+        return Ok(None);
+    };
+    let username = match header.to_str() {
+        Ok(username) if !username.trim().is_empty() => username.trim(),
+        _ => return Err(AuthError::InvalidCredentials),
+    };
+
+    let user = store::user::get_user(&state.db, username)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = err.to_string(), "database error: {err}");
+            AuthError::Internal
+        })?;
+
+    if let Some(user) = user {
+        return Ok(Some(user.into()));
+    }
+
+    if !conf.auto_create_users {
+        tracing::info!(username, "proxy authenticated unknown user");
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    let user = create_proxy_user(&state.db, username, conf.default_role)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = err.to_string(), "database error: {err}");
+            AuthError::Internal
+        })?;
+    tracing::info!(username, "created user authenticated by proxy");
+
+    Ok(Some(user.into()))
+}
+
+async fn create_proxy_user(
+    db: &Db,
+    username: &str,
+    role: Role,
+) -> Result<store::user::User, sqlx::Error> {
+    let res = store::user::create_user(db, username, None, role).await;
+    match res {
+        Ok(_) => (),
+        // Concurrent request already created the user.
+        Err(sqlx::Error::Database(err)) if err.code().as_ref().is_some_and(|c| c == "2067") => (),
+        Err(err) => return Err(err),
+    }
+
+    store::user::get_user(db, username)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)
+}
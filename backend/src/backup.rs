@@ -0,0 +1,222 @@
+//! Backups of the database, made with `VACUUM INTO`, so they are consistent even while
+//! the server is writing to it.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use sqlx::SqlitePool;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+use crate::config::BackupConfig;
+use crate::db::{self, Db};
+use crate::shutdown::Shutdown;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const SCHEDULED_PREFIX: &str = "lshop-";
+
+/// Writes a copy of the database to `output`, which must not exist yet.
+pub async fn backup(db: &Db, output: &Path, gzip: bool) -> anyhow::Result<()> {
+    if output.exists() {
+        anyhow::bail!("'{}' already exists", output.display());
+    }
+    verify_schema(db).await?;
+
+    let tmp = tmp_path(output);
+    remove_if_exists(&tmp)?;
+    sqlx::query("VACUUM INTO ?")
+        .bind(tmp.to_string_lossy())
+        .execute(db)
+        .await
+        .context("failed to copy the database")?;
+
+    let res = if gzip {
+        let (tmp, output) = (tmp.clone(), output.to_path_buf());
+        tokio::task::spawn_blocking(move || compress(&tmp, &output)).await?
+    } else {
+        std::fs::rename(&tmp, output)
+    };
+    remove_if_exists(&tmp)?;
+    res.with_context(|| format!("failed to write '{}'", output.display()))
+}
+
+/// Replaces the database at `db_url` with the backup. The server must not be running.
+/// Backups made by older versions are migrated, ones made by newer versions are rejected.
+pub async fn restore(db_url: &str, input: &Path) -> anyhow::Result<()> {
+    let target = db::file_path(db_url)?;
+    let tmp = tmp_path(&target);
+    remove_if_exists(&tmp)?;
+
+    let (src, dst) = (input.to_path_buf(), tmp.clone());
+    tokio::task::spawn_blocking(move || decompress(&src, &dst))
+        .await?
+        .with_context(|| format!("failed to read '{}'", input.display()))?;
+
+    if let Err(err) = prepare(&tmp).await {
+        remove_if_exists(&tmp)?;
+        return Err(err);
+    }
+
+    // Leftover journal of the replaced database would be applied to the backup.
+    for suffix in ["-wal", "-shm"] {
+        let mut journal = target.clone().into_os_string();
+        journal.push(suffix);
+        remove_if_exists(Path::new(&journal))?;
+    }
+    std::fs::rename(&tmp, &target)?;
+
+    Ok(())
+}
+
+/// Checks the restored copy and brings it to the current schema.
+async fn prepare(path: &Path) -> anyhow::Result<()> {
+    let opts = SqliteConnectOptions::new()
+        .filename(path)
+        .foreign_keys(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(opts)
+        .await?;
+
+    let res = async {
+        let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_one(&pool)
+            .await
+            .context("backup is not a database")?;
+        if integrity != "ok" {
+            anyhow::bail!("backup is corrupted: {integrity}");
+        }
+
+        verify_schema(&pool).await?;
+        db::migrate(&pool).await?;
+        Ok(())
+    }
+    .await;
+
+    pool.close().await;
+    res
+}
+
+/// Checks that all migrations applied to the database are known to this version.
+async fn verify_schema(pool: &SqlitePool) -> anyhow::Result<()> {
+    let applied: Vec<(i64, Vec<u8>)> =
+        sqlx::query_as("SELECT version, checksum FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(pool)
+            .await
+            .context("database has no migrations table")?;
+
+    for (version, checksum) in applied {
+        let Some(migration) = db::MIGRATOR.iter().find(|m| m.version == version) else {
+            anyhow::bail!("database has migration {version}, which is newer than this version");
+        };
+        if *migration.checksum != *checksum {
+            anyhow::bail!("migration {version} of the database was modified");
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes a backup every `interval` until shutdown, keeping the newest `keep` backups.
+pub async fn schedule(db: Db, conf: BackupConfig, shutdown: Shutdown) {
+    let mut interval = tokio::time::interval(Duration::from_secs(conf.interval));
+    // First tick is immediate, startup isn't a reason for a backup.
+    interval.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = shutdown.clone().requested() => return,
+        }
+
+        match scheduled_backup(&db, &conf, OffsetDateTime::now_utc()).await {
+            Ok(path) => tracing::info!("database backed up to {}", path.display()),
+            Err(err) => {
+                tracing::error!(error = err.to_string(), "scheduled backup failed: {err:#}")
+            }
+        }
+    }
+}
+
+pub async fn scheduled_backup(
+    db: &Db,
+    conf: &BackupConfig,
+    now: OffsetDateTime,
+) -> anyhow::Result<PathBuf> {
+    let dir = Path::new(&conf.dir);
+    std::fs::create_dir_all(dir)?;
+
+    let timestamp = now.format(format_description!(
+        "[year][month][day]T[hour][minute][second]Z"
+    ))?;
+    let ext = if conf.gzip { "db.gz" } else { "db" };
+    let path = dir.join(format!("{SCHEDULED_PREFIX}{timestamp}.{ext}"));
+
+    backup(db, &path, conf.gzip).await?;
+    prune(dir, conf.keep)?;
+    Ok(path)
+}
+
+/// Deletes all but the newest `keep` scheduled backups in the directory.
+fn prune(dir: &Path, keep: usize) -> io::Result<()> {
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with(SCHEDULED_PREFIX) && (name.ends_with(".db") || name.ends_with(".db.gz"))
+        {
+            backups.push(name);
+        }
+    }
+
+    // Timestamps in names sort chronologically.
+    backups.sort();
+    let remove = backups.len().saturating_sub(keep);
+    for name in &backups[..remove] {
+        std::fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
+
+fn compress(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(dst)?), Compression::default());
+    io::copy(&mut reader, &mut encoder)?;
+    encoder.finish()?.flush()
+}
+
+/// Copies the backup, decompressing it if it's gzipped.
+fn decompress(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut magic = [0; 2];
+    let read = reader.read(&mut magic)?;
+    // Read bytes are put back in front of the rest.
+    let mut reader = io::Cursor::new(magic[..read].to_vec()).chain(reader);
+
+    let mut writer = BufWriter::new(File::create(dst)?);
+    if magic == GZIP_MAGIC {
+        io::copy(&mut GzDecoder::new(reader), &mut writer)?;
+    } else {
+        io::copy(&mut reader, &mut writer)?;
+    }
+    writer.flush()
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
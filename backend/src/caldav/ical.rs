@@ -0,0 +1,256 @@
+//! The part of iCalendar (RFC 5545) that items map onto: a VTODO with a summary, a status
+//! and categories. Other properties sent by clients are dropped.
+
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
+
+use crate::store::item::Item;
+
+const DATE_TIME: &[BorrowedFormatItem] =
+    format_description!("[year][month][day]T[hour][minute][second]Z");
+
+/// Longest content line in octets, longer lines are folded.
+const MAX_LINE_LEN: usize = 75;
+
+/// VTODO as sent by a client.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Todo {
+    pub uid: String,
+    pub summary: String,
+    pub completed: bool,
+    /// `None` if the property is missing, clients that don't know categories keep
+    /// the item where it is.
+    pub categories: Option<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Calendar doesn't contain a VTODO, ie. it's an event.
+    NoTodo,
+    Invalid(&'static str),
+}
+
+/// Renders the item as a calendar with a single VTODO.
+pub fn render(uid: &str, item: &Item, categories: &[&str]) -> String {
+    let created = format_date_time(item.created_at);
+    let updated = format_date_time(item.updated_at);
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//lshop//lshop//EN".to_string(),
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", escape(uid)),
+        format!("DTSTAMP:{updated}"),
+        format!("CREATED:{created}"),
+        format!("LAST-MODIFIED:{updated}"),
+        format!("SUMMARY:{}", escape(&item.name)),
+    ];
+    if item.checked {
+        lines.push("STATUS:COMPLETED".to_string());
+        lines.push(format!("COMPLETED:{updated}"));
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+    if !categories.is_empty() {
+        let categories: Vec<String> = categories.iter().map(|c| escape(c)).collect();
+        lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    }
+    lines.push("END:VTODO".to_string());
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        fold(&mut out, &line);
+    }
+    out
+}
+
+fn format_date_time(date_time: time::OffsetDateTime) -> String {
+    date_time
+        .to_offset(time::UtcOffset::UTC)
+        .format(DATE_TIME)
+        .expect("date should be formattable")
+}
+
+/// Escapes a text value.
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Appends the content line, split into lines of at most 75 octets.
+fn fold(out: &mut String, line: &str) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > MAX_LINE_LEN {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Parses the first VTODO of the calendar. Other VTODOs with the same UID would be
+/// overrides of a recurring todo, which items don't have.
+pub fn parse(text: &str) -> Result<Todo, ParseError> {
+    let mut components: Vec<String> = vec![];
+    let mut seen_todo = false;
+    let mut uid = None;
+    let mut summary = None;
+    let mut status = None;
+    let mut completed_at = false;
+    let mut categories: Option<Vec<String>> = None;
+
+    for line in unfold(text) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((name, value)) = split_line(&line) else {
+            return Err(ParseError::Invalid("content line without a value"));
+        };
+
+        match name.as_str() {
+            "BEGIN" => {
+                let component = value.to_ascii_uppercase();
+                if components.is_empty() && component != "VCALENDAR" {
+                    return Err(ParseError::Invalid("not a calendar"));
+                }
+                components.push(component);
+                continue;
+            }
+            "END" => {
+                if components
+                    .pop()
+                    .is_none_or(|c| !c.eq_ignore_ascii_case(value))
+                {
+                    return Err(ParseError::Invalid("unbalanced components"));
+                }
+                if components.len() == 1 && value.eq_ignore_ascii_case("VTODO") {
+                    seen_todo = true;
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        // Only properties of the first VTODO, not of its alarms.
+        let in_todo = components.len() == 2 && components[1] == "VTODO";
+        if !in_todo || seen_todo {
+            continue;
+        }
+        match name.as_str() {
+            "UID" => uid = Some(unescape(value)),
+            "SUMMARY" => summary = Some(unescape(value)),
+            "STATUS" => status = Some(value.trim().to_ascii_uppercase()),
+            "COMPLETED" => completed_at = true,
+            "CATEGORIES" => categories
+                .get_or_insert_with(Vec::new)
+                .extend(split_list(value).into_iter().filter(|c| !c.is_empty())),
+            _ => {}
+        }
+    }
+
+    if !components.is_empty() {
+        return Err(ParseError::Invalid("unbalanced components"));
+    }
+    if !seen_todo {
+        return Err(ParseError::NoTodo);
+    }
+    let Some(uid) = uid.filter(|uid| !uid.is_empty()) else {
+        return Err(ParseError::Invalid("missing UID"));
+    };
+
+    let completed = match status.as_deref() {
+        Some(status) => status == "COMPLETED",
+        None => completed_at,
+    };
+    Ok(Todo {
+        uid,
+        summary: summary.unwrap_or_default(),
+        completed,
+        categories,
+    })
+}
+
+/// Joins folded lines, which continue with a space or a tab.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Splits a content line into its uppercase name, without parameters, and its value.
+fn split_line(line: &str) -> Option<(String, &str)> {
+    // Colons in quoted parameter values don't end the name.
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(idx, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(idx),
+        _ => None,
+    })?;
+
+    let (params, value) = (&line[..colon], &line[colon + 1..]);
+    let name = params.split(';').next().unwrap_or_default();
+    // Properties can be grouped, ie. `item1.SUMMARY`.
+    let name = name.rsplit('.').next().unwrap_or_default();
+    Some((name.trim().to_ascii_uppercase(), value))
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Splits a list value at commas that aren't escaped.
+fn split_list(value: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (idx, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                parts.push(unescape(&value[start..idx]).trim().to_string());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(unescape(&value[start..]).trim().to_string());
+    parts
+}
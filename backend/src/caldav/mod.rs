@@ -0,0 +1,713 @@
+//! CalDAV subset that serves all items as one calendar of VTODOs, so that reminder apps
+//! can sync the list natively. Store and section are the categories of the todo, a
+//! checked item is completed.
+//!
+//! Clients authenticate with HTTP basic auth, the password is an app token.
+//! Resources:
+//! - `/dav/principal/` is the authenticated user.
+//! - `/dav/calendars/` is the calendar home, with the single `list` calendar.
+//! - `/dav/calendars/list/{name}.ics` are the items.
+
+pub mod ical;
+mod xml;
+
+use std::collections::HashMap;
+
+use axum::{
+    body::{Bytes, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::{self, User},
+    db::Db,
+    handler::{
+        FieldError, Problem,
+        validate::{MAX_ITEM_NAME_LEN, normalize_name},
+    },
+    push,
+    state::AppState,
+    store::{self, item::Item, user::Role},
+    util::to_hex,
+};
+use xml::{CALDAV, CALENDARSERVER, DAV, Multistatus, PropName, PropRequest, Report};
+
+const ROOT_HREF: &str = "/dav/";
+const PRINCIPAL_HREF: &str = "/dav/principal/";
+const HOME_HREF: &str = "/dav/calendars/";
+const CALENDAR_HREF: &str = "/dav/calendars/list/";
+
+/// Items that weren't created by a CalDAV client are named after their id.
+const NATIVE_PREFIX: &str = "lshop-";
+
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+const ALLOW: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, REPORT";
+
+/// Characters that are kept in resource names of hrefs.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'@');
+
+#[derive(Debug, PartialEq, Eq)]
+enum Resource {
+    Root,
+    Principal,
+    Home,
+    Calendar,
+    Object(String),
+}
+
+impl Resource {
+    fn from_path(path: &str) -> Option<Self> {
+        let rest = path.strip_prefix("/dav")?;
+        let segments: Vec<&str> = rest.split('/').filter(|s| !s.is_empty()).collect();
+        let resource = match segments.as_slice() {
+            [] => Resource::Root,
+            ["principal"] => Resource::Principal,
+            ["calendars"] => Resource::Home,
+            ["calendars", "list"] => Resource::Calendar,
+            ["calendars", "list", name] => {
+                let name = percent_decode_str(name).decode_utf8().ok()?;
+                Resource::Object(name.into_owned())
+            }
+            _ => return None,
+        };
+        Some(resource)
+    }
+}
+
+/// Item as a calendar object resource.
+struct Entry {
+    name: String,
+    uid: String,
+    ics: String,
+    etag: String,
+    item: Item,
+}
+
+impl Entry {
+    fn new(item: Item, name: String, uid: String, categories: &[&str]) -> Self {
+        let ics = ical::render(&uid, &item, categories);
+        let etag = format!("\"{}\"", &to_hex(&Sha256::digest(ics.as_bytes()))[..16]);
+        Self {
+            name,
+            uid,
+            ics,
+            etag,
+            item,
+        }
+    }
+
+    fn href(&self) -> String {
+        format!(
+            "{CALENDAR_HREF}{}",
+            utf8_percent_encode(&self.name, SEGMENT)
+        )
+    }
+}
+
+/// Redirects clients that discover the server by its domain (RFC 6764).
+pub async fn well_known() -> Response {
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, ROOT_HREF)],
+    )
+        .into_response()
+}
+
+pub async fn serve(State(state): State<AppState>, req: Request) -> Response {
+    let (parts, body) = req.into_parts();
+    let Some(resource) = Resource::from_path(parts.uri.path()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if parts.method == "OPTIONS" {
+        return (
+            [
+                (header::ALLOW, ALLOW),
+                (
+                    header::HeaderName::from_static("dav"),
+                    "1, 3, calendar-access",
+                ),
+            ],
+            StatusCode::OK,
+        )
+            .into_response();
+    }
+
+    let user = match auth::get_user_from_app_token(&state.db, &parts.headers).await {
+        Ok(user) => user,
+        Err(err) => {
+            let mut res = err.into_response();
+            if res.status() == StatusCode::UNAUTHORIZED {
+                res.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Basic realm=\"lshop\", charset=\"UTF-8\""),
+                );
+            }
+            return res;
+        }
+    };
+
+    let body = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+
+    let db = &state.db;
+    let headers = &parts.headers;
+    let res = match (parts.method.as_str(), resource) {
+        ("PROPFIND", resource) => propfind(db, &user, resource, headers, &body).await,
+        ("REPORT", Resource::Calendar) => report(db, &body).await,
+        ("GET" | "HEAD", Resource::Object(name)) => get(db, &name).await,
+        ("PUT", Resource::Object(name)) => put(&state, &user, &name, headers, &body).await,
+        ("DELETE", Resource::Object(name)) => delete(db, &user, &name, headers).await,
+        _ => Ok((StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response()),
+    };
+    res.unwrap_or_else(IntoResponse::into_response)
+}
+
+async fn propfind(
+    db: &Db,
+    user: &User,
+    resource: Resource,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Result<Response, Problem> {
+    let Ok(props) = std::str::from_utf8(body)
+        .map_err(|err| err.to_string())
+        .and_then(xml::parse_propfind)
+    else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    // Infinite depth isn't supported, it's treated as 1.
+    let children = headers
+        .get("depth")
+        .is_none_or(|depth| depth.as_bytes() != b"0");
+
+    let mut ms = Multistatus::new();
+    match resource {
+        Resource::Root => {
+            ms.collection(ROOT_HREF, Collection::Root, user, &props);
+            if children {
+                ms.collection(PRINCIPAL_HREF, Collection::Principal, user, &props);
+                ms.collection(HOME_HREF, Collection::Home, user, &props);
+            }
+        }
+        Resource::Principal => ms.collection(PRINCIPAL_HREF, Collection::Principal, user, &props),
+        Resource::Home => {
+            ms.collection(HOME_HREF, Collection::Home, user, &props);
+            if children {
+                let ctag = ctag(&load_entries(db).await?);
+                ms.collection(CALENDAR_HREF, Collection::Calendar(&ctag), user, &props);
+            }
+        }
+        Resource::Calendar => {
+            let entries = load_entries(db).await?;
+            let ctag = ctag(&entries);
+            ms.collection(CALENDAR_HREF, Collection::Calendar(&ctag), user, &props);
+            if children {
+                for entry in &entries {
+                    ms.object(entry, &props);
+                }
+            }
+        }
+        Resource::Object(name) => {
+            let Some(entry) = load_entry(db, &name).await? else {
+                return Err(Problem::not_found());
+            };
+            ms.object(&entry, &props);
+        }
+    }
+    Ok(multistatus(ms))
+}
+
+async fn report(db: &Db, body: &Bytes) -> Result<Response, Problem> {
+    let Ok(report) = std::str::from_utf8(body)
+        .map_err(|err| err.to_string())
+        .and_then(xml::parse_report)
+    else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+
+    let mut ms = Multistatus::new();
+    match report {
+        Report::Multiget { props, hrefs } => {
+            let props = report_props(props);
+            let entries = load_entries(db).await?;
+            for href in hrefs {
+                let entry = href_name(&href)
+                    .and_then(|name| entries.iter().find(|entry| entry.name == name));
+                match entry {
+                    Some(entry) => ms.object(entry, &props),
+                    None => ms.not_found(&href),
+                }
+            }
+        }
+        Report::Query { props, todos } => {
+            let props = report_props(props);
+            if todos {
+                for entry in &load_entries(db).await? {
+                    ms.object(entry, &props);
+                }
+            }
+        }
+        Report::Unsupported => {
+            return Ok(dav_error(StatusCode::FORBIDDEN, "d:supported-report"));
+        }
+    }
+    Ok(multistatus(ms))
+}
+
+async fn get(db: &Db, name: &str) -> Result<Response, Problem> {
+    let Some(entry) = load_entry(db, name).await? else {
+        return Err(Problem::not_found());
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::ETAG, entry.etag.as_str()),
+        ],
+        entry.ics,
+    )
+        .into_response())
+}
+
+/// Creates or updates the item. The stored todo differs from the sent one, so no etag
+/// is returned and clients fetch it again.
+async fn put(
+    state: &AppState,
+    user: &User,
+    name: &str,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Result<Response, Problem> {
+    let db = &state.db;
+    let existing = load_entry(db, name).await?;
+    if let Some(res) = check_preconditions(headers, existing.as_ref()) {
+        return Ok(res);
+    }
+
+    let todo = match std::str::from_utf8(body)
+        .map_err(|_| ical::ParseError::Invalid("not utf-8"))
+        .and_then(ical::parse)
+    {
+        Ok(todo) => todo,
+        Err(ical::ParseError::NoTodo) => {
+            return Ok(dav_error(
+                StatusCode::FORBIDDEN,
+                "c:supported-calendar-component",
+            ));
+        }
+        Err(ical::ParseError::Invalid(_)) => {
+            return Ok(dav_error(StatusCode::FORBIDDEN, "c:valid-calendar-data"));
+        }
+    };
+
+    let summary = normalize_name(&todo.summary);
+    if summary.is_empty() || summary.chars().count() > MAX_ITEM_NAME_LEN {
+        return Err(Problem::validation(vec![FieldError::new(
+            "summary",
+            "invalid",
+            format!("must be between 1 and {MAX_ITEM_NAME_LEN} characters long"),
+        )]));
+    }
+
+    let Some(entry) = existing else {
+        if name.starts_with(NATIVE_PREFIX)
+            || todo.uid.starts_with(NATIVE_PREFIX)
+            || store::caldav::uid_exists(db, &todo.uid).await?
+        {
+            return Ok(dav_error(StatusCode::FORBIDDEN, "c:no-uid-conflict"));
+        }
+
+        let (store_id, section_id) = match &todo.categories {
+            Some(categories) => target(db, categories).await?,
+            None => (None, None),
+        };
+        let item = store::caldav::create(
+            db,
+            store_id,
+            section_id,
+            &summary,
+            todo.completed,
+            name,
+            &todo.uid,
+        )
+        .await?;
+        if !todo.completed {
+            push::spawn_items_created(state, user, std::slice::from_ref(&item));
+        }
+        return Ok(StatusCode::CREATED.into_response());
+    };
+
+    if todo.uid != entry.uid {
+        return Ok(dav_error(StatusCode::FORBIDDEN, "c:no-uid-conflict"));
+    }
+
+    let item = &entry.item;
+    let (store_id, section_id) = match &todo.categories {
+        Some(categories) => target(db, categories).await?,
+        None => (item.store_id, item.section_id),
+    };
+    // Shoppers can only add and check items.
+    let changed = summary != item.name
+        || (store_id, section_id) != (item.store_id, item.section_id)
+        || (item.checked && !todo.completed);
+    if changed && user.role < Role::Member {
+        return Err(Problem::forbidden());
+    }
+
+    store::item::replace(db, item.id, &summary, store_id, section_id, todo.completed).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn delete(
+    db: &Db,
+    user: &User,
+    name: &str,
+    headers: &HeaderMap,
+) -> Result<Response, Problem> {
+    if user.role < Role::Member {
+        return Err(Problem::forbidden());
+    }
+    let Some(entry) = load_entry(db, name).await? else {
+        return Err(Problem::not_found());
+    };
+    if let Some(res) = check_preconditions(headers, Some(&entry)) {
+        return Ok(res);
+    }
+
+    store::item::delete(db, entry.item.id).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Checks `If-Match` and `If-None-Match`, so that clients don't overwrite changes
+/// they haven't seen.
+fn check_preconditions(headers: &HeaderMap, entry: Option<&Entry>) -> Option<Response> {
+    let matches = |value: &HeaderValue| {
+        let value = value.to_str().unwrap_or_default();
+        value
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || entry.is_some_and(|entry| tag == entry.etag))
+    };
+
+    let failed = match (
+        headers.get(header::IF_MATCH),
+        headers.get(header::IF_NONE_MATCH),
+    ) {
+        (Some(if_match), _) => entry.is_none() || !matches(if_match),
+        (None, Some(if_none_match)) => entry.is_some() && matches(if_none_match),
+        (None, None) => false,
+    };
+    failed.then(|| StatusCode::PRECONDITION_FAILED.into_response())
+}
+
+/// Finds the store and the section named by the categories, ignoring case.
+async fn target(db: &Db, categories: &[String]) -> Result<(Option<i64>, Option<i64>), sqlx::Error> {
+    let named = |name: &str| {
+        categories
+            .iter()
+            .any(|c| c.to_lowercase() == name.to_lowercase())
+    };
+
+    let stores = store::shop::list(db).await?;
+    let Some(shop) = stores.iter().find(|s| named(&s.name)) else {
+        return Ok((None, None));
+    };
+    let sections = store::section::list(db, shop.id).await?;
+    let section = sections.iter().find(|s| named(&s.name));
+    Ok((Some(shop.id), section.map(|s| s.id)))
+}
+
+async fn load_entries(db: &Db) -> Result<Vec<Entry>, sqlx::Error> {
+    let (items, objects, stores, sections) = tokio::try_join!(
+        store::item::list_all(db),
+        store::caldav::list(db),
+        store::shop::list(db),
+        store::section::list_all(db),
+    )?;
+    let mut objects: HashMap<i64, store::caldav::Object> =
+        objects.into_iter().map(|o| (o.item_id, o)).collect();
+    let stores: HashMap<i64, String> = stores.into_iter().map(|s| (s.id, s.name)).collect();
+    let sections: HashMap<i64, String> = sections.into_iter().map(|s| (s.id, s.name)).collect();
+
+    let entries = items
+        .into_iter()
+        .map(|item| {
+            let categories: Vec<&str> = [
+                item.store_id.and_then(|id| stores.get(&id)),
+                item.section_id.and_then(|id| sections.get(&id)),
+            ]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+            let (name, uid) = match objects.remove(&item.id) {
+                Some(object) => (object.name, object.uid),
+                None => native_name(item.id),
+            };
+            Entry::new(item, name, uid, &categories)
+        })
+        .collect();
+    Ok(entries)
+}
+
+async fn load_entry(db: &Db, name: &str) -> Result<Option<Entry>, sqlx::Error> {
+    let (item_id, uid) = match store::caldav::get_by_name(db, name).await? {
+        Some(object) => (object.item_id, object.uid),
+        None => {
+            let id = name
+                .strip_prefix(NATIVE_PREFIX)
+                .and_then(|rest| rest.strip_suffix(".ics"))
+                .and_then(|id| id.parse::<i64>().ok());
+            let Some(id) = id else {
+                return Ok(None);
+            };
+            // Items created by clients are only found by their own name.
+            if store::caldav::get_by_item(db, id).await?.is_some() {
+                return Ok(None);
+            }
+            (id, native_name(id).1)
+        }
+    };
+
+    let Some(item) = store::item::get(db, item_id).await? else {
+        return Ok(None);
+    };
+    let shop = match item.store_id {
+        Some(id) => store::shop::get(db, id).await?,
+        None => None,
+    };
+    let section = match item.section_id {
+        Some(id) => store::section::get(db, id).await?,
+        None => None,
+    };
+    let categories: Vec<&str> = [
+        shop.as_ref().map(|s| &s.name),
+        section.as_ref().map(|s| &s.name),
+    ]
+    .into_iter()
+    .flatten()
+    .map(String::as_str)
+    .collect();
+
+    Ok(Some(Entry::new(item, name.to_string(), uid, &categories)))
+}
+
+/// Name and UID of an item that wasn't created by a client.
+fn native_name(item_id: i64) -> (String, String) {
+    let uid = format!("{NATIVE_PREFIX}{item_id}");
+    (format!("{uid}.ics"), uid)
+}
+
+/// Changes whenever any item of the calendar changes, so that clients know when to sync.
+fn ctag(entries: &[Entry]) -> String {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        hasher.update(entry.name.as_bytes());
+        hasher.update(entry.etag.as_bytes());
+    }
+    to_hex(&hasher.finalize()[..16])
+}
+
+/// Name of the object the href points to. Hrefs can be paths or full urls.
+fn href_name(href: &str) -> Option<String> {
+    let path = match href.find("://") {
+        Some(scheme_end) => {
+            let rest = &href[scheme_end + 3..];
+            &rest[rest.find('/')?..]
+        }
+        None => href,
+    };
+    let name = path.strip_prefix(CALENDAR_HREF)?;
+    if name.is_empty() || name.contains('/') {
+        return None;
+    }
+    percent_decode_str(name)
+        .decode_utf8()
+        .ok()
+        .map(|name| name.into_owned())
+}
+
+/// Reports return the etag and the data of the objects, if no properties are given.
+fn report_props(props: PropRequest) -> PropRequest {
+    match props {
+        PropRequest::Props(names) => PropRequest::Props(names),
+        PropRequest::All => PropRequest::Props(vec![
+            PropName::new(DAV, "getetag"),
+            PropName::new(CALDAV, "calendar-data"),
+        ]),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Collection<'a> {
+    Root,
+    Principal,
+    Home,
+    /// Calendar with its ctag.
+    Calendar(&'a str),
+}
+
+impl Multistatus {
+    fn collection(&mut self, href: &str, collection: Collection, user: &User, props: &PropRequest) {
+        let value = |prop: &PropName| collection_prop(collection, user, prop);
+        let (found, missing) = resolve_props(props, COLLECTION_PROPS, value);
+        self.response(href, &found, &missing);
+    }
+
+    fn object(&mut self, entry: &Entry, props: &PropRequest) {
+        let value = |prop: &PropName| object_prop(entry, prop);
+        let (found, missing) = resolve_props(props, OBJECT_PROPS, value);
+        self.response(&entry.href(), &found, &missing);
+    }
+}
+
+/// Properties returned for `allprop`, if the resource has them.
+const COLLECTION_PROPS: &[(&str, &str)] = &[
+    (DAV, "resourcetype"),
+    (DAV, "displayname"),
+    (DAV, "current-user-principal"),
+    (DAV, "principal-URL"),
+    (DAV, "owner"),
+    (DAV, "getetag"),
+    (CALDAV, "calendar-home-set"),
+    (CALDAV, "supported-calendar-component-set"),
+    (CALENDARSERVER, "getctag"),
+];
+const OBJECT_PROPS: &[(&str, &str)] = &[
+    (DAV, "resourcetype"),
+    (DAV, "getetag"),
+    (DAV, "getcontenttype"),
+];
+
+fn resolve_props(
+    props: &PropRequest,
+    all: &[(&str, &str)],
+    value: impl Fn(&PropName) -> Option<String>,
+) -> (Vec<(PropName, String)>, Vec<PropName>) {
+    let mut found = vec![];
+    let mut missing = vec![];
+    match props {
+        PropRequest::All => {
+            for (ns, name) in all {
+                let prop = PropName::new(ns, name);
+                if let Some(value) = value(&prop) {
+                    found.push((prop, value));
+                }
+            }
+        }
+        PropRequest::Props(names) => {
+            for prop in names {
+                match value(prop) {
+                    Some(value) => found.push((prop.clone(), value)),
+                    None => missing.push(prop.clone()),
+                }
+            }
+        }
+    }
+    (found, missing)
+}
+
+fn collection_prop(collection: Collection, user: &User, prop: &PropName) -> Option<String> {
+    let href = |href: &str| format!("<d:href>{href}</d:href>");
+    let calendar = matches!(collection, Collection::Calendar(_));
+
+    let value = if prop.is(DAV, "resourcetype") {
+        match collection {
+            Collection::Root | Collection::Home => "<d:collection/>".to_string(),
+            Collection::Principal => "<d:collection/><d:principal/>".to_string(),
+            Collection::Calendar(_) => "<d:collection/><c:calendar/>".to_string(),
+        }
+    } else if prop.is(DAV, "displayname") {
+        match collection {
+            Collection::Root => "lshop".to_string(),
+            Collection::Principal => xml::escape(&user.username),
+            Collection::Home => "Calendars".to_string(),
+            Collection::Calendar(_) => "Shopping list".to_string(),
+        }
+    } else if prop.is(DAV, "current-user-principal")
+        || (prop.is(DAV, "principal-URL") && matches!(collection, Collection::Principal))
+    {
+        href(PRINCIPAL_HREF)
+    } else if prop.is(CALDAV, "calendar-home-set")
+        && matches!(collection, Collection::Root | Collection::Principal)
+    {
+        href(HOME_HREF)
+    } else if prop.is(DAV, "owner") && calendar {
+        href(PRINCIPAL_HREF)
+    } else if prop.is(DAV, "current-user-privilege-set") && calendar {
+        ["read", "write", "write-content", "bind", "unbind"]
+            .iter()
+            .map(|p| format!("<d:privilege><d:{p}/></d:privilege>"))
+            .collect()
+    } else if prop.is(DAV, "supported-report-set") && calendar {
+        ["c:calendar-multiget", "c:calendar-query"]
+            .iter()
+            .map(|r| {
+                format!("<d:supported-report><d:report><{r}/></d:report></d:supported-report>")
+            })
+            .collect()
+    } else if prop.is(CALDAV, "supported-calendar-component-set") && calendar {
+        r#"<c:comp name="VTODO"/>"#.to_string()
+    } else if let Collection::Calendar(ctag) = collection
+        && (prop.is(CALENDARSERVER, "getctag") || prop.is(DAV, "getetag"))
+    {
+        if prop.is(DAV, "getetag") {
+            format!("\"{ctag}\"")
+        } else {
+            ctag.to_string()
+        }
+    } else {
+        return None;
+    };
+    Some(value)
+}
+
+fn object_prop(entry: &Entry, prop: &PropName) -> Option<String> {
+    let value = if prop.is(DAV, "resourcetype") {
+        String::new()
+    } else if prop.is(DAV, "getetag") {
+        xml::escape(&entry.etag)
+    } else if prop.is(DAV, "getcontenttype") {
+        "text/calendar; charset=utf-8; component=VTODO".to_string()
+    } else if prop.is(CALDAV, "calendar-data") {
+        xml::escape(&entry.ics)
+    } else if prop.is(DAV, "current-user-privilege-set") {
+        ["read", "write", "write-content"]
+            .iter()
+            .map(|p| format!("<d:privilege><d:{p}/></d:privilege>"))
+            .collect()
+    } else {
+        return None;
+    };
+    Some(value)
+}
+
+fn multistatus(ms: Multistatus) -> Response {
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        ms.finish(),
+    )
+        .into_response()
+}
+
+fn dav_error(status: StatusCode, precondition: &str) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        xml::error(precondition),
+    )
+        .into_response()
+}
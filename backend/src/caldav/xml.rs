@@ -0,0 +1,201 @@
+//! Request bodies of PROPFIND and REPORT, and the multistatus responses.
+
+use std::fmt::Write;
+
+pub const DAV: &str = "DAV:";
+pub const CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+pub const CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+
+/// Property name qualified by its namespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropName {
+    pub ns: String,
+    pub name: String,
+}
+
+impl PropName {
+    pub fn new(ns: &str, name: &str) -> Self {
+        Self {
+            ns: ns.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+}
+
+#[derive(Debug)]
+pub enum PropRequest {
+    /// All properties, except the expensive ones like calendar data.
+    All,
+    Props(Vec<PropName>),
+}
+
+#[derive(Debug)]
+pub enum Report {
+    Multiget {
+        props: PropRequest,
+        hrefs: Vec<String>,
+    },
+    Query {
+        props: PropRequest,
+        /// False if the filter asks for other components, which never match.
+        todos: bool,
+    },
+    Unsupported,
+}
+
+/// Parses the PROPFIND body. An empty body asks for all properties.
+pub fn parse_propfind(body: &str) -> Result<PropRequest, String> {
+    if body.trim().is_empty() {
+        return Ok(PropRequest::All);
+    }
+    let doc = roxmltree::Document::parse(body).map_err(|err| err.to_string())?;
+    let root = doc.root_element();
+    if !is(root, DAV, "propfind") {
+        return Err("expected a propfind element".to_string());
+    }
+    Ok(prop_request(root))
+}
+
+pub fn parse_report(body: &str) -> Result<Report, String> {
+    let doc = roxmltree::Document::parse(body).map_err(|err| err.to_string())?;
+    let root = doc.root_element();
+
+    let report = if is(root, CALDAV, "calendar-multiget") {
+        let hrefs = root
+            .children()
+            .filter(|n| is(*n, DAV, "href"))
+            .map(|n| n.text().unwrap_or_default().trim().to_string())
+            .collect();
+        Report::Multiget {
+            props: prop_request(root),
+            hrefs,
+        }
+    } else if is(root, CALDAV, "calendar-query") {
+        let todos = root
+            .descendants()
+            .filter(|n| is(*n, CALDAV, "comp-filter"))
+            .filter_map(|n| n.attribute("name"))
+            .all(|name| {
+                name.eq_ignore_ascii_case("VCALENDAR") || name.eq_ignore_ascii_case("VTODO")
+            });
+        Report::Query {
+            props: prop_request(root),
+            todos,
+        }
+    } else {
+        Report::Unsupported
+    };
+    Ok(report)
+}
+
+fn is(node: roxmltree::Node, ns: &str, name: &str) -> bool {
+    node.is_element() && node.tag_name().namespace() == Some(ns) && node.tag_name().name() == name
+}
+
+/// Reads the `prop` child of the request, `allprop` and `propname` return everything.
+fn prop_request(root: roxmltree::Node) -> PropRequest {
+    let Some(prop) = root.children().find(|n| is(*n, DAV, "prop")) else {
+        return PropRequest::All;
+    };
+    let names = prop
+        .children()
+        .filter(|n| n.is_element())
+        .map(|n| {
+            PropName::new(
+                n.tag_name().namespace().unwrap_or_default(),
+                n.tag_name().name(),
+            )
+        })
+        .collect();
+    PropRequest::Props(names)
+}
+
+/// Builds a 207 multistatus body. Values of properties are xml, which can use the
+/// `d`, `c` and `cs` prefixes of the DAV, CalDAV and CalendarServer namespaces.
+pub struct Multistatus {
+    out: String,
+}
+
+impl Multistatus {
+    pub fn new() -> Self {
+        let mut out = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        write!(
+            out,
+            r#"<d:multistatus xmlns:d="{DAV}" xmlns:c="{CALDAV}" xmlns:cs="{CALENDARSERVER}">"#
+        )
+        .unwrap();
+        Self { out }
+    }
+
+    /// Adds a resource with the found properties and the names of the missing ones.
+    pub fn response(&mut self, href: &str, found: &[(PropName, String)], missing: &[PropName]) {
+        write!(self.out, "<d:response><d:href>{}</d:href>", escape(href)).unwrap();
+        if !found.is_empty() {
+            self.out.push_str("<d:propstat><d:prop>");
+            for (prop, value) in found {
+                write_prop(&mut self.out, prop, value);
+            }
+            self.out
+                .push_str("</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>");
+        }
+        if !missing.is_empty() {
+            self.out.push_str("<d:propstat><d:prop>");
+            for prop in missing {
+                write_prop(&mut self.out, prop, "");
+            }
+            self.out
+                .push_str("</d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>");
+        }
+        self.out.push_str("</d:response>");
+    }
+
+    pub fn not_found(&mut self, href: &str) {
+        write!(
+            self.out,
+            "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+            escape(href)
+        )
+        .unwrap();
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push_str("</d:multistatus>");
+        self.out
+    }
+}
+
+/// Property element in its own default namespace, which works for any namespace.
+fn write_prop(out: &mut String, prop: &PropName, value: &str) {
+    let name = &prop.name;
+    let ns = escape(&prop.ns);
+    if value.is_empty() {
+        write!(out, r#"<{name} xmlns="{ns}"/>"#).unwrap();
+    } else {
+        write!(out, r#"<{name} xmlns="{ns}">{value}</{name}>"#).unwrap();
+    }
+}
+
+/// Body of an error response with the failed precondition, ie. `c:valid-calendar-data`.
+pub fn error(precondition: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><d:error xmlns:d="{DAV}" xmlns:c="{CALDAV}"><{precondition}/></d:error>"#
+    )
+}
+
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
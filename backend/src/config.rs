@@ -1,5 +1,10 @@
+use std::net::IpAddr;
+
 use config::ConfigError;
-use serde::Deserialize;
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer};
+
+use crate::store::user::Role;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -26,10 +31,300 @@ pub struct Config {
 
     pub address: String,
     pub port: u16,
+    /// How long in-flight requests have to finish after SIGINT or SIGTERM, in seconds.
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
 
     pub db_path: String,
 
     pub openai_api_key: String,
+
+    pub oidc: Option<OidcConfig>,
+
+    pub proxy_auth: Option<ProxyAuthConfig>,
+
+    pub webauthn: Option<WebauthnConfig>,
+
+    /// Directory with the built frontend, ie. `frontend/dist`. If it's not set, the
+    /// frontend embedded in the binary is served, when built with `embed-frontend`.
+    pub frontend_dir: Option<String>,
+
+    /// Terminates TLS in the backend, so that a reverse proxy isn't needed.
+    pub tls: Option<TlsConfig>,
+
+    /// Prometheus metrics at `/metrics`, not served if it's not set.
+    pub metrics: Option<MetricsConfig>,
+
+    /// Scheduled backups of the database, made while the server is running.
+    pub backup: Option<BackupConfig>,
+
+    /// Push notifications to subscribed browsers, not sent if it's not set.
+    pub web_push: Option<WebPushConfig>,
+
+    /// Email transport of the scheduled notifications.
+    pub smtp: Option<SmtpConfig>,
+
+    /// ntfy transport of the scheduled notifications.
+    pub ntfy: Option<NtfyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcConfig {
+    /// Issuer url, used for discovery (`<issuer>/.well-known/openid-configuration`).
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// Url of the callback endpoint as registered with the provider,
+    /// ie. `https://shop.example.com/api/v1/auth/oidc/callback`.
+    pub redirect_url: String,
+
+    #[serde(default = "default_oidc_scopes", deserialize_with = "deserialize_list")]
+    pub scopes: Vec<String>,
+    #[serde(default = "default_oidc_username_claim")]
+    pub username_claim: String,
+    #[serde(default = "default_oidc_groups_claim")]
+    pub groups_claim: String,
+
+    /// Users that are members of any of these groups are created on first login.
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub provision_groups: Vec<String>,
+    /// Link identity to an existing user with the same username on first login.
+    #[serde(default)]
+    pub link_existing_users: bool,
+    /// Role of the provisioned users.
+    #[serde(default)]
+    pub default_role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProxyAuthConfig {
+    /// Header containing username of the user authenticated by the proxy.
+    #[serde(default = "default_proxy_auth_header")]
+    pub header: String,
+
+    /// Addresses (`10.0.0.1`) or networks (`10.0.0.0/8`) of proxies whose header is trusted.
+    #[serde(deserialize_with = "deserialize_networks")]
+    pub trusted_proxies: Vec<IpNet>,
+
+    /// Create users that don't exist yet, instead of rejecting the request.
+    #[serde(default)]
+    pub auto_create_users: bool,
+    /// Role of the automatically created users.
+    #[serde(default)]
+    pub default_role: Role,
+}
+
+impl ProxyAuthConfig {
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        // IPv4 clients connecting to a dual stack socket are seen as IPv4 mapped IPv6 addresses.
+        let addr = addr.to_canonical();
+        self.trusted_proxies.iter().any(|net| net.contains(&addr))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnConfig {
+    /// Relying party id, the domain of the app, ie. `shop.example.com`.
+    pub rp_id: String,
+    /// Origin of the app, ie. `https://shop.example.com`.
+    pub rp_origin: String,
+    #[serde(default = "default_webauthn_rp_name")]
+    pub rp_name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackupConfig {
+    /// Directory where the backups are written.
+    pub dir: String,
+    /// Time between backups, in seconds.
+    #[serde(default = "default_backup_interval")]
+    pub interval: u64,
+    /// Number of backups that are kept, older ones are deleted.
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+/// Keys are generated with the `generate-vapid-keys` command.
+#[derive(Debug, Deserialize)]
+pub struct WebPushConfig {
+    /// Base64url encoded P-256 private key.
+    pub vapid_private_key: String,
+    /// Contact of the operator for push services, ie. `mailto:admin@example.com`.
+    pub subject: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to the port of the security mode, 587, 465 or 25.
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender of the emails, ie. `L Shop <shop@example.com>`.
+    pub from: String,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    #[default]
+    Starttls,
+    Tls,
+    /// Plain text, only for a relay on the same host.
+    None,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NtfyConfig {
+    /// Url of the ntfy server, ie. `https://ntfy.sh`.
+    pub url: String,
+    /// Access token for servers that require authentication.
+    pub token: Option<String>,
+}
+
+/// At least one of `token` or `address` has to be set, metrics aren't public.
+#[derive(Debug, Deserialize)]
+pub struct MetricsConfig {
+    /// Bearer token required to read the metrics.
+    pub token: Option<String>,
+    /// Serves the metrics on a separate address instead of the app's, ie. `127.0.0.1:9100`.
+    pub address: Option<String>,
+}
+
+/// Either `cert_path` and `key_path`, or `acme` has to be set.
+#[derive(Debug, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain. It's reloaded when it changes.
+    pub cert_path: Option<String>,
+    /// PEM file with the private key.
+    pub key_path: Option<String>,
+    /// How often the files are checked for changes, in seconds.
+    #[serde(default = "default_tls_reload_interval")]
+    pub reload_interval: u64,
+
+    pub acme: Option<AcmeConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcmeConfig {
+    #[serde(deserialize_with = "deserialize_list")]
+    pub domains: Vec<String>,
+    /// Contacts of the account, ie. `mailto:admin@example.com`.
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub contact: Vec<String>,
+    #[serde(default = "default_acme_directory_url")]
+    pub directory_url: String,
+    /// Directory where the account and certificates are stored between restarts.
+    #[serde(default = "default_acme_cache_dir")]
+    pub cache_dir: String,
+    #[serde(default)]
+    pub challenge: AcmeChallenge,
+    /// Port of the plain HTTP listener, used for `http_01` challenges and redirects to
+    /// https. It's 80 by default with `http_01`, with `tls_alpn_01` there's no listener
+    /// unless it's set.
+    pub http_port: Option<u16>,
+    /// PEM file with the root certificates trusted for the ACME server instead of the
+    /// public ones, ie. of a local Pebble instance.
+    pub ca_cert_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcmeChallenge {
+    #[default]
+    TlsAlpn01,
+    Http01,
+}
+
+fn default_backup_interval() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30
+}
+
+fn default_tls_reload_interval() -> u64 {
+    10
+}
+
+fn default_acme_directory_url() -> String {
+    "https://acme-v02.api.letsencrypt.org/directory".to_string()
+}
+
+fn default_acme_cache_dir() -> String {
+    "acme".to_string()
+}
+
+fn default_webauthn_rp_name() -> String {
+    "L Shop".to_string()
+}
+
+fn default_proxy_auth_header() -> String {
+    "Remote-User".to_string()
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec![
+        "openid".to_string(),
+        "profile".to_string(),
+        "groups".to_string(),
+    ]
+}
+
+fn default_oidc_username_claim() -> String {
+    "preferred_username".to_string()
+}
+
+fn default_oidc_groups_claim() -> String {
+    "groups".to_string()
+}
+
+fn deserialize_networks<'de, D>(deserializer: D) -> Result<Vec<IpNet>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_list(deserializer)?
+        .iter()
+        .map(|net| {
+            net.parse::<IpNet>()
+                .or_else(|_| net.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| serde::de::Error::custom(format!("invalid network: '{net}'")))
+        })
+        .collect()
+}
+
+/// Deserializes a list given either as a sequence or as a comma separated string.
+/// Lists can't be given as environment variables otherwise.
+fn deserialize_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Seq(Vec<String>),
+        Str(String),
+    }
+
+    let list = match List::deserialize(deserializer)? {
+        List::Seq(list) => list,
+        List::Str(s) => s
+            .split(',')
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .collect(),
+    };
+    Ok(list)
 }
 
 impl Config {
@@ -40,7 +335,7 @@ impl Config {
                 default_conf,
                 config::FileFormat::Toml,
             ))
-            .add_source(config::Environment::default())
+            .add_source(config::Environment::default().separator("__"))
             .build()?;
 
         settings.try_deserialize()
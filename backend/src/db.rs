@@ -1,6 +1,9 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use sqlx::SqlitePool;
+use sqlx::migrate::Migrator;
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
 
 pub type Db = SqlitePool;
@@ -13,9 +16,54 @@ pub async fn connect(db_url: &str) -> Result<SqlitePool, sqlx::Error> {
         .foreign_keys(true);
 
     let pool = SqlitePoolOptions::new().connect_with(opts).await?;
+    migrate(&pool).await?;
 
-    let migrator = sqlx::migrate!();
-    migrator.run(&pool).await?;
+    Ok(pool)
+}
+
+/// Connects to a new in-memory database with migrations applied.
+/// Each connection would see its own database, so the pool holds a single connection
+/// that is never closed.
+#[cfg(test)]
+pub async fn connect_in_memory() -> Result<SqlitePool, sqlx::Error> {
+    let opts = SqliteConnectOptions::from_str("sqlite::memory:")?.foreign_keys(true);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(opts)
+        .await?;
+    migrate(&pool).await?;
 
     Ok(pool)
 }
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Returns path of the database file in the connection url.
+pub fn file_path(db_url: &str) -> Result<PathBuf, sqlx::Error> {
+    let opts = SqliteConnectOptions::from_str(db_url)?;
+    Ok(opts.get_filename().to_path_buf())
+}
+
+pub async fn migrate(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
+/// Returns the number of migrations that aren't applied to the database.
+pub async fn pending_migrations(pool: &SqlitePool) -> Result<usize, sqlx::Error> {
+    let applied: HashSet<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+        .count())
+}
@@ -0,0 +1,297 @@
+//! Serving of the built frontend, so that a reverse proxy isn't needed.
+//!
+//! Files are read from a directory or from the binary once at startup, compressed with
+//! gzip and zstd and kept in memory. The frontend is a SPA, paths that don't match a
+//! file are answered with `index.html`.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::handler::Problem;
+use crate::util::to_hex;
+
+const INDEX: &str = "/index.html";
+
+const NO_CACHE: &str = "no-cache";
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const ONE_HOUR: &str = "public, max-age=3600";
+const ONE_WEEK: &str = "public, max-age=604800";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    fn header(self) -> Option<HeaderValue> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some(HeaderValue::from_static("gzip")),
+            Encoding::Zstd => Some(HeaderValue::from_static("zstd")),
+        }
+    }
+}
+
+struct Asset {
+    content_type: HeaderValue,
+    cache_control: &'static str,
+    etag: HeaderValue,
+    identity: Bytes,
+    gzip: Option<Bytes>,
+    zstd: Option<Bytes>,
+}
+
+impl Asset {
+    fn new(path: &str, content: Vec<u8>) -> io::Result<Self> {
+        let mime = mime_guess::from_path(path).first_or_octet_stream();
+        let compress = is_compressible(&mime);
+
+        let hash = Sha256::digest(&content);
+        let etag = format!("\"{}\"", to_hex(&hash[..16]));
+
+        let (gzip, zstd) = if compress {
+            (
+                smaller(gzip(&content)?, &content),
+                smaller(zstd::bulk::compress(&content, 19)?, &content),
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(Self {
+            content_type: HeaderValue::from_str(mime.as_ref()).expect("mime should be valid"),
+            cache_control: cache_control(path),
+            etag: HeaderValue::from_str(&etag).expect("etag should be valid"),
+            identity: content.into(),
+            gzip,
+            zstd,
+        })
+    }
+
+    /// Returns content in the requested encoding, if it's available.
+    fn body(&self, encoding: Encoding) -> (Encoding, Bytes) {
+        let compressed = match encoding {
+            Encoding::Identity => None,
+            Encoding::Gzip => self.gzip.clone(),
+            Encoding::Zstd => self.zstd.clone(),
+        };
+        match compressed {
+            Some(body) => (encoding, body),
+            None => (Encoding::Identity, self.identity.clone()),
+        }
+    }
+}
+
+pub struct Frontend {
+    /// Absolute path, ie. `/assets/index.js` -> asset
+    files: HashMap<String, Asset>,
+}
+
+impl Frontend {
+    /// Loads the frontend configured by `frontend_dir`, or the embedded one.
+    pub fn load(conf: &Config) -> anyhow::Result<Option<Arc<Self>>> {
+        let frontend = match &conf.frontend_dir {
+            Some(dir) => Some(Self::from_dir(Path::new(dir))?),
+            None => Self::embedded()?,
+        };
+
+        if let Some(frontend) = &frontend
+            && !frontend.files.contains_key(INDEX)
+        {
+            anyhow::bail!("frontend doesn't contain index.html");
+        }
+
+        Ok(frontend.map(Arc::new))
+    }
+
+    pub fn from_dir(dir: &Path) -> io::Result<Self> {
+        let mut files = Vec::new();
+        read_dir(dir, "", &mut files)?;
+        Self::from_files(files)
+    }
+
+    #[cfg(feature = "embed-frontend")]
+    fn embedded() -> io::Result<Option<Self>> {
+        #[derive(rust_embed::RustEmbed)]
+        #[folder = "../frontend/dist"]
+        struct Dist;
+
+        let files = Dist::iter()
+            .filter_map(|path| {
+                let file = Dist::get(&path)?;
+                Some((format!("/{path}"), file.data.into_owned()))
+            })
+            .collect();
+        Self::from_files(files).map(Some)
+    }
+
+    #[cfg(not(feature = "embed-frontend"))]
+    fn embedded() -> io::Result<Option<Self>> {
+        Ok(None)
+    }
+
+    fn from_files(files: Vec<(String, Vec<u8>)>) -> io::Result<Self> {
+        let files = files
+            .into_iter()
+            .map(|(path, content)| Ok((path.clone(), Asset::new(&path, content)?)))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { files })
+    }
+
+    /// Returns the file for the path, or `index.html` for client side routes.
+    /// Missing assets aren't replaced, a html page in place of a script only hides the error.
+    fn resolve(&self, path: &str) -> Option<&Asset> {
+        if let Some(asset) = self.files.get(path) {
+            return Some(asset);
+        }
+        if path.starts_with("/assets/") {
+            return None;
+        }
+        self.files.get(INDEX)
+    }
+}
+
+/// Fallback handler of the app, serves the frontend.
+pub async fn serve(
+    State(frontend): State<Option<Arc<Frontend>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let path = uri.path();
+    let is_api = path == "/api" || path.starts_with("/api/");
+
+    let Some(frontend) = frontend.filter(|_| !is_api) else {
+        return Problem::not_found().into_response();
+    };
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    let Some(asset) = frontend.resolve(path) else {
+        return Problem::not_found().into_response();
+    };
+
+    let mut res = if headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|etag| etag == asset.etag)
+    {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let (encoding, body) = asset.body(preferred_encoding(&headers));
+        let len = body.len();
+        let mut res = if method == Method::HEAD {
+            Response::new(Body::empty())
+        } else {
+            Response::new(Body::from(body))
+        };
+        let res_headers = res.headers_mut();
+        res_headers.insert(header::CONTENT_TYPE, asset.content_type.clone());
+        res_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        if let Some(value) = encoding.header() {
+            res_headers.insert(header::CONTENT_ENCODING, value);
+        }
+        res
+    };
+
+    let res_headers = res.headers_mut();
+    res_headers.insert(header::ETAG, asset.etag.clone());
+    res_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(asset.cache_control),
+    );
+    if asset.gzip.is_some() || asset.zstd.is_some() {
+        res_headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    res
+}
+
+/// Same policy as the example reverse proxy config in the readme.
+fn cache_control(path: &str) -> &'static str {
+    if path.starts_with("/assets/") {
+        IMMUTABLE
+    } else if path == "/manifest.json" {
+        ONE_HOUR
+    } else if path == "/apple-touch-icon.png"
+        || path.starts_with("/favicon")
+        || path.starts_with("/icon")
+    {
+        ONE_WEEK
+    } else {
+        // index.html and files that aren't fingerprinted
+        NO_CACHE
+    }
+}
+
+fn is_compressible(mime: &mime_guess::Mime) -> bool {
+    mime.type_() == mime_guess::mime::TEXT
+        || matches!(
+            mime.subtype().as_str(),
+            "javascript" | "json" | "manifest+json" | "xml" | "svg" | "wasm"
+        )
+        || mime.suffix().is_some_and(|s| s == "json" || s == "xml")
+}
+
+/// Picks the best encoding the client accepts, zstd is preferred over gzip.
+fn preferred_encoding(headers: &HeaderMap) -> Encoding {
+    let accepted: Vec<&str> = headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|v| {
+            let mut parts = v.split(';').map(str::trim);
+            let name = parts.next()?;
+            let rejected = parts.any(|p| {
+                p.strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            (!rejected).then_some(name)
+        })
+        .collect();
+
+    if accepted.iter().any(|e| e.eq_ignore_ascii_case("zstd")) {
+        Encoding::Zstd
+    } else if accepted.iter().any(|e| e.eq_ignore_ascii_case("gzip")) {
+        Encoding::Gzip
+    } else {
+        Encoding::Identity
+    }
+}
+
+fn gzip(content: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+/// Compressed content is kept only if it's actually smaller.
+fn smaller(compressed: Vec<u8>, original: &[u8]) -> Option<Bytes> {
+    (compressed.len() < original.len()).then(|| compressed.into())
+}
+
+fn read_dir(dir: &Path, prefix: &str, files: &mut Vec<(String, Vec<u8>)>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{prefix}/{name}");
+        if entry.file_type()?.is_dir() {
+            read_dir(&entry.path(), &path, files)?;
+        } else {
+            files.push((path, std::fs::read(entry.path())?));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use rand::TryRngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::User,
+    db::Db,
+    handler::{
+        FieldError, Problem,
+        validate::{MAX_NAME_LEN, ValidJson, Validate, Validator},
+    },
+    store::{self, app_token::AppToken},
+    util::to_hex,
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct AppTokenCreateReq {
+    /// Name of the device or app, to tell tokens apart.
+    name: String,
+}
+
+impl Validate for AppTokenCreateReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .name("name", &mut self.name, MAX_NAME_LEN)
+            .finish()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedAppToken {
+    #[serde(flatten)]
+    app_token: AppToken,
+
+    /// Token is returned only once, the database stores only its hash.
+    /// It's used as the password of HTTP basic auth, together with the username.
+    token: String,
+}
+
+/// Creates a token for apps that can't log in interactively, ie. CalDAV clients.
+#[utoipa::path(
+    post,
+    path = "/auth/tokens",
+    operation_id = "create_app_token",
+    tag = "auth",
+    request_body = AppTokenCreateReq,
+    responses(
+        (status = 201, description = "Created token", body = CreatedAppToken),
+        (status = 422, description = "Invalid name", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn create(
+    State(db): State<Db>,
+    user: User,
+    ValidJson(req): ValidJson<AppTokenCreateReq>,
+) -> Result<(StatusCode, Json<CreatedAppToken>), Problem> {
+    let mut token_bytes = [0u8; 32];
+    rand::rngs::OsRng
+        .try_fill_bytes(&mut token_bytes)
+        .expect("random should not fail");
+    let token = BASE64_URL_SAFE_NO_PAD.encode(token_bytes);
+    let token_hash = to_hex(&Sha256::digest(token_bytes)[..]);
+
+    let app_token = store::app_token::create(&db, user.id, &req.name, &token_hash).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedAppToken { app_token, token }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/tokens",
+    operation_id = "list_app_tokens",
+    tag = "auth",
+    responses((status = 200, description = "App tokens of the user", body = Vec<AppToken>))
+)]
+pub async fn list(State(db): State<Db>, user: User) -> Result<Json<Vec<AppToken>>, Problem> {
+    let tokens = store::app_token::list(&db, user.id).await?;
+    Ok(Json(tokens))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/auth/tokens/{id}",
+    operation_id = "delete_app_token",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 404, description = "Token not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn delete(
+    State(db): State<Db>,
+    user: User,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, Problem> {
+    let deleted = store::app_token::delete(&db, user.id, id).await?;
+    if !deleted {
+        return Err(Problem::not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
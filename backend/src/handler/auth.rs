@@ -10,25 +10,35 @@ use base64::prelude::BASE64_URL_SAFE_NO_PAD;
 use rand::TryRngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use webauthn_rs::prelude::PublicKeyCredential;
 
 use crate::auth::User;
+use crate::handler::validate::{ValidJson, Validate};
+use crate::handler::{FieldError, webauthn};
 use crate::state::AppState;
 use crate::util::to_hex;
 use crate::{db::Db, handler::Problem, store};
 
-#[derive(Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum AuthType {
-    Web,
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "auth_type", rename_all = "snake_case")]
+pub enum Credentials {
+    Web {
+        username: String,
+        password: String,
+    },
+    Passkey {
+        challenge_id: String,
+        /// `PublicKeyCredential` returned by `navigator.credentials.get()`.
+        #[schema(value_type = Object)]
+        credential: Box<PublicKeyCredential>,
+    },
 }
 
-#[derive(Deserialize)]
-pub struct Credentials {
-    // auth_type is future compatibility, we don't use it yet
-    #[allow(unused)]
-    auth_type: AuthType,
-    username: String,
-    password: String,
+impl Validate for Credentials {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Ok(())
+    }
 }
 
 pub enum LoginError {
@@ -36,7 +46,7 @@ pub enum LoginError {
     Internal,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Session {
     session: String,
 
@@ -44,23 +54,68 @@ pub struct Session {
     expires_at: time::OffsetDateTime,
 }
 
-impl IntoResponse for LoginError {
-    fn into_response(self) -> axum::response::Response {
-        let problem = match self {
+impl From<LoginError> for Problem {
+    fn from(err: LoginError) -> Self {
+        match err {
             LoginError::InvalidCredentials => Problem::invalid_credentials(),
             LoginError::Internal => Problem::internal(),
-        };
-        problem.into_response()
+        }
+    }
+}
+
+impl IntoResponse for LoginError {
+    fn into_response(self) -> axum::response::Response {
+        Problem::from(self).into_response()
     }
 }
 
+/// Logs in with password or passkey and sets the session cookie.
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    operation_id = "login",
+    tag = "auth",
+    request_body = Credentials,
+    security(()),
+    responses(
+        (status = 200, description = "Logged in, session cookie is set", body = Session),
+        (status = 401, description = "Invalid credentials", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn login(
     State(state): State<AppState>,
     jar: CookieJar,
-    Json(credentials): Json<Credentials>,
+    ValidJson(credentials): ValidJson<Credentials>,
 ) -> Result<(CookieJar, Json<Session>), LoginError> {
+    let user_id = match credentials {
+        Credentials::Web { username, password } => {
+            verify_password(&state.db, &username, &password).await?
+        }
+        Credentials::Passkey {
+            challenge_id,
+            credential,
+        } => webauthn::verify_login(&state, &challenge_id, &credential).await?,
+    };
+
+    // Create new session
+    let session = create_session(&state.db, user_id).await.map_err(|err| {
+        tracing::error!(
+            error = err.to_string(),
+            "database error during session generation: {err}"
+        );
+        LoginError::Internal
+    })?;
+
+    // Set new cookie
+    let cookie = session_cookie(&state, &session);
+
+    Ok((jar.add(cookie), Json(session)))
+}
+
+/// Checks username and password and returns id of the user.
+async fn verify_password(db: &Db, username: &str, password: &str) -> Result<i64, LoginError> {
     // Get user from db
-    let user_res = store::user::get_user(&state.db, &credentials.username).await;
+    let user_res = store::user::get_user(db, username).await;
     let user = match user_res {
         Ok(Some(u)) => u,
         Ok(None) => return Err(LoginError::InvalidCredentials),
@@ -70,58 +125,76 @@ pub async fn login(
         }
     };
 
+    // Users created by an identity provider can't log in with password
+    let Some(password_hash) = &user.password_hash else {
+        return Err(LoginError::InvalidCredentials);
+    };
+
     // Check password is correct
-    let hash = PasswordHash::new(&user.password_hash).map_err(|err| {
+    let hash = PasswordHash::new(password_hash).map_err(|err| {
         tracing::error!(error = err.to_string(), "password hash error: {err}");
         LoginError::Internal
     })?;
     let argon2 = Argon2::default();
 
-    let pass_check = argon2.verify_password(credentials.password.as_bytes(), &hash);
+    let pass_check = argon2.verify_password(password.as_bytes(), &hash);
     if pass_check.is_err() {
         return Err(LoginError::InvalidCredentials);
     }
 
-    // Create new session
-    let session = create_session(&state.db, user.id).await.map_err(|err| {
-        tracing::error!(
-            error = err.to_string(),
-            "database error during session generation: {err}"
-        );
-        LoginError::Internal
-    })?;
-
-    // Set new cookie
-    let cookie = Cookie::build(("session", session.session.clone()))
-        .path("/api")
-        // Safari doesn't save secure cookies on localhost...
-        .secure(state.config.environment.is_prod())
-        .http_only(true)
-        .same_site(SameSite::Lax)
-        .expires(session.expires_at);
-
-    Ok((jar.add(cookie), Json(session)))
+    Ok(user.id)
 }
 
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    operation_id = "logout",
+    tag = "auth",
+    responses((status = 204, description = "Session deleted"))
+)]
 pub async fn logout(
     State(db): State<Db>,
     user: User,
     jar: CookieJar,
 ) -> Result<(StatusCode, CookieJar), Problem> {
-    let res = store::user::delete_session(&db, &user.session_hash).await;
-    if let Err(err) = res {
-        tracing::error!(error = err.to_string(), "database error: {err}");
-        return Err(Problem::internal());
+    // Users authenticated by a proxy have to log out at the proxy.
+    if let Some(session_hash) = &user.session_hash {
+        let res = store::user::delete_session(&db, session_hash).await;
+        if let Err(err) = res {
+            tracing::error!(error = err.to_string(), "database error: {err}");
+            return Err(Problem::internal());
+        }
     }
 
     Ok((StatusCode::NO_CONTENT, jar.remove("session")))
 }
 
+#[utoipa::path(
+    get,
+    path = "/auth/me",
+    operation_id = "me",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Logged in user", body = User),
+        (status = 401, description = "Not logged in", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn me(user: User) -> Json<User> {
     Json(user)
 }
 
-async fn create_session(db: &Db, user_id: i64) -> Result<Session, sqlx::Error> {
+pub fn session_cookie(state: &AppState, session: &Session) -> Cookie<'static> {
+    Cookie::build(("session", session.session.clone()))
+        .path("/api")
+        // Safari doesn't save secure cookies on localhost...
+        .secure(state.config.environment.is_prod())
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .expires(session.expires_at)
+        .build()
+}
+
+pub async fn create_session(db: &Db, user_id: i64) -> Result<Session, sqlx::Error> {
     let mut sess_bytes = [0u8; 64];
     rand::rngs::OsRng
         .try_fill_bytes(&mut sess_bytes)
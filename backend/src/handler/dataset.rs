@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use password_hash::PasswordHash;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{RequireRole, role::Admin},
+    db::Db,
+    handler::{
+        FieldError, Problem,
+        validate::{MAX_ITEM_NAME_LEN, MAX_NAME_LEN, ValidJson, Validate, Validator},
+    },
+    store::{
+        self,
+        dataset::{Dataset, FORMAT_VERSION, ImportMode, ImportSummary},
+    },
+};
+
+/// Largest accepted import document.
+pub const MAX_IMPORT_SIZE: usize = 32 * 1024 * 1024;
+
+impl Validate for Dataset {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        v.check(
+            "version",
+            self.version == FORMAT_VERSION,
+            "unsupported",
+            &format!("must be {FORMAT_VERSION}"),
+        );
+
+        for (i, user) in self.users.iter().enumerate() {
+            v.check(
+                &format!("users[{i}].username"),
+                !user.username.trim().is_empty(),
+                "empty",
+                "must not be empty",
+            );
+            if let Some(hash) = &user.password_hash {
+                v.check(
+                    &format!("users[{i}].password_hash"),
+                    PasswordHash::new(hash).is_ok(),
+                    "invalid",
+                    "must be a PHC string",
+                );
+            }
+        }
+
+        let store_ids: Vec<i64> = self.stores.iter().map(|s| s.id).collect();
+        v.unique_ids("stores", &store_ids);
+        for (i, shop) in self.stores.iter_mut().enumerate() {
+            v.name(&format!("stores[{i}].name"), &mut shop.name, MAX_NAME_LEN);
+        }
+
+        let section_ids: Vec<i64> = self.sections.iter().map(|s| s.id).collect();
+        v.unique_ids("sections", &section_ids);
+        for (i, section) in self.sections.iter_mut().enumerate() {
+            v.name(
+                &format!("sections[{i}].name"),
+                &mut section.name,
+                MAX_NAME_LEN,
+            );
+            v.check(
+                &format!("sections[{i}].store_id"),
+                store_ids.contains(&section.store_id),
+                "unknown_reference",
+                "must be id of a store in the document",
+            );
+        }
+
+        let section_stores: HashMap<i64, i64> =
+            self.sections.iter().map(|s| (s.id, s.store_id)).collect();
+        let item_ids: Vec<i64> = self.items.iter().map(|i| i.id).collect();
+        v.unique_ids("items", &item_ids);
+        for (i, item) in self.items.iter_mut().enumerate() {
+            v.name(
+                &format!("items[{i}].name"),
+                &mut item.name,
+                MAX_ITEM_NAME_LEN,
+            );
+            if let Some(store_id) = item.store_id {
+                v.check(
+                    &format!("items[{i}].store_id"),
+                    store_ids.contains(&store_id),
+                    "unknown_reference",
+                    "must be id of a store in the document",
+                );
+            }
+            if let Some(section_id) = item.section_id {
+                match section_stores.get(&section_id) {
+                    Some(store_id) => v.check(
+                        &format!("items[{i}].section_id"),
+                        item.store_id == Some(*store_id),
+                        "section_store_mismatch",
+                        "section must belong to the item's store",
+                    ),
+                    None => v.check(
+                        &format!("items[{i}].section_id"),
+                        false,
+                        "unknown_reference",
+                        "must be id of a section in the document",
+                    ),
+                };
+            }
+        }
+
+        v.finish()
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// Include password hashes of the users, so they can log in on the other instance.
+    #[serde(default)]
+    password_hashes: bool,
+}
+
+/// Exports users, stores, sections and items as a versioned document.
+#[utoipa::path(
+    get,
+    path = "/export",
+    operation_id = "export_dataset",
+    tag = "dataset",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "All data", body = Dataset),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn export(
+    State(db): State<Db>,
+    _: RequireRole<Admin>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, Problem> {
+    let dataset = store::dataset::export(&db, query.password_hashes).await?;
+
+    let date = dataset.exported_at.date();
+    let disposition = format!("attachment; filename=\"lshop-{date}.json\"");
+    let mut res = Json(dataset).into_response();
+    res.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).expect("disposition should be valid"),
+    );
+    Ok(res)
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ImportQuery {
+    #[serde(default)]
+    #[param(inline)]
+    mode: ImportMode,
+    /// Only return what would be imported.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Imports a document created by the export, in a single transaction.
+#[utoipa::path(
+    post,
+    path = "/import",
+    operation_id = "import_dataset",
+    tag = "dataset",
+    params(ImportQuery),
+    request_body = Dataset,
+    responses(
+        (status = 200, description = "What was imported, or would be on a dry run", body = ImportSummary),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Unsupported version or invalid references", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn import(
+    State(db): State<Db>,
+    _: RequireRole<Admin>,
+    Query(query): Query<ImportQuery>,
+    ValidJson(dataset): ValidJson<Dataset>,
+) -> Result<Json<ImportSummary>, Problem> {
+    let summary = store::dataset::import(&db, &dataset, query.mode, query.dry_run).await?;
+    Ok(Json(summary))
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::db;
+use crate::state::AppState;
+
+/// Checks shouldn't keep monitors waiting, ie. when the database is locked.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Error,
+    Pending,
+    NotConfigured,
+}
+
+#[derive(Serialize)]
+pub struct Checks {
+    database: CheckStatus,
+    migrations: CheckStatus,
+    llm: CheckStatus,
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    ready: bool,
+    checks: Checks,
+}
+
+/// Liveness, the process is able to handle requests.
+pub async fn health() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness, the app has everything it needs to serve users.
+/// Responds with 503 if any of the checks fails.
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let database =
+        match tokio::time::timeout(CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(&state.db)).await
+        {
+            Ok(Ok(_)) => CheckStatus::Ok,
+            Ok(Err(err)) => {
+                tracing::error!(error = err.to_string(), "readiness database error: {err}");
+                CheckStatus::Error
+            }
+            Err(_) => CheckStatus::Error,
+        };
+
+    let migrations =
+        match tokio::time::timeout(CHECK_TIMEOUT, db::pending_migrations(&state.db)).await {
+            Ok(Ok(0)) => CheckStatus::Ok,
+            Ok(Ok(_)) => CheckStatus::Pending,
+            Ok(Err(_)) | Err(_) => CheckStatus::Error,
+        };
+
+    let llm = if state.llm.is_configured() {
+        CheckStatus::Ok
+    } else {
+        CheckStatus::NotConfigured
+    };
+
+    let checks = Checks {
+        database,
+        migrations,
+        llm,
+    };
+    let ready = [checks.database, checks.migrations, checks.llm]
+        .iter()
+        .all(|status| *status == CheckStatus::Ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(Readiness { ready, checks }))
+}
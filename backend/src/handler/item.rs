@@ -6,92 +6,132 @@ use axum::{
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use crate::{
-    auth::User,
+    auth::{RequireRole, User, role::Member},
     db::Db,
-    handler::Problem,
+    handler::{
+        FieldError, Problem, ProblemCode,
+        validate::{MAX_ITEM_NAME_LEN, ValidJson, Validate, Validator},
+    },
+    push,
+    state::AppState,
     store::{self, item::Item, section::Section, shop::Store},
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ItemCreateReq {
     pub store_id: Option<i64>,
+    /// Store is inferred from the section, if it's not given.
     pub section_id: Option<i64>,
     pub name: String,
 }
 
+impl Validate for ItemCreateReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .name("name", &mut self.name, MAX_ITEM_NAME_LEN)
+            .finish()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/items",
+    operation_id = "create_item",
+    tag = "items",
+    request_body = ItemCreateReq,
+    responses(
+        (status = 201, description = "Created item, added to the end of its section", body = Item),
+        (status = 404, description = "Store or section not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Section doesn't belong to the store", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid name", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn create(
-    State(db): State<Db>,
-    _: User,
-    Json(mut req): Json<ItemCreateReq>,
+    State(state): State<AppState>,
+    user: User,
+    ValidJson(mut req): ValidJson<ItemCreateReq>,
 ) -> Result<(StatusCode, Json<Item>), Problem> {
+    req.store_id = resolve_target(&state.db, req.store_id, req.section_id).await?;
+
+    // Insert to db
+    let item = store::item::create(&state.db, req.store_id, req.section_id, &req.name).await?;
+    push::spawn_items_created(&state, &user, std::slice::from_ref(&item));
+
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+/// Checks that the store and section exist and match. Returns the store of the item,
+/// which is inferred from the section if it isn't given.
+pub async fn resolve_target(
+    db: &Db,
+    store_id: Option<i64>,
+    section_id: Option<i64>,
+) -> Result<Option<i64>, Problem> {
     // Check given store exists
-    if let Some(store_id) = req.store_id {
-        let store = store::shop::get(&db, store_id).await?;
+    if let Some(store_id) = store_id {
+        let store = store::shop::get(db, store_id).await?;
         if store.is_none() {
-            return Err(Problem::new(
-                StatusCode::NOT_FOUND,
-                "Store not found".to_string(),
-            ));
+            return Err(Problem::from_code(ProblemCode::StoreNotFound));
         }
     }
 
     // Check section is valid
-    if let Some(section_id) = req.section_id {
+    if let Some(section_id) = section_id {
         // Check section exists
-        let section = store::section::get(&db, section_id).await?;
+        let section = store::section::get(db, section_id).await?;
 
         let Some(section) = section else {
-            return Err(Problem::new(
-                StatusCode::NOT_FOUND,
-                "Section not found".to_string(),
-            ));
+            return Err(Problem::from_code(ProblemCode::SectionNotFound));
         };
 
         // If store id is also given, check that it matches the section
-        if let Some(store_id) = req.store_id
+        if let Some(store_id) = store_id
             && store_id != section.store_id
         {
-            return Err(Problem::new(
-                StatusCode::CONFLICT,
-                "Section doesn't belong to the given store".to_string(),
-            ));
+            return Err(Problem::from_code(ProblemCode::SectionStoreMismatch));
         }
 
-        // Set store_id to section's store id. This handles case when only section_id is given.
-        req.store_id = Some(section.store_id);
+        // Section's store is used also when only section_id is given.
+        return Ok(Some(section.store_id));
     }
 
-    // Insert to db
-    let item = store::item::create(&db, req.store_id, req.section_id, &req.name).await?;
-
-    Ok((StatusCode::CREATED, Json(item)))
+    Ok(store_id)
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ItemListSection {
     #[serde(flatten)]
-    section: Section,
+    pub section: Section,
 
-    items: Vec<Item>,
+    pub items: Vec<Item>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ItemListStore {
     #[serde(flatten)]
-    store: Store,
+    pub store: Store,
 
-    unassigned: Vec<Item>,
-    sections: Vec<ItemListSection>,
+    pub unassigned: Vec<Item>,
+    pub sections: Vec<ItemListSection>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ItemList {
-    unassigned: Vec<Item>,
-    stores: Vec<ItemListStore>,
+    pub unassigned: Vec<Item>,
+    pub stores: Vec<ItemListStore>,
 }
 
+/// Returns unchecked items grouped by stores and sections, in display order.
+#[utoipa::path(
+    get,
+    path = "/items",
+    operation_id = "list_items",
+    tag = "items",
+    responses((status = 200, description = "Grouped items", body = ItemList))
+)]
 pub async fn list(State(db): State<Db>, _: User) -> Result<Json<ItemList>, Problem> {
     // Get stuff from db
     let items = store::item::list(&db).await?;
@@ -99,6 +139,12 @@ pub async fn list(State(db): State<Db>, _: User) -> Result<Json<ItemList>, Probl
     let (stores, sections) =
         tokio::try_join!(store::shop::list(&db), store::section::list_all(&db))?;
 
+    Ok(Json(group_items(items, stores, sections)))
+}
+
+/// Groups items by stores and sections and sorts everything in the order
+/// in which it should be shown.
+pub fn group_items(items: Vec<Item>, stores: Vec<Store>, sections: Vec<Section>) -> ItemList {
     // Construct hash maps
     let mut store_map: HashMap<i64, ItemListStore> = stores
         .into_iter()
@@ -179,28 +225,59 @@ pub async fn list(State(db): State<Db>, _: User) -> Result<Json<ItemList>, Probl
         }
     }
 
-    Ok(Json(list))
+    list
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ItemRenameReq {
     name: String,
 }
 
+impl Validate for ItemRenameReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .name("name", &mut self.name, MAX_ITEM_NAME_LEN)
+            .finish()
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/items/{item_id}/rename",
+    operation_id = "rename_item",
+    tag = "items",
+    request_body = ItemRenameReq,
+    responses(
+        (status = 200, description = "Renamed item", body = Item),
+        (status = 403, description = "User is only a shopper", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Item not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid name", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn rename(
     State(db): State<Db>,
-    _: User,
+    _: RequireRole<Member>,
     Path(id): Path<i64>,
-    Json(req): Json<ItemRenameReq>,
+    ValidJson(req): ValidJson<ItemRenameReq>,
 ) -> Result<Json<Item>, Problem> {
     let item = store::item::rename(&db, id, &req.name).await?;
     let Some(item) = item else {
-        return Err(Problem::not_found());
+        return Err(Problem::from_code(ProblemCode::ItemNotFound));
     };
 
     Ok(Json(item))
 }
 
+#[utoipa::path(
+    put,
+    path = "/items/{item_id}/checked",
+    operation_id = "check_item",
+    tag = "items",
+    responses(
+        (status = 200, description = "Checked item", body = Item),
+        (status = 404, description = "Item not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn set_checked(
     State(db): State<Db>,
     _: User,
@@ -208,28 +285,51 @@ pub async fn set_checked(
 ) -> Result<Json<Item>, Problem> {
     let item = store::item::set_checked(&db, id).await?;
     let Some(item) = item else {
-        return Err(Problem::not_found());
+        return Err(Problem::from_code(ProblemCode::ItemNotFound));
     };
 
     Ok(Json(item))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ItemMoveReq {
     store_id: Option<i64>,
     section_id: Option<i64>,
+    /// Position in the target section, items after it are moved down.
     index: i64,
 }
 
+impl Validate for ItemMoveReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .range("index", self.index, 0, i64::MAX)
+            .finish()
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/items/{item_id}/move",
+    operation_id = "move_item",
+    tag = "items",
+    request_body = ItemMoveReq,
+    responses(
+        (status = 200, description = "Moved item", body = Item),
+        (status = 403, description = "User is only a shopper", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Item, store or section not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Section doesn't belong to the store", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid index", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn move_item(
     State(db): State<Db>,
-    _: User,
+    _: RequireRole<Member>,
     Path(id): Path<i64>,
-    Json(req): Json<ItemMoveReq>,
+    ValidJson(req): ValidJson<ItemMoveReq>,
 ) -> Result<Json<Item>, Problem> {
     let item = store::item::move_item(&db, id, req.store_id, req.section_id, req.index).await?;
     let Some(item) = item else {
-        return Err(Problem::not_found());
+        return Err(Problem::from_code(ProblemCode::ItemNotFound));
     };
 
     Ok(Json(item))
@@ -0,0 +1,216 @@
+use std::collections::HashSet;
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{FromRequest, Path, Query, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::{RequireRole, User, role::Admin},
+    db::Db,
+    handler::{
+        FieldError, Problem, ProblemCode,
+        validate::{MAX_NAME_LEN, Validate, Validator},
+    },
+    store::{
+        self,
+        layout::{FORMAT_VERSION, Layout},
+        section::Section,
+        shop::Store,
+    },
+    util,
+};
+
+const TOML: &str = "application/toml";
+
+impl Validate for Layout {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        v.check(
+            "version",
+            self.version == FORMAT_VERSION,
+            "unsupported",
+            &format!("must be {FORMAT_VERSION}"),
+        );
+        v.name("name", &mut self.name, MAX_NAME_LEN);
+
+        let mut seen = HashSet::with_capacity(self.sections.len());
+        for (i, name) in self.sections.iter_mut().enumerate() {
+            let field = format!("sections[{i}]");
+            v.name(&field, name, MAX_NAME_LEN);
+            v.check(
+                &field,
+                seen.insert(name.to_lowercase()),
+                "duplicate",
+                "must not be repeated",
+            );
+        }
+
+        v.finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutFormat {
+    #[default]
+    Json,
+    Toml,
+}
+
+/// Layout in the request body, as json or as toml with the `application/toml` content type.
+///
+/// Other content types are rejected like [`ValidJson`](super::validate::ValidJson) does.
+pub struct LayoutBody(pub Layout);
+
+impl<S> FromRequest<S> for LayoutBody
+where
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let essence = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase());
+        let format = match essence.as_deref() {
+            Some("application/json") => LayoutFormat::Json,
+            Some(TOML) => LayoutFormat::Toml,
+            _ => {
+                return Err(Problem::new(
+                    ProblemCode::UnsupportedMediaType,
+                    "Expected request with `Content-Type: application/json` or `application/toml`"
+                        .into(),
+                ));
+            }
+        };
+        let body = Bytes::from_request(req, state).await.map_err(|rej| {
+            let code = match rej.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => ProblemCode::PayloadTooLarge,
+                _ => ProblemCode::MalformedBody,
+            };
+            Problem::new(code, rej.body_text())
+        })?;
+
+        let mut layout: Layout = match format {
+            LayoutFormat::Toml => std::str::from_utf8(&body)
+                .map_err(|err| err.to_string())
+                .and_then(|body| toml::from_str(body).map_err(|err| err.to_string())),
+            LayoutFormat::Json => serde_json::from_slice(&body).map_err(|err| err.to_string()),
+        }
+        .map_err(|err| Problem::new(ProblemCode::MalformedBody, err))?;
+
+        layout.validate().map_err(Problem::validation)?;
+        Ok(LayoutBody(layout))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct LayoutQuery {
+    #[serde(default)]
+    #[param(inline)]
+    format: LayoutFormat,
+}
+
+/// Returns the store's sections as a layout file, to be imported by another instance.
+#[utoipa::path(
+    get,
+    path = "/stores/{store_id}/layout",
+    operation_id = "export_layout",
+    tag = "stores",
+    params(LayoutQuery),
+    responses(
+        (status = 200, description = "Layout of the store", content((Layout = "application/json"), (Layout = "application/toml"))),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn export(
+    State(db): State<Db>,
+    Path(store_id): Path<i64>,
+    _: User,
+    Query(query): Query<LayoutQuery>,
+) -> Result<Response, Problem> {
+    let Some(layout) = store::layout::get(&db, store_id).await? else {
+        return Err(Problem::from_code(ProblemCode::StoreNotFound));
+    };
+
+    let (mut res, ext) = match query.format {
+        LayoutFormat::Json => (Json(&layout).into_response(), "json"),
+        LayoutFormat::Toml => {
+            let body = toml::to_string(&layout).map_err(|err| {
+                tracing::error!(error = err.to_string(), "failed to serialize layout: {err}");
+                Problem::internal()
+            })?;
+            (
+                ([(header::CONTENT_TYPE, TOML)], body).into_response(),
+                "toml",
+            )
+        }
+    };
+
+    let disposition = format!(
+        "attachment; filename=\"{}.{ext}\"",
+        util::file_stem(&layout.name, "layout")
+    );
+    res.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).expect("disposition should be valid"),
+    );
+    Ok(res)
+}
+
+/// Creates a new store with the sections of the layout.
+#[utoipa::path(
+    post,
+    path = "/stores/import",
+    operation_id = "import_layout",
+    tag = "stores",
+    request_body(content((Layout = "application/json"), (Layout = "application/toml"))),
+    responses(
+        (status = 200, description = "Created store", body = Store),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid layout", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn import(
+    State(db): State<Db>,
+    _: RequireRole<Admin>,
+    LayoutBody(layout): LayoutBody,
+) -> Result<Json<Store>, Problem> {
+    let shop = store::layout::create(&db, &layout).await?;
+    Ok(Json(shop))
+}
+
+/// Sets sections of the store to the ones of the layout. Sections with a matching name,
+/// ignoring case, keep their items. Items of the other sections become unassigned.
+/// Name of the store isn't changed.
+#[utoipa::path(
+    put,
+    path = "/stores/{store_id}/layout",
+    operation_id = "apply_layout",
+    tag = "stores",
+    request_body(content((Layout = "application/json"), (Layout = "application/toml"))),
+    responses(
+        (status = 200, description = "Sections of the store in the new order", body = Vec<Section>),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid layout", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn apply(
+    State(db): State<Db>,
+    Path(store_id): Path<i64>,
+    _: RequireRole<Admin>,
+    LayoutBody(layout): LayoutBody,
+) -> Result<Json<Vec<Section>>, Problem> {
+    let sections = store::layout::apply(&db, store_id, &layout).await?;
+    Ok(Json(sections))
+}
@@ -1,60 +1,343 @@
-use axum::{Json, http::StatusCode, response::IntoResponse};
+use axum::{
+    http::{HeaderValue, StatusCode, header},
+    response::IntoResponse,
+};
 use serde::{Serialize, ser::SerializeStruct};
+use utoipa::ToSchema;
 
+use crate::request_id;
+use crate::store::Error as StoreError;
+
+pub mod app_token;
 pub mod auth;
+pub mod dataset;
+pub mod health;
 pub mod item;
+pub mod layout;
+pub mod notification;
+pub mod oidc;
 pub mod organize;
+pub mod print;
+pub mod push;
 pub mod section;
+pub mod share;
 pub mod store;
+pub mod text;
+pub mod validate;
+pub mod version;
+pub mod voice;
+pub mod webauthn;
+pub mod webhook;
 
-pub struct Problem {
-    pub status: StatusCode,
+/// Stable machine readable identifiers of errors returned by the api.
+/// Codes must not be renamed, clients depend on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemCode {
+    Internal,
+    DatabaseBusy,
+    UpstreamUnavailable,
+
+    BadRequest,
+    MalformedBody,
+    ValidationFailed,
+    UnsupportedMediaType,
+    PayloadTooLarge,
+
+    MissingCredentials,
+    InvalidCredentials,
+    InvalidChallenge,
+    NoAccount,
+    Forbidden,
+
+    NotFound,
+    StoreNotFound,
+    SectionNotFound,
+    ItemNotFound,
+
+    AlreadyExists,
+    ConstraintViolation,
+    SectionStoreMismatch,
+    InvalidSectionIds,
+}
+
+impl ProblemCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ProblemCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ProblemCode::DatabaseBusy => StatusCode::SERVICE_UNAVAILABLE,
+            ProblemCode::UpstreamUnavailable => StatusCode::BAD_GATEWAY,
+            ProblemCode::BadRequest => StatusCode::BAD_REQUEST,
+            ProblemCode::MalformedBody => StatusCode::UNPROCESSABLE_ENTITY,
+            ProblemCode::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            ProblemCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ProblemCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ProblemCode::MissingCredentials => StatusCode::UNAUTHORIZED,
+            ProblemCode::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ProblemCode::InvalidChallenge => StatusCode::BAD_REQUEST,
+            ProblemCode::NoAccount => StatusCode::FORBIDDEN,
+            ProblemCode::Forbidden => StatusCode::FORBIDDEN,
+            ProblemCode::NotFound => StatusCode::NOT_FOUND,
+            ProblemCode::StoreNotFound => StatusCode::NOT_FOUND,
+            ProblemCode::SectionNotFound => StatusCode::NOT_FOUND,
+            ProblemCode::ItemNotFound => StatusCode::NOT_FOUND,
+            ProblemCode::AlreadyExists => StatusCode::CONFLICT,
+            ProblemCode::ConstraintViolation => StatusCode::CONFLICT,
+            ProblemCode::SectionStoreMismatch => StatusCode::CONFLICT,
+            ProblemCode::InvalidSectionIds => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ProblemCode::Internal => "Internal server error",
+            ProblemCode::DatabaseBusy => "Database is busy",
+            ProblemCode::UpstreamUnavailable => "Upstream service is unavailable",
+            ProblemCode::BadRequest => "Bad request",
+            ProblemCode::MalformedBody => "Malformed request body",
+            ProblemCode::ValidationFailed => "Validation failed",
+            ProblemCode::UnsupportedMediaType => "Unsupported media type",
+            ProblemCode::PayloadTooLarge => "Request body is too large",
+            ProblemCode::MissingCredentials => "Missing credentials",
+            ProblemCode::InvalidCredentials => "Invalid credentials",
+            ProblemCode::InvalidChallenge => "Invalid or expired challenge",
+            ProblemCode::NoAccount => "No account exists for this identity",
+            ProblemCode::Forbidden => "Forbidden",
+            ProblemCode::NotFound => "Not found",
+            ProblemCode::StoreNotFound => "Store not found",
+            ProblemCode::SectionNotFound => "Section not found",
+            ProblemCode::ItemNotFound => "Item not found",
+            ProblemCode::AlreadyExists => "Resource already exists",
+            ProblemCode::ConstraintViolation => "Constraint violation",
+            ProblemCode::SectionStoreMismatch => "Section doesn't belong to the given store",
+            ProblemCode::InvalidSectionIds => "Invalid section ids",
+        }
+    }
+
+    fn type_uri(&self) -> String {
+        let code = serde_json::to_value(self).expect("problem code should serialize");
+        let code = code.as_str().expect("problem code should be a string");
+        format!("urn:lshop:problem:{code}")
+    }
+}
+
+/// Kind of a database error that caused a problem, recorded by the metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DbErrorKind {
+    PoolTimedOut,
+    Busy,
+    Constraint,
+    Other,
+}
+
+/// Validation error of a single request field.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: &'static str,
     pub message: String,
 }
 
-// Manual implementation of Serialize, because StatusCode doesn't implement it.
+impl FieldError {
+    pub fn new(field: &str, code: &'static str, message: String) -> Self {
+        Self {
+            field: field.to_string(),
+            code,
+            message,
+        }
+    }
+}
+
+/// Error response in the `application/problem+json` format (RFC 9457).
+#[derive(Debug)]
+pub struct Problem {
+    pub code: ProblemCode,
+    pub detail: String,
+    pub errors: Vec<FieldError>,
+    /// Not part of the response.
+    pub db_error: Option<DbErrorKind>,
+}
+
+// Manual implementation of Serialize, because most of the fields are derived from the code.
 impl Serialize for Problem {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("Problem", 2)?;
-        state.serialize_field("status", &self.status.as_u16())?;
-        state.serialize_field("message", &self.message)?;
+        let mut state = serializer.serialize_struct("Problem", 7)?;
+        state.serialize_field("type", &self.code.type_uri())?;
+        state.serialize_field("title", self.code.title())?;
+        state.serialize_field("status", &self.code.status().as_u16())?;
+        state.serialize_field("detail", &self.detail)?;
+        state.serialize_field("code", &self.code)?;
+        if let Some(request_id) = request_id::current() {
+            state.serialize_field("request_id", &request_id)?;
+        }
+        if !self.errors.is_empty() {
+            state.serialize_field("errors", &self.errors)?;
+        }
         state.end()
     }
 }
 
+// Schema of the serialized `Problem`, used only in the api documentation.
+/// Error response in the `application/problem+json` format (RFC 9457).
+#[derive(ToSchema)]
+#[schema(as = Problem)]
+#[allow(dead_code)]
+struct ProblemSchema {
+    /// `urn:lshop:problem:<code>`
+    #[schema(rename = "type")]
+    type_uri: String,
+    title: String,
+    status: u16,
+    detail: String,
+    code: ProblemCode,
+    request_id: Option<String>,
+    /// Invalid fields, present only for `validation_failed`.
+    errors: Option<Vec<FieldError>>,
+}
+
+impl utoipa::PartialSchema for Problem {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        ProblemSchema::schema()
+    }
+}
+
+impl ToSchema for Problem {
+    fn name() -> std::borrow::Cow<'static, str> {
+        ProblemSchema::name()
+    }
+
+    fn schemas(
+        schemas: &mut Vec<(
+            String,
+            utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+        )>,
+    ) {
+        ProblemSchema::schemas(schemas)
+    }
+}
+
 impl IntoResponse for Problem {
     fn into_response(self) -> axum::response::Response {
-        (self.status, Json(self)).into_response()
+        let body = serde_json::to_vec(&self).expect("problem should serialize");
+        let mut res = (
+            self.code.status(),
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            )],
+            body,
+        )
+            .into_response();
+
+        // For the metrics
+        res.extensions_mut().insert(self.code);
+        if let Some(kind) = self.db_error {
+            res.extensions_mut().insert(kind);
+        }
+        res
     }
 }
 
 impl Problem {
-    pub fn new(status: StatusCode, message: String) -> Self {
-        Self { status, message }
+    pub fn new(code: ProblemCode, detail: String) -> Self {
+        Self {
+            code,
+            detail,
+            errors: vec![],
+            db_error: None,
+        }
+    }
+
+    fn db(code: ProblemCode, kind: DbErrorKind) -> Self {
+        Self {
+            db_error: Some(kind),
+            ..Problem::from_code(code)
+        }
+    }
+
+    /// Problem with the default detail of the code.
+    pub fn from_code(code: ProblemCode) -> Self {
+        Problem::new(code, code.title().to_string())
+    }
+
+    pub fn validation(errors: Vec<FieldError>) -> Self {
+        Self {
+            code: ProblemCode::ValidationFailed,
+            detail: "Request contains invalid fields".to_string(),
+            errors,
+            db_error: None,
+        }
     }
 
     pub fn internal() -> Self {
-        Problem::new(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Internal Server Error".to_string(),
-        )
+        Problem::from_code(ProblemCode::Internal)
     }
 
     pub fn invalid_credentials() -> Self {
-        Problem::new(StatusCode::UNAUTHORIZED, "Invalid credentials".to_string())
+        Problem::from_code(ProblemCode::InvalidCredentials)
+    }
+
+    pub fn forbidden() -> Self {
+        Problem::from_code(ProblemCode::Forbidden)
     }
 
     pub fn not_found() -> Self {
-        Problem::new(StatusCode::NOT_FOUND, "Not found".to_string())
+        Problem::from_code(ProblemCode::NotFound)
     }
 }
 
 impl From<sqlx::Error> for Problem {
     fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::RowNotFound => return Problem::not_found(),
+            sqlx::Error::PoolTimedOut => {
+                tracing::warn!(error = err.to_string(), "database error: {err}");
+                return Problem::db(ProblemCode::DatabaseBusy, DbErrorKind::PoolTimedOut);
+            }
+            sqlx::Error::Database(_) if crate::store::is_section_store_mismatch(&err) => {
+                return Problem::from_code(ProblemCode::SectionStoreMismatch);
+            }
+            sqlx::Error::Database(db_err) => {
+                // Extended sqlite result codes
+                let code = match db_err.code().as_deref() {
+                    Some("2067" | "1555") => {
+                        Some((ProblemCode::AlreadyExists, DbErrorKind::Constraint))
+                    }
+                    Some("787" | "275" | "1299" | "1811") => {
+                        Some((ProblemCode::ConstraintViolation, DbErrorKind::Constraint))
+                    }
+                    Some("5" | "6" | "261" | "517") => {
+                        Some((ProblemCode::DatabaseBusy, DbErrorKind::Busy))
+                    }
+                    _ => None,
+                };
+
+                if let Some((code, kind)) = code {
+                    tracing::warn!(error = err.to_string(), "database error: {err}");
+                    return Problem::db(code, kind);
+                }
+            }
+            _ => (),
+        }
+
         tracing::error!(error = err.to_string(), "database error: {err}");
-        Problem::internal()
+        Problem::db(ProblemCode::Internal, DbErrorKind::Other)
+    }
+}
+
+impl From<StoreError> for Problem {
+    fn from(err: StoreError) -> Self {
+        let code = match err {
+            StoreError::Db(err) => return err.into(),
+            StoreError::StoreNotFound => ProblemCode::StoreNotFound,
+            StoreError::SectionNotFound => ProblemCode::SectionNotFound,
+            StoreError::SectionStoreMismatch => ProblemCode::SectionStoreMismatch,
+            StoreError::InvalidSectionIds => ProblemCode::InvalidSectionIds,
+        };
+        Problem::from_code(code)
     }
 }
@@ -0,0 +1,273 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use jiff::tz::TimeZone;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::User,
+    handler::{
+        FieldError, Problem, ProblemCode,
+        validate::{ValidJson, Validate, Validator},
+    },
+    notify::{self, NotifyError, cron::Cron},
+    state::AppState,
+    store::{
+        self,
+        notification::{Kind, Schedule, ScheduleData, TransportKind},
+    },
+};
+
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_TOPIC_LEN: usize = 64;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ScheduleReq {
+    kind: Kind,
+    /// Only items of the store, all items if it's missing.
+    store_id: Option<i64>,
+    transport: TransportKind,
+    /// Email address or ntfy topic.
+    target: String,
+    /// Five field cron expression, ie. `0 7 * * *` for every day at 7:00.
+    cron: String,
+    /// IANA time zone of the cron expression, ie. `Europe/Ljubljana`. Defaults to UTC.
+    #[serde(default = "default_timezone")]
+    timezone: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Validate for ScheduleReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        self.target = self.target.trim().to_string();
+        self.cron = self.cron.trim().to_string();
+        self.timezone = self.timezone.trim().to_string();
+
+        let mut v = Validator::new();
+        match self.transport {
+            TransportKind::Email => v.check(
+                "target",
+                self.target.len() <= MAX_EMAIL_LEN
+                    && self.target.parse::<lettre::Address>().is_ok(),
+                "invalid_email",
+                "must be an email address",
+            ),
+            TransportKind::Ntfy => v.check(
+                "target",
+                (1..=MAX_TOPIC_LEN).contains(&self.target.len())
+                    && self
+                        .target
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                "invalid_topic",
+                &format!("must be 1 to {MAX_TOPIC_LEN} letters, digits, dashes or underscores"),
+            ),
+        };
+
+        let tz = match TimeZone::get(&self.timezone) {
+            Ok(tz) => {
+                // Names are found ignoring case.
+                if let Some(name) = tz.iana_name() {
+                    self.timezone = name.to_string();
+                }
+                tz
+            }
+            Err(_) => {
+                v.check(
+                    "timezone",
+                    false,
+                    "invalid_timezone",
+                    "must be an IANA time zone, ie. Europe/Ljubljana",
+                );
+                TimeZone::UTC
+            }
+        };
+
+        match self.cron.parse::<Cron>() {
+            Ok(cron) => v.check(
+                "cron",
+                cron.next_after(time::OffsetDateTime::now_utc(), &tz)
+                    .is_some(),
+                "invalid_cron",
+                "never runs",
+            ),
+            Err(err) => v.check("cron", false, "invalid_cron", &err.to_string()),
+        };
+        v.finish()
+    }
+}
+
+impl ScheduleReq {
+    fn data(&self) -> ScheduleData<'_> {
+        ScheduleData {
+            kind: self.kind,
+            store_id: self.store_id,
+            transport: self.transport,
+            target: &self.target,
+            cron: &self.cron,
+            timezone: &self.timezone,
+            enabled: self.enabled,
+        }
+    }
+
+    /// Checks the references, which the validation can't. Returns the first run.
+    async fn check(&self, state: &AppState) -> Result<time::OffsetDateTime, Problem> {
+        if state.notifier.transport(self.transport).is_none() {
+            return Err(Problem::validation(vec![FieldError::new(
+                "transport",
+                "not_configured",
+                "isn't configured on the server".to_string(),
+            )]));
+        }
+        if let Some(store_id) = self.store_id
+            && store::shop::get(&state.db, store_id).await?.is_none()
+        {
+            return Err(Problem::from_code(ProblemCode::StoreNotFound));
+        }
+
+        Ok(
+            notify::next_run(&self.cron, &self.timezone, time::OffsetDateTime::now_utc())
+                .expect("cron and time zone are validated"),
+        )
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SendResult {
+    /// False if there were no items to send.
+    sent: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/notifications/schedules",
+    operation_id = "list_notification_schedules",
+    tag = "notifications",
+    responses((status = 200, description = "Schedules of the user", body = Vec<Schedule>))
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Json<Vec<Schedule>>, Problem> {
+    let schedules = store::notification::list(&state.db, user.id).await?;
+    Ok(Json(schedules))
+}
+
+#[utoipa::path(
+    post,
+    path = "/notifications/schedules",
+    operation_id = "create_notification_schedule",
+    tag = "notifications",
+    request_body = ScheduleReq,
+    responses(
+        (status = 201, description = "Created schedule", body = Schedule),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid target or cron expression, or the transport isn't configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    user: User,
+    ValidJson(req): ValidJson<ScheduleReq>,
+) -> Result<(StatusCode, Json<Schedule>), Problem> {
+    let next_run_at = req.check(&state).await?;
+    let schedule =
+        store::notification::create(&state.db, user.id, &req.data(), next_run_at).await?;
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/notifications/schedules/{id}",
+    operation_id = "update_notification_schedule",
+    tag = "notifications",
+    request_body = ScheduleReq,
+    responses(
+        (status = 200, description = "Updated schedule", body = Schedule),
+        (status = 404, description = "Schedule or store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid target or cron expression, or the transport isn't configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    user: User,
+    ValidJson(req): ValidJson<ScheduleReq>,
+) -> Result<Json<Schedule>, Problem> {
+    let next_run_at = req.check(&state).await?;
+    let schedule = store::notification::update(&state.db, user.id, id, &req.data(), next_run_at)
+        .await?
+        .ok_or_else(Problem::not_found)?;
+    Ok(Json(schedule))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/notifications/schedules/{id}",
+    operation_id = "delete_notification_schedule",
+    tag = "notifications",
+    responses(
+        (status = 204, description = "Schedule deleted"),
+        (status = 404, description = "Schedule not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    user: User,
+) -> Result<StatusCode, Problem> {
+    if !store::notification::delete(&state.db, user.id, id).await? {
+        return Err(Problem::not_found());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Sends the message of the schedule now, ie. to try the target. The next run stays
+/// as scheduled.
+#[utoipa::path(
+    post,
+    path = "/notifications/schedules/{id}/send",
+    operation_id = "send_notification_schedule",
+    tag = "notifications",
+    responses(
+        (status = 200, description = "Message was sent, or there was nothing to send", body = SendResult),
+        (status = 404, description = "Schedule not found", body = Problem, content_type = "application/problem+json"),
+        (status = 502, description = "Transport failed", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn send(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    user: User,
+) -> Result<Json<SendResult>, Problem> {
+    let schedule = store::notification::get(&state.db, user.id, id)
+        .await?
+        .ok_or_else(Problem::not_found)?;
+
+    let now = time::OffsetDateTime::now_utc();
+    let sent = match notify::send(&state.db, &state.notifier, &schedule, now).await {
+        Ok(sent) => sent,
+        Err(NotifyError::Db(err)) => return Err(err.into()),
+        Err(err) => {
+            notify::finish(&state.db, schedule.id, None, now, Some(&err)).await?;
+            return Err(Problem::new(
+                ProblemCode::UpstreamUnavailable,
+                err.to_string(),
+            ));
+        }
+    };
+    notify::finish(&state.db, schedule.id, None, now, None).await?;
+    Ok(Json(SendResult { sent }))
+}
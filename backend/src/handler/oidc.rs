@@ -0,0 +1,230 @@
+use axum::extract::{Query, State};
+use axum::response::Redirect;
+use axum_extra::extract::CookieJar;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use utoipa::IntoParams;
+
+use crate::handler::auth::{create_session, session_cookie};
+use crate::handler::{Problem, ProblemCode};
+use crate::oidc::{Identity, OidcClient};
+use crate::state::AppState;
+use crate::store;
+use crate::store::user::User;
+use crate::util::to_hex;
+
+/// Ties the login request to the browser that started it, otherwise a callback url of
+/// someone else's login would log the victim in as them.
+const STATE_COOKIE: &str = "oidc_state";
+/// How long the user has to log in at the identity provider.
+const AUTH_REQUEST_LIFETIME: time::Duration = time::Duration::minutes(10);
+
+#[derive(Deserialize, IntoParams)]
+pub struct LoginQuery {
+    /// Local path to return to after login.
+    redirect: Option<String>,
+}
+
+/// Starts login with the identity provider.
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/login",
+    operation_id = "oidc_login",
+    tag = "auth",
+    params(LoginQuery),
+    security(()),
+    responses(
+        (status = 303, description = "Redirect to the identity provider"),
+        (status = 404, description = "Single sign-on isn't configured", body = Problem, content_type = "application/problem+json"),
+        (status = 502, description = "Identity provider isn't available", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<LoginQuery>,
+) -> Result<(CookieJar, Redirect), Problem> {
+    let client = get_client(&state)?;
+
+    // Only allow local redirects, otherwise the login can be used as an open redirect.
+    let redirect_to = match query.redirect {
+        Some(r) if r.starts_with('/') && !r.starts_with("//") && !r.starts_with("/\\") => r,
+        _ => "/".to_string(),
+    };
+
+    let auth_req = client.auth_request().await.map_err(|err| {
+        tracing::error!(error = err.to_string(), "oidc error: {err}");
+        Problem::new(
+            ProblemCode::UpstreamUnavailable,
+            "Identity provider is not available".to_string(),
+        )
+    })?;
+
+    let state_hash = to_hex(&Sha256::digest(&auth_req.state)[..]);
+    let expires_at = time::OffsetDateTime::now_utc() + AUTH_REQUEST_LIFETIME;
+    store::oidc::create_auth_request(
+        &state.db,
+        &state_hash,
+        &auth_req.code_verifier,
+        &auth_req.nonce,
+        &redirect_to,
+        expires_at,
+    )
+    .await?;
+
+    let cookie = Cookie::build((STATE_COOKIE, state_hash))
+        .path("/api")
+        .secure(state.config.environment.is_prod())
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(AUTH_REQUEST_LIFETIME)
+        .build();
+    Ok((jar.add(cookie), Redirect::to(auth_req.url.as_str())))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: String,
+
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Completes login with the identity provider and sets the session cookie.
+#[utoipa::path(
+    get,
+    path = "/auth/oidc/callback",
+    operation_id = "oidc_callback",
+    tag = "auth",
+    params(CallbackQuery),
+    security(()),
+    responses(
+        (status = 303, description = "Logged in, redirect back to the app"),
+        (status = 400, description = "Invalid or expired login request, or it was started in another browser", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Identity provider rejected the login", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "No account exists for the identity", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn callback(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<CallbackQuery>,
+) -> Result<(CookieJar, Redirect), Problem> {
+    let client = get_client(&state)?;
+
+    let invalid = || {
+        Problem::new(
+            ProblemCode::InvalidChallenge,
+            "Invalid or expired login request".to_string(),
+        )
+    };
+    let state_hash = to_hex(&Sha256::digest(&query.state)[..]);
+    if jar.get(STATE_COOKIE).map(|c| c.value()) != Some(state_hash.as_str()) {
+        return Err(invalid());
+    }
+    let Some(auth_req) = store::oidc::take_auth_request(&state.db, &state_hash).await? else {
+        return Err(invalid());
+    };
+    let jar = jar.remove(Cookie::build(STATE_COOKIE).path("/api"));
+
+    if let Some(error) = query.error {
+        tracing::info!(
+            error,
+            description = query.error_description,
+            "identity provider returned an error"
+        );
+        return Err(Problem::invalid_credentials());
+    }
+    let Some(code) = query.code else {
+        return Err(Problem::new(
+            ProblemCode::BadRequest,
+            "Missing authorization code".to_string(),
+        ));
+    };
+
+    let identity = client
+        .exchange_code(&code, &auth_req.code_verifier, &auth_req.nonce)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = err.to_string(), "oidc error: {err}");
+            Problem::invalid_credentials()
+        })?;
+
+    let user = get_or_create_user(&state, client, &identity).await?;
+
+    let session = create_session(&state.db, user.id).await?;
+    let cookie = session_cookie(&state, &session);
+
+    Ok((jar.add(cookie), Redirect::to(&auth_req.redirect_to)))
+}
+
+async fn get_or_create_user(
+    state: &AppState,
+    client: &OidcClient,
+    identity: &Identity,
+) -> Result<User, Problem> {
+    let user =
+        store::oidc::get_identity_user(&state.db, &identity.issuer, &identity.subject).await?;
+    if let Some(user) = user {
+        return Ok(user);
+    }
+
+    let Some(username) = &identity.username else {
+        tracing::info!(
+            subject = identity.subject,
+            "identity doesn't contain username claim '{}'",
+            client.config().username_claim
+        );
+        return Err(no_account());
+    };
+
+    if client.config().link_existing_users {
+        let user = store::oidc::link_existing_user(
+            &state.db,
+            username,
+            &identity.issuer,
+            &identity.subject,
+        )
+        .await?;
+        if let Some(user) = user {
+            tracing::info!(username, "linked existing user with identity");
+            return Ok(user);
+        }
+    }
+
+    if !client.can_provision(identity) {
+        return Err(no_account());
+    }
+
+    let res = store::oidc::provision_user(
+        &state.db,
+        username,
+        &identity.issuer,
+        &identity.subject,
+        client.config().default_role,
+    )
+    .await;
+    match res {
+        Ok(user) => {
+            tracing::info!(username, "provisioned user from identity provider");
+            Ok(user)
+        }
+        Err(sqlx::Error::Database(db)) if db.code().as_ref().is_some_and(|c| c == "2067") => {
+            Err(Problem::new(
+                ProblemCode::AlreadyExists,
+                format!("User with username '{username}' already exists"),
+            ))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn get_client(state: &AppState) -> Result<&OidcClient, Problem> {
+    state.oidc.as_deref().ok_or_else(Problem::not_found)
+}
+
+fn no_account() -> Problem {
+    Problem::from_code(ProblemCode::NoAccount)
+}
@@ -1,94 +1,80 @@
 use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
-use async_openai::{
-    error::OpenAIError,
-    types::responses::{CreateResponseArgs, ReasoningEffort, ResponseFormatJsonSchema},
-};
 use axum::{
     extract::{Path, State},
     http::StatusCode,
 };
-use serde::{Deserialize, Serialize};
-use thiserror::Error;
 
 use crate::{
-    auth::User,
-    handler::Problem,
-    state::{AppState, OpenAiClient},
+    auth::{RequireRole, role::Member},
+    handler::{Problem, ProblemCode},
+    metrics::{Outcome, Rejection},
+    state::AppState,
     store,
 };
 
-#[derive(Serialize)]
-struct PromptItem {
-    id: i64,
-    name: String,
-}
-
-impl From<store::item::Item> for PromptItem {
-    fn from(value: store::item::Item) -> Self {
-        Self {
-            id: value.id,
-            name: value.name,
-        }
-    }
-}
-
-impl From<store::section::Section> for PromptItem {
-    fn from(value: store::section::Section) -> Self {
-        Self {
-            id: value.id,
-            name: value.name,
-        }
-    }
-}
-
-#[derive(Serialize)]
-struct Prompt {
-    items: Vec<PromptItem>,
-    sections: Vec<PromptItem>,
-}
-
-#[derive(Debug, Deserialize)]
-struct Categorization {
-    section_id: i64,
-    item_id: i64,
-}
-
-#[derive(Debug, Deserialize)]
-struct CategorizationResponse {
-    categorized: Vec<Categorization>,
-}
-
+/// Assigns the store's unassigned items to its sections with a language model.
+#[utoipa::path(
+    post,
+    path = "/stores/{store_id}/organize",
+    operation_id = "organize_store",
+    tag = "items",
+    responses(
+        (status = 204, description = "Items organized"),
+        (status = 403, description = "User is only a shopper", body = Problem, content_type = "application/problem+json"),
+        (status = 502, description = "Language model isn't available", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn organize(
     State(state): State<AppState>,
     Path(store_id): Path<i64>,
-    _: User,
+    _: RequireRole<Member>,
 ) -> Result<StatusCode, Problem> {
+    organize_store(&state, store_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Organizes the store's unassigned items, see [`organize`].
+pub async fn organize_store(state: &AppState, store_id: i64) -> Result<(), Problem> {
     let (items, sections) = tokio::try_join!(
         store::item::unassigned_for_store(&state.db, store_id),
         store::section::list(&state.db, store_id),
     )?;
 
     if items.is_empty() || sections.is_empty() {
-        return Ok(StatusCode::NO_CONTENT);
+        return Ok(());
     }
 
     let valid_item_ids: HashSet<_> = items.iter().map(|sec| sec.id).collect();
     let valid_section_ids: HashSet<_> = sections.iter().map(|sec| sec.id).collect();
 
-    let categorized = openai_organize(&state.openai, items, sections)
-        .await
-        .map_err(|err| {
-            tracing::error!(
-                error = err.to_string(),
-                "error during ai categorization: {err}"
-            );
-            Problem::internal()
-        })?;
+    let start = Instant::now();
+    let categorized = state.llm.categorize(items, sections).await;
+    let outcome = match categorized {
+        Ok(_) => Outcome::Success,
+        Err(_) => Outcome::Failure,
+    };
+    state.metrics.organize_call(outcome, start.elapsed());
+
+    let categorized = categorized.map_err(|err| {
+        tracing::error!(
+            error = err.to_string(),
+            "error during ai categorization: {err}"
+        );
+        Problem::new(
+            ProblemCode::UpstreamUnavailable,
+            "Items couldn't be organized by the language model".to_string(),
+        )
+    })?;
+    if let Some(usage) = categorized.usage {
+        state.metrics.llm_usage(usage);
+    }
 
     // section id -> [item ids]
     let mut update_map: HashMap<i64, Vec<i64>> = HashMap::new();
-    for cat in categorized.categorized {
+    let mut assigned_item_ids = HashSet::new();
+    for cat in categorized.categorizations {
         if !valid_item_ids.contains(&cat.item_id) {
             tracing::info!(
                 item_id = cat.item_id,
@@ -96,6 +82,7 @@ pub async fn organize(
                 "llm organizer returned invalid item id: {}",
                 cat.item_id
             );
+            state.metrics.organize_rejected(Rejection::UnknownItem);
             continue;
         }
         if !valid_section_ids.contains(&cat.section_id) {
@@ -105,6 +92,18 @@ pub async fn organize(
                 "llm organizer returned invalid section id: {}",
                 cat.section_id
             );
+            state.metrics.organize_rejected(Rejection::UnknownSection);
+            continue;
+        }
+        // Each item can be in only one section, the first assignment wins.
+        if !assigned_item_ids.insert(cat.item_id) {
+            tracing::info!(
+                item_id = cat.item_id,
+                section_id = cat.section_id,
+                "llm organizer returned duplicate item id: {}",
+                cat.item_id
+            );
+            state.metrics.organize_rejected(Rejection::DuplicateItem);
             continue;
         }
 
@@ -116,80 +115,5 @@ pub async fn organize(
 
     store::item::organize(&state.db, store_id, &update_map).await?;
 
-    Ok(StatusCode::NO_CONTENT)
-}
-
-#[derive(Debug, Error)]
-enum PromptError {
-    #[error("openai error")]
-    OpenAi(#[from] OpenAIError),
-
-    #[error("invalid response recieved")]
-    InvalidResponse(#[from] serde_json::Error),
-}
-
-async fn openai_organize(
-    openai: &OpenAiClient,
-    items: Vec<store::item::Item>,
-    sections: Vec<store::section::Section>,
-) -> Result<CategorizationResponse, PromptError> {
-    let schema = serde_json::json!({
-        "type": "object",
-        "required": ["categorized"],
-        "properties": {
-            "categorized": {
-                "type": "array",
-                "items": {
-                    "type": "object",
-                    "required": [
-                        "section_id",
-                        "item_id"
-                    ],
-                    "properties": {
-                        "section_id": {
-                            "type": "integer"
-                        },
-                        "item_id": {
-                            "type": "integer"
-                        }
-                    },
-                    "additionalProperties": false,
-                }
-            }
-        },
-        "additionalProperties": false,
-    });
-
-    let prompt_data = Prompt {
-        items: items.into_iter().map(|it| it.into()).collect(),
-        sections: sections.into_iter().map(|sec| sec.into()).collect(),
-    };
-    let prompt_data = serde_json::to_string(&prompt_data).expect("prompt should be valid json");
-
-    let prompt = format!(
-        "You are a helpful store manager assistant. You are given a list of store sections and items. Section and items names are in slovene or english language. Your task is to organize the items into sections and return the mapping.\n\nEach item can be in at most one section. **IMPORTANT:** If an item doesn't belong in any of the sections, ignore it by not including it in the output.\n\n{prompt_data}"
-    );
-
-    let request = CreateResponseArgs::default()
-        .model("gpt-5-mini")
-        .reasoning(ReasoningEffort::Low)
-        .text(ResponseFormatJsonSchema {
-            description: Some("Mapping of items to sections".to_string()),
-            name: "categorization".to_string(),
-            schema: Some(schema),
-            strict: Some(true),
-        })
-        .input(prompt)
-        .build()?;
-
-    let response = openai.responses().create(request).await?;
-
-    let Some(response_text) = response.output_text() else {
-        return Ok(CategorizationResponse {
-            categorized: vec![],
-        });
-    };
-
-    let categorized = serde_json::from_str(&response_text)?;
-    Ok(categorized)
+    Ok(())
 }
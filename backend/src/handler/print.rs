@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderValue, header},
+    response::{Html, IntoResponse, Response},
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::User,
+    db::Db,
+    handler::{
+        FieldError, Problem, ProblemCode,
+        item::group_items,
+        validate::{Validate, Validator},
+    },
+    print::{self, PrintOptions},
+    store, util,
+};
+
+pub const MIN_FONT_SIZE: u32 = 6;
+pub const MAX_FONT_SIZE: u32 = 24;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrintFormat {
+    #[default]
+    Html,
+    Pdf,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct PrintQuery {
+    #[serde(default)]
+    #[param(inline)]
+    format: PrintFormat,
+    /// Font size of the items in points, headings are slightly larger.
+    #[serde(default = "default_font_size")]
+    #[param(minimum = 6, maximum = 24, default = 11)]
+    font_size: u32,
+    /// Number of columns on a page.
+    #[serde(default = "default_columns")]
+    #[param(minimum = 1, maximum = 2, default = 1)]
+    columns: u32,
+}
+
+fn default_font_size() -> u32 {
+    11
+}
+
+fn default_columns() -> u32 {
+    1
+}
+
+impl Validate for PrintQuery {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .range(
+                "font_size",
+                self.font_size.into(),
+                MIN_FONT_SIZE.into(),
+                MAX_FONT_SIZE.into(),
+            )
+            .range("columns", self.columns.into(), 1, 2)
+            .finish()
+    }
+}
+
+/// Renders the store's unchecked items by section, in the order of the store, as a
+/// compact page for printing.
+#[utoipa::path(
+    get,
+    path = "/stores/{store_id}/print",
+    operation_id = "print_store",
+    tag = "stores",
+    params(PrintQuery),
+    responses(
+        (status = 200, description = "Printable list", content(
+            (String = "text/html"),
+            (Vec<u8> = "application/pdf"),
+        )),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid options", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn print(
+    State(db): State<Db>,
+    Path(store_id): Path<i64>,
+    _: User,
+    Query(mut query): Query<PrintQuery>,
+) -> Result<Response, Problem> {
+    query.validate().map_err(Problem::validation)?;
+
+    let Some(shop) = store::shop::get(&db, store_id).await? else {
+        return Err(Problem::from_code(ProblemCode::StoreNotFound));
+    };
+    let (items, sections) = tokio::try_join!(
+        store::item::list_for_store(&db, store_id),
+        store::section::list(&db, store_id)
+    )?;
+    let mut list = group_items(items, vec![shop], sections);
+    let list = list.stores.pop().expect("store should be listed");
+
+    let opts = PrintOptions {
+        font_size: query.font_size,
+        columns: query.columns,
+    };
+    let res = match query.format {
+        PrintFormat::Html => Html(print::html(&list, opts)).into_response(),
+        PrintFormat::Pdf => {
+            let disposition = format!(
+                "inline; filename=\"{}.pdf\"",
+                util::file_stem(&list.store.name, "list")
+            );
+            let mut res = (
+                [(header::CONTENT_TYPE, "application/pdf")],
+                print::pdf(&list, opts),
+            )
+                .into_response();
+            res.headers_mut().insert(
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_str(&disposition).expect("disposition should be valid"),
+            );
+            res
+        }
+    };
+    Ok(res)
+}
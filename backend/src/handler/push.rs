@@ -0,0 +1,228 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json as DbJson;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::User,
+    handler::{
+        FieldError, Problem,
+        validate::{ValidJson, Validate, Validator},
+    },
+    push::{self, WebPush},
+    state::AppState,
+    store::{
+        self,
+        push::{Preferences, Subscription},
+    },
+};
+
+const MAX_ENDPOINT_LEN: usize = 2000;
+
+#[derive(Serialize, ToSchema)]
+pub struct PushKey {
+    /// Base64url encoded key for `applicationServerKey` of `pushManager.subscribe()`.
+    public_key: String,
+}
+
+/// Subscription as returned by `PushSubscription.toJSON()`.
+#[derive(Deserialize, ToSchema)]
+pub struct SubscribeReq {
+    endpoint: String,
+    keys: SubscriptionKeys,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SubscriptionKeys {
+    p256dh: String,
+    auth: String,
+}
+
+impl Validate for SubscribeReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let valid_endpoint =
+            url::Url::parse(&self.endpoint).is_ok_and(|url| push::is_allowed_endpoint(&url));
+        let p256dh = BASE64_URL_SAFE_NO_PAD.decode(self.keys.p256dh.trim_end_matches('='));
+        let auth = BASE64_URL_SAFE_NO_PAD.decode(self.keys.auth.trim_end_matches('='));
+
+        Validator::new()
+            .check(
+                "endpoint",
+                valid_endpoint && self.endpoint.len() <= MAX_ENDPOINT_LEN,
+                "invalid_url",
+                "must be an https url",
+            )
+            .check(
+                "keys.p256dh",
+                p256dh.is_ok_and(|key| p256::PublicKey::from_sec1_bytes(&key).is_ok()),
+                "invalid_key",
+                "must be a base64url encoded P-256 public key",
+            )
+            .check(
+                "keys.auth",
+                auth.is_ok_and(|auth| auth.len() == 16),
+                "invalid_key",
+                "must be a base64url encoded 16 byte secret",
+            )
+            .finish()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PreferencesReq {
+    /// Notify about items added by other users.
+    item_created: bool,
+    /// Only items of these stores, all items if it's empty.
+    #[serde(default)]
+    store_ids: Vec<i64>,
+}
+
+impl Validate for PreferencesReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .unique_ids("store_ids", &self.store_ids)
+            .finish()
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/push/key",
+    operation_id = "get_push_key",
+    tag = "push",
+    responses(
+        (status = 200, description = "Public key of the server", body = PushKey),
+        (status = 404, description = "Push notifications aren't configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn key(State(state): State<AppState>, _: User) -> Result<Json<PushKey>, Problem> {
+    let push = get_push(&state)?;
+    Ok(Json(PushKey {
+        public_key: push.public_key().to_string(),
+    }))
+}
+
+/// Subscribes the browser to notifications of the user. Subscribing an endpoint again
+/// replaces its keys.
+#[utoipa::path(
+    post,
+    path = "/push/subscriptions",
+    operation_id = "create_push_subscription",
+    tag = "push",
+    request_body = SubscribeReq,
+    responses(
+        (status = 201, description = "Saved subscription", body = Subscription),
+        (status = 404, description = "Push notifications aren't configured", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid endpoint or keys", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn subscribe(
+    State(state): State<AppState>,
+    user: User,
+    ValidJson(req): ValidJson<SubscribeReq>,
+) -> Result<(StatusCode, Json<Subscription>), Problem> {
+    get_push(&state)?;
+    let endpoint = url::Url::parse(&req.endpoint).expect("endpoint is validated");
+    if !push::resolves_publicly(&endpoint).await {
+        return Err(Problem::validation(vec![FieldError::new(
+            "endpoint",
+            "invalid_url",
+            "must not be a local or private address".to_string(),
+        )]));
+    }
+
+    // Browsers send the keys unpadded, but some libraries pad them.
+    let subscription = store::push::subscribe(
+        &state.db,
+        user.id,
+        &req.endpoint,
+        req.keys.p256dh.trim_end_matches('='),
+        req.keys.auth.trim_end_matches('='),
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/push/subscriptions",
+    operation_id = "list_push_subscriptions",
+    tag = "push",
+    responses((status = 200, description = "Subscriptions of the user", body = Vec<Subscription>))
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Json<Vec<Subscription>>, Problem> {
+    let subscriptions = store::push::list(&state.db, user.id).await?;
+    Ok(Json(subscriptions))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/push/subscriptions/{id}",
+    operation_id = "delete_push_subscription",
+    tag = "push",
+    responses(
+        (status = 204, description = "Subscription deleted"),
+        (status = 404, description = "Subscription not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn unsubscribe(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    user: User,
+) -> Result<StatusCode, Problem> {
+    if !store::push::unsubscribe(&state.db, user.id, id).await? {
+        return Err(Problem::not_found());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/push/preferences",
+    operation_id = "get_notification_preferences",
+    tag = "push",
+    responses((status = 200, description = "Notification preferences of the user", body = Preferences))
+)]
+pub async fn preferences(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Json<Preferences>, Problem> {
+    let prefs = store::push::get_preferences(&state.db, user.id).await?;
+    Ok(Json(prefs))
+}
+
+#[utoipa::path(
+    put,
+    path = "/push/preferences",
+    operation_id = "set_notification_preferences",
+    tag = "push",
+    request_body = PreferencesReq,
+    responses(
+        (status = 200, description = "Saved preferences", body = Preferences),
+        (status = 422, description = "Duplicate store ids", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn set_preferences(
+    State(state): State<AppState>,
+    user: User,
+    ValidJson(req): ValidJson<PreferencesReq>,
+) -> Result<Json<Preferences>, Problem> {
+    let prefs = Preferences {
+        item_created: req.item_created,
+        store_ids: DbJson(req.store_ids),
+    };
+    let prefs = store::push::set_preferences(&state.db, user.id, &prefs).await?;
+    Ok(Json(prefs))
+}
+
+fn get_push(state: &AppState) -> Result<&WebPush, Problem> {
+    state.push.as_deref().ok_or_else(Problem::not_found)
+}
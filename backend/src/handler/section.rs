@@ -1,42 +1,69 @@
-use std::collections::HashSet;
-
 use axum::{
     Json,
     extract::{Path, State},
     http::StatusCode,
 };
 use serde::Deserialize;
+use utoipa::ToSchema;
 
 use crate::{
-    auth::User,
+    auth::{RequireRole, User, role::Admin},
     db::Db,
-    handler::Problem,
+    handler::{
+        FieldError, Problem, ProblemCode,
+        validate::{MAX_NAME_LEN, ValidJson, Validate, Validator},
+    },
     store::{self, section::Section},
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SectionNameReq {
     name: String,
 }
 
+impl Validate for SectionNameReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .name("name", &mut self.name, MAX_NAME_LEN)
+            .finish()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/stores/{store_id}/sections",
+    operation_id = "create_section",
+    tag = "sections",
+    request_body = SectionNameReq,
+    responses(
+        (status = 200, description = "Created section", body = Section),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid name", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn create(
     State(db): State<Db>,
     Path(store_id): Path<i64>,
-    _: User,
-    Json(req): Json<SectionNameReq>,
+    _: RequireRole<Admin>,
+    ValidJson(req): ValidJson<SectionNameReq>,
 ) -> Result<Json<Section>, Problem> {
     check_store_exists(&db, store_id).await?;
 
-    let res = store::section::create(&db, store_id, &req.name).await;
-    match res {
-        Ok(section) => Ok(Json(section)),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
-    }
+    let section = store::section::create(&db, store_id, &req.name).await?;
+    Ok(Json(section))
 }
 
+#[utoipa::path(
+    get,
+    path = "/stores/{store_id}/sections",
+    operation_id = "list_sections",
+    tag = "sections",
+    responses(
+        (status = 200, description = "Sections of the store in order", body = Vec<Section>),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn list(
     State(db): State<Db>,
     Path(store_id): Path<i64>,
@@ -44,90 +71,99 @@ pub async fn list(
 ) -> Result<Json<Vec<Section>>, Problem> {
     check_store_exists(&db, store_id).await?;
 
-    let res = store::section::list(&db, store_id).await;
-    match res {
-        Ok(sections) => Ok(Json(sections)),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
-    }
+    let sections = store::section::list(&db, store_id).await?;
+    Ok(Json(sections))
 }
 
+#[utoipa::path(
+    put,
+    path = "/sections/{id}",
+    operation_id = "rename_section",
+    tag = "sections",
+    request_body = SectionNameReq,
+    responses(
+        (status = 200, description = "Renamed section", body = Section),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Section not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid name", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn update(
     State(db): State<Db>,
     Path(id): Path<i64>,
-    _: User,
-    Json(req): Json<SectionNameReq>,
+    _: RequireRole<Admin>,
+    ValidJson(req): ValidJson<SectionNameReq>,
 ) -> Result<Json<Section>, Problem> {
     let res = store::section::update(&db, id, &req.name).await;
-
     match res {
         Ok(section) => Ok(Json(section)),
-        Err(sqlx::Error::RowNotFound) => Err(Problem::not_found()),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
+        Err(sqlx::Error::RowNotFound) => Err(Problem::from_code(ProblemCode::SectionNotFound)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deletes the section. Its items are moved to the store's unassigned items.
+#[utoipa::path(
+    delete,
+    path = "/sections/{id}",
+    operation_id = "delete_section",
+    tag = "sections",
+    responses(
+        (status = 204, description = "Section deleted"),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn delete(
     State(db): State<Db>,
     Path(id): Path<i64>,
-    _: User,
+    _: RequireRole<Admin>,
 ) -> Result<StatusCode, Problem> {
-    let res = store::section::delete(&db, id).await;
-
-    match res {
-        Ok(_) => Ok(StatusCode::NO_CONTENT),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
-    }
+    store::section::delete(&db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ReorderReq {
+    /// Ids of all sections of the store in the new order.
     pub ids: Vec<i64>,
 }
 
+impl Validate for ReorderReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new().unique_ids("ids", &self.ids).finish()
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/stores/{store_id}/sections/reorder",
+    operation_id = "reorder_sections",
+    tag = "sections",
+    request_body = ReorderReq,
+    responses(
+        (status = 200, description = "Sections of the store in the new order", body = Vec<Section>),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Ids aren't exactly the sections of the store", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn reorder(
     State(db): State<Db>,
     Path(store_id): Path<i64>,
-    u: User,
-    Json(req): Json<ReorderReq>,
+    RequireRole(u, _): RequireRole<Admin>,
+    ValidJson(req): ValidJson<ReorderReq>,
 ) -> Result<Json<Vec<Section>>, Problem> {
     check_store_exists(&db, store_id).await?;
 
-    // Validate ids are correct. This means that the array has exactly all store sections
-    let existing = store::section::list(&db, store_id).await?;
-    let existing_ids: HashSet<_> = existing.into_iter().map(|s| s.id).collect();
-
-    let id_set: HashSet<i64> = HashSet::from_iter(req.ids.iter().copied());
-
-    if existing_ids != id_set {
-        return Err(Problem::new(
-            StatusCode::BAD_REQUEST,
-            "invalid section ids".to_string(),
-        ));
-    }
-
-    // Update and return
-    store::section::reorder(&db, &req.ids).await?;
+    // Store layer checks that ids are exactly all sections of the store.
+    store::section::reorder(&db, store_id, &req.ids).await?;
 
     list(State(db), Path(store_id), u).await
 }
 
 async fn check_store_exists(db: &Db, store_id: i64) -> Result<(), Problem> {
-    let res = store::shop::get(db, store_id).await;
-    match res {
-        Ok(Some(_)) => Ok(()),
-        Ok(None) => Err(Problem::not_found()),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
+    match store::shop::get(db, store_id).await? {
+        Some(_) => Ok(()),
+        None => Err(Problem::from_code(ProblemCode::StoreNotFound)),
     }
 }
@@ -0,0 +1,229 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use rand::TryRngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Guest, RequireRole, role::Member},
+    db::Db,
+    handler::{
+        FieldError, Problem, ProblemCode,
+        item::{ItemListStore, group_items},
+        validate::{ValidJson, Validate, Validator},
+    },
+    store::{
+        self,
+        item::Item,
+        share::{Permission, ShareLink},
+    },
+    util::to_hex,
+};
+
+const MAX_EXPIRES_IN_HOURS: i64 = 24 * 90;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ShareCreateReq {
+    permission: Permission,
+    /// Between 1 hour and 90 days.
+    expires_in_hours: i64,
+}
+
+impl Validate for ShareCreateReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .range(
+                "expires_in_hours",
+                self.expires_in_hours,
+                1,
+                MAX_EXPIRES_IN_HOURS,
+            )
+            .finish()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedShareLink {
+    #[serde(flatten)]
+    link: ShareLink,
+
+    /// Token is returned only once, the database stores only its hash.
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/stores/{store_id}/shares",
+    operation_id = "create_share",
+    tag = "shares",
+    request_body = ShareCreateReq,
+    responses(
+        (status = 201, description = "Created link with its token", body = CreatedShareLink),
+        (status = 403, description = "User is only a shopper", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid expiration", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn create(
+    State(db): State<Db>,
+    Path(store_id): Path<i64>,
+    RequireRole(user, _): RequireRole<Member>,
+    ValidJson(req): ValidJson<ShareCreateReq>,
+) -> Result<(StatusCode, Json<CreatedShareLink>), Problem> {
+    if store::shop::get(&db, store_id).await?.is_none() {
+        return Err(Problem::not_found());
+    }
+
+    let mut token_bytes = [0u8; 32];
+    rand::rngs::OsRng
+        .try_fill_bytes(&mut token_bytes)
+        .expect("random should not fail");
+    let token = BASE64_URL_SAFE_NO_PAD.encode(token_bytes);
+    let token_hash = to_hex(&Sha256::digest(token_bytes)[..]);
+
+    let expires_at = time::OffsetDateTime::now_utc() + time::Duration::hours(req.expires_in_hours);
+    let link = store::share::create(
+        &db,
+        store_id,
+        &token_hash,
+        req.permission,
+        user.id,
+        expires_at,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(CreatedShareLink { link, token })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stores/{store_id}/shares",
+    operation_id = "list_shares",
+    tag = "shares",
+    responses(
+        (status = 200, description = "Share links of the store", body = Vec<ShareLink>),
+        (status = 403, description = "User is only a shopper", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn list(
+    State(db): State<Db>,
+    Path(store_id): Path<i64>,
+    _: RequireRole<Member>,
+) -> Result<Json<Vec<ShareLink>>, Problem> {
+    let links = store::share::list(&db, store_id).await?;
+    Ok(Json(links))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/shares/{id}",
+    operation_id = "delete_share",
+    tag = "shares",
+    responses(
+        (status = 204, description = "Link revoked"),
+        (status = 403, description = "User is only a shopper", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Link not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn delete(
+    State(db): State<Db>,
+    Path(id): Path<i64>,
+    _: RequireRole<Member>,
+) -> Result<StatusCode, Problem> {
+    let deleted = store::share::delete(&db, id).await?;
+    if !deleted {
+        return Err(Problem::not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SharedList {
+    permission: Permission,
+
+    #[serde(flatten)]
+    store: ItemListStore,
+}
+
+/// Returns the shared store's items. Authenticated only by the token in the path.
+#[utoipa::path(
+    get,
+    path = "/share/{token}",
+    operation_id = "guest_list_items",
+    tag = "shares",
+    params(("token" = String, Path, description = "Token of the share link")),
+    security(()),
+    responses(
+        (status = 200, description = "Items of the shared store", body = SharedList),
+        (status = 401, description = "Invalid or expired link", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn guest_list(State(db): State<Db>, guest: Guest) -> Result<Json<SharedList>, Problem> {
+    let (shop, sections, items) = tokio::try_join!(
+        store::shop::get(&db, guest.store_id),
+        store::section::list(&db, guest.store_id),
+        store::item::list_for_store(&db, guest.store_id),
+    )?;
+    let Some(shop) = shop else {
+        return Err(Problem::not_found());
+    };
+
+    let list = group_items(items, vec![shop], sections);
+    let store = list
+        .stores
+        .into_iter()
+        .next()
+        .expect("grouped list should contain the store");
+
+    Ok(Json(SharedList {
+        permission: guest.permission,
+        store,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/share/{token}/items/{item_id}/checked",
+    operation_id = "guest_check_item",
+    tag = "shares",
+    params(
+        ("token" = String, Path, description = "Token of the share link"),
+        ("item_id" = i64, Path),
+    ),
+    security(()),
+    responses(
+        (status = 200, description = "Checked item", body = Item),
+        (status = 401, description = "Invalid or expired link", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Link doesn't allow checking items", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Item not found in the shared store", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn guest_set_checked(
+    State(db): State<Db>,
+    guest: Guest,
+    Path((_token, item_id)): Path<(String, i64)>,
+) -> Result<Json<Item>, Problem> {
+    if guest.permission != Permission::Check {
+        return Err(Problem::new(
+            ProblemCode::Forbidden,
+            "Share link doesn't allow checking items".to_string(),
+        ));
+    }
+
+    // Guests can only access items of the shared store.
+    let item = store::item::set_checked_in_store(&db, item_id, guest.store_id).await?;
+    let Some(item) = item else {
+        return Err(Problem::not_found());
+    };
+
+    tracing::info!(share_link = guest.link_id, item_id, "guest checked item");
+
+    Ok(Json(item))
+}
@@ -4,75 +4,107 @@ use axum::{
     http::StatusCode,
 };
 use serde::Deserialize;
+use utoipa::ToSchema;
 
 use crate::{
-    auth::User,
+    auth::{RequireRole, User, role::Admin},
     db::Db,
-    handler::Problem,
+    handler::{
+        FieldError, Problem, ProblemCode,
+        validate::{MAX_NAME_LEN, ValidJson, Validate, Validator},
+    },
     store::{self, shop::Store},
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct StoreNameReq {
     name: String,
 }
 
+impl Validate for StoreNameReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Validator::new()
+            .name("name", &mut self.name, MAX_NAME_LEN)
+            .finish()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/stores",
+    operation_id = "create_store",
+    tag = "stores",
+    request_body = StoreNameReq,
+    responses(
+        (status = 200, description = "Created store", body = Store),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid name", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn create(
     State(db): State<Db>,
-    _: User,
-    Json(req): Json<StoreNameReq>,
+    _: RequireRole<Admin>,
+    ValidJson(req): ValidJson<StoreNameReq>,
 ) -> Result<Json<Store>, Problem> {
-    let res = store::shop::create(&db, &req.name).await;
-    match res {
-        Ok(shop) => Ok(Json(shop)),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
-    }
+    let shop = store::shop::create(&db, &req.name).await?;
+    Ok(Json(shop))
 }
 
+#[utoipa::path(
+    get,
+    path = "/stores",
+    operation_id = "list_stores",
+    tag = "stores",
+    responses((status = 200, description = "All stores", body = Vec<Store>))
+)]
 pub async fn list(State(db): State<Db>, _: User) -> Result<Json<Vec<Store>>, Problem> {
-    let res = store::shop::list(&db).await;
-    match res {
-        Ok(shops) => Ok(Json(shops)),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
-    }
+    let shops = store::shop::list(&db).await?;
+    Ok(Json(shops))
 }
 
+#[utoipa::path(
+    put,
+    path = "/stores/{store_id}",
+    operation_id = "rename_store",
+    tag = "stores",
+    request_body = StoreNameReq,
+    responses(
+        (status = 200, description = "Renamed store", body = Store),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Store not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid name", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn update(
     State(db): State<Db>,
     Path(id): Path<i64>,
-    _: User,
-    Json(req): Json<StoreNameReq>,
+    _: RequireRole<Admin>,
+    ValidJson(req): ValidJson<StoreNameReq>,
 ) -> Result<Json<Store>, Problem> {
     let res = store::shop::update(&db, id, &req.name).await;
-
     match res {
         Ok(shop) => Ok(Json(shop)),
-        Err(sqlx::Error::RowNotFound) => Err(Problem::not_found()),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
+        Err(sqlx::Error::RowNotFound) => Err(Problem::from_code(ProblemCode::StoreNotFound)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deletes the store. Its items are moved to the unassigned items.
+#[utoipa::path(
+    delete,
+    path = "/stores/{store_id}",
+    operation_id = "delete_store",
+    tag = "stores",
+    responses(
+        (status = 204, description = "Store deleted"),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 pub async fn delete(
     State(db): State<Db>,
     Path(id): Path<i64>,
-    _: User,
+    _: RequireRole<Admin>,
 ) -> Result<StatusCode, Problem> {
-    let res = store::shop::delete(&db, id).await;
-
-    match res {
-        Ok(_) => Ok(StatusCode::NO_CONTENT),
-        Err(err) => {
-            tracing::error!(error = err.to_string(), "database error: {err}");
-            Err(Problem::internal())
-        }
-    }
+    store::shop::delete(&db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,349 @@
+//! Items as plain text, to paste lists from chats and notes apps and to share them back.
+
+use std::fmt::Write;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::User,
+    handler::{
+        FieldError, Problem,
+        item::{ItemList, group_items, resolve_target},
+        organize::organize_store,
+        validate::{MAX_ITEM_NAME_LEN, ValidJson, Validate, Validator, normalize_name},
+    },
+    push,
+    state::AppState,
+    store::{self, item::Item, user::Role},
+};
+
+/// Most items that can be added at once.
+pub const MAX_TEXT_ITEMS: usize = 200;
+
+const BULLETS: &[char] = &['-', '*', '+', '•', '·', '◦', '▪', '–', '—'];
+const UNCHECKED_BOXES: &[&str] = &["[ ]", "[]", "☐", "⬜"];
+const CHECKED_BOXES: &[&str] = &["[x]", "[X]", "☑", "☒", "✅", "✔", "✓"];
+
+#[derive(Deserialize, ToSchema)]
+pub struct TextImportReq {
+    /// One item per line. Bullets, checkboxes and numbering are removed, headings
+    /// (`# Dairy`, `Dairy:`) and checked items are skipped. Names escaped with a backslash,
+    /// ie. `- \[x] task` or `- Note\:`, are kept as they are.
+    text: String,
+    store_id: Option<i64>,
+    /// Store is inferred from the section, if it's not given.
+    section_id: Option<i64>,
+    /// Organize the store's unassigned items afterwards. Requires a store and no section.
+    #[serde(default)]
+    organize: bool,
+
+    #[serde(skip)]
+    names: Vec<String>,
+}
+
+impl Validate for TextImportReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        self.names = parse_items(&self.text);
+
+        let mut v = Validator::new();
+        v.check(
+            "text",
+            !self.names.is_empty(),
+            "empty",
+            "must contain at least one item",
+        );
+        v.check(
+            "text",
+            self.names.len() <= MAX_TEXT_ITEMS,
+            "too_many_items",
+            &format!("must contain at most {MAX_TEXT_ITEMS} items"),
+        );
+        if let Some(idx) = self
+            .names
+            .iter()
+            .position(|name| name.chars().count() > MAX_ITEM_NAME_LEN)
+        {
+            v.check(
+                &format!("text[{idx}]"),
+                false,
+                "too_long",
+                &format!("must be at most {MAX_ITEM_NAME_LEN} characters long"),
+            );
+        }
+        if self.organize {
+            v.check(
+                "organize",
+                self.store_id.is_some() && self.section_id.is_none(),
+                "invalid",
+                "requires a store and no section",
+            );
+        }
+        v.finish()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TextImported {
+    /// Created items, in the order of the text.
+    items: Vec<Item>,
+    /// False if organizing was requested but the language model failed,
+    /// the items are created anyway.
+    organized: bool,
+}
+
+/// Adds items from a pasted list, all at the end of the same section.
+#[utoipa::path(
+    post,
+    path = "/items/text",
+    operation_id = "import_text",
+    tag = "items",
+    request_body = TextImportReq,
+    responses(
+        (status = 201, description = "Created items", body = TextImported),
+        (status = 403, description = "Organizing requires the member role", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Store or section not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Section doesn't belong to the store", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "No items or invalid items", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn import(
+    State(state): State<AppState>,
+    user: User,
+    ValidJson(req): ValidJson<TextImportReq>,
+) -> Result<(StatusCode, Json<TextImported>), Problem> {
+    if req.organize && user.role < Role::Member {
+        return Err(Problem::forbidden());
+    }
+
+    let store_id = resolve_target(&state.db, req.store_id, req.section_id).await?;
+    let mut items =
+        store::item::create_many(&state.db, store_id, req.section_id, &req.names).await?;
+
+    let mut organized = false;
+    if let (true, Some(store_id)) = (req.organize, store_id) {
+        // Items are already created, a failure of the model only leaves them unassigned.
+        organized = organize_store(&state, store_id).await.is_ok();
+        if organized {
+            for item in &mut items {
+                if let Some(updated) = store::item::get(&state.db, item.id).await? {
+                    *item = updated;
+                }
+            }
+        }
+    }
+
+    push::spawn_items_created(&state, &user, &items);
+    Ok((StatusCode::CREATED, Json(TextImported { items, organized })))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextFormat {
+    #[default]
+    Markdown,
+    Plain,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TextExportQuery {
+    #[serde(default)]
+    #[param(inline)]
+    format: TextFormat,
+    /// Only items of the store.
+    store_id: Option<i64>,
+}
+
+/// Returns unchecked items grouped by stores and sections as text for sharing.
+#[utoipa::path(
+    get,
+    path = "/items/text",
+    operation_id = "export_text",
+    tag = "items",
+    params(TextExportQuery),
+    responses(
+        (status = 200, description = "Items as text", content(
+            (String = "text/markdown"),
+            (String = "text/plain"),
+        )),
+    )
+)]
+pub async fn export(
+    State(state): State<AppState>,
+    _: User,
+    Query(query): Query<TextExportQuery>,
+) -> Result<Response, Problem> {
+    let db = &state.db;
+    let items = store::item::list(db).await?;
+    let (stores, sections) = tokio::try_join!(store::shop::list(db), store::section::list_all(db))?;
+    let mut list = group_items(items, stores, sections);
+
+    if let Some(store_id) = query.store_id {
+        list.unassigned.clear();
+        list.stores.retain(|s| s.store.id == store_id);
+    }
+
+    let (content_type, body) = match query.format {
+        TextFormat::Markdown => ("text/markdown; charset=utf-8", render_markdown(&list)),
+        TextFormat::Plain => ("text/plain; charset=utf-8", render_plain(&list)),
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+/// Returns names of the unchecked items in the text.
+pub fn parse_items(text: &str) -> Vec<String> {
+    text.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<String> {
+    let mut rest = line.trim();
+    if rest.starts_with('#') || (rest.ends_with(':') && !rest.ends_with("\\:")) {
+        return None;
+    }
+    let colon = match rest.strip_suffix("\\:") {
+        Some(r) => {
+            rest = r;
+            ":"
+        }
+        None => "",
+    };
+
+    // Prefixes can be combined, ie. `- [ ] milk` or `1. - milk`.
+    loop {
+        let before = rest;
+        // Escaped names are kept as they are, ie. `- \[x] task`.
+        if let Some(r) = rest.strip_prefix('\\') {
+            rest = r;
+            break;
+        }
+        if let Some(r) = rest.strip_prefix(BULLETS) {
+            rest = r.trim_start();
+        }
+        if let Some(r) = strip_number(rest) {
+            rest = r.trim_start();
+        }
+        if let Some(r) = UNCHECKED_BOXES.iter().find_map(|b| rest.strip_prefix(b)) {
+            rest = r.trim_start();
+        }
+        if CHECKED_BOXES.iter().any(|b| rest.starts_with(b)) {
+            return None;
+        }
+        if rest == before {
+            break;
+        }
+    }
+
+    let name = normalize_name(&format!("{rest}{colon}"));
+    (!name.is_empty()).then_some(name)
+}
+
+/// Escapes names that would be taken for a heading, a list marker or a checkbox,
+/// so that they're kept as they are when the text is rendered or pasted back.
+fn escape(name: &str) -> String {
+    let marked = name.starts_with(['#', '>', '\\'])
+        || name.starts_with(BULLETS)
+        || strip_number(name).is_some()
+        || UNCHECKED_BOXES
+            .iter()
+            .chain(CHECKED_BOXES)
+            .any(|b| name.starts_with(b));
+    let mut out = String::with_capacity(name.len() + 2);
+    if marked {
+        out.push('\\');
+    }
+    match name.strip_suffix(':') {
+        Some(name) => write!(out, "{name}\\:").unwrap(),
+        None => out.push_str(name),
+    }
+    out
+}
+
+/// Strips `1.`, `1)` or `(1)`. Numbers that are part of the name, ie. `2 apples` or
+/// `1.5 kg flour`, aren't followed by whitespace and are kept.
+fn strip_number(s: &str) -> Option<&str> {
+    let s_inner = s.strip_prefix('(').unwrap_or(s);
+    let digits = s_inner.len()
+        - s_inner
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    if digits == 0 {
+        return None;
+    }
+    let after = &s_inner[digits..];
+    let after = if s_inner.len() != s.len() {
+        after.strip_prefix(')')?
+    } else {
+        after.strip_prefix(['.', ')'])?
+    };
+    (after.is_empty() || after.starts_with(char::is_whitespace)).then_some(after)
+}
+
+fn render_markdown(list: &ItemList) -> String {
+    let mut out = String::new();
+    for item in &list.unassigned {
+        writeln!(out, "- [ ] {}", escape(&item.name)).unwrap();
+    }
+
+    for shop in &list.stores {
+        let empty = shop.unassigned.is_empty() && shop.sections.iter().all(|s| s.items.is_empty());
+        if empty {
+            continue;
+        }
+        separate(&mut out);
+        writeln!(out, "## {}", escape(&shop.store.name)).unwrap();
+        for item in &shop.unassigned {
+            writeln!(out, "- [ ] {}", escape(&item.name)).unwrap();
+        }
+        for section in shop.sections.iter().filter(|s| !s.items.is_empty()) {
+            writeln!(out, "\n### {}", escape(&section.section.name)).unwrap();
+            for item in &section.items {
+                writeln!(out, "- [ ] {}", escape(&item.name)).unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// Headings end with a colon, so that the text can be pasted back.
+pub fn render_plain(list: &ItemList) -> String {
+    let mut out = String::new();
+    let group = |out: &mut String, heading: Option<String>, items: &[Item]| {
+        if items.is_empty() {
+            return;
+        }
+        separate(out);
+        if let Some(heading) = heading {
+            writeln!(out, "{heading}:").unwrap();
+        }
+        for item in items {
+            writeln!(out, "- {}", escape(&item.name)).unwrap();
+        }
+    };
+
+    group(&mut out, None, &list.unassigned);
+    for shop in &list.stores {
+        group(&mut out, Some(escape(&shop.store.name)), &shop.unassigned);
+        for section in &shop.sections {
+            let heading = format!(
+                "{} / {}",
+                escape(&shop.store.name),
+                escape(&section.section.name)
+            );
+            group(&mut out, Some(heading), &section.items);
+        }
+    }
+    out
+}
+
+/// Puts an empty line between groups.
+fn separate(out: &mut String) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+}
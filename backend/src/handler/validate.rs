@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use axum::Json;
+use axum::extract::{FromRequest, Request, rejection::JsonRejection};
+use axum::http::StatusCode;
+use serde::de::DeserializeOwned;
+
+use crate::handler::{FieldError, Problem, ProblemCode};
+
+pub const MAX_NAME_LEN: usize = 100;
+pub const MAX_ITEM_NAME_LEN: usize = 200;
+
+/// Request body that normalizes and validates itself after deserialization.
+pub trait Validate {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>>;
+}
+
+/// Json extractor that validates the body.
+/// Malformed json and invalid fields are rejected with 422 [`Problem`], a wrong content
+/// type with 415 and a body over the limit with 413.
+pub struct ValidJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(mut value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(json_problem)?;
+
+        value.validate().map_err(Problem::validation)?;
+        Ok(ValidJson(value))
+    }
+}
+
+fn json_problem(rej: JsonRejection) -> Problem {
+    let code = match &rej {
+        JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_) => {
+            ProblemCode::MalformedBody
+        }
+        _ => match rej.status() {
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ProblemCode::UnsupportedMediaType,
+            StatusCode::PAYLOAD_TOO_LARGE => ProblemCode::PayloadTooLarge,
+            _ => ProblemCode::BadRequest,
+        },
+    };
+    Problem::new(code, rej.body_text())
+}
+
+/// Collects field errors of a single request.
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trims the name and collapses whitespace, then checks it's not empty or too long.
+    pub fn name(&mut self, field: &str, name: &mut String, max_len: usize) -> &mut Self {
+        *name = normalize_name(name);
+
+        if name.is_empty() {
+            self.errors.push(FieldError::new(
+                field,
+                "empty",
+                "must not be empty".to_string(),
+            ));
+        } else if name.chars().count() > max_len {
+            self.errors.push(FieldError::new(
+                field,
+                "too_long",
+                format!("must be at most {max_len} characters long"),
+            ));
+        }
+
+        self
+    }
+
+    pub fn range(&mut self, field: &str, value: i64, min: i64, max: i64) -> &mut Self {
+        if !(min..=max).contains(&value) {
+            self.errors.push(FieldError::new(
+                field,
+                "out_of_range",
+                format!("must be between {min} and {max}"),
+            ));
+        }
+
+        self
+    }
+
+    pub fn unique_ids(&mut self, field: &str, ids: &[i64]) -> &mut Self {
+        let mut seen = HashSet::with_capacity(ids.len());
+        if !ids.iter().all(|id| seen.insert(*id)) {
+            self.errors.push(FieldError::new(
+                field,
+                "duplicate",
+                "must not contain duplicates".to_string(),
+            ));
+        }
+
+        self
+    }
+
+    /// Adds an error with the code and message, if the condition doesn't hold.
+    pub fn check(
+        &mut self,
+        field: &str,
+        valid: bool,
+        code: &'static str,
+        message: &str,
+    ) -> &mut Self {
+        if !valid {
+            self.errors
+                .push(FieldError::new(field, code, message.to_string()));
+        }
+
+        self
+    }
+
+    pub fn finish(&mut self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+}
+
+/// Trims the name and replaces all whitespace runs, including newlines, with a single space.
+pub fn normalize_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
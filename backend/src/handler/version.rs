@@ -0,0 +1,93 @@
+use axum::Json;
+use axum::extract::State;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::api_version::{self, ApiVersion};
+use crate::state::AppState;
+
+/// Optional features of the server, the frontend hides what isn't available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Organize,
+    ShareLinks,
+    Caldav,
+    Oidc,
+    Passkeys,
+    ProxyAuth,
+    Metrics,
+    Push,
+    Notifications,
+}
+
+#[derive(Serialize)]
+pub struct VersionInfo {
+    name: &'static str,
+    path: &'static str,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    deprecated_at: Option<OffsetDateTime>,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    sunset_at: Option<OffsetDateTime>,
+}
+
+impl From<&ApiVersion> for VersionInfo {
+    fn from(value: &ApiVersion) -> Self {
+        Self {
+            name: value.name,
+            path: value.path,
+            deprecated_at: value.deprecation.as_ref().map(|dep| dep.since),
+            sunset_at: value.deprecation.as_ref().and_then(|dep| dep.sunset),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Versions {
+    /// Version the current frontend should use.
+    current: &'static str,
+    versions: Vec<VersionInfo>,
+    server_version: &'static str,
+    capabilities: Vec<Capability>,
+}
+
+/// Lists api versions served and features enabled on this server.
+/// It's not versioned, so it's served at `/api/versions`.
+pub async fn versions(State(state): State<AppState>) -> Json<Versions> {
+    let mut capabilities = vec![];
+    if state.llm.is_configured() {
+        capabilities.push(Capability::Organize);
+    }
+    capabilities.push(Capability::ShareLinks);
+    capabilities.push(Capability::Caldav);
+    if state.oidc.is_some() {
+        capabilities.push(Capability::Oidc);
+    }
+    if state.webauthn.is_some() {
+        capabilities.push(Capability::Passkeys);
+    }
+    if state.config.proxy_auth.is_some() {
+        capabilities.push(Capability::ProxyAuth);
+    }
+    if state.config.metrics.is_some() {
+        capabilities.push(Capability::Metrics);
+    }
+    if state.push.is_some() {
+        capabilities.push(Capability::Push);
+    }
+    if state.notifier.is_configured() {
+        capabilities.push(Capability::Notifications);
+    }
+
+    Json(Versions {
+        current: api_version::CURRENT,
+        versions: api_version::VERSIONS
+            .iter()
+            .map(VersionInfo::from)
+            .collect(),
+        server_version: env!("CARGO_PKG_VERSION"),
+        capabilities,
+    })
+}
@@ -0,0 +1,330 @@
+//! Adding items with a single spoken command, for Siri and Google shortcuts or Home
+//! Assistant Assist. The answer is a sentence that the assistant reads out.
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::User,
+    handler::{
+        FieldError, Problem,
+        validate::{MAX_ITEM_NAME_LEN, ValidJson, Validate, Validator, normalize_name},
+    },
+    llm::ParsedCommand,
+    push,
+    state::AppState,
+    store::{self, item::Item, shop::Store},
+};
+
+const MAX_TEXT_LEN: usize = 1000;
+/// Most items that can be added with one command.
+const MAX_VOICE_ITEMS: usize = 50;
+/// Answers name at most this many items, otherwise only their count.
+const MAX_SPOKEN_ITEMS: usize = 5;
+
+/// Words before the items, ie. "please add" or "we need".
+const COMMAND_PREFIXES: &[&str] = &[
+    "please",
+    "can you",
+    "could you",
+    "hey",
+    "add",
+    "put",
+    "buy",
+    "get",
+    "we need",
+    "i need",
+    "we're out of",
+    "we are out of",
+];
+/// Words before the store, "to" is always taken as a store, the others only if the
+/// store exists.
+const STORE_PREPOSITIONS: &[&str] = &[" to ", " at ", " on ", " for "];
+/// Targets that mean no store, ie. "to the shopping list".
+const LIST_NAMES: &[&str] = &["list", "shopping list", "grocery list"];
+const ITEM_SEPARATORS: &[&str] = &[", and ", " and ", " & ", " plus "];
+const ARTICLES: &[&str] = &["a", "an", "some", "the"];
+const NUMBERS: &[&str] = &[
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven",
+    "twelve",
+];
+
+#[derive(Deserialize, ToSchema)]
+pub struct VoiceReq {
+    /// Transcript of the command, ie. "add milk and two loaves of bread to Mercator".
+    text: String,
+}
+
+impl Validate for VoiceReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        self.text = self.text.trim().to_string();
+        let len = self.text.chars().count();
+        Validator::new()
+            .check(
+                "text",
+                (1..=MAX_TEXT_LEN).contains(&len),
+                "invalid_length",
+                &format!("must be between 1 and {MAX_TEXT_LEN} characters long"),
+            )
+            .finish()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VoiceAdded {
+    /// Short answer for text-to-speech, ie. "Added milk and 2 loaves of bread to Mercator."
+    speech: String,
+    items: Vec<Item>,
+    /// Store of the items, missing if no known store was mentioned.
+    store_id: Option<i64>,
+}
+
+/// Adds the items of a spoken command. The language model splits the text when it's
+/// configured, otherwise simple rules do. A store that doesn't exist is mentioned in
+/// the answer and the items are added without a store.
+#[utoipa::path(
+    post,
+    path = "/items/voice",
+    operation_id = "add_items_by_voice",
+    tag = "items",
+    request_body = VoiceReq,
+    responses(
+        (status = 201, description = "Added items with the answer", body = VoiceAdded),
+        (status = 422, description = "No items were found in the text", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn add(
+    State(state): State<AppState>,
+    user: User,
+    ValidJson(req): ValidJson<VoiceReq>,
+) -> Result<(StatusCode, Json<VoiceAdded>), Problem> {
+    let stores = store::shop::list(&state.db).await?;
+    let store_names: Vec<String> = stores.iter().map(|s| s.name.clone()).collect();
+
+    let command = if state.llm.is_configured() {
+        match state
+            .llm
+            .parse_command(req.text.clone(), store_names.clone())
+            .await
+        {
+            Ok(command) => command,
+            Err(err) => {
+                // Rules understand most commands, a failure of the model isn't fatal.
+                tracing::warn!(
+                    error = err.to_string(),
+                    "error during voice command parsing: {err}"
+                );
+                parse_command(&req.text, &store_names)
+            }
+        }
+    } else {
+        parse_command(&req.text, &store_names)
+    };
+    if let Some(usage) = command.usage {
+        state.metrics.llm_usage(usage);
+    }
+
+    let mut names: Vec<String> = command
+        .items
+        .iter()
+        .map(|name| normalize_name(name))
+        .filter(|name| !name.is_empty() && name.chars().count() <= MAX_ITEM_NAME_LEN)
+        .collect();
+    names.truncate(MAX_VOICE_ITEMS);
+    if names.is_empty() {
+        return Err(Problem::validation(vec![FieldError::new(
+            "text",
+            "no_items",
+            "no items were found in the text".to_string(),
+        )]));
+    }
+
+    let said_store = command
+        .store
+        .map(|s| normalize_name(&s))
+        .filter(|s| !s.is_empty());
+    let store = said_store
+        .as_deref()
+        .and_then(|s| resolve_store(&stores, s));
+    let store_id = store.map(|s| s.id);
+
+    let items = store::item::create_many(&state.db, store_id, None, &names).await?;
+    push::spawn_items_created(&state, &user, &items);
+
+    let unknown_store = said_store.as_deref().filter(|_| store.is_none());
+    let speech = speech(&names, store.map(|s| s.name.as_str()), unknown_store);
+    Ok((
+        StatusCode::CREATED,
+        Json(VoiceAdded {
+            speech,
+            items,
+            store_id,
+        }),
+    ))
+}
+
+/// Splits the command with rules, for when the language model isn't used.
+pub fn parse_command(text: &str, stores: &[String]) -> ParsedCommand {
+    let mut text = text
+        .trim()
+        .trim_end_matches(['.', '!', '?'])
+        .trim()
+        .to_string();
+    strip_words(&mut text, COMMAND_PREFIXES, true);
+    strip_words(&mut text, &["please"], false);
+
+    let (items, store) = split_store(&text, stores);
+    let items = split_items(items);
+    ParsedCommand {
+        items,
+        store,
+        usage: None,
+    }
+}
+
+/// Strips the words from the start, or the end, of the text, as long as any matches.
+fn strip_words(text: &mut String, words: &[&str], start: bool) {
+    loop {
+        // Keeps the byte offsets of the text.
+        let lower = text.to_ascii_lowercase();
+        let found = words.iter().find(|word| {
+            if start {
+                lower.starts_with(*word) && lower[word.len()..].starts_with(char::is_whitespace)
+            } else {
+                lower.ends_with(*word) && lower[..lower.len() - word.len()].ends_with([' ', ','])
+            }
+        });
+        let Some(word) = found else {
+            return;
+        };
+        *text = if start {
+            text[word.len()..].trim_start().to_string()
+        } else {
+            text[..text.len() - word.len()]
+                .trim_end_matches([' ', ','])
+                .to_string()
+        };
+    }
+}
+
+/// Splits off the store at the end of the text, ie. "to Mercator" or "on the Spar
+/// list". Returns the rest and the store as it was said.
+fn split_store<'a>(text: &'a str, stores: &[String]) -> (&'a str, Option<String>) {
+    // The space in front also finds a store without items, ie. "to the list".
+    let lower = format!(" {}", text.to_ascii_lowercase());
+    let found = STORE_PREPOSITIONS
+        .iter()
+        .filter_map(|prep| lower.rfind(prep).map(|idx| (idx, *prep)))
+        .max_by_key(|(idx, _)| *idx);
+    let Some((idx, prep)) = found else {
+        return (text, None);
+    };
+    let items = &text[..idx.saturating_sub(1)];
+
+    let mut store = text[idx + prep.len() - 1..].to_string();
+    strip_words(&mut store, &["the", "my", "our"], true);
+    if LIST_NAMES
+        .iter()
+        .any(|name| store.eq_ignore_ascii_case(name))
+    {
+        // "to the list" or "on my shopping list"
+        return (items, None);
+    }
+    // "to the Spar list"
+    strip_words(
+        &mut store,
+        &["shopping list", "grocery list", "list"],
+        false,
+    );
+    let store = store.trim_end_matches("'s").trim();
+
+    if store.is_empty() {
+        (items, None)
+    } else if prep == " to "
+        || stores
+            .iter()
+            .any(|s| s.to_lowercase() == store.to_lowercase())
+    {
+        (items, Some(store.to_string()))
+    } else {
+        // "food for the cat"
+        (text, None)
+    }
+}
+
+fn split_items(text: &str) -> Vec<String> {
+    let mut text = text.to_string();
+    for sep in ITEM_SEPARATORS {
+        text = replace_ignore_case(&text, sep, ",");
+    }
+
+    text.split(',')
+        .filter_map(|part| {
+            let mut words: Vec<&str> = part.split_whitespace().collect();
+            // "a bottle of wine" is "bottle of wine", but "a" alone is kept.
+            if words.len() > 1 && ARTICLES.contains(&words[0].to_lowercase().as_str()) {
+                words.remove(0);
+            }
+            let number = words.first().and_then(|first| {
+                let first = first.to_lowercase();
+                NUMBERS.iter().position(|n| *n == first)
+            });
+            let mut name = words.join(" ");
+            if let (Some(idx), true) = (number, words.len() > 1) {
+                name = format!("{} {}", idx + 1, words[1..].join(" "));
+            }
+            let name = normalize_name(&name);
+            (!name.is_empty()).then_some(name)
+        })
+        .collect()
+}
+
+fn replace_ignore_case(text: &str, from: &str, to: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (idx, _) in lower.match_indices(from) {
+        out.push_str(&text[last..idx]);
+        out.push_str(to);
+        last = idx + from.len();
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Finds the store by its name, ignoring case. A name that's part of only one store's
+/// name also matches, ie. "Spar" for "Interspar".
+fn resolve_store<'a>(stores: &'a [Store], name: &str) -> Option<&'a Store> {
+    let name = name.to_lowercase();
+    if let Some(store) = stores.iter().find(|s| s.name.to_lowercase() == name) {
+        return Some(store);
+    }
+    let mut partial = stores
+        .iter()
+        .filter(|s| s.name.to_lowercase().contains(&name));
+    match (partial.next(), partial.next()) {
+        (Some(store), None) => Some(store),
+        _ => None,
+    }
+}
+
+/// Answer like "Added milk and 2 loaves of bread to Mercator."
+fn speech(names: &[String], store: Option<&str>, unknown_store: Option<&str>) -> String {
+    let items = match names {
+        [name] => name.clone(),
+        _ if names.len() > MAX_SPOKEN_ITEMS => format!("{} items", names.len()),
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+        [] => "nothing".to_string(),
+    };
+    let target = match store {
+        Some(store) => store.to_string(),
+        None => "the list".to_string(),
+    };
+
+    let mut speech = format!("Added {items} to {target}.");
+    if let Some(unknown) = unknown_store {
+        speech.push_str(&format!(" There's no store called {unknown}."));
+    }
+    speech
+}
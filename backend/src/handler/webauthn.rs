@@ -0,0 +1,382 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use webauthn_rs::Webauthn;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, Passkey, PasskeyAuthentication, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse, Uuid,
+};
+
+use crate::auth::User;
+use crate::handler::auth::LoginError;
+use crate::handler::validate::{MAX_NAME_LEN, ValidJson, Validate, Validator, normalize_name};
+use crate::handler::{FieldError, Problem, ProblemCode};
+use crate::state::AppState;
+use crate::store::{self, webauthn::ChallengeKind, webauthn::Credential};
+use crate::util::{random_token, to_hex};
+
+#[derive(Serialize, ToSchema)]
+pub struct RegisterStartResp {
+    challenge_id: String,
+    /// Options for `navigator.credentials.create()`.
+    #[schema(value_type = Object)]
+    options: CreationChallengeResponse,
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/webauthn/register/start",
+    operation_id = "passkey_register_start",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Registration challenge", body = RegisterStartResp),
+        (status = 404, description = "Passkeys aren't configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn register_start(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Json<RegisterStartResp>, Problem> {
+    let webauthn = get_webauthn(&state)?;
+
+    // Prevent registering the same authenticator twice.
+    let existing = store::webauthn::list_credentials(&state.db, user.id).await?;
+    let exclude = existing
+        .iter()
+        .filter_map(|cred| parse_passkey(cred).ok())
+        .map(|pk| pk.cred_id().clone())
+        .collect();
+
+    let (options, reg_state) = webauthn
+        .start_passkey_registration(
+            user_handle(user.id),
+            &user.username,
+            &user.username,
+            Some(exclude),
+        )
+        .map_err(|err| {
+            tracing::error!(error = err.to_string(), "webauthn error: {err}");
+            Problem::internal()
+        })?;
+
+    let challenge_id =
+        save_challenge(&state, user.id, ChallengeKind::Registration, &reg_state).await?;
+
+    Ok(Json(RegisterStartResp {
+        challenge_id,
+        options,
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterFinishReq {
+    challenge_id: String,
+    name: String,
+    /// Credential returned by `navigator.credentials.create()`.
+    #[schema(value_type = Object)]
+    credential: RegisterPublicKeyCredential,
+}
+
+impl Validate for RegisterFinishReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        // Name is optional, empty name is replaced with a default.
+        if normalize_name(&self.name).is_empty() {
+            self.name = "Passkey".to_string();
+        }
+
+        Validator::new()
+            .name("name", &mut self.name, MAX_NAME_LEN)
+            .finish()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/webauthn/register/finish",
+    operation_id = "passkey_register_finish",
+    tag = "auth",
+    request_body = RegisterFinishReq,
+    responses(
+        (status = 201, description = "Registered passkey", body = Credential),
+        (status = 400, description = "Invalid challenge or attestation", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Passkey is already registered", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn register_finish(
+    State(state): State<AppState>,
+    user: User,
+    ValidJson(req): ValidJson<RegisterFinishReq>,
+) -> Result<(StatusCode, Json<Credential>), Problem> {
+    let webauthn = get_webauthn(&state)?;
+
+    let challenge = store::webauthn::take_challenge(
+        &state.db,
+        &hash_challenge_id(&req.challenge_id),
+        ChallengeKind::Registration,
+    )
+    .await?;
+    let Some(challenge) = challenge.filter(|ch| ch.user_id == user.id) else {
+        return Err(invalid_challenge());
+    };
+
+    let reg_state: PasskeyRegistration = serde_json::from_str(&challenge.state).map_err(|err| {
+        tracing::error!(error = err.to_string(), "invalid webauthn state: {err}");
+        Problem::internal()
+    })?;
+
+    let passkey = webauthn
+        .finish_passkey_registration(&req.credential, &reg_state)
+        .map_err(|err| {
+            tracing::info!(
+                error = err.to_string(),
+                "passkey registration failed: {err}"
+            );
+            Problem::new(
+                ProblemCode::BadRequest,
+                "Passkey registration failed".to_string(),
+            )
+        })?;
+
+    let credential_id = BASE64_URL_SAFE_NO_PAD.encode(passkey.cred_id().as_slice());
+    let passkey = serde_json::to_string(&passkey).expect("passkey should serialize");
+
+    let res =
+        store::webauthn::create_credential(&state.db, user.id, &credential_id, &req.name, &passkey)
+            .await;
+    match res {
+        Ok(cred) => Ok((StatusCode::CREATED, Json(cred))),
+        Err(sqlx::Error::Database(db)) if db.code().as_ref().is_some_and(|c| c == "2067") => {
+            Err(Problem::new(
+                ProblemCode::AlreadyExists,
+                "Passkey is already registered".to_string(),
+            ))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LoginStartReq {
+    username: String,
+}
+
+impl Validate for LoginStartReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoginStartResp {
+    challenge_id: String,
+    /// Options for `navigator.credentials.get()`.
+    #[schema(value_type = Object)]
+    options: RequestChallengeResponse,
+}
+
+/// Starts passkey login. The assertion is sent to the login endpoint.
+#[utoipa::path(
+    post,
+    path = "/auth/webauthn/login/start",
+    operation_id = "passkey_login_start",
+    tag = "auth",
+    request_body = LoginStartReq,
+    security(()),
+    responses(
+        (status = 200, description = "Authentication challenge", body = LoginStartResp),
+        (status = 401, description = "User has no passkeys", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Passkeys aren't configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn login_start(
+    State(state): State<AppState>,
+    ValidJson(req): ValidJson<LoginStartReq>,
+) -> Result<Json<LoginStartResp>, Problem> {
+    let webauthn = get_webauthn(&state)?;
+
+    let user = store::user::get_user(&state.db, &req.username)
+        .await
+        .map_err(db_error)?
+        .ok_or(LoginError::InvalidCredentials)?;
+
+    let passkeys: Vec<Passkey> = store::webauthn::list_credentials(&state.db, user.id)
+        .await
+        .map_err(db_error)?
+        .iter()
+        .filter_map(|cred| parse_passkey(cred).ok())
+        .collect();
+    if passkeys.is_empty() {
+        return Err(LoginError::InvalidCredentials.into());
+    }
+
+    let (options, auth_state) =
+        webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|err| {
+                tracing::error!(error = err.to_string(), "webauthn error: {err}");
+                LoginError::Internal
+            })?;
+
+    let challenge_id = save_challenge(&state, user.id, ChallengeKind::Authentication, &auth_state)
+        .await
+        .map_err(|_| LoginError::Internal)?;
+
+    Ok(Json(LoginStartResp {
+        challenge_id,
+        options,
+    }))
+}
+
+/// Verifies passkey assertion and returns id of the authenticated user.
+pub async fn verify_login(
+    state: &AppState,
+    challenge_id: &str,
+    credential: &PublicKeyCredential,
+) -> Result<i64, LoginError> {
+    let webauthn = get_webauthn(state).map_err(|_| LoginError::InvalidCredentials)?;
+
+    let challenge = store::webauthn::take_challenge(
+        &state.db,
+        &hash_challenge_id(challenge_id),
+        ChallengeKind::Authentication,
+    )
+    .await
+    .map_err(db_error)?
+    .ok_or(LoginError::InvalidCredentials)?;
+
+    let auth_state: PasskeyAuthentication =
+        serde_json::from_str(&challenge.state).map_err(|err| {
+            tracing::error!(error = err.to_string(), "invalid webauthn state: {err}");
+            LoginError::Internal
+        })?;
+
+    let result = webauthn
+        .finish_passkey_authentication(credential, &auth_state)
+        .map_err(|err| {
+            tracing::info!(
+                error = err.to_string(),
+                "passkey authentication failed: {err}"
+            );
+            LoginError::InvalidCredentials
+        })?;
+
+    let credential_id = BASE64_URL_SAFE_NO_PAD.encode(result.cred_id().as_slice());
+    let cred = store::webauthn::get_credential(&state.db, &credential_id)
+        .await
+        .map_err(db_error)?
+        .filter(|cred| cred.user_id == challenge.user_id)
+        .ok_or(LoginError::InvalidCredentials)?;
+
+    // Store updated signature counter and backup state.
+    let mut updated = None;
+    if result.needs_update() {
+        let mut passkey = parse_passkey(&cred).map_err(|_| LoginError::Internal)?;
+        if passkey.update_credential(&result).is_some() {
+            updated = Some(serde_json::to_string(&passkey).expect("passkey should serialize"));
+        }
+    }
+    store::webauthn::update_credential_usage(&state.db, cred.id, updated.as_deref())
+        .await
+        .map_err(db_error)?;
+
+    Ok(cred.user_id)
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/webauthn/credentials",
+    operation_id = "list_passkeys",
+    tag = "auth",
+    responses((status = 200, description = "Passkeys of the user", body = Vec<Credential>))
+)]
+pub async fn list_credentials(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Json<Vec<Credential>>, Problem> {
+    let creds = store::webauthn::list_credentials(&state.db, user.id).await?;
+    Ok(Json(creds))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/auth/webauthn/credentials/{id}",
+    operation_id = "delete_passkey",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Passkey deleted"),
+        (status = 404, description = "Passkey not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn delete_credential(
+    State(state): State<AppState>,
+    user: User,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, Problem> {
+    let deleted = store::webauthn::delete_credential(&state.db, user.id, id).await?;
+    if !deleted {
+        return Err(Problem::not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn save_challenge<T: Serialize>(
+    state: &AppState,
+    user_id: i64,
+    kind: ChallengeKind,
+    ceremony_state: &T,
+) -> Result<String, Problem> {
+    let challenge_id = random_token();
+    let ceremony_state =
+        serde_json::to_string(ceremony_state).expect("webauthn state should serialize");
+    let expires_at = time::OffsetDateTime::now_utc() + time::Duration::minutes(5);
+
+    store::webauthn::create_challenge(
+        &state.db,
+        &hash_challenge_id(&challenge_id),
+        user_id,
+        kind,
+        &ceremony_state,
+        expires_at,
+    )
+    .await?;
+
+    Ok(challenge_id)
+}
+
+fn get_webauthn(state: &AppState) -> Result<&Webauthn, Problem> {
+    state.webauthn.as_deref().ok_or_else(Problem::not_found)
+}
+
+// User handle has to be stable for all credentials of the user.
+fn user_handle(user_id: i64) -> Uuid {
+    Uuid::from_u64_pair(0, user_id as u64)
+}
+
+fn hash_challenge_id(challenge_id: &str) -> String {
+    to_hex(&Sha256::digest(challenge_id)[..])
+}
+
+fn parse_passkey(cred: &Credential) -> Result<Passkey, serde_json::Error> {
+    serde_json::from_str(&cred.passkey).inspect_err(|err| {
+        tracing::error!(
+            credential = cred.id,
+            error = err.to_string(),
+            "invalid stored passkey: {err}"
+        );
+    })
+}
+
+fn invalid_challenge() -> Problem {
+    Problem::from_code(ProblemCode::InvalidChallenge)
+}
+
+fn db_error(err: sqlx::Error) -> LoginError {
+    tracing::error!(error = err.to_string(), "database error: {err}");
+    LoginError::Internal
+}
@@ -0,0 +1,228 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{RequireRole, role::Admin},
+    db::Db,
+    handler::{
+        FieldError, Problem,
+        validate::{ValidJson, Validate, Validator},
+    },
+    store::{
+        self,
+        webhook::{Delivery, Event, Webhook},
+    },
+};
+
+const MAX_URL_LEN: usize = 2000;
+const MIN_SECRET_LEN: usize = 16;
+const MAX_SECRET_LEN: usize = 200;
+/// Deliveries returned by the log.
+const DELIVERY_LOG_LEN: i64 = 100;
+
+#[derive(Deserialize, ToSchema)]
+pub struct WebhookCreateReq {
+    /// Http or https url that receives the events.
+    url: String,
+    /// Events sent to the url, all events if it's empty.
+    #[serde(default)]
+    events: Vec<Event>,
+    /// Key of the signatures, at least 16 characters.
+    secret: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+impl Validate for WebhookCreateReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        validate_target(&mut v, &mut self.url, &mut self.events);
+        validate_secret(&mut v, &self.secret);
+        v.finish()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WebhookUpdateReq {
+    url: String,
+    #[serde(default)]
+    events: Vec<Event>,
+    /// New key of the signatures, the current one is kept if it's missing.
+    secret: Option<String>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+impl Validate for WebhookUpdateReq {
+    fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        validate_target(&mut v, &mut self.url, &mut self.events);
+        if let Some(secret) = &self.secret {
+            validate_secret(&mut v, secret);
+        }
+        v.finish()
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn validate_target(v: &mut Validator, url: &mut String, events: &mut Vec<Event>) {
+    *url = url.trim().to_string();
+    let valid_url = url::Url::parse(url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some());
+    v.check(
+        "url",
+        valid_url,
+        "invalid_url",
+        "must be an http or https url",
+    )
+    .check(
+        "url",
+        url.len() <= MAX_URL_LEN,
+        "too_long",
+        &format!("must be at most {MAX_URL_LEN} characters long"),
+    );
+
+    let mut seen = Vec::with_capacity(events.len());
+    events.retain(|event| {
+        let first = !seen.contains(event);
+        seen.push(*event);
+        first
+    });
+}
+
+fn validate_secret(v: &mut Validator, secret: &str) {
+    let len = secret.chars().count();
+    v.check(
+        "secret",
+        (MIN_SECRET_LEN..=MAX_SECRET_LEN).contains(&len),
+        "invalid_length",
+        &format!("must be between {MIN_SECRET_LEN} and {MAX_SECRET_LEN} characters long"),
+    );
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    operation_id = "create_webhook",
+    tag = "webhooks",
+    request_body = WebhookCreateReq,
+    responses(
+        (status = 201, description = "Created webhook", body = Webhook),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid url or secret", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn create(
+    State(db): State<Db>,
+    _: RequireRole<Admin>,
+    ValidJson(req): ValidJson<WebhookCreateReq>,
+) -> Result<(StatusCode, Json<Webhook>), Problem> {
+    let webhook =
+        store::webhook::create(&db, &req.url, &req.events, &req.secret, req.enabled).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    operation_id = "list_webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "All webhooks", body = Vec<Webhook>),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn list(
+    State(db): State<Db>,
+    _: RequireRole<Admin>,
+) -> Result<Json<Vec<Webhook>>, Problem> {
+    let webhooks = store::webhook::list(&db).await?;
+    Ok(Json(webhooks))
+}
+
+#[utoipa::path(
+    put,
+    path = "/webhooks/{id}",
+    operation_id = "update_webhook",
+    tag = "webhooks",
+    request_body = WebhookUpdateReq,
+    responses(
+        (status = 200, description = "Updated webhook", body = Webhook),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid url or secret", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn update(
+    State(db): State<Db>,
+    Path(id): Path<i64>,
+    _: RequireRole<Admin>,
+    ValidJson(req): ValidJson<WebhookUpdateReq>,
+) -> Result<Json<Webhook>, Problem> {
+    let webhook = store::webhook::update(
+        &db,
+        id,
+        &req.url,
+        &req.events,
+        req.secret.as_deref(),
+        req.enabled,
+    )
+    .await?
+    .ok_or_else(Problem::not_found)?;
+    Ok(Json(webhook))
+}
+
+/// Deletes the webhook and its delivery log, pending deliveries aren't sent.
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    operation_id = "delete_webhook",
+    tag = "webhooks",
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn delete(
+    State(db): State<Db>,
+    Path(id): Path<i64>,
+    _: RequireRole<Admin>,
+) -> Result<StatusCode, Problem> {
+    if !store::webhook::delete(&db, id).await? {
+        return Err(Problem::not_found());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Newest 100 deliveries of the webhook, with the result of their last attempt.
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    operation_id = "list_webhook_deliveries",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Deliveries, newest first", body = Vec<Delivery>),
+        (status = 403, description = "User isn't an admin", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+pub async fn deliveries(
+    State(db): State<Db>,
+    Path(id): Path<i64>,
+    _: RequireRole<Admin>,
+) -> Result<Json<Vec<Delivery>>, Problem> {
+    if store::webhook::get(&db, id).await?.is_none() {
+        return Err(Problem::not_found());
+    }
+    let deliveries = store::webhook::list_deliveries(&db, id, DELIVERY_LOG_LEN).await?;
+    Ok(Json(deliveries))
+}
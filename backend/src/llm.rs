@@ -0,0 +1,292 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use async_openai::{
+    error::OpenAIError,
+    types::responses::{CreateResponseArgs, ReasoningEffort, ResponseFormatJsonSchema},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::store::{item::Item, section::Section};
+
+pub type OpenAiClient = async_openai::Client<async_openai::config::OpenAIConfig>;
+
+pub type LlmFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, LlmError>> + Send + 'a>>;
+
+#[derive(Debug, Error)]
+pub enum LlmError {
+    #[error("openai error")]
+    OpenAi(#[from] OpenAIError),
+
+    #[error("invalid response recieved")]
+    InvalidResponse(#[from] serde_json::Error),
+}
+
+/// Assignment of an item to a section, as proposed by the language model.
+/// Ids are not validated, the model can return ids that don't exist.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Categorization {
+    pub section_id: i64,
+    pub item_id: i64,
+}
+
+/// Tokens billed for a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Categorized {
+    pub categorizations: Vec<Categorization>,
+    /// Not every provider reports usage.
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CategorizationResponse {
+    categorized: Vec<Categorization>,
+}
+
+/// Items and store of a spoken command.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedCommand {
+    pub items: Vec<String>,
+    /// Store as it was said, it's not resolved to an existing store.
+    pub store: Option<String>,
+    /// Not every provider reports usage.
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandResponse {
+    items: Vec<String>,
+    store: Option<String>,
+}
+
+/// Language model used by the app. It's a trait, so that it can be replaced in tests.
+pub trait Llm: Send + Sync {
+    /// Assigns items to the sections. Items that don't belong to any section are left out.
+    fn categorize<'a>(
+        &'a self,
+        items: Vec<Item>,
+        sections: Vec<Section>,
+    ) -> LlmFuture<'a, Categorized>;
+
+    /// Splits a spoken command, ie. "add milk and bread to Mercator", into item names
+    /// and the store it mentions. Store names are given as hints.
+    fn parse_command<'a>(
+        &'a self,
+        text: String,
+        stores: Vec<String>,
+    ) -> LlmFuture<'a, ParsedCommand>;
+
+    /// Whether the provider has everything it needs to be called, ie. an api key.
+    fn is_configured(&self) -> bool;
+}
+
+#[derive(Serialize)]
+struct PromptItem {
+    id: i64,
+    name: String,
+}
+
+impl From<Item> for PromptItem {
+    fn from(value: Item) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+        }
+    }
+}
+
+impl From<Section> for PromptItem {
+    fn from(value: Section) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Prompt {
+    items: Vec<PromptItem>,
+    sections: Vec<PromptItem>,
+}
+
+/// Api key in the default `config.toml`.
+const PLACEHOLDER_API_KEY: &str = "<your key>";
+
+pub struct OpenAiLlm {
+    client: OpenAiClient,
+    configured: bool,
+}
+
+impl OpenAiLlm {
+    pub fn new(api_key: &str) -> Self {
+        let config = async_openai::config::OpenAIConfig::new().with_api_key(api_key);
+        Self {
+            client: async_openai::Client::with_config(config),
+            configured: !api_key.is_empty() && api_key != PLACEHOLDER_API_KEY,
+        }
+    }
+
+    async fn categorize_items(
+        &self,
+        items: Vec<Item>,
+        sections: Vec<Section>,
+    ) -> Result<Categorized, LlmError> {
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["categorized"],
+            "properties": {
+                "categorized": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": [
+                            "section_id",
+                            "item_id"
+                        ],
+                        "properties": {
+                            "section_id": {
+                                "type": "integer"
+                            },
+                            "item_id": {
+                                "type": "integer"
+                            }
+                        },
+                        "additionalProperties": false,
+                    }
+                }
+            },
+            "additionalProperties": false,
+        });
+
+        let prompt_data = Prompt {
+            items: items.into_iter().map(|it| it.into()).collect(),
+            sections: sections.into_iter().map(|sec| sec.into()).collect(),
+        };
+        let prompt_data = serde_json::to_string(&prompt_data).expect("prompt should be valid json");
+
+        let prompt = format!(
+            "You are a helpful store manager assistant. You are given a list of store sections and items. Section and items names are in slovene or english language. Your task is to organize the items into sections and return the mapping.\n\nEach item can be in at most one section. **IMPORTANT:** If an item doesn't belong in any of the sections, ignore it by not including it in the output.\n\n{prompt_data}"
+        );
+
+        let request = CreateResponseArgs::default()
+            .model("gpt-5-mini")
+            .reasoning(ReasoningEffort::Low)
+            .text(ResponseFormatJsonSchema {
+                description: Some("Mapping of items to sections".to_string()),
+                name: "categorization".to_string(),
+                schema: Some(schema),
+                strict: Some(true),
+            })
+            .input(prompt)
+            .build()?;
+
+        let response = self.client.responses().create(request).await?;
+        let usage = response.usage.as_ref().map(|usage| TokenUsage {
+            input: usage.input_tokens.into(),
+            output: usage.output_tokens.into(),
+        });
+
+        let Some(response_text) = response.output_text() else {
+            return Ok(Categorized {
+                categorizations: vec![],
+                usage,
+            });
+        };
+
+        let response: CategorizationResponse = serde_json::from_str(&response_text)?;
+        Ok(Categorized {
+            categorizations: response.categorized,
+            usage,
+        })
+    }
+
+    async fn parse_voice_command(
+        &self,
+        text: String,
+        stores: Vec<String>,
+    ) -> Result<ParsedCommand, LlmError> {
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["items", "store"],
+            "properties": {
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "string"
+                    }
+                },
+                "store": {
+                    "type": ["string", "null"]
+                }
+            },
+            "additionalProperties": false,
+        });
+
+        let prompt_data = serde_json::json!({ "command": text, "stores": stores }).to_string();
+        let prompt = format!(
+            "You are a shopping list assistant. You are given a spoken command in slovene or english language and the names of the stores. Your task is to return the items that the command adds and the store it mentions.\n\nItem names are short and in the language of the command, quantities are written with digits, ie. \"two loaves of bread\" is \"2 loaves of bread\". Store is the name from the list that the command refers to, the name as it was said if it's not in the list, or null if no store is mentioned.\n\n{prompt_data}"
+        );
+
+        let request = CreateResponseArgs::default()
+            .model("gpt-5-mini")
+            .reasoning(ReasoningEffort::Low)
+            .text(ResponseFormatJsonSchema {
+                description: Some("Items and store of the command".to_string()),
+                name: "command".to_string(),
+                schema: Some(schema),
+                strict: Some(true),
+            })
+            .input(prompt)
+            .build()?;
+
+        let response = self.client.responses().create(request).await?;
+        let usage = response.usage.as_ref().map(|usage| TokenUsage {
+            input: usage.input_tokens.into(),
+            output: usage.output_tokens.into(),
+        });
+
+        let Some(response_text) = response.output_text() else {
+            return Ok(ParsedCommand {
+                usage,
+                ..Default::default()
+            });
+        };
+
+        let response: CommandResponse = serde_json::from_str(&response_text)?;
+        Ok(ParsedCommand {
+            items: response.items,
+            store: response.store,
+            usage,
+        })
+    }
+}
+
+impl Llm for OpenAiLlm {
+    fn categorize<'a>(
+        &'a self,
+        items: Vec<Item>,
+        sections: Vec<Section>,
+    ) -> LlmFuture<'a, Categorized> {
+        Box::pin(self.categorize_items(items, sections))
+    }
+
+    fn parse_command<'a>(
+        &'a self,
+        text: String,
+        stores: Vec<String>,
+    ) -> LlmFuture<'a, ParsedCommand> {
+        Box::pin(self.parse_voice_command(text, stores))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.configured
+    }
+}
@@ -1,20 +1,42 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use clap::{Parser, Subcommand};
 use tracing::Level;
 use tracing_subscriber::EnvFilter;
 
 use crate::app::start_server;
 use crate::config::Config;
+use crate::llm::OpenAiLlm;
 use crate::state::AppState;
+use crate::store::user::Role;
 
 mod admin;
+mod api_version;
 mod app;
 mod auth;
+mod backup;
+mod caldav;
 mod config;
 mod db;
+mod frontend;
 mod handler;
+mod llm;
+mod metrics;
+mod notify;
+mod oidc;
+mod openapi;
+mod print;
+mod push;
+mod request_id;
+mod shutdown;
 mod state;
 mod store;
+#[cfg(test)]
+mod tests;
+mod tls;
 mod util;
+mod webhook;
 
 #[derive(Debug, Parser)]
 struct Cli {
@@ -24,7 +46,51 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Command {
-    CreateUser,
+    CreateUser {
+        /// One of: shopper, member, admin
+        #[arg(long, default_value = "admin")]
+        role: Role,
+    },
+    SetRole {
+        username: String,
+        /// One of: shopper, member, admin
+        role: Role,
+    },
+    /// Copies the database to a file, it's safe while the server is running.
+    Backup {
+        output: PathBuf,
+        #[arg(long)]
+        gzip: bool,
+    },
+    /// Writes all data as json to the file, or to stdout.
+    Export {
+        output: Option<PathBuf>,
+        /// Include password hashes of the users.
+        #[arg(long)]
+        password_hashes: bool,
+    },
+    /// Imports a file created by `export`, adding it to the existing data.
+    Import {
+        input: PathBuf,
+        /// Delete all stores, sections and items first.
+        #[arg(long)]
+        replace: bool,
+        /// Only print what would be imported.
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Replaces the database with a backup. The server must be stopped.
+    Restore {
+        input: PathBuf,
+        /// Don't ask for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Prints a new key pair for Web Push, the private key goes to `WEB_PUSH__VAPID_PRIVATE_KEY`.
+    GenerateVapidKeys,
 }
 
 #[tokio::main]
@@ -35,20 +101,45 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
 
+    // Keys are generated before the push config exists.
+    if let Some(Command::GenerateVapidKeys) = &cli.command {
+        let (private_key, public_key) = push::generate_keys();
+        println!("private key: {private_key}");
+        println!("public key:  {public_key}");
+        return Ok(());
+    }
+
     let conf = Config::new()?;
     init_tracing(&conf);
 
+    // The database file is replaced, so it can't be opened first.
+    if let Some(Command::Restore { input, yes }) = &cli.command {
+        return admin::restore(&conf, input, *yes).await;
+    }
+
     let db_pool = db::connect(&conf.db_path).await?;
 
-    let openai_config =
-        async_openai::config::OpenAIConfig::new().with_api_key(&conf.openai_api_key);
-    let openai = async_openai::Client::with_config(openai_config);
+    let llm = Arc::new(OpenAiLlm::new(&conf.openai_api_key));
 
-    let state = AppState::new(db_pool, conf, openai);
+    let state = AppState::new(db_pool, conf, llm)?;
 
     match cli.command {
         None => start_server(state).await,
-        Some(Command::CreateUser) => admin::create_user(state).await,
+        Some(Command::CreateUser { role }) => admin::create_user(state, role).await,
+        Some(Command::SetRole { username, role }) => admin::set_role(state, &username, role).await,
+        Some(Command::Backup { output, gzip }) => admin::backup(state, &output, gzip).await,
+        Some(Command::Export {
+            output,
+            password_hashes,
+        }) => admin::export(state, output.as_deref(), password_hashes).await,
+        Some(Command::Import {
+            input,
+            replace,
+            dry_run,
+            yes,
+        }) => admin::import(state, &input, replace, dry_run, yes).await,
+        Some(Command::Restore { .. }) => unreachable!("restore is handled before connecting"),
+        Some(Command::GenerateVapidKeys) => unreachable!("keys are generated before the config"),
     }
 }
 
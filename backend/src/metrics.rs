@@ -0,0 +1,309 @@
+//! Prometheus metrics of the app, served at `/metrics`.
+
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{Histogram, exponential_buckets};
+use prometheus_client::registry::Registry;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::handler::{DbErrorKind, ProblemCode};
+use crate::llm::TokenUsage;
+use crate::state::AppState;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RouteLabels {
+    method: String,
+    route: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ProblemLabels {
+    code: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct DbErrorLabels {
+    kind: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct OutcomeLabels {
+    outcome: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TokenKind {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TokenLabels {
+    kind: String,
+}
+
+/// Why an assignment returned by the language model was ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    UnknownItem,
+    UnknownSection,
+    DuplicateItem,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RejectionLabels {
+    reason: String,
+}
+
+type HistogramFamily<L> = Family<L, Histogram, fn() -> Histogram>;
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: Family<RequestLabels, Counter>,
+    http_duration: HistogramFamily<RouteLabels>,
+    problems: Family<ProblemLabels, Counter>,
+    db_errors: Family<DbErrorLabels, Counter>,
+    db_connections: Gauge,
+    db_idle_connections: Gauge,
+    organize_calls: Family<OutcomeLabels, Counter>,
+    organize_duration: HistogramFamily<OutcomeLabels>,
+    llm_tokens: Family<TokenLabels, Counter>,
+    organize_rejected: Family<RejectionLabels, Counter>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("lshop");
+
+        let http_requests = Family::default();
+        registry.register(
+            "http_requests",
+            "Handled requests by route and status",
+            http_requests.clone(),
+        );
+        let http_duration: HistogramFamily<RouteLabels> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.001, 2.0, 14)));
+        registry.register(
+            "http_request_duration_seconds",
+            "Time to handle a request",
+            http_duration.clone(),
+        );
+        let problems = Family::default();
+        registry.register(
+            "http_problems",
+            "Error responses by problem code",
+            problems.clone(),
+        );
+        let db_errors = Family::default();
+        registry.register(
+            "db_errors",
+            "Failed database queries that reached a handler",
+            db_errors.clone(),
+        );
+        let db_connections = Gauge::default();
+        registry.register(
+            "db_connections",
+            "Open connections of the database pool",
+            db_connections.clone(),
+        );
+        let db_idle_connections = Gauge::default();
+        registry.register(
+            "db_idle_connections",
+            "Idle connections of the database pool",
+            db_idle_connections.clone(),
+        );
+        let organize_calls = Family::default();
+        registry.register(
+            "organize_calls",
+            "Calls of the language model to organize items",
+            organize_calls.clone(),
+        );
+        let organize_duration: HistogramFamily<OutcomeLabels> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.25, 2.0, 10)));
+        registry.register(
+            "organize_duration_seconds",
+            "Time the language model took to organize items",
+            organize_duration.clone(),
+        );
+        let llm_tokens = Family::default();
+        registry.register(
+            "llm_tokens",
+            "Tokens used by the language model",
+            llm_tokens.clone(),
+        );
+        let organize_rejected = Family::default();
+        registry.register(
+            "organize_rejected_assignments",
+            "Assignments returned by the language model that were ignored",
+            organize_rejected.clone(),
+        );
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            problems,
+            db_errors,
+            db_connections,
+            db_idle_connections,
+            organize_calls,
+            organize_duration,
+            llm_tokens,
+            organize_rejected,
+        }
+    }
+
+    pub fn organize_call(&self, outcome: Outcome, duration: Duration) {
+        let labels = OutcomeLabels {
+            outcome: label(outcome),
+        };
+        self.organize_calls.get_or_create(&labels).inc();
+        self.organize_duration
+            .get_or_create(&labels)
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn llm_usage(&self, usage: TokenUsage) {
+        for (kind, tokens) in [
+            (TokenKind::Input, usage.input),
+            (TokenKind::Output, usage.output),
+        ] {
+            self.llm_tokens
+                .get_or_create(&TokenLabels { kind: label(kind) })
+                .inc_by(tokens);
+        }
+    }
+
+    pub fn organize_rejected(&self, reason: Rejection) {
+        self.organize_rejected
+            .get_or_create(&RejectionLabels {
+                reason: label(reason),
+            })
+            .inc();
+    }
+
+    fn encode(&self, db: &sqlx::SqlitePool) -> String {
+        self.db_connections.set(db.size().into());
+        self.db_idle_connections.set(db.num_idle() as i64);
+
+        let mut out = String::new();
+        prometheus_client::encoding::text::encode(&mut out, &self.registry)
+            .expect("writing to a string can't fail");
+        out
+    }
+}
+
+/// Label value of an enum, in snake case like in the api.
+fn label(value: impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(value)) => value,
+        _ => unreachable!("label should serialize to a string"),
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Middleware that records every request.
+pub async fn track(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    // Unmatched paths are grouped, otherwise anyone could create new series.
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "fallback".to_string());
+
+    let res = next.run(req).await;
+
+    let metrics = &state.metrics;
+    metrics
+        .http_duration
+        .get_or_create(&RouteLabels {
+            method: method.clone(),
+            route: route.clone(),
+        })
+        .observe(start.elapsed().as_secs_f64());
+    metrics
+        .http_requests
+        .get_or_create(&RequestLabels {
+            method,
+            route,
+            status: res.status().as_u16(),
+        })
+        .inc();
+    if let Some(code) = res.extensions().get::<ProblemCode>() {
+        metrics
+            .problems
+            .get_or_create(&ProblemLabels { code: label(code) })
+            .inc();
+    }
+    if let Some(kind) = res.extensions().get::<DbErrorKind>() {
+        metrics
+            .db_errors
+            .get_or_create(&DbErrorLabels { kind: label(kind) })
+            .inc();
+    }
+
+    res
+}
+
+/// Serves the metrics, if the request has the configured token.
+pub async fn serve(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(conf) = &state.config.metrics else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if let Some(token) = &conf.token {
+        let given = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        // Digests are compared, so that the time doesn't depend on the matching prefix.
+        if Sha256::digest(given) != Sha256::digest(token) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+            )
+                .into_response();
+        }
+    }
+
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE))],
+        state.metrics.encode(&state.db),
+    )
+        .into_response()
+}
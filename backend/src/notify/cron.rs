@@ -0,0 +1,215 @@
+//! Five field cron expressions: minute, hour, day of month, month and day of week.
+//! Fields are `*`, values, ranges, lists and steps, ie. `0 8 * * mon-fri` or
+//! `*/30 9-17 * * *`. Months and days of the week can be written as names.
+//! Expressions are evaluated in the local time of a time zone, like crontab does.
+
+use std::str::FromStr;
+
+use jiff::tz::TimeZone;
+use thiserror::Error;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+/// Schedules are searched this far ahead, so that `29 2` is found after any date.
+const SEARCH_DAYS: usize = 5 * 366;
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CronError {
+    #[error("expected 5 fields: minute, hour, day of month, month and day of week")]
+    FieldCount,
+
+    #[error("invalid {0} field")]
+    Field(&'static str),
+}
+
+/// Parsed expression, the fields are bit sets of the allowed values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day of month field is `*`.
+    any_day: bool,
+    /// Day of week field is `*`.
+    any_weekday: bool,
+}
+
+impl FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            s => s,
+        };
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CronError::FieldCount);
+        };
+
+        let mut weekdays = parse_field(weekday, "day of week", 0, 7, WEEKDAYS)?;
+        // Sunday is both 0 and 7.
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        let cron = Self {
+            minutes: parse_field(minute, "minute", 0, 59, &[])?,
+            hours: parse_field(hour, "hour", 0, 23, &[])?,
+            days: parse_field(day, "day of month", 1, 31, &[])?,
+            months: parse_field(month, "month", 1, 12, MONTHS)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        };
+        Ok(cron)
+    }
+}
+
+impl Cron {
+    /// First time after `after` that matches the expression in the local time of the
+    /// zone. Times skipped by a daylight saving change run an hour later, times that
+    /// repeat run once. Returns `None` if it never matches, ie. for `0 0 31 2 *`.
+    pub fn next_after(&self, after: OffsetDateTime, tz: &TimeZone) -> Option<OffsetDateTime> {
+        let mut local = local_time(after, tz)?;
+        loop {
+            local = self.next_local_after(local)?;
+            let next = instant(local, tz)?;
+            // Earlier occurrence of a repeated time can be before `after`.
+            if next > after {
+                return Some(next);
+            }
+        }
+    }
+
+    /// First local time after `after` that matches the expression.
+    fn next_local_after(&self, after: PrimitiveDateTime) -> Option<PrimitiveDateTime> {
+        let mut date = after.date();
+        // Minutes since midnight, the current minute has already started.
+        let mut from = u32::from(after.hour()) * 60 + u32::from(after.minute()) + 1;
+
+        for _ in 0..SEARCH_DAYS {
+            if self.matches_day(date)
+                && let Some(time) = self.first_time_from(from)
+            {
+                return Some(PrimitiveDateTime::new(date, time));
+            }
+            date = date.next_day()?;
+            from = 0;
+        }
+        None
+    }
+
+    fn matches_day(&self, date: Date) -> bool {
+        if !has(self.months, u8::from(date.month()).into()) {
+            return false;
+        }
+        let day = has(self.days, date.day().into());
+        let weekday = has(
+            self.weekdays,
+            date.weekday().number_days_from_sunday().into(),
+        );
+        // Like in crontab, a day matches either field when both are restricted.
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    fn first_time_from(&self, from: u32) -> Option<Time> {
+        (from..24 * 60)
+            .find(|m| has(self.hours, m / 60) && has(self.minutes, m % 60))
+            .map(|m| Time::from_hms((m / 60) as u8, (m % 60) as u8, 0).expect("time is valid"))
+    }
+}
+
+/// Wall clock time of the zone at the instant.
+fn local_time(at: OffsetDateTime, tz: &TimeZone) -> Option<PrimitiveDateTime> {
+    let timestamp = jiff::Timestamp::from_second(at.unix_timestamp()).ok()?;
+    let offset = UtcOffset::from_whole_seconds(tz.to_offset(timestamp).seconds()).ok()?;
+    let at = at.to_offset(offset);
+    Some(PrimitiveDateTime::new(at.date(), at.time()))
+}
+
+/// Instant of the wall clock time in the zone.
+fn instant(local: PrimitiveDateTime, tz: &TimeZone) -> Option<OffsetDateTime> {
+    let civil = jiff::civil::DateTime::new(
+        local.year().try_into().ok()?,
+        u8::from(local.month()) as i8,
+        local.day() as i8,
+        local.hour() as i8,
+        local.minute() as i8,
+        0,
+        0,
+    )
+    .ok()?;
+    let timestamp = tz.to_timestamp(civil).ok()?;
+    OffsetDateTime::from_unix_timestamp(timestamp.as_second()).ok()
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn parse_field(
+    field: &str,
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+) -> Result<u64, CronError> {
+    let err = || CronError::Field(name);
+    let value = |s: &str| -> Result<u32, CronError> {
+        let lower = s.to_ascii_lowercase();
+        let value = match names.iter().position(|n| *n == lower) {
+            // Names of months start at 1, of weekdays at 0.
+            Some(idx) => idx as u32 + min,
+            None => s.parse().map_err(|_| err())?,
+        };
+        (min..=max)
+            .contains(&value)
+            .then_some(value)
+            .ok_or_else(err)
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| err())?;
+                if step == 0 {
+                    return Err(err());
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // `5/15` starts at 5 and runs to the end of the range.
+            None if step.is_some() => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if start > end {
+            return Err(err());
+        }
+        for v in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
@@ -0,0 +1,250 @@
+//! Scheduled notifications with the list of a store or a digest of added items, sent
+//! by email or to an ntfy topic. Schedules are cron expressions set by each user.
+
+pub mod cron;
+mod ntfy;
+mod smtp;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use jiff::tz::TimeZone;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+use crate::config::Config;
+use crate::db::Db;
+use crate::handler::item::{ItemList, group_items};
+use crate::handler::text::render_plain;
+use crate::shutdown::Shutdown;
+use crate::store::{
+    self,
+    notification::{Kind, Schedule, TransportKind},
+};
+use cron::Cron;
+use ntfy::NtfyTransport;
+use smtp::SmtpTransport;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const BATCH_SIZE: i64 = 20;
+const MAX_ERROR_LEN: usize = 500;
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<(), NotifyError>> + Send + 'a>>;
+
+#[derive(Debug, Error)]
+pub enum NotifyError {
+    #[error("database error: {0}")]
+    Db(#[from] sqlx::Error),
+
+    #[error("{0:?} transport isn't configured")]
+    NotConfigured(TransportKind),
+
+    #[error("invalid email address: {0}")]
+    InvalidAddress(String),
+
+    #[error("email error: {0}")]
+    Email(#[from] lettre::error::Error),
+
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("ntfy responded with {0}")]
+    Rejected(reqwest::StatusCode),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub title: String,
+    /// Plain text.
+    pub body: String,
+}
+
+/// Way of delivering messages. It's a trait, so that transports can be added without
+/// changing the scheduler.
+pub trait Transport: Send + Sync {
+    /// Sends the message to the target, an email address or a topic.
+    fn send<'a>(&'a self, target: &'a str, message: &'a Message) -> SendFuture<'a>;
+}
+
+/// Configured transports.
+pub struct Notifier {
+    email: Option<Box<dyn Transport>>,
+    ntfy: Option<Box<dyn Transport>>,
+}
+
+impl Notifier {
+    pub fn new(conf: &Config) -> Result<Self, NotifyError> {
+        let email = match &conf.smtp {
+            Some(smtp_conf) => Some(Box::new(SmtpTransport::new(smtp_conf)?) as Box<dyn Transport>),
+            None => None,
+        };
+        let ntfy = conf
+            .ntfy
+            .as_ref()
+            .map(|ntfy_conf| Box::new(NtfyTransport::new(ntfy_conf)) as Box<dyn Transport>);
+
+        Ok(Self { email, ntfy })
+    }
+
+    pub fn transport(&self, kind: TransportKind) -> Option<&dyn Transport> {
+        match kind {
+            TransportKind::Email => self.email.as_deref(),
+            TransportKind::Ntfy => self.ntfy.as_deref(),
+        }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.email.is_some() || self.ntfy.is_some()
+    }
+}
+
+/// Runs due schedules until shutdown.
+pub async fn run(db: Db, notifier: Arc<Notifier>, shutdown: Shutdown) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = shutdown.clone().requested() => return,
+        }
+
+        if let Err(err) = run_due(&db, &notifier, OffsetDateTime::now_utc()).await {
+            tracing::error!(
+                error = err.to_string(),
+                "scheduled notifications failed: {err}"
+            );
+        }
+    }
+}
+
+/// Sends the schedules that are due at `now` and moves them to their next run. A run
+/// that failed isn't retried, a digest then includes the items in the next run.
+/// Returns the number of runs.
+pub async fn run_due(
+    db: &Db,
+    notifier: &Notifier,
+    now: OffsetDateTime,
+) -> Result<usize, sqlx::Error> {
+    let due = store::notification::due(db, now, BATCH_SIZE).await?;
+    for schedule in &due {
+        let next_run_at = match next_run(&schedule.cron, &schedule.timezone, now) {
+            Some(next) => next,
+            None => {
+                tracing::warn!(schedule_id = schedule.id, "schedule never runs again");
+                now + time::Duration::days(1)
+            }
+        };
+
+        let error = send(db, notifier, schedule, now).await.err();
+        if let Some(err) = &error {
+            tracing::warn!(
+                schedule_id = schedule.id,
+                "scheduled notification failed: {err}"
+            );
+        }
+        finish(db, schedule.id, Some(next_run_at), now, error.as_ref()).await?;
+    }
+    Ok(due.len())
+}
+
+/// Next run of the cron expression after `now`, in the local time of the time zone.
+/// Returns `None` if either is invalid or the expression never matches.
+pub fn next_run(cron: &str, timezone: &str, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    let cron: Cron = cron.parse().ok()?;
+    let tz = TimeZone::get(timezone).ok()?;
+    cron.next_after(now, &tz)
+}
+
+/// Sends the message of the schedule. Returns false if there was nothing to send.
+pub async fn send(
+    db: &Db,
+    notifier: &Notifier,
+    schedule: &Schedule,
+    now: OffsetDateTime,
+) -> Result<bool, NotifyError> {
+    let transport = notifier
+        .transport(schedule.transport)
+        .ok_or(NotifyError::NotConfigured(schedule.transport))?;
+    let Some(message) = message(db, schedule, now).await? else {
+        return Ok(false);
+    };
+    transport.send(&schedule.target, &message).await?;
+    Ok(true)
+}
+
+/// Records the run, successful runs become the start of the next digest.
+pub async fn finish(
+    db: &Db,
+    id: i64,
+    next_run_at: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+    error: Option<&NotifyError>,
+) -> Result<(), sqlx::Error> {
+    let sent_at = error.is_none().then_some(now);
+    let error = error.map(|err| truncate(&err.to_string()));
+    store::notification::finish_run(db, id, next_run_at, sent_at, error.as_deref()).await
+}
+
+/// Message with the unchecked items of the schedule, grouped like the list. Returns
+/// `None` if there are no items.
+pub async fn message(
+    db: &Db,
+    schedule: &Schedule,
+    now: OffsetDateTime,
+) -> Result<Option<Message>, sqlx::Error> {
+    let mut items = store::item::list(db).await?;
+    if schedule.kind == Kind::Digest {
+        let since = schedule.last_sent_at.unwrap_or(schedule.created_at);
+        items.retain(|item| item.created_at > since && item.created_at <= now);
+    }
+    let (stores, sections) = tokio::try_join!(store::shop::list(db), store::section::list_all(db))?;
+    let mut list = group_items(items, stores, sections);
+
+    if let Some(store_id) = schedule.store_id {
+        list.unassigned.clear();
+        list.stores.retain(|s| s.store.id == store_id);
+    }
+
+    let count = count_items(&list);
+    if count == 0 {
+        return Ok(None);
+    }
+    let store_name = schedule
+        .store_id
+        .and_then(|_| list.stores.first())
+        .map(|s| s.store.name.clone());
+    let noun = if count == 1 { "item" } else { "items" };
+
+    let title = match (schedule.kind, store_name) {
+        (Kind::List, Some(store)) => format!("Shopping list for {store}: {count} {noun}"),
+        (Kind::List, None) => format!("Shopping list: {count} {noun}"),
+        (Kind::Digest, Some(store)) => format!("{count} {noun} added to {store}"),
+        (Kind::Digest, None) => format!("{count} {noun} added to the list"),
+    };
+    Ok(Some(Message {
+        title,
+        body: render_plain(&list),
+    }))
+}
+
+fn count_items(list: &ItemList) -> usize {
+    list.unassigned.len()
+        + list
+            .stores
+            .iter()
+            .map(|s| s.unassigned.len() + s.sections.iter().map(|s| s.items.len()).sum::<usize>())
+            .sum::<usize>()
+}
+
+fn truncate(error: &str) -> String {
+    match error.char_indices().nth(MAX_ERROR_LEN) {
+        Some((idx, _)) => format!("{}…", &error[..idx]),
+        None => error.to_string(),
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::config::NtfyConfig;
+use crate::notify::{Message, NotifyError, SendFuture, Transport};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Publishes as json, so that titles aren't limited to header values.
+#[derive(Serialize)]
+struct Publish<'a> {
+    topic: &'a str,
+    title: &'a str,
+    message: &'a str,
+    tags: &'a [&'a str],
+}
+
+pub struct NtfyTransport {
+    http: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl NtfyTransport {
+    pub fn new(conf: &NtfyConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("http client should build");
+
+        Self {
+            http,
+            url: conf.url.trim_end_matches('/').to_string(),
+            token: conf.token.clone(),
+        }
+    }
+}
+
+impl Transport for NtfyTransport {
+    fn send<'a>(&'a self, target: &'a str, message: &'a Message) -> SendFuture<'a> {
+        Box::pin(async move {
+            let mut req = self.http.post(&self.url).json(&Publish {
+                topic: target,
+                title: &message.title,
+                message: &message.body,
+                tags: &["shopping_cart"],
+            });
+            if let Some(token) = &self.token {
+                req = req.bearer_auth(token);
+            }
+
+            let res = req.send().await?;
+            if !res.status().is_success() {
+                return Err(NotifyError::Rejected(res.status()));
+            }
+            Ok(())
+        })
+    }
+}
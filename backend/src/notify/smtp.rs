@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use lettre::message::{Mailbox, header::ContentType};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+use crate::config::{SmtpConfig, SmtpSecurity};
+use crate::notify::{Message, NotifyError, SendFuture, Transport};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpTransport {
+    pub fn new(conf: &SmtpConfig) -> Result<Self, NotifyError> {
+        let from = conf
+            .from
+            .parse()
+            .map_err(|_| NotifyError::InvalidAddress(conf.from.clone()))?;
+
+        let mut builder = match conf.security {
+            SmtpSecurity::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&conf.host)?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&conf.host)?,
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&conf.host)
+            }
+        };
+        if let Some(port) = conf.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&conf.username, &conf.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            mailer: builder.timeout(Some(TIMEOUT)).build(),
+            from,
+        })
+    }
+}
+
+impl Transport for SmtpTransport {
+    fn send<'a>(&'a self, target: &'a str, message: &'a Message) -> SendFuture<'a> {
+        Box::pin(async move {
+            let to: Mailbox = target
+                .parse()
+                .map_err(|_| NotifyError::InvalidAddress(target.to_string()))?;
+            let email = lettre::Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(&message.title)
+                .header(ContentType::TEXT_PLAIN)
+                .body(message.body.clone())?;
+
+            self.mailer.send(email).await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,257 @@
+use base64::prelude::*;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use url::Url;
+
+use crate::config::OidcConfig;
+use crate::util::random_token;
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("invalid url: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("discovery document issuer '{0}' doesn't match configured issuer")]
+    IssuerMismatch(String),
+
+    #[error("token response doesn't contain an id token")]
+    MissingIdToken,
+
+    #[error("malformed id token")]
+    MalformedIdToken,
+
+    #[error("invalid id token: {0}")]
+    InvalidIdToken(&'static str),
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, aud: &str) -> bool {
+        match self {
+            Audience::Single(a) => a == aud,
+            Audience::Multiple(auds) => auds.iter().any(|a| a == aud),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: i64,
+    nonce: Option<String>,
+
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Verified identity of the user that logged in with the provider.
+#[derive(Debug)]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+    pub username: Option<String>,
+    pub groups: Vec<String>,
+}
+
+/// Secrets that are generated at the start of the login flow.
+/// They have to be stored until the user returns to the callback.
+pub struct AuthRequest {
+    pub url: Url,
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            metadata: OnceCell::new(),
+        }
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    /// Provider metadata is fetched on first use, so that the server can start
+    /// even if the provider is temporarily unavailable.
+    async fn metadata(&self) -> Result<&ProviderMetadata, OidcError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let issuer = self.config.issuer_url.trim_end_matches('/');
+                let url = format!("{issuer}/.well-known/openid-configuration");
+
+                let metadata: ProviderMetadata = self
+                    .http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                if metadata.issuer.trim_end_matches('/') != issuer {
+                    return Err(OidcError::IssuerMismatch(metadata.issuer));
+                }
+
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// Creates authorization code request with PKCE.
+    pub async fn auth_request(&self) -> Result<AuthRequest, OidcError> {
+        let metadata = self.metadata().await?;
+
+        let state = random_token();
+        let nonce = random_token();
+        let code_verifier = random_token();
+        let code_challenge = BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(&code_verifier));
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(AuthRequest {
+            url,
+            state,
+            code_verifier,
+            nonce,
+        })
+    }
+
+    /// Exchanges the authorization code for tokens and returns identity from the id token.
+    ///
+    /// Id token is received directly from the token endpoint over a TLS connection,
+    /// so its signature doesn't have to be validated (OpenID Connect Core 3.1.3.7).
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<Identity, OidcError> {
+        let metadata = self.metadata().await?;
+
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("code_verifier", code_verifier),
+        ];
+
+        let mut request = self.http.post(&metadata.token_endpoint);
+        match &self.config.client_secret {
+            Some(secret) => request = request.basic_auth(&self.config.client_id, Some(secret)),
+            None => params.push(("client_id", &self.config.client_id)),
+        }
+
+        let response: TokenResponse = request
+            .form(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let id_token = response.id_token.ok_or(OidcError::MissingIdToken)?;
+        let claims = decode_claims(&id_token)?;
+
+        if claims.iss != metadata.issuer {
+            return Err(OidcError::InvalidIdToken("issuer mismatch"));
+        }
+        if !claims.aud.contains(&self.config.client_id) {
+            return Err(OidcError::InvalidIdToken("audience mismatch"));
+        }
+        if claims.exp <= time::OffsetDateTime::now_utc().unix_timestamp() {
+            return Err(OidcError::InvalidIdToken("token expired"));
+        }
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidIdToken("nonce mismatch"));
+        }
+
+        let username = claims
+            .other
+            .get(&self.config.username_claim)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string());
+
+        let groups = match claims.other.get(&self.config.groups_claim) {
+            Some(serde_json::Value::Array(groups)) => groups
+                .iter()
+                .filter_map(|g| g.as_str())
+                .map(|g| g.to_string())
+                .collect(),
+            Some(serde_json::Value::String(group)) => vec![group.clone()],
+            _ => vec![],
+        };
+
+        Ok(Identity {
+            issuer: claims.iss,
+            subject: claims.sub,
+            username,
+            groups,
+        })
+    }
+
+    /// Returns true if the identity is allowed to get an account on first login.
+    pub fn can_provision(&self, identity: &Identity) -> bool {
+        identity
+            .groups
+            .iter()
+            .any(|g| self.config.provision_groups.contains(g))
+    }
+}
+
+fn decode_claims(id_token: &str) -> Result<IdTokenClaims, OidcError> {
+    let mut parts = id_token.split('.');
+    let (Some(_header), Some(payload), Some(_signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(OidcError::MalformedIdToken);
+    };
+
+    let payload = BASE64_URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| OidcError::MalformedIdToken)?;
+    serde_json::from_slice(&payload).map_err(|_| OidcError::MalformedIdToken)
+}
@@ -0,0 +1,108 @@
+use axum::Json;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::handler::{
+    self, app_token, auth, dataset, item, layout, notification, oidc, organize, print, push,
+    section, share, store, text, voice, webauthn, webhook,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "L Shop", description = "Shared shopping list organized by stores and sections."),
+    servers((url = "/api/v1")),
+    paths(
+        auth::login,
+        auth::logout,
+        auth::me,
+        oidc::login,
+        oidc::callback,
+        webauthn::register_start,
+        webauthn::register_finish,
+        webauthn::login_start,
+        webauthn::list_credentials,
+        webauthn::delete_credential,
+        app_token::create,
+        app_token::list,
+        app_token::delete,
+        store::create,
+        store::list,
+        store::update,
+        store::delete,
+        layout::export,
+        layout::import,
+        layout::apply,
+        print::print,
+        section::create,
+        section::list,
+        section::update,
+        section::delete,
+        section::reorder,
+        item::create,
+        item::list,
+        item::rename,
+        item::set_checked,
+        item::move_item,
+        text::import,
+        text::export,
+        voice::add,
+        organize::organize,
+        share::create,
+        share::list,
+        share::delete,
+        share::guest_list,
+        share::guest_set_checked,
+        notification::list,
+        notification::create,
+        notification::update,
+        notification::delete,
+        notification::send,
+        push::key,
+        push::subscribe,
+        push::list,
+        push::unsubscribe,
+        push::preferences,
+        push::set_preferences,
+        webhook::create,
+        webhook::list,
+        webhook::update,
+        webhook::delete,
+        webhook::deliveries,
+        dataset::export,
+        dataset::import,
+    ),
+    components(schemas(handler::ProblemCode, handler::FieldError)),
+    modifiers(&SessionCookie),
+    security(("session" = [])),
+    tags(
+        (name = "auth", description = "Login with password, passkeys or single sign-on"),
+        (name = "stores"),
+        (name = "sections", description = "Sections of a store, ie. fruit or dairy"),
+        (name = "items", description = "Items on the shopping list"),
+        (name = "shares", description = "Links that give guests access to a single store"),
+        (name = "notifications", description = "Scheduled notifications by email or ntfy"),
+        (name = "push", description = "Push notifications to subscribed browsers"),
+        (name = "webhooks", description = "Events sent to other services, ie. chat bots or home automation"),
+        (name = "dataset", description = "Export and import of all data, ie. to move to another instance"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SessionCookie;
+
+impl Modify for SessionCookie {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "session",
+                "Session cookie set by the login endpoints",
+            ))),
+        );
+    }
+}
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -0,0 +1,505 @@
+//! Printable shopping list of a store, as html for the browser's print dialog or as pdf.
+//!
+//! The pdf embeds DejaVu Sans, subset to the glyphs of the list, so that names in any
+//! language the font covers print as they are. Characters the font doesn't have are
+//! replaced with `?`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Write as _;
+
+use flate2::{Compression, write::ZlibEncoder};
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use subsetter::GlyphRemapper;
+use ttf_parser::{Face, GlyphId};
+
+use crate::handler::item::ItemListStore;
+use crate::store::item::Item;
+
+/// Heading of the store's items without a section.
+const OTHER_ITEMS: &str = "Other items";
+
+#[derive(Debug, Clone, Copy)]
+pub struct PrintOptions {
+    /// Font size of the items in points.
+    pub font_size: u32,
+    pub columns: u32,
+}
+
+/// Non-empty sections in store order, followed by the items without a section.
+fn groups(list: &ItemListStore) -> Vec<(&str, &[Item])> {
+    let mut groups: Vec<(&str, &[Item])> = list
+        .sections
+        .iter()
+        .filter(|s| !s.items.is_empty())
+        .map(|s| (s.section.name.as_str(), s.items.as_slice()))
+        .collect();
+    if !list.unassigned.is_empty() {
+        groups.push((OTHER_ITEMS, &list.unassigned));
+    }
+    groups
+}
+
+pub fn html(list: &ItemListStore, opts: PrintOptions) -> String {
+    let mut out = String::new();
+    let title = escape(&list.store.name);
+    write!(
+        out,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+@page {{ size: A4; margin: 12mm; }}
+body {{ font: {size}pt/1.3 Helvetica, Arial, sans-serif; margin: 0; color: #000; }}
+h1 {{ font-size: 1.4em; margin: 0 0 0.5em; }}
+main {{ columns: {columns}; column-gap: 2em; }}
+section {{ break-inside: avoid; margin: 0 0 0.6em; }}
+h2 {{ font-size: 1.1em; margin: 0 0 0.2em; border-bottom: 1px solid #888; }}
+ul {{ list-style: none; margin: 0; padding: 0; }}
+li {{ padding-left: 1.2em; text-indent: -1.2em; }}
+li::before {{ content: ""; display: inline-block; width: 0.75em; height: 0.75em; margin-right: 0.45em; border: 1px solid #000; vertical-align: -0.05em; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<main>
+"#,
+        size = opts.font_size,
+        columns = opts.columns,
+    )
+    .unwrap();
+
+    let groups = groups(list);
+    if groups.is_empty() {
+        out.push_str("<p>No items.</p>\n");
+    }
+    for (heading, items) in groups {
+        writeln!(out, "<section>\n<h2>{}</h2>\n<ul>", escape(heading)).unwrap();
+        for item in items {
+            writeln!(out, "<li>{}</li>", escape(&item.name)).unwrap();
+        }
+        out.push_str("</ul>\n</section>\n");
+    }
+
+    out.push_str("</main>\n</body>\n</html>\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 36.0;
+const COLUMN_GAP: f32 = 18.0;
+
+const REGULAR_TTF: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+const BOLD_TTF: &[u8] = include_bytes!("../fonts/DejaVuSans-Bold.ttf");
+
+/// Font embedded into the pdf, with only the glyphs that the text uses.
+struct Font {
+    face: Face<'static>,
+    data: &'static [u8],
+    /// Name in the page resources.
+    resource: Name<'static>,
+    /// Name of the subset, the tag in front marks it as one.
+    base_font: &'static str,
+    glyphs: GlyphRemapper,
+    /// Character of each glyph by its id in the subset, for copying text out of the pdf.
+    chars: BTreeMap<u16, char>,
+}
+
+impl Font {
+    fn new(data: &'static [u8], resource: Name<'static>, base_font: &'static str) -> Self {
+        Self {
+            face: Face::parse(data, 0).expect("embedded font should parse"),
+            data,
+            resource,
+            base_font,
+            glyphs: GlyphRemapper::new(),
+            chars: BTreeMap::new(),
+        }
+    }
+
+    /// Glyph of the character, or of `?` if the font doesn't have it.
+    fn glyph(&self, c: char) -> (GlyphId, char) {
+        match self.face.glyph_index(c) {
+            Some(glyph) => (glyph, c),
+            None => (self.face.glyph_index('?').unwrap_or(GlyphId(0)), '?'),
+        }
+    }
+
+    /// Advance of the glyph in thousandths of the font size.
+    fn advance(&self, glyph: GlyphId) -> f32 {
+        let advance = self.face.glyph_hor_advance(glyph).unwrap_or(0);
+        self.scale(advance as f32)
+    }
+
+    /// Converts font units to thousandths of the font size.
+    fn scale(&self, units: f32) -> f32 {
+        units * 1000.0 / self.face.units_per_em() as f32
+    }
+
+    fn width(&self, text: &str, size: f32) -> f32 {
+        let width: f32 = text.chars().map(|c| self.advance(self.glyph(c).0)).sum();
+        width * size / 1000.0
+    }
+
+    /// Encodes the text as two byte glyph ids of the subset, which are the character
+    /// codes of the Identity-H encoding.
+    fn encode(&mut self, text: &str) -> Vec<u8> {
+        let mut out = Vec::with_capacity(text.len() * 2);
+        for c in text.chars() {
+            let (glyph, c) = self.glyph(c);
+            let id = self.glyphs.remap(glyph.0);
+            self.chars.entry(id).or_insert(c);
+            out.extend_from_slice(&id.to_be_bytes());
+        }
+        out
+    }
+}
+
+/// Renders an A4 pdf. Items flow down the columns and then onto the next page,
+/// a heading is never left at the bottom of a column without its first item.
+pub fn pdf(list: &ItemListStore, opts: PrintOptions) -> Vec<u8> {
+    let mut writer = PdfLayout::new(opts);
+    writer.title(&list.store.name);
+
+    let groups = groups(list);
+    if groups.is_empty() {
+        writer.line(false, writer.size, 0.0, "No items.");
+    }
+    for (heading, items) in groups {
+        writer.group(heading, items);
+    }
+
+    writer.finish(&list.store.name)
+}
+
+struct PdfLayout {
+    size: f32,
+    line_height: f32,
+    column_width: f32,
+    columns: u32,
+    regular: Font,
+    bold: Font,
+    pages: Vec<Content>,
+    column: u32,
+    /// Top of the columns on the current page, the first page has the title above them.
+    top: f32,
+    /// Top of the next line.
+    y: f32,
+}
+
+impl PdfLayout {
+    fn new(opts: PrintOptions) -> Self {
+        let size = opts.font_size as f32;
+        let columns = opts.columns.max(1);
+        let content_width = PAGE_WIDTH - 2.0 * MARGIN;
+        let top = PAGE_HEIGHT - MARGIN;
+        Self {
+            size,
+            line_height: size * 1.3,
+            column_width: (content_width - COLUMN_GAP * (columns - 1) as f32) / columns as f32,
+            columns,
+            regular: Font::new(REGULAR_TTF, Name(b"F1"), "LSHOPA+DejaVuSans"),
+            bold: Font::new(BOLD_TTF, Name(b"F2"), "LSHOPB+DejaVuSans-Bold"),
+            pages: vec![Content::new()],
+            column: 0,
+            top,
+            y: top,
+        }
+    }
+
+    fn font(&self, bold: bool) -> &Font {
+        if bold { &self.bold } else { &self.regular }
+    }
+
+    fn content(&mut self) -> &mut Content {
+        self.pages.last_mut().expect("there should be a page")
+    }
+
+    fn column_x(&self) -> f32 {
+        MARGIN + self.column as f32 * (self.column_width + COLUMN_GAP)
+    }
+
+    fn at_column_top(&self) -> bool {
+        self.y == self.top
+    }
+
+    /// Moves to the next column or page, if the height doesn't fit below the cursor.
+    fn reserve(&mut self, height: f32) {
+        if self.at_column_top() || self.y - height >= MARGIN {
+            return;
+        }
+        if self.column + 1 < self.columns {
+            self.column += 1;
+        } else {
+            self.pages.push(Content::new());
+            self.column = 0;
+            self.top = PAGE_HEIGHT - MARGIN;
+        }
+        self.y = self.top;
+    }
+
+    /// Shows a single line of text at the cursor and moves below it.
+    fn line(&mut self, bold: bool, size: f32, indent: f32, text: &str) {
+        let x = self.column_x() + indent;
+        let y = self.y - size;
+        let font = if bold {
+            &mut self.bold
+        } else {
+            &mut self.regular
+        };
+        let resource = font.resource;
+        let encoded = font.encode(text);
+        self.content()
+            .begin_text()
+            .set_font(resource, size)
+            .next_line(x, y)
+            .show(Str(&encoded))
+            .end_text();
+        self.y -= size * 1.3;
+    }
+
+    fn title(&mut self, name: &str) {
+        let size = self.size * 1.6;
+        let width = PAGE_WIDTH - 2.0 * MARGIN;
+        for line in wrap(self.font(true), name, width, size) {
+            self.line(true, size, 0.0, &line);
+        }
+        self.y -= self.size * 0.6;
+        self.top = self.y;
+    }
+
+    fn group(&mut self, heading: &str, items: &[Item]) {
+        let heading_size = self.size * 1.15;
+        let heading_lines = wrap(self.font(true), heading, self.column_width, heading_size);
+        let heading_height = heading_lines.len() as f32 * heading_size * 1.3;
+        let gap = self.size * 0.6;
+
+        // Heading is kept together with the first line of the first item.
+        self.reserve(gap + heading_height + self.line_height);
+        if !self.at_column_top() {
+            self.y -= gap;
+        }
+        for line in &heading_lines {
+            self.line(true, heading_size, 0.0, line);
+        }
+        let rule_y = self.y + heading_size * 0.1;
+        let x = self.column_x();
+        let width = self.column_width;
+        self.content()
+            .set_line_width(0.5)
+            .move_to(x, rule_y)
+            .line_to(x + width, rule_y)
+            .stroke();
+        self.y -= self.size * 0.15;
+
+        for item in items {
+            self.item(&item.name);
+        }
+    }
+
+    fn item(&mut self, name: &str) {
+        let indent = self.size * 1.2;
+        let lines = wrap(
+            self.font(false),
+            name,
+            self.column_width - indent,
+            self.size,
+        );
+        self.reserve(self.line_height);
+
+        // Checkbox sits on the baseline of the first line.
+        let box_size = self.size * 0.7;
+        let x = self.column_x();
+        let baseline = self.y - self.size;
+        self.content()
+            .set_line_width(0.75)
+            .rect(x + 1.0, baseline, box_size, box_size)
+            .stroke();
+
+        for (idx, line) in lines.iter().enumerate() {
+            if idx > 0 {
+                self.reserve(self.line_height);
+            }
+            self.line(false, self.size, indent, line);
+        }
+    }
+
+    /// Writes the pages with the subsets of the fonts they use.
+    fn finish(self, title: &str) -> Vec<u8> {
+        let mut next_id = Ref::new(1);
+        let catalog_id = next_id.bump();
+        let tree_id = next_id.bump();
+        let info_id = next_id.bump();
+        let page_ids: Vec<(Ref, Ref)> = self
+            .pages
+            .iter()
+            .map(|_| (next_id.bump(), next_id.bump()))
+            .collect();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(tree_id);
+        pdf.pages(tree_id)
+            .kids(page_ids.iter().map(|(page_id, _)| *page_id))
+            .count(page_ids.len() as i32);
+        pdf.document_info(info_id)
+            .title(TextStr(title))
+            .producer(TextStr("lshop"));
+
+        let fonts: Vec<(Name, Ref)> = [&self.regular, &self.bold]
+            .into_iter()
+            .map(|font| {
+                let font_id = next_id.bump();
+                write_font(&mut pdf, font, font_id, &mut next_id);
+                (font.resource, font_id)
+            })
+            .collect();
+
+        for (content, (page_id, content_id)) in self.pages.into_iter().zip(page_ids) {
+            let mut page = pdf.page(page_id);
+            page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+            page.parent(tree_id);
+            page.contents(content_id);
+            let mut resources = page.resources();
+            let mut page_fonts = resources.fonts();
+            for (resource, font_id) in &fonts {
+                page_fonts.pair(*resource, *font_id);
+            }
+            page_fonts.finish();
+            resources.finish();
+            page.finish();
+            pdf.stream(content_id, &content.finish());
+        }
+
+        pdf.finish()
+    }
+}
+
+/// Writes the font as a Type0 font with the Identity-H encoding, with the subset
+/// embedded and a map of the glyphs back to text.
+fn write_font(pdf: &mut Pdf, font: &Font, id: Ref, next_id: &mut Ref) {
+    let cid_font_id = next_id.bump();
+    let descriptor_id = next_id.bump();
+    let file_id = next_id.bump();
+    let cmap_id = next_id.bump();
+    let base_font = Name(font.base_font.as_bytes());
+    let system_info = SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"Identity"),
+        supplement: 0,
+    };
+
+    pdf.type0_font(id)
+        .base_font(base_font)
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_font_id)
+        .to_unicode(cmap_id);
+
+    // Glyph ids of the subset are the character ids.
+    let mut cid_font = pdf.cid_font(cid_font_id);
+    cid_font
+        .subtype(CidFontType::Type2)
+        .base_font(base_font)
+        .system_info(system_info)
+        .font_descriptor(descriptor_id)
+        .default_width(0.0)
+        .cid_to_gid_map_predefined(Name(b"Identity"));
+    let widths: Vec<f32> = font
+        .glyphs
+        .remapped_gids()
+        .map(|glyph| font.advance(GlyphId(glyph)))
+        .collect();
+    cid_font.widths().consecutive(0, widths);
+    cid_font.finish();
+
+    let face = &font.face;
+    let bbox = face.global_bounding_box();
+    pdf.font_descriptor(descriptor_id)
+        .name(base_font)
+        .flags(FontFlags::NON_SYMBOLIC)
+        .bbox(Rect::new(
+            font.scale(bbox.x_min as f32),
+            font.scale(bbox.y_min as f32),
+            font.scale(bbox.x_max as f32),
+            font.scale(bbox.y_max as f32),
+        ))
+        .italic_angle(face.italic_angle())
+        .ascent(font.scale(face.ascender() as f32))
+        .descent(font.scale(face.descender() as f32))
+        .cap_height(font.scale(face.capital_height().unwrap_or(face.ascender()) as f32))
+        .stem_v(80.0)
+        .font_file2(file_id);
+
+    let subset =
+        subsetter::subset(font.data, 0, &font.glyphs).expect("embedded font should subset");
+    pdf.stream(file_id, &deflate(&subset))
+        .filter(Filter::FlateDecode);
+
+    let mut cmap = UnicodeCmap::new(Name(b"Custom"), system_info);
+    for (&glyph, &c) in &font.chars {
+        cmap.pair(glyph, c);
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .expect("writing to memory shouldn't fail");
+    encoder.finish().expect("writing to memory shouldn't fail")
+}
+
+/// Splits the text into lines that fit the width, at spaces if possible.
+fn wrap(font: &Font, text: &str, max_width: f32, size: f32) -> Vec<String> {
+    let width = |s: &str| font.width(s, size);
+
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split(' ').filter(|w| !w.is_empty()) {
+        let mut candidate = line.clone();
+        if !candidate.is_empty() {
+            candidate.push(' ');
+        }
+        candidate.push_str(word);
+        if width(&candidate) <= max_width {
+            line = candidate;
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        // Words longer than a line are broken anywhere.
+        for c in word.chars() {
+            line.push(c);
+            if line.chars().count() > 1 && width(&line) > max_width {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,450 @@
+//! Web Push (RFC 8030) with VAPID (RFC 8292) authentication and aes128gcm message
+//! encryption (RFC 8291), so that notifications reach phones without the app open.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use base64::prelude::*;
+use hkdf::Hkdf;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use rand::TryRngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::auth::User;
+use crate::config::WebPushConfig;
+use crate::db::Db;
+use crate::state::AppState;
+use crate::store::{self, item::Item, push::Subscription};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the push service keeps a message for an offline device, in seconds.
+const TTL: u32 = 24 * 60 * 60;
+/// Lifetime of the VAPID token, at most 24 hours are allowed.
+const TOKEN_LIFETIME: time::Duration = time::Duration::hours(12);
+/// Record size of the encrypted message, messages always fit in a single record.
+const RECORD_SIZE: u32 = 4096;
+/// Longest message that fits the 4096 bytes push services accept, after the header of
+/// 86 bytes, the tag of 16 bytes and the padding delimiter.
+const MAX_PAYLOAD_LEN: usize = 4096 - 86 - 16 - 1;
+/// Notifications list at most this many item names.
+const MAX_LISTED_ITEMS: usize = 5;
+/// Names in notifications are cut to this many characters.
+const MAX_NAME_CHARS: usize = 40;
+
+#[derive(Debug, Error)]
+pub enum PushError {
+    #[error("invalid vapid private key")]
+    InvalidKey,
+
+    #[error("invalid subscription keys")]
+    InvalidSubscription,
+
+    #[error("push endpoint must be an https url")]
+    InvalidEndpoint,
+
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("push service responded with {0}")]
+    Rejected(reqwest::StatusCode),
+}
+
+/// Notification shown by the service worker.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    /// Notifications with the same tag replace each other.
+    pub tag: String,
+    /// Page opened by clicking the notification.
+    pub url: String,
+}
+
+/// Result of a message that reached the push service.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Sent {
+    Accepted,
+    /// Subscription expired or was revoked by the browser, it should be deleted.
+    Gone,
+}
+
+/// Push services are reached only over https and by name, endpoints are chosen by users.
+/// Tests run the push service locally over http.
+pub fn is_allowed_endpoint(url: &url::Url) -> bool {
+    match (url.scheme(), url.host()) {
+        ("https", Some(url::Host::Domain(_))) => true,
+        ("http", Some(url::Host::Ipv4(ip))) => cfg!(test) && ip.is_loopback(),
+        _ => false,
+    }
+}
+
+/// Checks that the host of the endpoint resolves only to public addresses, so that
+/// users can't make the server send requests into the local network.
+pub async fn resolves_publicly(url: &url::Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(domain)) => public_addrs(domain).await.is_ok(),
+        // Local push service of the tests, see `is_allowed_endpoint`.
+        _ => is_allowed_endpoint(url),
+    }
+}
+
+async fn public_addrs(domain: &str) -> Result<Vec<SocketAddr>, std::io::Error> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, 0)).await?.collect();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{domain} resolves to a local or private address"),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Address isn't loopback, private, link-local, unique-local or otherwise reserved.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10.
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Resolver of the push client, which refuses hosts with local or private addresses
+/// also when they change after the subscription was checked.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs = public_addrs(name.as_str()).await?;
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+pub struct WebPush {
+    key: SigningKey,
+    /// Uncompressed public key, base64url encoded, used as `applicationServerKey`.
+    public_key: String,
+    subject: String,
+    http: reqwest::Client,
+}
+
+impl WebPush {
+    pub fn new(conf: &WebPushConfig) -> Result<Self, PushError> {
+        let bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(conf.vapid_private_key.trim())
+            .map_err(|_| PushError::InvalidKey)?;
+        let secret = SecretKey::from_slice(&bytes).map_err(|_| PushError::InvalidKey)?;
+        let public_key = encode_public_key(&secret.public_key());
+
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            // Endpoints are given by users, a redirect could lead into the local network.
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()
+            .expect("http client should build");
+
+        Ok(Self {
+            key: SigningKey::from(secret),
+            public_key,
+            subject: conf.subject.clone(),
+            http,
+        })
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Sends the notification to a single subscription.
+    pub async fn send(
+        &self,
+        sub: &Subscription,
+        notification: &Notification,
+    ) -> Result<Sent, PushError> {
+        if !url::Url::parse(&sub.endpoint).is_ok_and(|url| is_allowed_endpoint(&url)) {
+            return Err(PushError::InvalidEndpoint);
+        }
+        let payload = payload(notification);
+        let body = encrypt(&sub.p256dh, &sub.auth, &payload)?;
+        let token = self.vapid_token(&sub.endpoint, time::OffsetDateTime::now_utc())?;
+
+        let res = self
+            .http
+            .post(&sub.endpoint)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("vapid t={token}, k={}", self.public_key),
+            )
+            .header(reqwest::header::CONTENT_ENCODING, "aes128gcm")
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header("TTL", TTL.to_string())
+            .header("Urgency", "normal")
+            .body(body)
+            .send()
+            .await?;
+
+        match res.status() {
+            status if status.is_success() => Ok(Sent::Accepted),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Ok(Sent::Gone),
+            status => Err(PushError::Rejected(status)),
+        }
+    }
+
+    /// Signed JWT that identifies the server to the push service of the endpoint.
+    fn vapid_token(&self, endpoint: &str, now: time::OffsetDateTime) -> Result<String, PushError> {
+        let url = url::Url::parse(endpoint).map_err(|_| PushError::InvalidSubscription)?;
+        let claims = serde_json::json!({
+            "aud": url.origin().ascii_serialization(),
+            "exp": (now + TOKEN_LIFETIME).unix_timestamp(),
+            "sub": self.subject,
+        });
+
+        let signing_input = format!(
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(r#"{"typ":"JWT","alg":"ES256"}"#),
+            BASE64_URL_SAFE_NO_PAD.encode(claims.to_string()),
+        );
+        let signature: Signature = self.key.sign(signing_input.as_bytes());
+        Ok(format!(
+            "{signing_input}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+}
+
+/// Generates a VAPID key pair. Returns the private and the public key, base64url encoded.
+pub fn generate_keys() -> (String, String) {
+    let secret = random_secret_key();
+    (
+        BASE64_URL_SAFE_NO_PAD.encode(secret.to_bytes()),
+        encode_public_key(&secret.public_key()),
+    )
+}
+
+fn encode_public_key(key: &PublicKey) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(key.to_encoded_point(false).as_bytes())
+}
+
+fn random_secret_key() -> SecretKey {
+    loop {
+        let mut bytes = [0u8; 32];
+        rand::rngs::OsRng
+            .try_fill_bytes(&mut bytes)
+            .expect("random should not fail");
+        // Fails only for zero or values above the curve order, practically never.
+        if let Ok(key) = SecretKey::from_slice(&bytes) {
+            return key;
+        }
+    }
+}
+
+/// Encrypts the payload for the subscription's browser, with its base64url encoded
+/// public key and authentication secret.
+pub fn encrypt(p256dh: &str, auth: &str, payload: &[u8]) -> Result<Vec<u8>, PushError> {
+    let ua_public = BASE64_URL_SAFE_NO_PAD
+        .decode(p256dh)
+        .map_err(|_| PushError::InvalidSubscription)?;
+    let auth = BASE64_URL_SAFE_NO_PAD
+        .decode(auth)
+        .map_err(|_| PushError::InvalidSubscription)?;
+    let ua_key =
+        PublicKey::from_sec1_bytes(&ua_public).map_err(|_| PushError::InvalidSubscription)?;
+
+    let as_secret = random_secret_key();
+    let as_public = as_secret.public_key().to_encoded_point(false);
+    let shared = p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_key.as_affine());
+
+    // Input keying material combines the shared secret with the authentication secret.
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(&ua_public);
+    key_info.extend_from_slice(as_public.as_bytes());
+    let mut ikm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&auth), shared.raw_secret_bytes())
+        .expand(&key_info, &mut ikm)
+        .expect("length should be valid");
+
+    let mut salt = [0u8; 16];
+    rand::rngs::OsRng
+        .try_fill_bytes(&mut salt)
+        .expect("random should not fail");
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+    let mut cek = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    hkdf.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+        .expect("length should be valid");
+    hkdf.expand(b"Content-Encoding: nonce\0", &mut nonce)
+        .expect("length should be valid");
+
+    // Padding delimiter of the last record.
+    let mut plaintext = payload.to_vec();
+    plaintext.push(2);
+    let ciphertext = Aes128Gcm::new(&cek.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| PushError::InvalidSubscription)?;
+
+    let mut body = Vec::with_capacity(16 + 4 + 1 + 65 + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.len() as u8);
+    body.extend_from_slice(as_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+/// Sends the notifications about new items in the background, so that the request
+/// doesn't wait for the push services. Does nothing if Web Push isn't configured.
+pub fn spawn_items_created(state: &AppState, author: &User, items: &[Item]) {
+    let Some(push) = state.push.clone() else {
+        return;
+    };
+    let (db, items) = (state.db.clone(), items.to_vec());
+    let (author_id, author_name) = (author.id, author.username.clone());
+    tokio::spawn(async move {
+        if let Err(err) = notify_items_created(&db, &push, author_id, &author_name, &items).await {
+            tracing::error!(error = err.to_string(), "push notifications failed: {err}");
+        }
+    });
+}
+
+/// Notifies users other than the author about the added items, according to their
+/// preferences. Subscriptions that expired are deleted.
+pub async fn notify_items_created(
+    db: &Db,
+    push: &WebPush,
+    author_id: i64,
+    author_name: &str,
+    items: &[Item],
+) -> Result<(), sqlx::Error> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    let stores: HashMap<i64, String> = store::shop::list(db)
+        .await?
+        .into_iter()
+        .map(|s| (s.id, s.name))
+        .collect();
+
+    for (sub, prefs) in store::push::others(db, author_id).await? {
+        let wanted: Vec<&Item> = items
+            .iter()
+            .filter(|item| prefs.wants_item(item.store_id))
+            .collect();
+        if wanted.is_empty() {
+            continue;
+        }
+
+        let notification = items_created(author_name, &wanted, &stores);
+        match push.send(&sub, &notification).await {
+            Ok(Sent::Accepted) => {}
+            Ok(Sent::Gone) => store::push::delete_expired(db, sub.id).await?,
+            Err(err) => {
+                tracing::warn!(
+                    subscription_id = sub.id,
+                    error = err.to_string(),
+                    "push notification failed: {err}"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Notification about new items, ie. "alice added bread" with the store as its body.
+pub fn items_created(author: &str, items: &[&Item], stores: &HashMap<i64, String>) -> Notification {
+    let author = shorten(author, MAX_NAME_CHARS);
+    let title = match items {
+        [item] => format!("{author} added {}", shorten(&item.name, MAX_NAME_CHARS)),
+        _ => format!("{author} added {} items", items.len()),
+    };
+
+    let body = match items {
+        [item] => item
+            .store_id
+            .and_then(|id| stores.get(&id))
+            .map(|name| shorten(name, MAX_NAME_CHARS))
+            .unwrap_or_default(),
+        _ => {
+            let mut listed: Vec<String> = items
+                .iter()
+                .take(MAX_LISTED_ITEMS)
+                .map(|i| shorten(&i.name, MAX_NAME_CHARS))
+                .collect();
+            if items.len() > MAX_LISTED_ITEMS {
+                listed.push("…".to_string());
+            }
+            listed.join(", ")
+        }
+    };
+
+    Notification {
+        title,
+        body,
+        tag: "items".to_string(),
+        url: "/".to_string(),
+    }
+}
+
+/// Name cut to `max` characters, ending with `…` if it was longer.
+fn shorten(name: &str, max: usize) -> String {
+    if name.chars().count() <= max {
+        return name.to_string();
+    }
+    let mut short: String = name.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Serialized notification, with the body and then the title shortened until it fits
+/// a single message.
+pub fn payload(notification: &Notification) -> Vec<u8> {
+    let mut notification = notification.clone();
+    loop {
+        let payload = serde_json::to_vec(&notification).expect("notification should serialize");
+        let over = payload.len().saturating_sub(MAX_PAYLOAD_LEN);
+        let text = if !notification.body.is_empty() {
+            &mut notification.body
+        } else {
+            &mut notification.title
+        };
+        if over == 0 || text.is_empty() {
+            return payload;
+        }
+        // Each character is at least a byte, the ellipsis takes 3 of them. Escaping
+        // only makes characters longer, so the payload shrinks by the overflow.
+        let keep = text.chars().count().saturating_sub(over + 3);
+        *text = if keep == 0 {
+            String::new()
+        } else {
+            shorten(text, keep)
+        };
+    }
+}
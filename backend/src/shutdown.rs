@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+/// Shutdown requested by SIGINT or SIGTERM.
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Starts listening for the signals.
+    pub fn listen() -> Self {
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move {
+            signal().await;
+            tracing::info!("shutdown requested, draining connections");
+            let _ = tx.send(true);
+        });
+        Self(rx)
+    }
+
+    /// Resolves when shutdown is requested.
+    pub async fn requested(mut self) {
+        // Error means the sender is gone, which happens only if signal handling failed.
+        let _ = self.0.wait_for(|requested| *requested).await;
+    }
+
+    /// Resolves when connections had `timeout` time to drain after shutdown was requested.
+    pub async fn drain_timeout(self, timeout: Duration) {
+        self.requested().await;
+        tokio::time::sleep(timeout).await;
+        tracing::warn!("connections didn't drain in {timeout:?}, shutting down anyway");
+    }
+}
+
+async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!(
+                error = err.to_string(),
+                "failed to listen for SIGINT: {err}"
+            );
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(err) => {
+                tracing::error!(
+                    error = err.to_string(),
+                    "failed to listen for SIGTERM: {err}"
+                );
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -1,24 +1,86 @@
 use std::sync::Arc;
 
 use axum::extract::FromRef;
+use webauthn_rs::{Webauthn, WebauthnBuilder};
 
 use crate::config::Config;
-
-pub type OpenAiClient = async_openai::Client<async_openai::config::OpenAIConfig>;
+use crate::frontend::Frontend;
+use crate::llm::Llm;
+use crate::metrics::Metrics;
+use crate::notify::Notifier;
+use crate::oidc::OidcClient;
+use crate::push::WebPush;
 
 #[derive(Clone, FromRef)]
 pub struct AppState {
     pub db: sqlx::SqlitePool,
     pub config: Arc<Config>,
-    pub openai: OpenAiClient,
+    pub llm: Arc<dyn Llm>,
+    pub oidc: Option<Arc<OidcClient>>,
+    pub webauthn: Option<Arc<Webauthn>>,
+    pub frontend: Option<Arc<Frontend>>,
+    pub metrics: Arc<Metrics>,
+    pub push: Option<Arc<WebPush>>,
+    pub notifier: Arc<Notifier>,
 }
 
 impl AppState {
-    pub fn new(db: sqlx::SqlitePool, conf: Config, openai: OpenAiClient) -> Self {
-        Self {
+    pub fn new(db: sqlx::SqlitePool, conf: Config, llm: Arc<dyn Llm>) -> anyhow::Result<Self> {
+        let oidc = conf
+            .oidc
+            .clone()
+            .map(|oidc_conf| Arc::new(OidcClient::new(oidc_conf)));
+
+        let webauthn = match &conf.webauthn {
+            Some(wa_conf) => {
+                let origin = url::Url::parse(&wa_conf.rp_origin)?;
+                let webauthn = WebauthnBuilder::new(&wa_conf.rp_id, &origin)?
+                    .rp_name(&wa_conf.rp_name)
+                    .build()?;
+                Some(Arc::new(webauthn))
+            }
+            None => None,
+        };
+
+        let push = match &conf.web_push {
+            Some(push_conf) => Some(Arc::new(WebPush::new(push_conf)?)),
+            None => None,
+        };
+
+        let notifier = Notifier::new(&conf)?;
+
+        if let Some(metrics_conf) = &conf.metrics
+            && metrics_conf.token.is_none()
+            && metrics_conf.address.is_none()
+        {
+            anyhow::bail!("metrics require a token or a separate address");
+        }
+        if let Some(backup_conf) = &conf.backup {
+            if backup_conf.interval == 0 {
+                anyhow::bail!("backup interval must be at least one second");
+            }
+            if backup_conf.keep == 0 {
+                anyhow::bail!("backups to keep must be at least one");
+            }
+        }
+
+        Ok(Self {
             db,
             config: Arc::new(conf),
-            openai,
-        }
+            llm,
+            oidc,
+            webauthn,
+            frontend: None,
+            metrics: Arc::new(Metrics::new()),
+            push,
+            notifier: Arc::new(notifier),
+        })
+    }
+
+    /// Loads the frontend, which compresses all files. Only the server needs it, so
+    /// the commands don't load it.
+    pub fn load_frontend(&mut self) -> anyhow::Result<()> {
+        self.frontend = Frontend::load(&self.config)?;
+        Ok(())
     }
 }
@@ -0,0 +1,86 @@
+use serde::Serialize;
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+use crate::db::Db;
+use crate::store::user::User;
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct AppToken {
+    pub id: i64,
+    pub name: String,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<time::OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+}
+
+pub async fn create(
+    db: &Db,
+    user_id: i64,
+    name: &str,
+    token_hash: &str,
+) -> Result<AppToken, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "INSERT INTO app_tokens (user_id, name, token_hash, created_at)
+         VALUES (?, ?, ?, ?)
+         RETURNING id, name, last_used_at, created_at",
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(token_hash)
+    .bind(now)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn list(db: &Db, user_id: i64) -> Result<Vec<AppToken>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, name, last_used_at, created_at FROM app_tokens
+         WHERE user_id = ? ORDER BY created_at ASC",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Returns the user with the username, if the token belongs to them.
+/// Marks the token as used.
+pub async fn get_user(
+    db: &Db,
+    username: &str,
+    token_hash: &str,
+) -> Result<Option<User>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let token_id: Option<i64> = sqlx::query_scalar(
+        "UPDATE app_tokens SET last_used_at = ?
+         WHERE token_hash = ?
+           AND user_id = (SELECT id FROM users WHERE username = ?)
+         RETURNING id",
+    )
+    .bind(now)
+    .bind(token_hash)
+    .bind(username)
+    .fetch_optional(db)
+    .await?;
+    if token_id.is_none() {
+        return Ok(None);
+    }
+
+    crate::store::user::get_user(db, username).await
+}
+
+/// Deletes the user's token. Returns false if it doesn't exist.
+pub async fn delete(db: &Db, user_id: i64, id: i64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM app_tokens WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
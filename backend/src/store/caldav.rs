@@ -0,0 +1,64 @@
+//! Resource names and UIDs of items that were created by CalDAV clients.
+
+use sqlx::prelude::FromRow;
+
+use crate::db::Db;
+use crate::store::item::{self, Item};
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Object {
+    pub item_id: i64,
+    /// Last segment of the resource's path.
+    pub name: String,
+    pub uid: String,
+}
+
+pub async fn list(db: &Db) -> Result<Vec<Object>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM caldav_objects")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn get_by_name(db: &Db, name: &str) -> Result<Option<Object>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM caldav_objects WHERE name = ?")
+        .bind(name)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn get_by_item(db: &Db, item_id: i64) -> Result<Option<Object>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM caldav_objects WHERE item_id = ?")
+        .bind(item_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn uid_exists(db: &Db, uid: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM caldav_objects WHERE uid = ?)")
+        .bind(uid)
+        .fetch_one(db)
+        .await
+}
+
+/// Creates the item of a new resource together with its name and UID, so a failed
+/// insert doesn't leave an item behind.
+pub async fn create(
+    db: &Db,
+    store_id: Option<i64>,
+    section_id: Option<i64>,
+    summary: &str,
+    checked: bool,
+    name: &str,
+    uid: &str,
+) -> Result<Item, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let item = item::insert(&mut tx, store_id, section_id, summary, checked).await?;
+    sqlx::query("INSERT INTO caldav_objects (item_id, name, uid) VALUES (?, ?, ?)")
+        .bind(item.id)
+        .bind(name)
+        .bind(uid)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(item)
+}
@@ -0,0 +1,412 @@
+//! Export of all stores, sections and items into a single document, and import of it
+//! into another database.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use sqlx::{Sqlite, Transaction};
+use utoipa::ToSchema;
+
+use crate::db::Db;
+use crate::store::item::Item;
+use crate::store::shop::Store;
+use crate::store::user::Role;
+use crate::store::webhook::{self, Event};
+
+/// Version of the document format, incremented on incompatible changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Snapshot of the whole database. Ids are used only for references inside the document,
+/// they are assigned anew on import.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Dataset {
+    pub version: u32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub exported_at: time::OffsetDateTime,
+
+    #[serde(default)]
+    pub users: Vec<DatasetUser>,
+    #[serde(default)]
+    pub stores: Vec<DatasetStore>,
+    #[serde(default)]
+    pub sections: Vec<DatasetSection>,
+    #[serde(default)]
+    pub items: Vec<DatasetItem>,
+}
+
+/// Sessions, passkeys and linked identities aren't exported.
+#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
+pub struct DatasetUser {
+    pub username: String,
+    pub role: Role,
+    /// Exported only on request, users without it can't log in with a password.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
+pub struct DatasetStore {
+    pub id: i64,
+    pub name: String,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
+pub struct DatasetSection {
+    pub id: i64,
+    pub store_id: i64,
+    pub name: String,
+    pub ord: i64,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
+pub struct DatasetItem {
+    pub id: i64,
+    pub store_id: Option<i64>,
+    pub section_id: Option<i64>,
+    pub name: String,
+    pub checked: bool,
+    pub ord: i64,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Adds the document to the existing data. Stores and sections with the same name
+    /// are reused, items are always added.
+    #[default]
+    Merge,
+    /// Deletes all stores, sections and items first. Users are merged in both modes,
+    /// deleting them would also delete their sessions and passkeys.
+    Replace,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ImportSummary {
+    pub dry_run: bool,
+    pub users_created: u64,
+    /// Users with the same username already existed, they are left unchanged.
+    pub users_existing: u64,
+    pub stores_created: u64,
+    pub stores_merged: u64,
+    pub sections_created: u64,
+    pub sections_merged: u64,
+    pub items_created: u64,
+    /// Deleted by [`ImportMode::Replace`].
+    pub stores_deleted: u64,
+    pub sections_deleted: u64,
+    pub items_deleted: u64,
+}
+
+pub async fn export(db: &Db, password_hashes: bool) -> Result<Dataset, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let mut users: Vec<DatasetUser> = sqlx::query_as(
+        "SELECT username, role, password_hash, created_at, updated_at FROM users ORDER BY id",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    if !password_hashes {
+        for user in &mut users {
+            user.password_hash = None;
+        }
+    }
+
+    let stores = sqlx::query_as("SELECT * FROM stores ORDER BY id")
+        .fetch_all(&mut *tx)
+        .await?;
+    let sections = sqlx::query_as("SELECT * FROM sections ORDER BY store_id, ord, id")
+        .fetch_all(&mut *tx)
+        .await?;
+    let items = sqlx::query_as(
+        "SELECT id, store_id, section_id, name, checked, ord, created_at, updated_at
+         FROM items
+         ORDER BY checked, store_id, section_id, ord, id",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Dataset {
+        version: FORMAT_VERSION,
+        exported_at: time::OffsetDateTime::now_utc(),
+        users,
+        stores,
+        sections,
+        items,
+    })
+}
+
+/// Imports the dataset in a single transaction. A dry run is rolled back, so its summary
+/// is exactly what the import would do. References in the dataset must be valid.
+/// Created stores and items queue their webhook events, merged ones, sections and the
+/// deletions of a replace don't.
+pub async fn import(
+    db: &Db,
+    dataset: &Dataset,
+    mode: ImportMode,
+    dry_run: bool,
+) -> Result<ImportSummary, sqlx::Error> {
+    let mut summary = ImportSummary {
+        dry_run,
+        ..Default::default()
+    };
+
+    let mut tx = db.begin().await?;
+
+    if mode == ImportMode::Replace {
+        // Items are deleted first, deleting stores would only detach them.
+        summary.items_deleted = sqlx::query("DELETE FROM items")
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        summary.sections_deleted = sqlx::query("DELETE FROM sections")
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        summary.stores_deleted = sqlx::query("DELETE FROM stores")
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+
+    import_users(&mut tx, &dataset.users, &mut summary).await?;
+    let store_ids = import_stores(&mut tx, &dataset.stores, &mut summary).await?;
+    let section_ids = import_sections(&mut tx, &dataset.sections, &store_ids, &mut summary).await?;
+    import_items(
+        &mut tx,
+        &dataset.items,
+        &store_ids,
+        &section_ids,
+        &mut summary,
+    )
+    .await?;
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    Ok(summary)
+}
+
+async fn import_users(
+    tx: &mut Transaction<'_, Sqlite>,
+    users: &[DatasetUser],
+    summary: &mut ImportSummary,
+) -> Result<(), sqlx::Error> {
+    for user in users {
+        let res = sqlx::query(
+            "INSERT INTO users (username, password_hash, role, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (username) DO NOTHING",
+        )
+        .bind(&user.username)
+        .bind(&user.password_hash)
+        .bind(user.role)
+        .bind(user.created_at)
+        .bind(user.updated_at)
+        .execute(&mut **tx)
+        .await?;
+
+        if res.rows_affected() > 0 {
+            summary.users_created += 1;
+        } else {
+            summary.users_existing += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Returns ids of the stores in the database by their ids in the dataset.
+async fn import_stores(
+    tx: &mut Transaction<'_, Sqlite>,
+    stores: &[DatasetStore],
+    summary: &mut ImportSummary,
+) -> Result<HashMap<i64, i64>, sqlx::Error> {
+    // Only stores that existed before are merged, not ones with the same name in the dataset.
+    let mut existing: HashMap<String, i64> = HashMap::new();
+    let rows: Vec<(i64, String)> = sqlx::query_as("SELECT id, name FROM stores ORDER BY id DESC")
+        .fetch_all(&mut **tx)
+        .await?;
+    // The oldest store wins when names are duplicated.
+    existing.extend(rows.into_iter().map(|(id, name)| (name, id)));
+
+    let mut ids = HashMap::with_capacity(stores.len());
+    for store in stores {
+        let id = match existing.get(&store.name) {
+            Some(id) => {
+                summary.stores_merged += 1;
+                *id
+            }
+            None => {
+                summary.stores_created += 1;
+                let created: Store = sqlx::query_as(
+                    "INSERT INTO stores (name, created_at, updated_at) VALUES (?, ?, ?)
+                     RETURNING *",
+                )
+                .bind(&store.name)
+                .bind(store.created_at)
+                .bind(store.updated_at)
+                .fetch_one(&mut **tx)
+                .await?;
+                webhook::enqueue(tx, Event::StoreCreated, &created).await?;
+                created.id
+            }
+        };
+        ids.insert(store.id, id);
+    }
+    Ok(ids)
+}
+
+/// Returns ids of the sections in the database by their ids in the dataset.
+/// New sections are put after the existing sections of their store, in the order of the dataset.
+async fn import_sections(
+    tx: &mut Transaction<'_, Sqlite>,
+    sections: &[DatasetSection],
+    store_ids: &HashMap<i64, i64>,
+    summary: &mut ImportSummary,
+) -> Result<HashMap<i64, i64>, sqlx::Error> {
+    let mut existing: HashMap<(i64, String), i64> = HashMap::new();
+    let rows: Vec<(i64, i64, String)> =
+        sqlx::query_as("SELECT id, store_id, name FROM sections ORDER BY id DESC")
+            .fetch_all(&mut **tx)
+            .await?;
+    existing.extend(
+        rows.into_iter()
+            .map(|(id, store_id, name)| ((store_id, name), id)),
+    );
+
+    let mut sorted: Vec<&DatasetSection> = sections.iter().collect();
+    sorted.sort_by_key(|section| (section.store_id, section.ord, section.id));
+
+    let mut next_ord: HashMap<i64, i64> = HashMap::new();
+    let mut ids = HashMap::with_capacity(sections.len());
+    for section in sorted {
+        let store_id = store_ids[&section.store_id];
+        if let Some(id) = existing.get(&(store_id, section.name.clone())) {
+            summary.sections_merged += 1;
+            ids.insert(section.id, *id);
+            continue;
+        }
+
+        let ord = match next_ord.get_mut(&store_id) {
+            Some(ord) => ord,
+            None => {
+                let max: i64 = sqlx::query_scalar(
+                    "SELECT COALESCE(MAX(ord), 0) FROM sections WHERE store_id = ?",
+                )
+                .bind(store_id)
+                .fetch_one(&mut **tx)
+                .await?;
+                next_ord.entry(store_id).or_insert(max)
+            }
+        };
+        *ord += 1;
+
+        let id = sqlx::query(
+            "INSERT INTO sections (store_id, name, ord, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(store_id)
+        .bind(&section.name)
+        .bind(*ord)
+        .bind(section.created_at)
+        .bind(section.updated_at)
+        .execute(&mut **tx)
+        .await?
+        .last_insert_rowid();
+        summary.sections_created += 1;
+        ids.insert(section.id, id);
+    }
+    Ok(ids)
+}
+
+/// Unchecked items are put after the existing items of their section, in the order of
+/// the dataset. Checked items keep their `ord`, it isn't used for them.
+async fn import_items(
+    tx: &mut Transaction<'_, Sqlite>,
+    items: &[DatasetItem],
+    store_ids: &HashMap<i64, i64>,
+    section_ids: &HashMap<i64, i64>,
+    summary: &mut ImportSummary,
+) -> Result<(), sqlx::Error> {
+    let mut sorted: Vec<&DatasetItem> = items.iter().collect();
+    sorted.sort_by_key(|item| {
+        (
+            item.checked,
+            item.store_id,
+            item.section_id,
+            item.ord,
+            item.id,
+        )
+    });
+
+    let mut next_ord: HashMap<(Option<i64>, Option<i64>), i64> = HashMap::new();
+    for item in sorted {
+        let store_id = item.store_id.map(|id| store_ids[&id]);
+        let section_id = item.section_id.map(|id| section_ids[&id]);
+
+        let ord = if item.checked {
+            item.ord
+        } else {
+            let ord = match next_ord.get_mut(&(store_id, section_id)) {
+                Some(ord) => ord,
+                None => {
+                    let max: i64 = sqlx::query_scalar(
+                        "SELECT COALESCE(MAX(ord), 0) FROM items
+                         WHERE store_id IS ?
+                           AND section_id IS ?
+                           AND checked = FALSE",
+                    )
+                    .bind(store_id)
+                    .bind(section_id)
+                    .fetch_one(&mut **tx)
+                    .await?;
+                    next_ord.entry((store_id, section_id)).or_insert(max)
+                }
+            };
+            *ord += 1;
+            *ord
+        };
+
+        let created: Item = sqlx::query_as(
+            "INSERT INTO items (store_id, section_id, name, checked, ord, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             RETURNING *",
+        )
+        .bind(store_id)
+        .bind(section_id)
+        .bind(&item.name)
+        .bind(item.checked)
+        .bind(ord)
+        .bind(item.created_at)
+        .bind(item.updated_at)
+        .fetch_one(&mut **tx)
+        .await?;
+        webhook::enqueue(tx, Event::ItemCreated, &created).await?;
+        summary.items_created += 1;
+    }
+    Ok(())
+}
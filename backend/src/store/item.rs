@@ -3,10 +3,14 @@ use std::collections::HashMap;
 use serde::Serialize;
 use sqlx::prelude::{FromRow, Row};
 use sqlx::{Executor, QueryBuilder, Sqlite};
+use utoipa::ToSchema;
 
 use crate::db::Db;
+use crate::store::Error;
+use crate::store::shop::Store;
+use crate::store::webhook::{self, Event};
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
 pub struct Item {
     pub id: i64,
     pub store_id: Option<i64>,
@@ -30,29 +34,79 @@ pub async fn create(
     section_id: Option<i64>,
     name: &str,
 ) -> Result<Item, sqlx::Error> {
-    let now = time::OffsetDateTime::now_utc();
-
     let mut tx = db.begin().await?;
-    let curr_ord = max_ord(&mut *tx, store_id, section_id).await?;
+    let item = insert(&mut tx, store_id, section_id, name, false).await?;
+    tx.commit().await?;
+
+    Ok(item)
+}
+
+/// Creates the item at the end of the section within the transaction. A checked item
+/// only queues the creation, it's not counted as checked off.
+pub async fn insert(
+    tx: &mut sqlx::SqliteTransaction<'_>,
+    store_id: Option<i64>,
+    section_id: Option<i64>,
+    name: &str,
+    checked: bool,
+) -> Result<Item, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+    let curr_ord = max_ord(&mut **tx, store_id, section_id).await?;
     let ord = curr_ord + 1;
 
     let item: Item = sqlx::query_as(
-        "INSERT INTO items (store_id, section_id, name, ord, created_at, updated_at) 
-         VALUES (?, ?, ?, ?, ?, ?) 
+        "INSERT INTO items (store_id, section_id, name, checked, ord, created_at, updated_at) 
+         VALUES (?, ?, ?, ?, ?, ?, ?) 
          RETURNING *",
     )
     .bind(store_id)
     .bind(section_id)
     .bind(name)
+    .bind(checked)
     .bind(ord)
     .bind(now)
     .bind(now)
-    .fetch_one(&mut *tx)
+    .fetch_one(&mut **tx)
     .await?;
+    webhook::enqueue(tx, Event::ItemCreated, &item).await?;
+
+    Ok(item)
+}
+
+/// Creates the items at the end of the section, in the given order.
+pub async fn create_many(
+    db: &Db,
+    store_id: Option<i64>,
+    section_id: Option<i64>,
+    names: &[String],
+) -> Result<Vec<Item>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let mut tx = db.begin().await?;
+    let curr_ord = max_ord(&mut *tx, store_id, section_id).await?;
+
+    let mut items = Vec::with_capacity(names.len());
+    for (idx, name) in names.iter().enumerate() {
+        let item: Item = sqlx::query_as(
+            "INSERT INTO items (store_id, section_id, name, ord, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             RETURNING *",
+        )
+        .bind(store_id)
+        .bind(section_id)
+        .bind(name)
+        .bind(curr_ord + idx as i64 + 1)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await?;
+        webhook::enqueue(&mut tx, Event::ItemCreated, &item).await?;
+        items.push(item);
+    }
 
     tx.commit().await?;
 
-    Ok(item)
+    Ok(items)
 }
 
 pub async fn list(db: &Db) -> Result<Vec<Item>, sqlx::Error> {
@@ -61,6 +115,27 @@ pub async fn list(db: &Db) -> Result<Vec<Item>, sqlx::Error> {
         .await
 }
 
+/// Lists all items, checked ones included.
+pub async fn list_all(db: &Db) -> Result<Vec<Item>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM items ORDER BY id ASC")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn list_for_store(db: &Db, store_id: i64) -> Result<Vec<Item>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM items WHERE store_id = ? AND checked = FALSE")
+        .bind(store_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn get(db: &Db, id: i64) -> Result<Option<Item>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM items WHERE id = ?")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
 pub async fn unassigned_for_store<'c, E: Executor<'c, Database = Sqlite>>(
     db: E,
     store_id: i64,
@@ -85,31 +160,78 @@ pub async fn organize(
     let mut tx = db.begin().await?;
 
     for (section_id, items) in update.iter() {
-        organize_section(&mut tx, store_id, *section_id, items).await?
+        for item in organize_section(&mut tx, store_id, *section_id, items).await? {
+            webhook::enqueue(&mut tx, Event::ItemUpdated, &item).await?;
+        }
     }
 
+    // Close gaps left by the organized items.
+    let now = time::OffsetDateTime::now_utc();
+    sqlx::query(
+        "WITH renumbered(id, ord) AS (
+            SELECT id, ROW_NUMBER() OVER (ORDER BY ord)
+            FROM items
+            WHERE store_id = ?
+              AND section_id IS NULL
+              AND checked = FALSE
+         )
+         UPDATE items
+         SET ord = renumbered.ord, updated_at = ?
+         FROM renumbered
+         WHERE items.id = renumbered.id
+           AND items.ord != renumbered.ord",
+    )
+    .bind(store_id)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
     tx.commit().await
 }
 
 pub async fn rename(db: &Db, id: i64, name: &str) -> Result<Option<Item>, sqlx::Error> {
     let now = time::OffsetDateTime::now_utc();
+    let mut tx = db.begin().await?;
 
-    sqlx::query_as("UPDATE items SET name = ?, updated_at = ? WHERE id = ? RETURNING *")
-        .bind(name)
-        .bind(now)
-        .bind(id)
-        .fetch_optional(db)
-        .await
+    let item: Option<Item> =
+        sqlx::query_as("UPDATE items SET name = ?, updated_at = ? WHERE id = ? RETURNING *")
+            .bind(name)
+            .bind(now)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    if let Some(item) = &item {
+        webhook::enqueue(&mut tx, Event::ItemUpdated, item).await?;
+    }
+
+    tx.commit().await?;
+    Ok(item)
 }
 
 pub async fn set_checked(db: &Db, id: i64) -> Result<Option<Item>, sqlx::Error> {
+    check(db, id, None).await
+}
+
+/// Checks the item only if it's in the store, for share links of the store.
+pub async fn set_checked_in_store(
+    db: &Db,
+    id: i64,
+    store_id: i64,
+) -> Result<Option<Item>, sqlx::Error> {
+    check(db, id, Some(store_id)).await
+}
+
+/// Checks the item, if it's in the store when one is given.
+async fn check(db: &Db, id: i64, store_id: Option<i64>) -> Result<Option<Item>, sqlx::Error> {
     let now = time::OffsetDateTime::now_utc();
     let mut tx = db.begin().await?;
 
-    let Some(item): Option<Item> = sqlx::query_as("SELECT * FROM items WHERE id = ?")
-        .bind(id)
-        .fetch_optional(&mut *tx)
-        .await?
+    let Some(item): Option<Item> =
+        sqlx::query_as("SELECT * FROM items WHERE id = ? AND store_id IS COALESCE(?, store_id)")
+            .bind(id)
+            .bind(store_id)
+            .fetch_optional(&mut *tx)
+            .await?
     else {
         tx.rollback().await?;
         return Ok(None);
@@ -132,24 +254,174 @@ pub async fn set_checked(db: &Db, id: i64) -> Result<Option<Item>, sqlx::Error>
         .await?;
     }
 
-    let updated =
-        sqlx::query_as("UPDATE items SET checked = TRUE, updated_at = ? WHERE id = ? RETURNING *")
-            .bind(now)
-            .bind(id)
-            .fetch_one(&mut *tx)
-            .await?;
+    let updated: Option<Item> = sqlx::query_as(
+        "UPDATE items SET checked = TRUE, updated_at = ?
+         WHERE id = ? AND store_id IS COALESCE(?, store_id)
+         RETURNING *",
+    )
+    .bind(now)
+    .bind(id)
+    .bind(store_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(updated) = updated else {
+        tx.rollback().await?;
+        return Ok(None);
+    };
+    if !item.checked {
+        notify_checked(&mut tx, &updated).await?;
+    }
+
+    tx.commit().await?;
+    Ok(Some(updated))
+}
+
+/// Sets all fields of the item at once. An item that is unchecked or moved to another
+/// section is put at the end of it, the gap it leaves is closed.
+/// Section has to belong to the store.
+pub async fn replace(
+    db: &Db,
+    id: i64,
+    name: &str,
+    store_id: Option<i64>,
+    section_id: Option<i64>,
+    checked: bool,
+) -> Result<Option<Item>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+    let mut tx = db.begin().await?;
+
+    let Some(item): Option<Item> = sqlx::query_as("SELECT * FROM items WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+    else {
+        tx.rollback().await?;
+        return Ok(None);
+    };
+
+    let moved = item.store_id != store_id || item.section_id != section_id;
+    if !item.checked && (moved || checked) {
+        close_gap(&mut tx, &item, now).await?;
+    }
+    let ord = if !checked && (moved || item.checked) {
+        max_ord(&mut *tx, store_id, section_id).await? + 1
+    } else {
+        item.ord
+    };
+
+    let updated: Item = sqlx::query_as(
+        "UPDATE items
+         SET name = ?, store_id = ?, section_id = ?, checked = ?, ord = ?, updated_at = ?
+         WHERE id = ?
+         RETURNING *",
+    )
+    .bind(name)
+    .bind(store_id)
+    .bind(section_id)
+    .bind(checked)
+    .bind(ord)
+    .bind(now)
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if moved || item.name != updated.name {
+        webhook::enqueue(&mut tx, Event::ItemUpdated, &updated).await?;
+    }
+    if checked && !item.checked {
+        notify_checked(&mut tx, &updated).await?;
+    } else if !checked && item.checked {
+        webhook::enqueue(&mut tx, Event::ItemUnchecked, &updated).await?;
+    }
 
     tx.commit().await?;
     Ok(Some(updated))
 }
 
+/// Deletes the item. Returns false if it doesn't exist.
+pub async fn delete(db: &Db, id: i64) -> Result<bool, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+    let mut tx = db.begin().await?;
+
+    let Some(item): Option<Item> = sqlx::query_as("SELECT * FROM items WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+    else {
+        tx.rollback().await?;
+        return Ok(false);
+    };
+
+    if !item.checked {
+        close_gap(&mut tx, &item, now).await?;
+    }
+    sqlx::query("DELETE FROM items WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    webhook::enqueue(&mut tx, Event::ItemDeleted, &item).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Queues the events of a checked item. Checking the last unchecked item of a store
+/// finishes the trip to it.
+async fn notify_checked(
+    tx: &mut sqlx::SqliteTransaction<'_>,
+    item: &Item,
+) -> Result<(), sqlx::Error> {
+    webhook::enqueue(tx, Event::ItemChecked, item).await?;
+
+    let Some(store_id) = item.store_id else {
+        return Ok(());
+    };
+    let unchecked: bool =
+        sqlx::query("SELECT EXISTS (SELECT 1 FROM items WHERE store_id = ? AND checked = FALSE)")
+            .bind(store_id)
+            .fetch_one(&mut **tx)
+            .await?
+            .get(0);
+    let store: Option<Store> = sqlx::query_as("SELECT * FROM stores WHERE id = ?")
+        .bind(store_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    if !unchecked && let Some(store) = store {
+        webhook::enqueue(tx, Event::StoreCompleted, &store).await?;
+    }
+    Ok(())
+}
+
+/// Moves the unchecked items after the item up by one, before the item leaves its position.
+async fn close_gap(
+    tx: &mut sqlx::SqliteTransaction<'_>,
+    item: &Item,
+    now: time::OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE items
+         SET ord = ord - 1, updated_at = ?
+         WHERE checked = FALSE
+           AND store_id IS ?
+           AND section_id IS ?
+           AND ord > ?",
+    )
+    .bind(now)
+    .bind(item.store_id)
+    .bind(item.section_id)
+    .bind(item.ord)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
 pub async fn move_item(
     db: &Db,
     id: i64,
     store_id: Option<i64>,
     section_id: Option<i64>,
     index: i64,
-) -> Result<Option<Item>, sqlx::Error> {
+) -> Result<Option<Item>, Error> {
     let now = time::OffsetDateTime::now_utc();
 
     let mut tx = db.begin().await?;
@@ -164,8 +436,44 @@ pub async fn move_item(
         return Ok(None);
     };
 
+    // Target section has to belong to the target store.
+    if let Some(store_id) = store_id {
+        let exists: bool = sqlx::query("SELECT EXISTS (SELECT 1 FROM stores WHERE id = ?)")
+            .bind(store_id)
+            .fetch_one(&mut *tx)
+            .await?
+            .get(0);
+        if !exists {
+            tx.rollback().await?;
+            return Err(Error::StoreNotFound);
+        }
+    }
+    if let Some(section_id) = section_id {
+        let section_store_id: Option<i64> =
+            sqlx::query("SELECT store_id FROM sections WHERE id = ?")
+                .bind(section_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .map(|row| row.get(0));
+        let Some(section_store_id) = section_store_id else {
+            tx.rollback().await?;
+            return Err(Error::SectionNotFound);
+        };
+        if store_id != Some(section_store_id) {
+            tx.rollback().await?;
+            return Err(Error::SectionStoreMismatch);
+        }
+    }
+
     // Get target order before performing other ops
-    let target_ord = get_target_ord(&mut tx, store_id, section_id, index).await?;
+    let mut target_ord = get_target_ord(&mut tx, store_id, section_id, index).await?;
+
+    // When moving inside the same bucket past its end, the item becomes the last one.
+    // It doesn't open a new position, because it also leaves its current one.
+    if item.store_id == store_id && item.section_id == section_id {
+        let last_ord = max_ord(&mut *tx, store_id, section_id).await?;
+        target_ord = target_ord.min(last_ord);
+    }
 
     // Move items in current section to close the created gap.
     sqlx::query(
@@ -213,6 +521,10 @@ pub async fn move_item(
     .bind(id)
     .fetch_one(&mut *tx)
     .await?;
+    // Reordering inside the section isn't an event, webhooks don't see the order.
+    if item.store_id != store_id || item.section_id != section_id {
+        webhook::enqueue(&mut tx, Event::ItemUpdated, &updated).await?;
+    }
 
     tx.commit().await?;
 
@@ -255,33 +567,41 @@ async fn organize_section(
     store_id: i64,
     section_id: i64,
     items: &[i64],
-) -> Result<(), sqlx::Error> {
+) -> Result<Vec<Item>, sqlx::Error> {
     if items.is_empty() {
-        return Ok(());
+        return Ok(vec![]);
     }
 
     let now = time::OffsetDateTime::now_utc();
     let ord_start = max_ord(&mut **tx, Some(store_id), Some(section_id)).await?;
 
-    let mut qb = QueryBuilder::<Sqlite>::new("WITH updates(id, ord) AS (");
+    let mut qb = QueryBuilder::<Sqlite>::new("WITH updates(id, pos) AS (");
 
     qb.push_values(items.iter().enumerate(), |mut b, (idx, id)| {
-        let ord = ord_start + (idx as i64) + 1;
-        b.push_bind(*id).push_bind(ord);
+        b.push_bind(*id).push_bind(idx as i64);
     });
 
-    qb.push(") ")
+    // Items were read before the model answered, ones that were checked or moved since
+    // are left alone and the rest is numbered without gaps.
+    qb.push("), eligible(id, ord) AS (SELECT items.id, ")
+        .push_bind(ord_start)
         .push(
-            "UPDATE items
-             SET ord = updates.ord, section_id = ",
+            " + ROW_NUMBER() OVER (ORDER BY updates.pos)
+             FROM updates JOIN items ON items.id = updates.id
+             WHERE items.store_id = ",
+        )
+        .push_bind(store_id)
+        .push(
+            " AND items.section_id IS NULL AND items.checked = FALSE)
+             UPDATE items
+             SET ord = eligible.ord, section_id = ",
         )
         .push_bind(section_id)
         .push(", updated_at = ")
         .push_bind(now)
-        .push("FROM updates WHERE items.id = updates.id");
+        .push(" FROM eligible WHERE items.id = eligible.id RETURNING *");
 
-    qb.build().execute(&mut **tx).await?;
-    Ok(())
+    qb.build_query_as().fetch_all(&mut **tx).await
 }
 
 async fn max_ord<'c, E: Executor<'c, Database = Sqlite>>(
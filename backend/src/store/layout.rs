@@ -0,0 +1,143 @@
+//! Layouts are the ordered section names of a store, shared as small files between users
+//! of the same store chain.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Sqlite, Transaction};
+use utoipa::ToSchema;
+
+use crate::db::Db;
+use crate::store::Error;
+use crate::store::section::{self, Section};
+use crate::store::shop::Store;
+use crate::store::webhook::{self, Event};
+
+/// Version of the layout file format, incremented on incompatible changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Layout {
+    pub version: u32,
+    /// Name of the store, used when the layout is imported as a new store.
+    pub name: String,
+    /// Section names in the order of the store.
+    pub sections: Vec<String>,
+}
+
+pub async fn get(db: &Db, store_id: i64) -> Result<Option<Layout>, sqlx::Error> {
+    let Some(store) = crate::store::shop::get(db, store_id).await? else {
+        return Ok(None);
+    };
+    let sections = section::list(db, store_id).await?;
+
+    Ok(Some(Layout {
+        version: FORMAT_VERSION,
+        name: store.name,
+        sections: sections.into_iter().map(|s| s.name).collect(),
+    }))
+}
+
+/// Creates a new store with the sections of the layout.
+pub async fn create(db: &Db, layout: &Layout) -> Result<Store, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let mut tx = db.begin().await?;
+
+    let store: Store = sqlx::query_as(
+        "INSERT INTO stores (name, created_at, updated_at) VALUES (?, ?, ?) RETURNING *",
+    )
+    .bind(&layout.name)
+    .bind(now)
+    .bind(now)
+    .fetch_one(&mut *tx)
+    .await?;
+    webhook::enqueue(&mut tx, Event::StoreCreated, &store).await?;
+    apply_in(&mut tx, store.id, &layout.sections, now).await?;
+
+    tx.commit().await?;
+    Ok(store)
+}
+
+/// Sets sections of an existing store to the ones of the layout, in its order.
+/// Sections are matched by name ignoring case, matching sections keep their items.
+/// Sections missing from the layout are deleted, their items become unassigned.
+pub async fn apply(db: &Db, store_id: i64, layout: &Layout) -> Result<Vec<Section>, Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let mut tx = db.begin().await?;
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM stores WHERE id = ?)")
+        .bind(store_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !exists {
+        tx.rollback().await?;
+        return Err(Error::StoreNotFound);
+    }
+
+    apply_in(&mut tx, store_id, &layout.sections, now).await?;
+
+    let sections = sqlx::query_as("SELECT * FROM sections WHERE store_id = ? ORDER BY ord ASC")
+        .bind(store_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(sections)
+}
+
+/// Section names of the layout must be unique, ignoring case.
+async fn apply_in(
+    tx: &mut Transaction<'_, Sqlite>,
+    store_id: i64,
+    names: &[String],
+    now: time::OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    let mut existing: Vec<Section> =
+        sqlx::query_as("SELECT * FROM sections WHERE store_id = ? ORDER BY ord ASC, id ASC")
+            .bind(store_id)
+            .fetch_all(&mut **tx)
+            .await?;
+
+    // Kept sections are taken out, the rest is deleted.
+    let mut kept = Vec::with_capacity(names.len());
+    for name in names {
+        let pos = existing
+            .iter()
+            .position(|s| s.name.to_lowercase() == name.to_lowercase());
+        kept.push(pos.map(|pos| existing.remove(pos)));
+    }
+    for section in &existing {
+        section::delete_in(tx, section, now).await?;
+    }
+
+    for (idx, (name, section)) in names.iter().zip(kept).enumerate() {
+        let ord = idx as i64 + 1;
+        match section {
+            Some(section) if section.name == *name && section.ord == ord => {}
+            Some(section) => {
+                sqlx::query("UPDATE sections SET name = ?, ord = ?, updated_at = ? WHERE id = ?")
+                    .bind(name)
+                    .bind(ord)
+                    .bind(now)
+                    .bind(section.id)
+                    .execute(&mut **tx)
+                    .await?;
+            }
+            None => {
+                sqlx::query(
+                    "INSERT INTO sections (store_id, name, ord, created_at, updated_at)
+                     VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(store_id)
+                .bind(name)
+                .bind(ord)
+                .bind(now)
+                .bind(now)
+                .execute(&mut **tx)
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,4 +1,52 @@
+use thiserror::Error;
+
+pub mod app_token;
+pub mod caldav;
+pub mod dataset;
 pub mod item;
+pub mod layout;
+pub mod notification;
+pub mod oidc;
+pub mod push;
 pub mod section;
+pub mod share;
 pub mod shop;
 pub mod user;
+pub mod webauthn;
+pub mod webhook;
+
+/// Message of the trigger that keeps item sections inside the item's store.
+pub const SECTION_STORE_MISMATCH: &str = "section_store_mismatch";
+
+/// Error of operations that validate references between stores, sections and items.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("database error: {0}")]
+    Db(sqlx::Error),
+
+    #[error("store not found")]
+    StoreNotFound,
+
+    #[error("section not found")]
+    SectionNotFound,
+
+    #[error("section doesn't belong to the store")]
+    SectionStoreMismatch,
+
+    #[error("section ids don't match sections of the store")]
+    InvalidSectionIds,
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        if is_section_store_mismatch(&err) {
+            return Error::SectionStoreMismatch;
+        }
+        Error::Db(err)
+    }
+}
+
+/// Returns true if the error was raised by the section consistency triggers.
+pub fn is_section_store_mismatch(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::Database(db_err) if db_err.message() == SECTION_STORE_MISMATCH)
+}
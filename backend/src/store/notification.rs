@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+use crate::db::Db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum Kind {
+    /// Unchecked items.
+    List,
+    /// Items added since the last digest that are still unchecked.
+    Digest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum TransportKind {
+    Email,
+    Ntfy,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct Schedule {
+    pub id: i64,
+    pub kind: Kind,
+    pub store_id: Option<i64>,
+    pub transport: TransportKind,
+    /// Email address or ntfy topic.
+    pub target: String,
+    /// Five field cron expression, evaluated in the local time of the time zone.
+    pub cron: String,
+    /// IANA time zone name, ie. `Europe/Ljubljana`.
+    pub timezone: String,
+    pub enabled: bool,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_run_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_sent_at: Option<time::OffsetDateTime>,
+    /// Error of the last run, missing if it succeeded.
+    pub last_error: Option<String>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: time::OffsetDateTime,
+}
+
+/// Fields that are set by the user.
+pub struct ScheduleData<'a> {
+    pub kind: Kind,
+    pub store_id: Option<i64>,
+    pub transport: TransportKind,
+    pub target: &'a str,
+    pub cron: &'a str,
+    pub timezone: &'a str,
+    pub enabled: bool,
+}
+
+pub async fn create(
+    db: &Db,
+    user_id: i64,
+    data: &ScheduleData<'_>,
+    next_run_at: time::OffsetDateTime,
+) -> Result<Schedule, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "INSERT INTO notification_schedules
+         (user_id, kind, store_id, transport, target, cron, timezone, enabled, next_run_at,
+          created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         RETURNING *",
+    )
+    .bind(user_id)
+    .bind(data.kind)
+    .bind(data.store_id)
+    .bind(data.transport)
+    .bind(data.target)
+    .bind(data.cron)
+    .bind(data.timezone)
+    .bind(data.enabled)
+    .bind(next_run_at)
+    .bind(now)
+    .bind(now)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn list(db: &Db, user_id: i64) -> Result<Vec<Schedule>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM notification_schedules WHERE user_id = ? ORDER BY id ASC")
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn get(db: &Db, user_id: i64, id: i64) -> Result<Option<Schedule>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM notification_schedules WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Updates the user's schedule. The last error is cleared, as the target may have
+/// been fixed.
+pub async fn update(
+    db: &Db,
+    user_id: i64,
+    id: i64,
+    data: &ScheduleData<'_>,
+    next_run_at: time::OffsetDateTime,
+) -> Result<Option<Schedule>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "UPDATE notification_schedules
+         SET kind = ?, store_id = ?, transport = ?, target = ?, cron = ?, timezone = ?, enabled = ?,
+             next_run_at = ?, last_error = NULL, updated_at = ?
+         WHERE id = ? AND user_id = ?
+         RETURNING *",
+    )
+    .bind(data.kind)
+    .bind(data.store_id)
+    .bind(data.transport)
+    .bind(data.target)
+    .bind(data.cron)
+    .bind(data.timezone)
+    .bind(data.enabled)
+    .bind(next_run_at)
+    .bind(now)
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+}
+
+/// Deletes the user's schedule. Returns false if it doesn't exist.
+pub async fn delete(db: &Db, user_id: i64, id: i64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM notification_schedules WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Enabled schedules whose next run is at or before `now`, oldest first.
+pub async fn due(
+    db: &Db,
+    now: time::OffsetDateTime,
+    limit: i64,
+) -> Result<Vec<Schedule>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT * FROM notification_schedules
+         WHERE enabled = TRUE AND next_run_at <= ?
+         ORDER BY next_run_at ASC
+         LIMIT ?",
+    )
+    .bind(now)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+/// Records the result of a run. `sent_at` is set if the run succeeded, and then
+/// becomes the start of the next digest.
+pub async fn finish_run(
+    db: &Db,
+    id: i64,
+    next_run_at: Option<time::OffsetDateTime>,
+    sent_at: Option<time::OffsetDateTime>,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE notification_schedules
+         SET next_run_at = COALESCE(?, next_run_at), last_sent_at = COALESCE(?, last_sent_at),
+             last_error = ?
+         WHERE id = ?",
+    )
+    .bind(next_run_at)
+    .bind(sent_at)
+    .bind(error)
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use sqlx::prelude::FromRow;
+use sqlx::{Executor, Sqlite};
+
+use crate::db::Db;
+use crate::store::user::{Role, User};
+
+#[derive(FromRow)]
+pub struct AuthRequest {
+    pub code_verifier: String,
+    pub nonce: String,
+    pub redirect_to: String,
+}
+
+pub async fn create_auth_request(
+    db: &Db,
+    state_hash: &str,
+    code_verifier: &str,
+    nonce: &str,
+    redirect_to: &str,
+    expires_at: time::OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let mut tx = db.begin().await?;
+
+    // Clean up requests of users that never returned from the provider.
+    sqlx::query("DELETE FROM oidc_auth_requests WHERE expires_at <= ?")
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO oidc_auth_requests
+            (state_hash, code_verifier, nonce, redirect_to, expires_at, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(state_hash)
+    .bind(code_verifier)
+    .bind(nonce)
+    .bind(redirect_to)
+    .bind(expires_at)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Returns and deletes the request, so that each state can be used only once.
+pub async fn take_auth_request(
+    db: &Db,
+    state_hash: &str,
+) -> Result<Option<AuthRequest>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "DELETE FROM oidc_auth_requests
+         WHERE state_hash = ?
+           AND expires_at > ?
+         RETURNING *",
+    )
+    .bind(state_hash)
+    .bind(now)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn get_identity_user(
+    db: &Db,
+    issuer: &str,
+    subject: &str,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT u.* FROM users u
+         INNER JOIN user_identities ident ON u.id = ident.user_id
+         WHERE ident.issuer = ?
+           AND ident.subject = ?",
+    )
+    .bind(issuer)
+    .bind(subject)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn create_identity<'c, E: Executor<'c, Database = Sqlite>>(
+    db: E,
+    user_id: i64,
+    issuer: &str,
+    subject: &str,
+) -> Result<(), sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query(
+        "INSERT INTO user_identities (issuer, subject, user_id, created_at)
+         VALUES (?, ?, ?, ?)",
+    )
+    .bind(issuer)
+    .bind(subject)
+    .bind(user_id)
+    .bind(now)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Links an existing user with the identity.
+/// Returns `None` if user doesn't exist or is already linked with another identity of the issuer.
+pub async fn link_existing_user(
+    db: &Db,
+    username: &str,
+    issuer: &str,
+    subject: &str,
+) -> Result<Option<User>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let user: Option<User> = sqlx::query_as(
+        "SELECT u.* FROM users u
+         WHERE u.username = ?
+           AND NOT EXISTS (
+             SELECT 1 FROM user_identities ident
+             WHERE ident.user_id = u.id
+               AND ident.issuer = ?
+           )",
+    )
+    .bind(username)
+    .bind(issuer)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(user) = user else {
+        tx.rollback().await?;
+        return Ok(None);
+    };
+
+    create_identity(&mut *tx, user.id, issuer, subject).await?;
+    tx.commit().await?;
+
+    Ok(Some(user))
+}
+
+/// Creates a new user without password and links it with the identity.
+pub async fn provision_user(
+    db: &Db,
+    username: &str,
+    issuer: &str,
+    subject: &str,
+    role: Role,
+) -> Result<User, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let user_id = crate::store::user::create_user(&mut *tx, username, None, role).await?;
+    create_identity(&mut *tx, user_id, issuer, subject).await?;
+    let user = crate::store::user::get_user_by_id(&mut *tx, user_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    tx.commit().await?;
+    Ok(user)
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use sqlx::types::Json;
+use utoipa::ToSchema;
+
+use crate::db::Db;
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct Subscription {
+    pub id: i64,
+    pub endpoint: String,
+    /// Public key of the browser, base64url encoded.
+    #[serde(skip)]
+    pub p256dh: String,
+    /// Authentication secret of the browser, base64url encoded.
+    #[serde(skip)]
+    pub auth: String,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, Deserialize, ToSchema)]
+pub struct Preferences {
+    /// Notify about items added by other users.
+    pub item_created: bool,
+    /// Only items of these stores, all items if it's empty.
+    #[schema(value_type = Vec<i64>)]
+    pub store_ids: Json<Vec<i64>>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            item_created: true,
+            store_ids: Json(vec![]),
+        }
+    }
+}
+
+impl Preferences {
+    pub fn wants_item(&self, store_id: Option<i64>) -> bool {
+        self.item_created
+            && (self.store_ids.is_empty()
+                || store_id.is_some_and(|id| self.store_ids.contains(&id)))
+    }
+}
+
+/// Saves the subscription of the user. Subscribing the same endpoint again replaces
+/// its keys, and moves it to the user if another user subscribed it before.
+pub async fn subscribe(
+    db: &Db,
+    user_id: i64,
+    endpoint: &str,
+    p256dh: &str,
+    auth: &str,
+) -> Result<Subscription, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "INSERT INTO push_subscriptions (user_id, endpoint, p256dh, auth, created_at)
+         VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT (endpoint) DO UPDATE
+         SET user_id = excluded.user_id, p256dh = excluded.p256dh, auth = excluded.auth
+         RETURNING id, endpoint, p256dh, auth, created_at",
+    )
+    .bind(user_id)
+    .bind(endpoint)
+    .bind(p256dh)
+    .bind(auth)
+    .bind(now)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn list(db: &Db, user_id: i64) -> Result<Vec<Subscription>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, endpoint, p256dh, auth, created_at FROM push_subscriptions
+         WHERE user_id = ? ORDER BY created_at ASC",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Deletes the user's subscription. Returns false if it doesn't exist.
+pub async fn unsubscribe(db: &Db, user_id: i64, id: i64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM push_subscriptions WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Deletes a subscription that the push service no longer accepts.
+pub async fn delete_expired(db: &Db, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM push_subscriptions WHERE id = ?")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Subscriptions of all users except `user_id`, with the preferences of their users.
+pub async fn others(
+    db: &Db,
+    user_id: i64,
+) -> Result<Vec<(Subscription, Preferences)>, sqlx::Error> {
+    #[derive(FromRow)]
+    struct Row {
+        #[sqlx(flatten)]
+        subscription: Subscription,
+        item_created: Option<bool>,
+        store_ids: Option<Json<Vec<i64>>>,
+    }
+
+    let rows: Vec<Row> = sqlx::query_as(
+        "SELECT s.id, s.endpoint, s.p256dh, s.auth, s.created_at, p.item_created, p.store_ids
+         FROM push_subscriptions s
+         LEFT JOIN notification_preferences p ON p.user_id = s.user_id
+         WHERE s.user_id != ?
+         ORDER BY s.id ASC",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let prefs = match (row.item_created, row.store_ids) {
+                (Some(item_created), Some(store_ids)) => Preferences {
+                    item_created,
+                    store_ids,
+                },
+                _ => Preferences::default(),
+            };
+            (row.subscription, prefs)
+        })
+        .collect())
+}
+
+pub async fn get_preferences(db: &Db, user_id: i64) -> Result<Preferences, sqlx::Error> {
+    let prefs: Option<Preferences> = sqlx::query_as(
+        "SELECT item_created, store_ids FROM notification_preferences WHERE user_id = ?",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(prefs.unwrap_or_default())
+}
+
+pub async fn set_preferences(
+    db: &Db,
+    user_id: i64,
+    prefs: &Preferences,
+) -> Result<Preferences, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "INSERT INTO notification_preferences (user_id, item_created, store_ids, updated_at)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT (user_id) DO UPDATE
+         SET item_created = excluded.item_created, store_ids = excluded.store_ids,
+             updated_at = excluded.updated_at
+         RETURNING item_created, store_ids",
+    )
+    .bind(user_id)
+    .bind(prefs.item_created)
+    .bind(&prefs.store_ids)
+    .bind(now)
+    .fetch_one(db)
+    .await
+}
@@ -1,9 +1,13 @@
+use std::collections::HashSet;
+
 use serde::Serialize;
 use sqlx::prelude::{FromRow, Row};
+use sqlx::{Sqlite, Transaction};
+use utoipa::ToSchema;
 
-use crate::db::Db;
+use crate::{db::Db, store::Error};
 
-#[derive(Debug, Clone, FromRow, Serialize)]
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
 pub struct Section {
     pub id: i64,
     pub store_id: i64,
@@ -81,17 +85,87 @@ pub async fn update(db: &Db, id: i64, name: &str) -> Result<Section, sqlx::Error
     .await
 }
 
+/// Deletes the section. Its items are moved to the end of the store's unassigned items.
 pub async fn delete(db: &Db, id: i64) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM sections WHERE id = ?")
+    let now = time::OffsetDateTime::now_utc();
+
+    let mut tx = db.begin().await?;
+
+    let Some(section): Option<Section> = sqlx::query_as("SELECT * FROM sections WHERE id = ?")
         .bind(id)
-        .execute(db)
+        .fetch_optional(&mut *tx)
+        .await?
+    else {
+        tx.rollback().await?;
+        return Ok(());
+    };
+
+    delete_in(&mut tx, &section, now).await?;
+
+    tx.commit().await
+}
+
+/// Deletes the section inside of the transaction, see [`delete`].
+pub(crate) async fn delete_in(
+    tx: &mut Transaction<'_, Sqlite>,
+    section: &Section,
+    now: time::OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    let unassigned_ord: i64 = sqlx::query(
+        "SELECT COALESCE(MAX(ord), 0) FROM items
+         WHERE store_id = ?
+           AND section_id IS NULL
+           AND checked = FALSE",
+    )
+    .bind(section.store_id)
+    .fetch_one(&mut **tx)
+    .await?
+    .get(0);
+
+    sqlx::query(
+        "UPDATE items
+         SET section_id = NULL,
+             ord = CASE WHEN checked THEN ord ELSE ord + ? END,
+             updated_at = ?
+         WHERE section_id = ?",
+    )
+    .bind(unassigned_ord)
+    .bind(now)
+    .bind(section.id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query("DELETE FROM sections WHERE id = ?")
+        .bind(section.id)
+        .execute(&mut **tx)
         .await?;
+
     Ok(())
 }
 
-pub async fn reorder(db: &Db, ids: &[i64]) -> Result<(), sqlx::Error> {
+/// Sets order of the store's sections. `ids` must contain exactly all sections of the store.
+pub async fn reorder(db: &Db, store_id: i64, ids: &[i64]) -> Result<(), Error> {
     let now = time::OffsetDateTime::now_utc();
 
+    let mut tx = db.begin().await?;
+
+    let existing: HashSet<i64> = sqlx::query("SELECT id FROM sections WHERE store_id = ?")
+        .bind(store_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    let given: HashSet<i64> = ids.iter().copied().collect();
+
+    if ids.len() != existing.len() || given != existing {
+        tx.rollback().await?;
+        return Err(Error::InvalidSectionIds);
+    }
+    if ids.is_empty() {
+        return tx.commit().await.map_err(Error::from);
+    }
+
     let mut qb = sqlx::QueryBuilder::<sqlx::Sqlite>::new("WITH new_order(id, new_ord) AS (");
 
     qb.push_values(ids.iter().enumerate(), |mut b, (idx, id)| {
@@ -105,10 +179,14 @@ pub async fn reorder(db: &Db, ids: &[i64]) -> Result<(), sqlx::Error> {
     qb.push_bind(now);
     qb.push(
         "FROM new_order 
-         WHERE sections.id = new_order.id",
+         WHERE sections.id = new_order.id
+           AND sections.store_id = ",
     );
+    qb.push_bind(store_id);
+
+    qb.build().execute(&mut *tx).await?;
 
-    qb.build().execute(db).await?;
+    tx.commit().await?;
     Ok(())
 }
 
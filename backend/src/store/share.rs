@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+use crate::db::Db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum Permission {
+    /// Guest can only see the list.
+    View,
+    /// Guest can see the list and check items.
+    Check,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct ShareLink {
+    pub id: i64,
+    pub store_id: i64,
+    pub permission: Permission,
+    pub created_by: Option<i64>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+}
+
+pub async fn create(
+    db: &Db,
+    store_id: i64,
+    token_hash: &str,
+    permission: Permission,
+    created_by: i64,
+    expires_at: time::OffsetDateTime,
+) -> Result<ShareLink, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "INSERT INTO share_links
+            (store_id, token_hash, permission, created_by, expires_at, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)
+         RETURNING *",
+    )
+    .bind(store_id)
+    .bind(token_hash)
+    .bind(permission)
+    .bind(created_by)
+    .bind(expires_at)
+    .bind(now)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn list(db: &Db, store_id: i64) -> Result<Vec<ShareLink>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM share_links WHERE store_id = ? ORDER BY created_at DESC")
+        .bind(store_id)
+        .fetch_all(db)
+        .await
+}
+
+/// Returns the link for the token, if it isn't expired.
+pub async fn get_valid(db: &Db, token_hash: &str) -> Result<Option<ShareLink>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as("SELECT * FROM share_links WHERE token_hash = ? AND expires_at > ?")
+        .bind(token_hash)
+        .bind(now)
+        .fetch_optional(db)
+        .await
+}
+
+/// Deletes the link. Returns false if it doesn't exist.
+pub async fn delete(db: &Db, id: i64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM share_links WHERE id = ?")
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
@@ -1,9 +1,11 @@
 use serde::Serialize;
 use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
 
 use crate::db::Db;
+use crate::store::webhook::{self, Event};
 
-#[derive(Debug, Clone, FromRow, Serialize)]
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
 pub struct Store {
     pub id: i64,
     pub name: String,
@@ -17,20 +19,25 @@ pub struct Store {
 pub async fn create(db: &Db, name: &str) -> Result<Store, sqlx::Error> {
     let now = time::OffsetDateTime::now_utc();
 
+    let mut tx = db.begin().await?;
     let res = sqlx::query("INSERT INTO stores (name, created_at, updated_at) VALUES (?, ?, ?)")
         .bind(name)
         .bind(now)
         .bind(now)
-        .execute(db)
+        .execute(&mut *tx)
         .await?;
     let id = res.last_insert_rowid();
 
-    Ok(Store {
+    let store = Store {
         id,
         name: name.to_string(),
         created_at: now,
         updated_at: now,
-    })
+    };
+    webhook::enqueue(&mut tx, Event::StoreCreated, &store).await?;
+
+    tx.commit().await?;
+    Ok(store)
 }
 
 pub async fn list(db: &Db) -> Result<Vec<Store>, sqlx::Error> {
@@ -41,8 +48,9 @@ pub async fn list(db: &Db) -> Result<Vec<Store>, sqlx::Error> {
 
 pub async fn update(db: &Db, id: i64, name: &str) -> Result<Store, sqlx::Error> {
     let now = time::OffsetDateTime::now_utc();
+    let mut tx = db.begin().await?;
 
-    sqlx::query_as(
+    let store: Store = sqlx::query_as(
         "UPDATE stores 
          SET name = ?, updated_at = ?
          WHERE id = ? 
@@ -51,16 +59,60 @@ pub async fn update(db: &Db, id: i64, name: &str) -> Result<Store, sqlx::Error>
     .bind(name)
     .bind(now)
     .bind(id)
-    .fetch_one(db)
-    .await
+    .fetch_one(&mut *tx)
+    .await?;
+    webhook::enqueue(&mut tx, Event::StoreUpdated, &store).await?;
+
+    tx.commit().await?;
+    Ok(store)
 }
 
+/// Deletes the store. Its items are moved to the end of the unassigned items,
+/// keeping the order of the store's sections.
 pub async fn delete(db: &Db, id: i64) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM stores WHERE id = ?")
+    let now = time::OffsetDateTime::now_utc();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "WITH moved(id, ord) AS (
+            SELECT items.id,
+                   (SELECT COALESCE(MAX(ord), 0) FROM items
+                    WHERE store_id IS NULL AND checked = FALSE)
+                   + ROW_NUMBER() OVER (ORDER BY COALESCE(sections.ord, 0), items.ord)
+            FROM items
+            LEFT JOIN sections ON sections.id = items.section_id
+            WHERE items.store_id = ?
+              AND items.checked = FALSE
+         )
+         UPDATE items
+         SET ord = moved.ord
+         FROM moved
+         WHERE items.id = moved.id",
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "UPDATE items
+         SET store_id = NULL, section_id = NULL, updated_at = ?
+         WHERE store_id = ?",
+    )
+    .bind(now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    let store: Option<Store> = sqlx::query_as("DELETE FROM stores WHERE id = ? RETURNING *")
         .bind(id)
-        .execute(db)
+        .fetch_optional(&mut *tx)
         .await?;
-    Ok(())
+    if let Some(store) = store {
+        webhook::enqueue(&mut tx, Event::StoreDeleted, &store).await?;
+    }
+
+    tx.commit().await
 }
 
 pub async fn get(db: &Db, id: i64) -> Result<Option<Store>, sqlx::Error> {
@@ -1,38 +1,99 @@
+use std::str::FromStr;
+
 use password_hash::PasswordHash;
+use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use sqlx::{Executor, Sqlite};
+use utoipa::ToSchema;
 
 use crate::db::Db;
 
+/// Roles are ordered, each role has all permissions of the roles before it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum Role {
+    /// Can view the list, add and check items.
+    Shopper,
+    /// Can also manage items and share links.
+    #[default]
+    Member,
+    /// Can also manage stores and sections.
+    Admin,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Role::Shopper => "shopper",
+            Role::Member => "member",
+            Role::Admin => "admin",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shopper" => Ok(Role::Shopper),
+            "member" => Ok(Role::Member),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!(
+                "invalid role '{s}', expected one of: shopper, member, admin"
+            )),
+        }
+    }
+}
+
 #[derive(FromRow)]
 pub struct User {
     pub id: i64,
     pub username: String,
-    pub password_hash: String,
+    pub password_hash: Option<String>,
+    pub role: Role,
     pub created_at: time::OffsetDateTime,
     pub updated_at: time::OffsetDateTime,
 }
 
-pub async fn create_user(
-    db: &Db,
+pub async fn create_user<'c, E: Executor<'c, Database = Sqlite>>(
+    db: E,
     username: &str,
-    password_hash: &PasswordHash<'_>,
-) -> Result<(), sqlx::Error> {
+    password_hash: Option<&PasswordHash<'_>>,
+    role: Role,
+) -> Result<i64, sqlx::Error> {
     let now = time::OffsetDateTime::now_utc();
-    let pass_hash_str = password_hash.to_string();
+    let pass_hash_str = password_hash.map(|h| h.to_string());
 
-    sqlx::query(
+    let res = sqlx::query(
         "INSERT INTO users
-            (username, password_hash, created_at, updated_at) 
-        VALUES (?, ?, ?, ?)",
+            (username, password_hash, role, created_at, updated_at) 
+        VALUES (?, ?, ?, ?, ?)",
     )
     .bind(username)
     .bind(pass_hash_str)
+    .bind(role)
     .bind(now)
     .bind(now)
     .execute(db)
     .await?;
 
-    Ok(())
+    Ok(res.last_insert_rowid())
 }
 
 pub async fn get_user(db: &Db, username: &str) -> Result<Option<User>, sqlx::Error> {
@@ -42,6 +103,30 @@ pub async fn get_user(db: &Db, username: &str) -> Result<Option<User>, sqlx::Err
         .await
 }
 
+pub async fn get_user_by_id<'c, E: Executor<'c, Database = Sqlite>>(
+    db: E,
+    id: i64,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Sets role of the user. Returns false if user doesn't exist.
+pub async fn set_role(db: &Db, username: &str, role: Role) -> Result<bool, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let res = sqlx::query("UPDATE users SET role = ?, updated_at = ? WHERE username = ?")
+        .bind(role)
+        .bind(now)
+        .bind(username)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
 pub async fn create_session(
     db: &Db,
     user_id: i64,
@@ -0,0 +1,170 @@
+use serde::Serialize;
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+
+use crate::db::Db;
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct Credential {
+    pub id: i64,
+    #[serde(skip)]
+    pub user_id: i64,
+    pub name: String,
+
+    /// Serialized `webauthn_rs::prelude::Passkey`.
+    #[serde(skip)]
+    pub passkey: String,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<time::OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum ChallengeKind {
+    Registration,
+    Authentication,
+}
+
+#[derive(FromRow)]
+pub struct Challenge {
+    pub user_id: i64,
+    /// Serialized registration or authentication state.
+    pub state: String,
+}
+
+pub async fn create_credential(
+    db: &Db,
+    user_id: i64,
+    credential_id: &str,
+    name: &str,
+    passkey: &str,
+) -> Result<Credential, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "INSERT INTO webauthn_credentials
+            (user_id, credential_id, name, passkey, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?)
+         RETURNING *",
+    )
+    .bind(user_id)
+    .bind(credential_id)
+    .bind(name)
+    .bind(passkey)
+    .bind(now)
+    .bind(now)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn list_credentials(db: &Db, user_id: i64) -> Result<Vec<Credential>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM webauthn_credentials WHERE user_id = ? ORDER BY created_at ASC")
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn get_credential(
+    db: &Db,
+    credential_id: &str,
+) -> Result<Option<Credential>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM webauthn_credentials WHERE credential_id = ?")
+        .bind(credential_id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Stores the passkey with updated counter after successful authentication.
+pub async fn update_credential_usage(
+    db: &Db,
+    id: i64,
+    passkey: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query(
+        "UPDATE webauthn_credentials
+         SET passkey = COALESCE(?, passkey), last_used_at = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(passkey)
+    .bind(now)
+    .bind(now)
+    .bind(id)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes credential of the given user. Returns false if it doesn't exist.
+pub async fn delete_credential(db: &Db, user_id: i64, id: i64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM webauthn_credentials WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn create_challenge(
+    db: &Db,
+    challenge_hash: &str,
+    user_id: i64,
+    kind: ChallengeKind,
+    state: &str,
+    expires_at: time::OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("DELETE FROM webauthn_challenges WHERE expires_at <= ?")
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO webauthn_challenges
+            (challenge_hash, user_id, kind, state, expires_at, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(challenge_hash)
+    .bind(user_id)
+    .bind(kind)
+    .bind(state)
+    .bind(expires_at)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Returns and deletes the challenge, so that each challenge can be used only once.
+pub async fn take_challenge(
+    db: &Db,
+    challenge_hash: &str,
+    kind: ChallengeKind,
+) -> Result<Option<Challenge>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "DELETE FROM webauthn_challenges
+         WHERE challenge_hash = ?
+           AND kind = ?
+           AND expires_at > ?
+         RETURNING *",
+    )
+    .bind(challenge_hash)
+    .bind(kind)
+    .bind(now)
+    .fetch_optional(db)
+    .await
+}
@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use sqlx::prelude::FromRow;
+use sqlx::types::Json;
+use utoipa::ToSchema;
+
+use crate::db::Db;
+
+/// Delivered at most this many times, then the delivery is given up.
+pub const MAX_ATTEMPTS: i64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum Event {
+    #[serde(rename = "item.created")]
+    ItemCreated,
+    /// Item was renamed or moved to another store or section.
+    #[serde(rename = "item.updated")]
+    ItemUpdated,
+    #[serde(rename = "item.checked")]
+    ItemChecked,
+    #[serde(rename = "item.unchecked")]
+    ItemUnchecked,
+    #[serde(rename = "item.deleted")]
+    ItemDeleted,
+    #[serde(rename = "store.created")]
+    StoreCreated,
+    #[serde(rename = "store.updated")]
+    StoreUpdated,
+    #[serde(rename = "store.deleted")]
+    StoreDeleted,
+    /// Last unchecked item of the store was checked, the trip is finished.
+    #[serde(rename = "store.completed")]
+    StoreCompleted,
+}
+
+impl Event {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Event::ItemCreated => "item.created",
+            Event::ItemUpdated => "item.updated",
+            Event::ItemChecked => "item.checked",
+            Event::ItemUnchecked => "item.unchecked",
+            Event::ItemDeleted => "item.deleted",
+            Event::StoreCreated => "store.created",
+            Event::StoreUpdated => "store.updated",
+            Event::StoreDeleted => "store.deleted",
+            Event::StoreCompleted => "store.completed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Events sent to the url, all events if it's empty.
+    #[schema(value_type = Vec<Event>)]
+    pub events: Json<Vec<Event>>,
+    /// Key of the signatures, it's never returned.
+    #[serde(skip)]
+    pub secret: String,
+    pub enabled: bool,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: time::OffsetDateTime,
+}
+
+impl Webhook {
+    pub fn subscribed(&self, event: Event) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for the first attempt or for a retry.
+    Pending,
+    Delivered,
+    /// All attempts failed.
+    Failed,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct Delivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub event: String,
+    /// Json body that is sent.
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    /// Status code of the last response, missing if the request failed.
+    pub last_status_code: Option<i64>,
+    pub last_error: Option<String>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_attempt_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub delivered_at: Option<time::OffsetDateTime>,
+}
+
+/// Body of the POST request.
+#[derive(Serialize)]
+struct Payload<'a, T: Serialize> {
+    event: &'a str,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: time::OffsetDateTime,
+    data: &'a T,
+}
+
+pub async fn create(
+    db: &Db,
+    url: &str,
+    events: &[Event],
+    secret: &str,
+    enabled: bool,
+) -> Result<Webhook, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    sqlx::query_as(
+        "INSERT INTO webhooks (url, events, secret, enabled, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?)
+         RETURNING *",
+    )
+    .bind(url)
+    .bind(Json(events))
+    .bind(secret)
+    .bind(enabled)
+    .bind(now)
+    .bind(now)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn list(db: &Db) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM webhooks ORDER BY id ASC")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn get(db: &Db, id: i64) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM webhooks WHERE id = ?")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Updates the webhook, the secret is kept if it's `None`. Disabling the webhook gives
+/// up its pending deliveries, they would be stale once it's enabled again.
+pub async fn update(
+    db: &Db,
+    id: i64,
+    url: &str,
+    events: &[Event],
+    secret: Option<&str>,
+    enabled: bool,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    let now = time::OffsetDateTime::now_utc();
+    let mut tx = db.begin().await?;
+
+    let webhook: Option<Webhook> = sqlx::query_as(
+        "UPDATE webhooks
+         SET url = ?, events = ?, secret = COALESCE(?, secret), enabled = ?, updated_at = ?
+         WHERE id = ?
+         RETURNING *",
+    )
+    .bind(url)
+    .bind(Json(events))
+    .bind(secret)
+    .bind(enabled)
+    .bind(now)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if !enabled {
+        sqlx::query(
+            "UPDATE webhook_deliveries
+             SET status = ?, last_error = 'webhook was disabled'
+             WHERE webhook_id = ? AND status = ?",
+        )
+        .bind(DeliveryStatus::Failed)
+        .bind(id)
+        .bind(DeliveryStatus::Pending)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(webhook)
+}
+
+/// Deletes the webhook with its deliveries. Returns false if it doesn't exist.
+pub async fn delete(db: &Db, id: i64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM webhooks WHERE id = ?")
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Queues a delivery of the event for each enabled webhook that subscribed to it.
+/// Called in the transaction of the change, so that events are sent only for
+/// committed changes.
+pub async fn enqueue<T: Serialize>(
+    conn: &mut SqliteConnection,
+    event: Event,
+    data: &T,
+) -> Result<(), sqlx::Error> {
+    let webhooks: Vec<Webhook> = sqlx::query_as("SELECT * FROM webhooks WHERE enabled = TRUE")
+        .fetch_all(&mut *conn)
+        .await?;
+    let webhooks: Vec<_> = webhooks.iter().filter(|w| w.subscribed(event)).collect();
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let now = time::OffsetDateTime::now_utc();
+    let payload = serde_json::to_string(&Payload {
+        event: event.as_str(),
+        created_at: now,
+        data,
+    })
+    .map_err(|err| sqlx::Error::Encode(err.into()))?;
+
+    for webhook in webhooks {
+        sqlx::query(
+            "INSERT INTO webhook_deliveries
+                (webhook_id, event, payload, status, next_attempt_at, created_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(webhook.id)
+        .bind(event.as_str())
+        .bind(&payload)
+        .bind(DeliveryStatus::Pending)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Newest deliveries of the webhook.
+pub async fn list_deliveries(
+    db: &Db,
+    webhook_id: i64,
+    limit: i64,
+) -> Result<Vec<Delivery>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT * FROM webhook_deliveries
+         WHERE webhook_id = ?
+         ORDER BY id DESC
+         LIMIT ?",
+    )
+    .bind(webhook_id)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+/// Pending deliveries of enabled webhooks that are due at `now`, oldest first, with
+/// their webhooks.
+pub async fn due_deliveries(
+    db: &Db,
+    now: time::OffsetDateTime,
+    limit: i64,
+) -> Result<Vec<(Delivery, Webhook)>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let deliveries: Vec<Delivery> = sqlx::query_as(
+        "SELECT webhook_deliveries.* FROM webhook_deliveries
+         JOIN webhooks ON webhooks.id = webhook_deliveries.webhook_id
+         WHERE webhooks.enabled = TRUE
+           AND webhook_deliveries.status = ?
+           AND webhook_deliveries.next_attempt_at <= ?
+         ORDER BY webhook_deliveries.next_attempt_at ASC, webhook_deliveries.id ASC
+         LIMIT ?",
+    )
+    .bind(DeliveryStatus::Pending)
+    .bind(now)
+    .bind(limit)
+    .fetch_all(&mut *tx)
+    .await?;
+    let webhooks: Vec<Webhook> = sqlx::query_as("SELECT * FROM webhooks WHERE enabled = TRUE")
+        .fetch_all(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let due = deliveries
+        .into_iter()
+        .filter_map(|delivery| {
+            let webhook = webhooks.iter().find(|w| w.id == delivery.webhook_id)?;
+            Some((delivery, webhook.clone()))
+        })
+        .collect();
+    Ok(due)
+}
+
+pub async fn mark_delivered(
+    db: &Db,
+    id: i64,
+    status_code: i64,
+    now: time::OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = ?, attempts = attempts + 1, last_status_code = ?, last_error = NULL,
+             delivered_at = ?
+         WHERE id = ?",
+    )
+    .bind(DeliveryStatus::Delivered)
+    .bind(status_code)
+    .bind(now)
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Records a failed attempt. The delivery is retried at `retry_at`, or given up
+/// if it's `None`.
+pub async fn mark_failed(
+    db: &Db,
+    id: i64,
+    status_code: Option<i64>,
+    error: &str,
+    retry_at: Option<time::OffsetDateTime>,
+) -> Result<(), sqlx::Error> {
+    let status = match retry_at {
+        Some(_) => DeliveryStatus::Pending,
+        None => DeliveryStatus::Failed,
+    };
+
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = ?, attempts = attempts + 1, last_status_code = ?, last_error = ?,
+             next_attempt_at = COALESCE(?, next_attempt_at)
+         WHERE id = ?",
+    )
+    .bind(status)
+    .bind(status_code)
+    .bind(error)
+    .bind(retry_at)
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Deletes finished deliveries created before `before`.
+pub async fn prune_deliveries(db: &Db, before: time::OffsetDateTime) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM webhook_deliveries WHERE status != ? AND created_at < ?")
+        .bind(DeliveryStatus::Pending)
+        .bind(before)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected())
+}
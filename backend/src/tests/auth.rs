@@ -0,0 +1,129 @@
+use axum::http::StatusCode;
+use ipnet::IpNet;
+use serde_json::{Value, json};
+
+use crate::config::ProxyAuthConfig;
+use crate::store::user::Role;
+use crate::tests::{TestApp, test_config};
+
+#[tokio::test]
+async fn login_creates_session() {
+    let app = TestApp::new().await;
+    let user = app.login("alice", Role::Member).await;
+
+    let res = app
+        .get("/api/v1/auth/me")
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let me: Value = res.json();
+    assert_eq!(me["username"], "alice");
+    assert_eq!(me["role"], "member");
+}
+
+#[tokio::test]
+async fn login_with_wrong_password_fails() {
+    let app = TestApp::new().await;
+    app.create_user("alice", Role::Member).await;
+
+    app.post("/api/v1/auth/login")
+        .json(json!({ "auth_type": "web", "username": "alice", "password": "wrong" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "invalid_credentials");
+
+    app.post("/api/v1/auth/login")
+        .json(json!({ "auth_type": "web", "username": "bob", "password": "password" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "invalid_credentials");
+}
+
+#[tokio::test]
+async fn malformed_login_body_is_rejected() {
+    let app = TestApp::new().await;
+
+    app.post("/api/v1/auth/login")
+        .raw_json("{\"auth_type\": ")
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "malformed_body");
+
+    app.post("/api/v1/auth/login")
+        .body("text/plain", "{}")
+        .send()
+        .await
+        .assert_problem(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type");
+
+    let password = "a".repeat(3 * 1024 * 1024);
+    app.post("/api/v1/auth/login")
+        .json(json!({ "auth_type": "web", "username": "bob", "password": password }))
+        .send()
+        .await
+        .assert_problem(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large");
+}
+
+#[tokio::test]
+async fn me_requires_session() {
+    let app = TestApp::new().await;
+
+    app.get("/api/v1/auth/me")
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "missing_credentials");
+
+    app.get("/api/v1/auth/me")
+        .header("cookie", "session=invalid")
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "invalid_credentials");
+}
+
+#[tokio::test]
+async fn logout_deletes_session() {
+    let app = TestApp::new().await;
+    let user = app.login("alice", Role::Member).await;
+
+    app.post("/api/v1/auth/logout")
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    app.get("/api/v1/auth/me")
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn proxy_header_is_trusted_only_from_proxies() {
+    let mut config = test_config();
+    config.proxy_auth = Some(ProxyAuthConfig {
+        header: "Remote-User".to_string(),
+        trusted_proxies: vec!["10.0.0.0/8".parse::<IpNet>().unwrap()],
+        auto_create_users: true,
+        default_role: Role::Shopper,
+    });
+    let app = TestApp::with_config(config).await;
+
+    let res = app
+        .get("/api/v1/auth/me")
+        .peer("10.1.2.3:4000".parse().unwrap())
+        .header("Remote-User", "bob")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let me: Value = res.json();
+    assert_eq!(me["username"], "bob");
+    assert_eq!(me["role"], "shopper");
+
+    app.get("/api/v1/auth/me")
+        .peer("192.168.1.1:4000".parse().unwrap())
+        .header("Remote-User", "bob")
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "missing_credentials");
+}
@@ -0,0 +1,172 @@
+//! `VACUUM INTO` from the in-memory database of `TestApp` writes to memory as well,
+//! so these tests use database files.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use time::macros::datetime;
+
+use crate::backup;
+use crate::config::BackupConfig;
+use crate::db::{self, Db};
+use crate::state::AppState;
+use crate::store::item;
+use crate::tests::{StubLlm, test_config};
+
+fn db_url(path: &Path) -> String {
+    format!("sqlite://{}", path.display())
+}
+
+async fn file_db(path: &Path) -> Db {
+    db::connect(&db_url(path)).await.unwrap()
+}
+
+async fn item_names(db: &Db) -> Vec<String> {
+    sqlx::query_scalar("SELECT name FROM items ORDER BY id")
+        .fetch_all(db)
+        .await
+        .unwrap()
+}
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn backup_and_restore() {
+    for gzip in [false, true] {
+        let dir = tempfile::tempdir().unwrap();
+        let db = file_db(&dir.path().join("source.db")).await;
+        item::create(&db, None, None, "milk").await.unwrap();
+        let backup_path = dir.path().join("backup.db");
+
+        backup::backup(&db, &backup_path, gzip).await.unwrap();
+        let content = std::fs::read(&backup_path).unwrap();
+        assert_eq!(content.starts_with(&[0x1f, 0x8b]), gzip);
+
+        // Restore replaces an existing database.
+        let target = dir.path().join("data.db");
+        let target_db = file_db(&target).await;
+        item::create(&target_db, None, None, "bread").await.unwrap();
+        target_db.close().await;
+        backup::restore(&db_url(&target), &backup_path)
+            .await
+            .unwrap();
+
+        assert_eq!(item_names(&file_db(&target).await).await, ["milk"]);
+    }
+}
+
+#[tokio::test]
+async fn backup_refuses_to_overwrite() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = file_db(&dir.path().join("source.db")).await;
+    let path = dir.path().join("backup.db");
+    std::fs::write(&path, "keep").unwrap();
+
+    assert!(backup::backup(&db, &path, false).await.is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep");
+}
+
+#[tokio::test]
+async fn restore_rejects_invalid_backups() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = db_url(&dir.path().join("data.db"));
+
+    let not_db = dir.path().join("not-a-db");
+    std::fs::write(&not_db, "definitely not a database").unwrap();
+    let err = backup::restore(&target, &not_db).await.unwrap_err();
+    assert!(err.to_string().contains("not a database"), "{err:#}");
+
+    // Backup made by a later version
+    let db = file_db(&dir.path().join("source.db")).await;
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+         VALUES (99990101000000, 'future', 1, x'00', 0)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    let later = dir.path().join("later.db");
+    sqlx::query("VACUUM INTO ?")
+        .bind(later.to_string_lossy())
+        .execute(&db)
+        .await
+        .unwrap();
+    db.close().await;
+    let err = backup::restore(&target, &later).await.unwrap_err();
+    assert!(err.to_string().contains("99990101000000"), "{err:#}");
+
+    // Nothing is left behind.
+    assert!(
+        !file_names(dir.path())
+            .iter()
+            .any(|f| f.starts_with("data.db"))
+    );
+}
+
+#[tokio::test]
+async fn scheduled_backups_are_pruned() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = file_db(&dir.path().join("source.db")).await;
+    let backup_dir = dir.path().join("backups");
+    std::fs::create_dir(&backup_dir).unwrap();
+    std::fs::write(backup_dir.join("unrelated.db"), "").unwrap();
+    let conf = BackupConfig {
+        dir: backup_dir.to_string_lossy().into(),
+        interval: 60,
+        keep: 2,
+        gzip: true,
+    };
+
+    for now in [
+        datetime!(2026-10-17 03:00 UTC),
+        datetime!(2026-10-18 03:00 UTC),
+        datetime!(2026-10-19 03:00 UTC),
+    ] {
+        backup::scheduled_backup(&db, &conf, now).await.unwrap();
+    }
+
+    assert_eq!(
+        file_names(&backup_dir),
+        [
+            "lshop-20261018T030000Z.db.gz",
+            "lshop-20261019T030000Z.db.gz",
+            "unrelated.db",
+        ]
+    );
+}
+
+/// Error of the app state with the backup config.
+async fn backup_config_error(interval: u64, keep: usize) -> Option<String> {
+    let mut config = test_config();
+    config.backup = Some(BackupConfig {
+        dir: "backups".to_string(),
+        interval,
+        keep,
+        gzip: false,
+    });
+    let db = db::connect_in_memory().await.unwrap();
+    AppState::new(db, config, Arc::new(StubLlm::new()))
+        .err()
+        .map(|err| err.to_string())
+}
+
+#[tokio::test]
+async fn zero_backup_interval_is_rejected() {
+    let err = backup_config_error(0, 7).await.unwrap();
+    assert!(err.contains("interval"), "{err}");
+    assert_eq!(backup_config_error(1, 7).await, None);
+}
+
+#[tokio::test]
+async fn zero_backups_to_keep_is_rejected() {
+    let err = backup_config_error(60, 0).await.unwrap();
+    assert!(err.contains("keep"), "{err}");
+    assert_eq!(backup_config_error(60, 1).await, None);
+}
@@ -0,0 +1,554 @@
+use axum::http::{Method, StatusCode};
+use base64::prelude::*;
+use serde_json::{Value, json};
+
+use crate::caldav::ical::{self, Todo};
+use crate::store::user::Role;
+use crate::tests::{TestApp, TestRequest, TestResponse, TestUser};
+
+const PROP_ETAGS: &str = r#"<?xml version="1.0"?>
+<propfind xmlns="DAV:" xmlns:cs="http://calendarserver.org/ns/">
+  <prop><getetag/><cs:getctag/></prop>
+</propfind>"#;
+
+/// App token and credentials of a logged in user.
+struct DavUser {
+    user: TestUser,
+    token_id: i64,
+    basic: String,
+}
+
+async fn dav_user(app: &TestApp, username: &str, role: Role) -> DavUser {
+    let user = app.login(username, role).await;
+    let body: Value = app
+        .post("/api/v1/auth/tokens")
+        .user(&user)
+        .json(json!({ "name": "  Phone " }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(body["name"], "Phone");
+    let token = body["token"].as_str().unwrap();
+
+    DavUser {
+        user,
+        token_id: body["id"].as_i64().unwrap(),
+        basic: basic(username, token),
+    }
+}
+
+fn basic(username: &str, token: &str) -> String {
+    format!(
+        "Basic {}",
+        BASE64_STANDARD.encode(format!("{username}:{token}"))
+    )
+}
+
+fn dav<'a>(app: &'a TestApp, user: &DavUser, method: &str, uri: &str) -> TestRequest<'a> {
+    app.request(Method::from_bytes(method.as_bytes()).unwrap(), uri)
+        .header("authorization", &user.basic)
+}
+
+fn text(res: TestResponse) -> String {
+    String::from_utf8(res.body).unwrap()
+}
+
+fn todo(uid: &str, extra: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\nBEGIN:VTODO\r\n\
+         UID:{uid}\r\nDTSTAMP:20261019T100000Z\r\n{extra}END:VTODO\r\nEND:VCALENDAR\r\n"
+    )
+}
+
+#[test]
+fn parse_todo() {
+    let text = "BEGIN:VCALENDAR\r\n\
+        VERSION:2.0\r\n\
+        BEGIN:VTODO\r\n\
+        UID:abc\r\n\
+        SUMMARY;LANGUAGE=en:Milk\\, 2 l\r\n\
+        \x20and cream\r\n\
+        CATEGORIES:Mart,Dairy\\, cold\r\n\
+        CATEGORIES;X-PARAM=\"a:b\":Extra\r\n\
+        COMPLETED:20261019T100000Z\r\n\
+        BEGIN:VALARM\r\n\
+        SUMMARY:Alarm\r\n\
+        END:VALARM\r\n\
+        END:VTODO\r\n\
+        END:VCALENDAR\r\n";
+    assert_eq!(
+        ical::parse(text),
+        Ok(Todo {
+            uid: "abc".to_string(),
+            summary: "Milk, 2 land cream".to_string(),
+            completed: true,
+            categories: Some(vec![
+                "Mart".to_string(),
+                "Dairy, cold".to_string(),
+                "Extra".to_string()
+            ]),
+        })
+    );
+
+    let event = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:x\nEND:VEVENT\nEND:VCALENDAR\n";
+    assert_eq!(ical::parse(event), Err(ical::ParseError::NoTodo));
+    let unbalanced = "BEGIN:VCALENDAR\nBEGIN:VTODO\nUID:x\nEND:VCALENDAR\n";
+    assert!(matches!(
+        ical::parse(unbalanced),
+        Err(ical::ParseError::Invalid(_))
+    ));
+}
+
+#[tokio::test]
+async fn app_tokens_authenticate_dav_requests() {
+    let app = TestApp::new().await;
+    let user = dav_user(&app, "shopper", Role::Shopper).await;
+
+    let tokens: Value = app
+        .get("/api/v1/auth/tokens")
+        .user(&user.user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(tokens[0]["name"], "Phone");
+    assert!(tokens[0]["last_used_at"].is_null());
+
+    // Discovery and capabilities don't need credentials.
+    let res = app
+        .get("/.well-known/caldav")
+        .send()
+        .await
+        .assert_status(StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(res.headers["location"], "/dav/");
+    let res = app
+        .request(Method::OPTIONS, "/dav/")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert!(
+        res.headers["dav"]
+            .to_str()
+            .unwrap()
+            .contains("calendar-access")
+    );
+
+    dav(&app, &user, "PROPFIND", "/dav/principal/")
+        .header("depth", "0")
+        .send()
+        .await
+        .assert_status(StatusCode::MULTI_STATUS);
+    let tokens: Value = app
+        .get("/api/v1/auth/tokens")
+        .user(&user.user)
+        .send()
+        .await
+        .json();
+    assert!(tokens[0]["last_used_at"].is_string());
+
+    // Session cookies and wrong tokens aren't accepted.
+    let res = app
+        .request(Method::from_bytes(b"PROPFIND").unwrap(), "/dav/principal/")
+        .user(&user.user)
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+    assert!(
+        res.headers["www-authenticate"]
+            .to_str()
+            .unwrap()
+            .starts_with("Basic")
+    );
+    let wrong = DavUser {
+        user: TestUser {
+            cookie: user.user.cookie.clone(),
+        },
+        token_id: user.token_id,
+        basic: basic("shopper", "AAAA"),
+    };
+    dav(&app, &wrong, "PROPFIND", "/dav/principal/")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    // Revoked tokens stop working.
+    app.delete(&format!("/api/v1/auth/tokens/{}", user.token_id))
+        .user(&user.user)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    dav(&app, &user, "PROPFIND", "/dav/principal/")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn discover_and_list_items() {
+    let app = TestApp::new().await;
+    let admin = dav_user(&app, "admin", Role::Admin).await;
+    let store = app.create_store(&admin.user, "Mart").await;
+    let dairy = app.create_section(&admin.user, store, "Dairy").await;
+    let milk = app
+        .create_item(&admin.user, Some(store), Some(dairy), "milk & honey")
+        .await;
+
+    let res = dav(&app, &admin, "PROPFIND", "/dav/principal/")
+        .header("depth", "0")
+        .body(
+            "application/xml",
+            r#"<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+                <d:prop><c:calendar-home-set/><d:displayname/><d:unknown/></d:prop>
+            </d:propfind>"#,
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::MULTI_STATUS);
+    let body = text(res);
+    assert!(body.contains("<d:href>/dav/calendars/</d:href>"), "{body}");
+    assert!(body.contains(">admin</displayname>"), "{body}");
+    assert!(body.contains(r#"<unknown xmlns="DAV:"/>"#), "{body}");
+    assert!(body.contains("404 Not Found"), "{body}");
+
+    let res = dav(&app, &admin, "PROPFIND", "/dav/calendars/")
+        .header("depth", "1")
+        .send()
+        .await
+        .assert_status(StatusCode::MULTI_STATUS);
+    let body = text(res);
+    assert!(
+        body.contains("<d:href>/dav/calendars/list/</d:href>"),
+        "{body}"
+    );
+    assert!(body.contains("<c:calendar/>"), "{body}");
+    assert!(body.contains(r#"<c:comp name="VTODO"/>"#), "{body}");
+
+    let res = dav(&app, &admin, "PROPFIND", "/dav/calendars/list/")
+        .header("depth", "1")
+        .body("application/xml", PROP_ETAGS)
+        .send()
+        .await
+        .assert_status(StatusCode::MULTI_STATUS);
+    let before = text(res);
+    let href = format!("<d:href>/dav/calendars/list/lshop-{milk}.ics</d:href>");
+    assert!(before.contains(&href), "{before}");
+
+    // Ctag changes with any item.
+    app.put(&format!("/api/v1/items/{milk}/checked"))
+        .user(&admin.user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let res = dav(&app, &admin, "PROPFIND", "/dav/calendars/list/")
+        .header("depth", "1")
+        .body("application/xml", PROP_ETAGS)
+        .send()
+        .await;
+    let after = text(res);
+    assert!(after.contains(&href), "{after}");
+    let ctag = |body: &str| body.split("getctag").nth(1).unwrap().to_string();
+    assert_ne!(ctag(&before), ctag(&after));
+}
+
+#[tokio::test]
+async fn get_and_report_items() {
+    let app = TestApp::new().await;
+    let admin = dav_user(&app, "admin", Role::Admin).await;
+    let store = app.create_store(&admin.user, "Mart").await;
+    let dairy = app.create_section(&admin.user, store, "Dairy").await;
+    let milk = app
+        .create_item(&admin.user, Some(store), Some(dairy), "milk, 2 l")
+        .await;
+    let soap = app.create_item(&admin.user, None, None, "soap").await;
+
+    let res = dav(
+        &app,
+        &admin,
+        "GET",
+        &format!("/dav/calendars/list/lshop-{milk}.ics"),
+    )
+    .send()
+    .await
+    .assert_status(StatusCode::OK);
+    assert_eq!(res.headers["content-type"], "text/calendar; charset=utf-8");
+    let etag = res.headers["etag"].to_str().unwrap().to_string();
+    let ics = text(res);
+    assert!(ics.contains(&format!("UID:lshop-{milk}\r\n")), "{ics}");
+    assert!(ics.contains("SUMMARY:milk\\, 2 l\r\n"), "{ics}");
+    assert!(ics.contains("STATUS:NEEDS-ACTION\r\n"), "{ics}");
+    assert!(ics.contains("CATEGORIES:Mart,Dairy\r\n"), "{ics}");
+
+    dav(&app, &admin, "GET", "/dav/calendars/list/lshop-999.ics")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    let multiget = format!(
+        r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:prop><d:getetag/><c:calendar-data/></d:prop>
+            <d:href>/dav/calendars/list/lshop-{milk}.ics</d:href>
+            <d:href>https://example.com/dav/calendars/list/missing.ics</d:href>
+        </c:calendar-multiget>"#
+    );
+    let res = dav(&app, &admin, "REPORT", "/dav/calendars/list/")
+        .body("application/xml", &multiget)
+        .send()
+        .await
+        .assert_status(StatusCode::MULTI_STATUS);
+    let body = text(res);
+    assert!(body.contains(&etag.replace('"', "&quot;")), "{body}");
+    assert!(body.contains("SUMMARY:milk\\, 2 l"), "{body}");
+    assert!(!body.contains("SUMMARY:soap"), "{body}");
+    assert!(
+        body.contains("<d:href>https://example.com/dav/calendars/list/missing.ics</d:href><d:status>HTTP/1.1 404 Not Found"),
+        "{body}"
+    );
+
+    let query = |component: &str| {
+        format!(
+            r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+                <d:prop><d:getetag/></d:prop>
+                <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="{component}"/></c:comp-filter></c:filter>
+            </c:calendar-query>"#
+        )
+    };
+    let res = dav(&app, &admin, "REPORT", "/dav/calendars/list/")
+        .body("application/xml", &query("VTODO"))
+        .send()
+        .await
+        .assert_status(StatusCode::MULTI_STATUS);
+    let body = text(res);
+    assert!(body.contains(&format!("lshop-{milk}.ics")), "{body}");
+    assert!(body.contains(&format!("lshop-{soap}.ics")), "{body}");
+    assert!(!body.contains("calendar-data"), "{body}");
+
+    let res = dav(&app, &admin, "REPORT", "/dav/calendars/list/")
+        .body("application/xml", &query("VEVENT"))
+        .send()
+        .await
+        .assert_status(StatusCode::MULTI_STATUS);
+    assert!(!text(res).contains("<d:response>"));
+
+    dav(&app, &admin, "REPORT", "/dav/calendars/list/")
+        .body(
+            "application/xml",
+            r#"<d:sync-collection xmlns:d="DAV:"><d:sync-token/></d:sync-collection>"#,
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn put_creates_and_updates_items() {
+    let app = TestApp::new().await;
+    let admin = dav_user(&app, "admin", Role::Admin).await;
+    let shopper = dav_user(&app, "shopper", Role::Shopper).await;
+    let store = app.create_store(&admin.user, "Mart").await;
+    let dairy = app.create_section(&admin.user, store, "Dairy").await;
+    let uri = "/dav/calendars/list/ABC-1.ics";
+
+    // Shoppers can add items, categories pick the store and the section.
+    dav(&app, &shopper, "PUT", uri)
+        .header("if-none-match", "*")
+        .body(
+            "text/calendar",
+            &todo("ABC-1", "SUMMARY: Milk \r\nCATEGORIES:dairy,MART\r\n"),
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+    let items = app.all_items().await;
+    assert_eq!(items.len(), 1);
+    let item = &items[0];
+    assert_eq!(item.name, "Milk");
+    assert_eq!((item.store_id, item.section_id), (Some(store), Some(dairy)));
+
+    // Item keeps the client's name and UID.
+    let res = dav(&app, &shopper, "GET", uri)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let etag = res.headers["etag"].to_str().unwrap().to_string();
+    assert!(text(res).contains("UID:ABC-1\r\n"));
+    dav(
+        &app,
+        &shopper,
+        "GET",
+        &format!("/dav/calendars/list/lshop-{}.ics", item.id),
+    )
+    .send()
+    .await
+    .assert_status(StatusCode::NOT_FOUND);
+
+    // Changes are rejected if the client hasn't seen the current version.
+    dav(&app, &shopper, "PUT", uri)
+        .header("if-none-match", "*")
+        .body("text/calendar", &todo("ABC-1", "SUMMARY:Milk\r\n"))
+        .send()
+        .await
+        .assert_status(StatusCode::PRECONDITION_FAILED);
+    dav(&app, &shopper, "PUT", uri)
+        .header("if-match", "\"stale\"")
+        .body("text/calendar", &todo("ABC-1", "SUMMARY:Milk\r\n"))
+        .send()
+        .await
+        .assert_status(StatusCode::PRECONDITION_FAILED);
+
+    // Shoppers can check items, but not rename them.
+    dav(&app, &shopper, "PUT", uri)
+        .body("text/calendar", &todo("ABC-1", "SUMMARY:Oat milk\r\n"))
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+    dav(&app, &shopper, "PUT", uri)
+        .header("if-match", &etag)
+        .body(
+            "text/calendar",
+            &todo("ABC-1", "SUMMARY:Milk\r\nSTATUS:COMPLETED\r\n"),
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    assert!(app.all_items().await[0].checked);
+
+    // Without categories the item stays in its section.
+    dav(&app, &admin, "PUT", uri)
+        .body(
+            "text/calendar",
+            &todo("ABC-1", "SUMMARY:Oat milk\r\nSTATUS:NEEDS-ACTION\r\n"),
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    let item = &app.all_items().await[0];
+    assert_eq!(item.name, "Oat milk");
+    assert!(!item.checked);
+    assert_eq!(item.section_id, Some(dairy));
+
+    // Events, changed UIDs and reserved names aren't accepted.
+    let res = dav(&app, &admin, "PUT", "/dav/calendars/list/event.ics")
+        .body(
+            "text/calendar",
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:e\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    assert!(text(res).contains("<c:supported-calendar-component/>"));
+    dav(&app, &admin, "PUT", uri)
+        .body("text/calendar", &todo("XYZ", "SUMMARY:Milk\r\n"))
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    dav(&app, &admin, "PUT", "/dav/calendars/list/lshop-100.ics")
+        .body("text/calendar", &todo("lshop-100", "SUMMARY:Milk\r\n"))
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    dav(&app, &admin, "PUT", "/dav/calendars/list/empty.ics")
+        .body("text/calendar", &todo("empty", "SUMMARY: \r\n"))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    // Only members can delete items.
+    dav(&app, &shopper, "DELETE", uri)
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+    dav(&app, &admin, "DELETE", uri)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    assert!(app.all_items().await.is_empty());
+    dav(&app, &admin, "GET", uri)
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn put_completed_todo_creates_checked_item() {
+    let app = TestApp::new().await;
+    let admin = dav_user(&app, "admin", Role::Admin).await;
+    app.post("/api/v1/webhooks")
+        .user(&admin.user)
+        .json(json!({ "url": "https://example.com/hook", "secret": "0123456789abcdef" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    dav(&app, &admin, "PUT", "/dav/calendars/list/done.ics")
+        .body(
+            "text/calendar",
+            &todo("done", "SUMMARY:Milk\r\nSTATUS:COMPLETED\r\n"),
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+    let items = app.all_items().await;
+    assert_eq!(items.len(), 1);
+    assert!(items[0].checked);
+
+    // Item is created checked, it's never checked off.
+    let events: Vec<String> =
+        sqlx::query_scalar("SELECT event FROM webhook_deliveries ORDER BY id")
+            .fetch_all(&app.state.db)
+            .await
+            .unwrap();
+    assert_eq!(events, ["item.created"]);
+
+    // Taken UIDs don't leave an item behind.
+    dav(&app, &admin, "PUT", "/dav/calendars/list/again.ics")
+        .body("text/calendar", &todo("done", "SUMMARY:Milk\r\n"))
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    assert_eq!(app.all_items().await.len(), 1);
+}
+
+#[tokio::test]
+async fn deleting_items_keeps_order() {
+    let app = TestApp::new().await;
+    let admin = dav_user(&app, "admin", Role::Admin).await;
+    let ids = [
+        app.create_item(&admin.user, None, None, "a").await,
+        app.create_item(&admin.user, None, None, "b").await,
+        app.create_item(&admin.user, None, None, "c").await,
+    ];
+
+    dav(
+        &app,
+        &admin,
+        "DELETE",
+        &format!("/dav/calendars/list/lshop-{}.ics", ids[0]),
+    )
+    .send()
+    .await
+    .assert_status(StatusCode::NO_CONTENT);
+    dav(
+        &app,
+        &admin,
+        "PUT",
+        &format!("/dav/calendars/list/lshop-{}.ics", ids[2]),
+    )
+    .body(
+        "text/calendar",
+        &todo(
+            &format!("lshop-{}", ids[2]),
+            "SUMMARY:c\r\nSTATUS:COMPLETED\r\n",
+        ),
+    )
+    .send()
+    .await
+    .assert_status(StatusCode::NO_CONTENT);
+
+    let items = app.all_items().await;
+    let ords: Vec<_> = items
+        .iter()
+        .map(|i| (i.name.as_str(), i.ord, i.checked))
+        .collect();
+    assert_eq!(ords, [("b", 1, false), ("c", 2, true)]);
+}
@@ -0,0 +1,240 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::store::user::Role;
+use crate::tests::{PASSWORD, TestApp, TestUser};
+
+/// Store with sections in reverse order of creation and items in both sections.
+async fn seed(app: &TestApp, admin: &TestUser) {
+    let store = app.create_store(admin, "Mart").await;
+    let fruit = app.create_section(admin, store, "Fruit").await;
+    let dairy = app.create_section(admin, store, "Dairy").await;
+    app.put(&format!("/api/v1/stores/{store}/sections/reorder"))
+        .user(admin)
+        .json(json!({ "ids": [dairy, fruit] }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    app.create_item(admin, Some(store), Some(dairy), "milk")
+        .await;
+    app.create_item(admin, Some(store), Some(dairy), "cheese")
+        .await;
+    let apple = app
+        .create_item(admin, Some(store), Some(fruit), "apple")
+        .await;
+    app.create_item(admin, None, None, "soap").await;
+    app.put(&format!("/api/v1/items/{apple}/checked"))
+        .user(admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+}
+
+/// Rows that should survive an export and import, without ids.
+async fn rows(app: &TestApp) -> Vec<(Option<String>, Option<String>, String, bool, i64, String)> {
+    sqlx::query_as(
+        "SELECT stores.name, sections.name, items.name, items.checked, items.ord, items.created_at
+         FROM items
+         LEFT JOIN stores ON stores.id = items.store_id
+         LEFT JOIN sections ON sections.id = items.section_id
+         ORDER BY items.checked, stores.name, sections.ord, items.ord",
+    )
+    .fetch_all(&app.state.db)
+    .await
+    .unwrap()
+}
+
+async fn export(app: &TestApp, admin: &TestUser, query: &str) -> Value {
+    app.get(&format!("/api/v1/export{query}"))
+        .user(admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json()
+}
+
+#[tokio::test]
+async fn export_and_import_into_another_instance() {
+    let source = TestApp::new().await;
+    let admin = source.login("ana", Role::Admin).await;
+    source.create_user("ben", Role::Shopper).await;
+    seed(&source, &admin).await;
+
+    let res = source
+        .get("/api/v1/export?password_hashes=true")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert!(
+        res.headers["content-disposition"]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment; filename=\"lshop-")
+    );
+    let dataset: Value = res.json();
+    assert_eq!(dataset["version"], 1);
+    assert_eq!(dataset["users"][1]["username"], "ben");
+    assert_eq!(dataset["users"][1]["role"], "shopper");
+
+    let target = TestApp::new().await;
+    let target_admin = target.login("admin", Role::Admin).await;
+    target.create_store(&target_admin, "Old").await;
+    target.create_item(&target_admin, None, None, "old").await;
+
+    let summary: Value = target
+        .post("/api/v1/import?mode=replace")
+        .user(&target_admin)
+        .json(dataset)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(summary["users_created"], 2);
+    assert_eq!(summary["stores_created"], 1);
+    assert_eq!(summary["sections_created"], 2);
+    assert_eq!(summary["items_created"], 4);
+    assert_eq!(summary["stores_deleted"], 1);
+    assert_eq!(summary["items_deleted"], 1);
+
+    assert_eq!(rows(&target).await, rows(&source).await);
+    let sections: Vec<(String, i64)> =
+        sqlx::query_as("SELECT name, ord FROM sections ORDER BY ord")
+            .fetch_all(&target.state.db)
+            .await
+            .unwrap();
+    assert_eq!(
+        sections,
+        [("Dairy".to_string(), 1), ("Fruit".to_string(), 2)]
+    );
+
+    // Password hashes were exported.
+    target
+        .post("/api/v1/auth/login")
+        .json(json!({ "auth_type": "web", "username": "ben", "password": PASSWORD }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+}
+
+#[tokio::test]
+async fn export_requires_admin_and_omits_hashes() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+
+    app.get("/api/v1/export")
+        .user(&member)
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+    app.post("/api/v1/import")
+        .user(&member)
+        .json(export(&app, &admin, "").await)
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+
+    let dataset = export(&app, &admin, "").await;
+    assert!(dataset["users"][0].get("password_hash").is_none());
+}
+
+#[tokio::test]
+async fn merge_with_dry_run() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    seed(&app, &admin).await;
+    let mut dataset = export(&app, &admin, "").await;
+    // Dataset is from another instance, with a section that doesn't exist here.
+    dataset["sections"][1]["name"] = json!("Produce");
+
+    let before = rows(&app).await;
+    let summary: Value = app
+        .post("/api/v1/import?dry_run=true")
+        .user(&admin)
+        .json(dataset.clone())
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(summary["dry_run"], true);
+    assert_eq!(summary["users_existing"], 1);
+    assert_eq!(summary["stores_merged"], 1);
+    assert_eq!(summary["sections_merged"], 1);
+    assert_eq!(summary["sections_created"], 1);
+    assert_eq!(summary["items_created"], 4);
+    assert_eq!(rows(&app).await, before);
+
+    app.post("/api/v1/import")
+        .user(&admin)
+        .json(dataset)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    // Imported items are added after the existing ones, new section after existing sections.
+    let dairy: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT items.name, items.ord FROM items
+         JOIN sections ON sections.id = items.section_id
+         WHERE sections.name = 'Dairy'
+         ORDER BY items.ord",
+    )
+    .fetch_all(&app.state.db)
+    .await
+    .unwrap();
+    let names: Vec<_> = dairy.iter().map(|(name, _)| name.as_str()).collect();
+    let ords: Vec<_> = dairy.iter().map(|(_, ord)| *ord).collect();
+    assert_eq!(names, ["milk", "cheese", "milk", "cheese"]);
+    assert_eq!(ords, [1, 2, 3, 4]);
+
+    let sections: Vec<String> = sqlx::query_scalar("SELECT name FROM sections ORDER BY ord")
+        .fetch_all(&app.state.db)
+        .await
+        .unwrap();
+    assert_eq!(sections, ["Dairy", "Fruit", "Produce"]);
+}
+
+#[tokio::test]
+async fn invalid_documents_are_rejected() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    seed(&app, &admin).await;
+    let dataset = export(&app, &admin, "").await;
+
+    let mut newer = dataset.clone();
+    newer["version"] = json!(2);
+    let res = app
+        .post("/api/v1/import")
+        .user(&admin)
+        .json(newer)
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    assert_eq!(res.json::<Value>()["errors"][0]["field"], "version");
+
+    let mut broken = dataset;
+    broken["sections"][0]["store_id"] = json!(999);
+    let res = app
+        .post("/api/v1/import")
+        .user(&admin)
+        .json(broken)
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let errors: Vec<Value> = res.json::<Value>()["errors"].as_array().unwrap().clone();
+    let fields: Vec<_> = errors
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    // Items of the section are now in another store than the section.
+    assert_eq!(
+        fields,
+        [
+            "sections[0].store_id",
+            "items[1].section_id",
+            "items[2].section_id"
+        ]
+    );
+    assert_eq!(errors[1]["code"], "section_store_mismatch");
+}
@@ -0,0 +1,133 @@
+use axum::http::{StatusCode, header};
+
+use crate::tests::{TestApp, test_config};
+
+const INDEX: &str = "<!doctype html><html><body><div id=\"root\"></div></body></html>";
+
+async fn app_with_frontend() -> (TestApp, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("assets")).unwrap();
+    std::fs::write(dir.path().join("index.html"), INDEX).unwrap();
+    std::fs::write(
+        dir.path().join("assets/index-abc123.js"),
+        "console.log('hello');\n".repeat(100),
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("manifest.json"), "{}").unwrap();
+    std::fs::write(dir.path().join("icon-192.png"), [0x89, b'P', b'N', b'G']).unwrap();
+
+    let mut config = test_config();
+    config.frontend_dir = Some(dir.path().to_string_lossy().into_owned());
+    (TestApp::with_config(config).await, dir)
+}
+
+#[tokio::test]
+async fn serves_files_with_cache_policy() {
+    let (app, _dir) = app_with_frontend().await;
+
+    let res = app.get("/").send().await.assert_status(StatusCode::OK);
+    assert_eq!(res.headers[header::CONTENT_TYPE], "text/html");
+    assert_eq!(res.headers[header::CACHE_CONTROL], "no-cache");
+    assert_eq!(res.body, INDEX.as_bytes());
+
+    let res = app
+        .get("/assets/index-abc123.js")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(
+        res.headers[header::CACHE_CONTROL],
+        "public, max-age=31536000, immutable"
+    );
+
+    let res = app.get("/manifest.json").send().await;
+    assert_eq!(res.headers[header::CACHE_CONTROL], "public, max-age=3600");
+
+    let res = app.get("/icon-192.png").send().await;
+    assert_eq!(res.headers[header::CACHE_CONTROL], "public, max-age=604800");
+}
+
+#[tokio::test]
+async fn client_routes_fall_back_to_index() {
+    let (app, _dir) = app_with_frontend().await;
+
+    let res = app
+        .get("/stores/3")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.body, INDEX.as_bytes());
+    assert_eq!(res.headers[header::CACHE_CONTROL], "no-cache");
+
+    // Missing scripts and api routes are not replaced with the page.
+    app.get("/assets/missing.js")
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    app.get("/api/v1/missing")
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn serves_precompressed_content() {
+    let (app, _dir) = app_with_frontend().await;
+    let path = "/assets/index-abc123.js";
+
+    let res = app
+        .get(path)
+        .header("accept-encoding", "gzip, deflate, br, zstd")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.headers[header::CONTENT_ENCODING], "zstd");
+    assert_eq!(res.headers[header::VARY], "accept-encoding");
+    let body = zstd::decode_all(res.body.as_slice()).unwrap();
+    assert_eq!(body, "console.log('hello');\n".repeat(100).as_bytes());
+
+    let res = app
+        .get(path)
+        .header("accept-encoding", "gzip, zstd;q=0")
+        .send()
+        .await;
+    assert_eq!(res.headers[header::CONTENT_ENCODING], "gzip");
+
+    let res = app.get(path).send().await;
+    assert!(res.headers.get(header::CONTENT_ENCODING).is_none());
+
+    // Not worth compressing
+    let res = app
+        .get("/icon-192.png")
+        .header("accept-encoding", "gzip")
+        .send()
+        .await;
+    assert!(res.headers.get(header::CONTENT_ENCODING).is_none());
+}
+
+#[tokio::test]
+async fn not_modified_when_etag_matches() {
+    let (app, _dir) = app_with_frontend().await;
+
+    let res = app.get("/").send().await.assert_status(StatusCode::OK);
+    let etag = res.headers[header::ETAG].to_str().unwrap().to_string();
+
+    let res = app
+        .get("/")
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_MODIFIED);
+    assert!(res.body.is_empty());
+}
+
+// With the feature, the embedded frontend is served instead.
+#[cfg(not(feature = "embed-frontend"))]
+#[tokio::test]
+async fn no_frontend_configured() {
+    let app = TestApp::new().await;
+    app.get("/")
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+}
@@ -0,0 +1,80 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::tests::TestApp;
+
+#[tokio::test]
+async fn health_without_session() {
+    let app = TestApp::new().await;
+
+    let res = app
+        .get("/api/health")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Value>(), json!({ "status": "ok" }));
+}
+
+#[tokio::test]
+async fn ready_when_all_checks_pass() {
+    let app = TestApp::new().await;
+
+    let res = app
+        .get("/api/ready")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(
+        res.json::<Value>(),
+        json!({
+            "ready": true,
+            "checks": { "database": "ok", "migrations": "ok", "llm": "ok" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn not_ready_without_llm() {
+    let app = TestApp::new().await;
+    app.llm.set_configured(false);
+
+    let res = app
+        .get("/api/ready")
+        .send()
+        .await
+        .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = res.json();
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["checks"]["llm"], "not_configured");
+}
+
+#[tokio::test]
+async fn not_ready_with_pending_migrations() {
+    let app = TestApp::new().await;
+    sqlx::query(
+        "DELETE FROM _sqlx_migrations WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)",
+    )
+    .execute(&app.state.db)
+    .await
+    .unwrap();
+
+    let res = app
+        .get("/api/ready")
+        .send()
+        .await
+        .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.json::<Value>()["checks"]["migrations"], "pending");
+}
+
+#[tokio::test]
+async fn not_ready_without_database() {
+    let app = TestApp::new().await;
+    app.state.db.close().await;
+
+    let res = app
+        .get("/api/ready")
+        .send()
+        .await
+        .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.json::<Value>()["checks"]["database"], "error");
+}
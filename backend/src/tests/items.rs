@@ -0,0 +1,236 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+fn ids(items: &Value) -> Vec<i64> {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|it| it["id"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn create_and_list_grouped() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let fruit = app.create_section(&admin, store_id, "Fruit").await;
+
+    let soap = app.create_item(&admin, None, None, "soap").await;
+    let bread = app.create_item(&admin, Some(store_id), None, "bread").await;
+    // Store is inferred from the section.
+    let apple = app.create_item(&admin, None, Some(fruit), "apple").await;
+
+    let res = app
+        .get("/api/v1/items")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let list: Value = res.json();
+    assert_eq!(ids(&list["unassigned"]), [soap]);
+    assert_eq!(list["stores"][0]["id"], store_id);
+    assert_eq!(ids(&list["stores"][0]["unassigned"]), [bread]);
+    assert_eq!(list["stores"][0]["sections"][0]["id"], fruit);
+    assert_eq!(ids(&list["stores"][0]["sections"][0]["items"]), [apple]);
+}
+
+#[tokio::test]
+async fn create_validates_references() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let other_id = app.create_store(&admin, "Other").await;
+    let fruit = app.create_section(&admin, store_id, "Fruit").await;
+
+    let cases = [
+        (
+            json!({ "store_id": 42, "name": "x" }),
+            StatusCode::NOT_FOUND,
+            "store_not_found",
+        ),
+        (
+            json!({ "section_id": 42, "name": "x" }),
+            StatusCode::NOT_FOUND,
+            "section_not_found",
+        ),
+        (
+            json!({ "store_id": other_id, "section_id": fruit, "name": "x" }),
+            StatusCode::CONFLICT,
+            "section_store_mismatch",
+        ),
+        (
+            json!({ "name": "x".repeat(201) }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "validation_failed",
+        ),
+        (
+            json!({ "store_id": 1 }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "malformed_body",
+        ),
+    ];
+    for (body, status, code) in cases {
+        app.post("/api/v1/items")
+            .user(&admin)
+            .json(body)
+            .send()
+            .await
+            .assert_problem(status, code);
+    }
+
+    assert!(app.all_items().await.is_empty());
+}
+
+#[tokio::test]
+async fn rename() {
+    let app = TestApp::new().await;
+    let member = app.login("member", Role::Member).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let id = app.create_item(&member, None, None, "soap").await;
+
+    let res = app
+        .put(&format!("/api/v1/items/{id}/rename"))
+        .user(&member)
+        .json(json!({ "name": " hand  soap " }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Value>()["name"], "hand soap");
+
+    app.put(&format!("/api/v1/items/{id}/rename"))
+        .user(&shopper)
+        .json(json!({ "name": "soap" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+    app.put("/api/v1/items/42/rename")
+        .user(&member)
+        .json(json!({ "name": "soap" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "item_not_found");
+    app.put(&format!("/api/v1/items/{id}/rename"))
+        .user(&member)
+        .json(json!({ "name": "" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+}
+
+#[tokio::test]
+async fn set_checked_hides_item() {
+    let app = TestApp::new().await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let soap = app.create_item(&shopper, None, None, "soap").await;
+    let milk = app.create_item(&shopper, None, None, "milk").await;
+
+    let res = app
+        .put(&format!("/api/v1/items/{soap}/checked"))
+        .user(&shopper)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Value>()["checked"], true);
+
+    let list: Value = app.get("/api/v1/items").user(&shopper).send().await.json();
+    assert_eq!(ids(&list["unassigned"]), [milk]);
+
+    app.put("/api/v1/items/42/checked")
+        .user(&shopper)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "item_not_found");
+}
+
+#[tokio::test]
+async fn move_item() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let fruit = app.create_section(&admin, store_id, "Fruit").await;
+
+    let apple = app.create_item(&admin, None, Some(fruit), "apple").await;
+    let pear = app.create_item(&admin, None, Some(fruit), "pear").await;
+    let plum = app.create_item(&admin, None, None, "plum").await;
+
+    let res = app
+        .put(&format!("/api/v1/items/{plum}/move"))
+        .user(&admin)
+        .json(json!({ "store_id": store_id, "section_id": fruit, "index": 1 }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Value>()["section_id"], fruit);
+
+    let list: Value = app.get("/api/v1/items").user(&admin).send().await.json();
+    assert_eq!(
+        ids(&list["stores"][0]["sections"][0]["items"]),
+        [apple, plum, pear]
+    );
+}
+
+#[tokio::test]
+async fn move_validates_references() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let other_id = app.create_store(&admin, "Other").await;
+    let fruit = app.create_section(&admin, store_id, "Fruit").await;
+    let id = app.create_item(&admin, None, None, "apple").await;
+
+    let uri = format!("/api/v1/items/{id}/move");
+    let cases = [
+        (
+            json!({ "store_id": other_id, "section_id": fruit, "index": 0 }),
+            StatusCode::CONFLICT,
+            "section_store_mismatch",
+        ),
+        (
+            json!({ "section_id": fruit, "index": 0 }),
+            StatusCode::CONFLICT,
+            "section_store_mismatch",
+        ),
+        (
+            json!({ "store_id": 42, "index": 0 }),
+            StatusCode::NOT_FOUND,
+            "store_not_found",
+        ),
+        (
+            json!({ "store_id": store_id, "section_id": 42, "index": 0 }),
+            StatusCode::NOT_FOUND,
+            "section_not_found",
+        ),
+        (
+            json!({ "index": -1 }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "validation_failed",
+        ),
+    ];
+    for (body, status, code) in cases {
+        app.put(&uri)
+            .user(&admin)
+            .json(body)
+            .send()
+            .await
+            .assert_problem(status, code);
+    }
+
+    app.put(&uri)
+        .user(&shopper)
+        .json(json!({ "index": 0 }))
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+    app.put("/api/v1/items/42/move")
+        .user(&admin)
+        .json(json!({ "index": 0 }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "item_not_found");
+}
@@ -0,0 +1,198 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+fn section_names(sections: &Value) -> Vec<&str> {
+    sections
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn export_as_json_and_toml() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let store = app.create_store(&admin, "Big Mart").await;
+    app.create_section(&admin, store, "Fruit").await;
+    app.create_section(&admin, store, "Dairy").await;
+
+    let res = app
+        .get(&format!("/api/v1/stores/{store}/layout"))
+        .user(&shopper)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(
+        res.headers["content-disposition"],
+        "attachment; filename=\"big-mart.json\""
+    );
+    assert_eq!(
+        res.json::<Value>(),
+        json!({ "version": 1, "name": "Big Mart", "sections": ["Fruit", "Dairy"] })
+    );
+
+    let res = app
+        .get(&format!("/api/v1/stores/{store}/layout?format=toml"))
+        .user(&shopper)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.headers["content-type"], "application/toml");
+    let body = String::from_utf8(res.body).unwrap();
+    assert!(body.contains("sections = ["), "{body}");
+
+    app.get("/api/v1/stores/42/layout")
+        .user(&shopper)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "store_not_found");
+}
+
+#[tokio::test]
+async fn import_as_new_store() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+    let layout = r#"
+        version = 1
+        name = "Mart"
+        sections = ["Fruit", " Frozen  food ", "Dairy"]
+    "#;
+
+    app.post("/api/v1/stores/import")
+        .user(&member)
+        .body("application/toml", layout)
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+
+    let res = app
+        .post("/api/v1/stores/import")
+        .user(&admin)
+        .body("application/toml", layout)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let store: Value = res.json();
+    assert_eq!(store["name"], "Mart");
+
+    let sections: Value = app
+        .get(&format!("/api/v1/stores/{}/sections", store["id"]))
+        .user(&admin)
+        .send()
+        .await
+        .json();
+    assert_eq!(section_names(&sections), ["Fruit", "Frozen food", "Dairy"]);
+}
+
+#[tokio::test]
+async fn apply_keeps_items_of_matching_sections() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Mart").await;
+    let dairy = app.create_section(&admin, store, "Dairy").await;
+    let fruit = app.create_section(&admin, store, "Fruit").await;
+    let bakery = app.create_section(&admin, store, "Bakery").await;
+    let milk = app
+        .create_item(&admin, Some(store), Some(dairy), "milk")
+        .await;
+    let apple = app
+        .create_item(&admin, Some(store), Some(fruit), "apple")
+        .await;
+    let bread = app
+        .create_item(&admin, Some(store), Some(bakery), "bread")
+        .await;
+
+    let res = app
+        .put(&format!("/api/v1/stores/{store}/layout"))
+        .user(&admin)
+        .json(json!({ "version": 1, "name": "Other", "sections": ["fruit", "Frozen", "Dairy"] }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let sections: Value = res.json();
+    assert_eq!(section_names(&sections), ["fruit", "Frozen", "Dairy"]);
+    assert_eq!(sections[0]["id"], fruit);
+    assert_eq!(sections[2]["id"], dairy);
+
+    let items = app.all_items().await;
+    let section_of = |id: i64| items.iter().find(|i| i.id == id).unwrap().section_id;
+    assert_eq!(section_of(milk), Some(dairy));
+    assert_eq!(section_of(apple), Some(fruit));
+    // Section was deleted, the item stays in the store.
+    assert_eq!(section_of(bread), None);
+    assert_eq!(
+        items.iter().find(|i| i.id == bread).unwrap().store_id,
+        Some(store)
+    );
+
+    // Name of the store isn't changed.
+    let stores: Value = app.get("/api/v1/stores").user(&admin).send().await.json();
+    assert_eq!(stores[0]["name"], "Mart");
+}
+
+#[tokio::test]
+async fn invalid_layouts_are_rejected() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Mart").await;
+
+    let res = app
+        .put(&format!("/api/v1/stores/{store}/layout"))
+        .user(&admin)
+        .json(json!({ "version": 2, "name": "Mart", "sections": ["Dairy", " dairy"] }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let errors = res.json::<Value>()["errors"].clone();
+    assert_eq!(errors[0]["field"], "version");
+    assert_eq!(errors[1]["field"], "sections[1]");
+    assert_eq!(errors[1]["code"], "duplicate");
+
+    app.post("/api/v1/stores/import")
+        .user(&admin)
+        .body("application/toml", "version = ")
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "malformed_body");
+
+    app.put("/api/v1/stores/42/layout")
+        .user(&admin)
+        .json(json!({ "version": 1, "name": "Mart", "sections": [] }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "store_not_found");
+}
+
+#[tokio::test]
+async fn only_json_and_toml_layouts_are_accepted() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let layout = r#"{ "version": 1, "name": "Mart", "sections": [] }"#;
+
+    app.post("/api/v1/stores/import")
+        .user(&admin)
+        .body("text/plain", layout)
+        .send()
+        .await
+        .assert_problem(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type");
+
+    app.post("/api/v1/stores/import")
+        .user(&admin)
+        .send()
+        .await
+        .assert_problem(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type");
+
+    app.post("/api/v1/stores/import")
+        .user(&admin)
+        .body("application/json; charset=utf-8", layout)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+}
@@ -0,0 +1,181 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::config::MetricsConfig;
+use crate::llm::Categorization;
+use crate::store::user::Role;
+use crate::tests::{TestApp, TestResponse, test_config};
+
+const TOKEN: &str = "secret";
+
+async fn app_with_metrics() -> TestApp {
+    let mut config = test_config();
+    config.metrics = Some(MetricsConfig {
+        token: Some(TOKEN.to_string()),
+        address: None,
+    });
+    TestApp::with_config(config).await
+}
+
+async fn scrape(app: &TestApp) -> String {
+    let res: TestResponse = app
+        .get("/metrics")
+        .header("authorization", &format!("Bearer {TOKEN}"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    String::from_utf8(res.body).unwrap()
+}
+
+#[tokio::test]
+async fn metrics_require_token() {
+    let app = app_with_metrics().await;
+
+    app.get("/metrics")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+    app.get("/metrics")
+        .header("authorization", "Bearer wrong")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    let res = app
+        .get("/metrics")
+        .header("authorization", &format!("Bearer {TOKEN}"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert!(
+        res.headers["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("application/openmetrics-text")
+    );
+}
+
+#[tokio::test]
+async fn metrics_capability() {
+    let app = app_with_metrics().await;
+    let body: Value = app.get("/api/versions").send().await.json();
+    assert!(
+        body["capabilities"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("metrics"))
+    );
+}
+
+#[tokio::test]
+async fn metrics_not_served_by_default() {
+    let app = TestApp::new().await;
+    app.get("/metrics")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    // Served only on the separate address.
+    let mut config = test_config();
+    config.metrics = Some(MetricsConfig {
+        token: None,
+        address: Some("127.0.0.1:0".to_string()),
+    });
+    let app = TestApp::with_config(config).await;
+    app.get("/metrics")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn records_requests_by_route() {
+    let app = app_with_metrics().await;
+    let user = app.login("ana", Role::Member).await;
+
+    app.get("/api/v1/items").user(&user).send().await;
+    app.get("/api/v1/items").user(&user).send().await;
+    app.get("/api/v1/items").send().await;
+
+    let metrics = scrape(&app).await;
+    assert!(metrics.contains(
+        r#"lshop_http_requests_total{method="GET",route="/api/v1/items",status="200"} 2"#
+    ));
+    assert!(metrics.contains(
+        r#"lshop_http_requests_total{method="GET",route="/api/v1/items",status="401"} 1"#
+    ));
+    assert!(metrics.contains(r#"lshop_http_problems_total{code="missing_credentials"} 1"#));
+    assert!(metrics.contains(
+        r#"lshop_http_request_duration_seconds_count{method="GET",route="/api/v1/items"} 3"#
+    ));
+    assert!(metrics.contains("lshop_db_connections 1"));
+}
+
+#[tokio::test]
+async fn records_db_errors() {
+    let app = app_with_metrics().await;
+    let user = app.login("ana", Role::Member).await;
+    sqlx::query("DROP TABLE items")
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+
+    app.get("/api/v1/items")
+        .user(&user)
+        .send()
+        .await
+        .assert_problem(StatusCode::INTERNAL_SERVER_ERROR, "internal");
+
+    let metrics = scrape(&app).await;
+    assert!(metrics.contains(r#"lshop_db_errors_total{kind="other"} 1"#));
+}
+
+#[tokio::test]
+async fn records_organize_calls() {
+    let app = app_with_metrics().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let fruit = app.create_section(&admin, store_id, "fruit").await;
+    let apple = app.create_item(&admin, Some(store_id), None, "apple").await;
+
+    app.llm.set(move |_, _| {
+        Ok(vec![
+            Categorization {
+                section_id: fruit,
+                item_id: apple,
+            },
+            Categorization {
+                section_id: fruit,
+                item_id: apple,
+            },
+            Categorization {
+                section_id: 42,
+                item_id: apple,
+            },
+            Categorization {
+                section_id: fruit,
+                item_id: 42,
+            },
+        ])
+    });
+
+    app.post(&format!("/api/v1/stores/{store_id}/organize"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let metrics = scrape(&app).await;
+    assert!(metrics.contains(r#"lshop_organize_calls_total{outcome="success"} 1"#));
+    assert!(metrics.contains(r#"lshop_organize_duration_seconds_count{outcome="success"} 1"#));
+    assert!(metrics.contains(r#"lshop_llm_tokens_total{kind="input"} 100"#));
+    assert!(metrics.contains(r#"lshop_llm_tokens_total{kind="output"} 10"#));
+    for reason in ["duplicate_item", "unknown_section", "unknown_item"] {
+        assert!(
+            metrics.contains(&format!(
+                r#"lshop_organize_rejected_assignments_total{{reason="{reason}"}} 1"#
+            )),
+            "{reason} should be counted"
+        );
+    }
+}
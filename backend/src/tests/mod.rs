@@ -0,0 +1,437 @@
+//! Test harness for the HTTP api.
+//!
+//! Every test gets its own app with an in-memory database and a stub language model.
+//! Requests are sent directly to the router, without binding a socket.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, Method, Request, StatusCode, header};
+use password_hash::rand_core::OsRng;
+use password_hash::{PasswordHasher, SaltString};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::app::create_app;
+use crate::config::{Config, Environment};
+use crate::llm::{
+    Categorization, Categorized, Llm, LlmError, LlmFuture, ParsedCommand, TokenUsage,
+};
+use crate::state::AppState;
+use crate::store::{self, item::Item, section::Section, user::Role};
+
+mod auth;
+mod backup;
+mod caldav;
+mod dataset;
+mod frontend;
+mod health;
+mod items;
+mod layout;
+mod metrics;
+mod notifications;
+mod oidc;
+mod openapi;
+mod ordering;
+mod organize;
+mod print;
+mod push;
+mod sections;
+mod shares;
+mod stores;
+mod text;
+mod tls;
+mod versions;
+mod voice;
+mod webauthn;
+mod webhooks;
+
+pub const PASSWORD: &str = "password";
+
+type CategorizeFn =
+    dyn Fn(&[Item], &[Section]) -> Result<Vec<Categorization>, LlmError> + Send + Sync;
+type ParseFn = dyn Fn(&str, &[String]) -> Result<ParsedCommand, LlmError> + Send + Sync;
+
+/// Usage reported by the stub for every request.
+pub const STUB_USAGE: TokenUsage = TokenUsage {
+    input: 100,
+    output: 10,
+};
+
+/// Language model that answers with a configurable function instead of calling the api.
+pub struct StubLlm {
+    categorize: Mutex<Arc<CategorizeFn>>,
+    parse: Mutex<Arc<ParseFn>>,
+    configured: AtomicBool,
+}
+
+impl StubLlm {
+    /// By default each item is assigned to the section with the longest name that
+    /// is contained in the item name, and commands are parsed with the rules.
+    fn new() -> Self {
+        Self {
+            categorize: Mutex::new(Arc::new(|items, sections| {
+                Ok(items
+                    .iter()
+                    .filter_map(|item| {
+                        sections
+                            .iter()
+                            .filter(|sec| item.name.contains(&sec.name))
+                            .max_by_key(|sec| sec.name.len())
+                            .map(|sec| Categorization {
+                                section_id: sec.id,
+                                item_id: item.id,
+                            })
+                    })
+                    .collect())
+            })),
+            parse: Mutex::new(Arc::new(|text, stores| {
+                Ok(crate::handler::voice::parse_command(text, stores))
+            })),
+            configured: AtomicBool::new(true),
+        }
+    }
+
+    pub fn set(
+        &self,
+        f: impl Fn(&[Item], &[Section]) -> Result<Vec<Categorization>, LlmError> + Send + Sync + 'static,
+    ) {
+        *self.categorize.lock().unwrap() = Arc::new(f);
+    }
+
+    pub fn set_parse(
+        &self,
+        f: impl Fn(&str, &[String]) -> Result<ParsedCommand, LlmError> + Send + Sync + 'static,
+    ) {
+        *self.parse.lock().unwrap() = Arc::new(f);
+    }
+
+    pub fn set_configured(&self, configured: bool) {
+        self.configured.store(configured, Ordering::Relaxed);
+    }
+}
+
+impl Llm for StubLlm {
+    fn categorize<'a>(
+        &'a self,
+        items: Vec<Item>,
+        sections: Vec<Section>,
+    ) -> LlmFuture<'a, Categorized> {
+        let f = self.categorize.lock().unwrap().clone();
+        Box::pin(async move {
+            Ok(Categorized {
+                categorizations: f(&items, &sections)?,
+                usage: Some(STUB_USAGE),
+            })
+        })
+    }
+
+    fn parse_command<'a>(
+        &'a self,
+        text: String,
+        stores: Vec<String>,
+    ) -> LlmFuture<'a, ParsedCommand> {
+        let f = self.parse.lock().unwrap().clone();
+        Box::pin(async move {
+            Ok(ParsedCommand {
+                usage: Some(STUB_USAGE),
+                ..f(&text, &stores)?
+            })
+        })
+    }
+
+    fn is_configured(&self) -> bool {
+        self.configured.load(Ordering::Relaxed)
+    }
+}
+
+pub fn test_config() -> Config {
+    Config {
+        environment: Environment::Dev,
+        address: "127.0.0.1".to_string(),
+        port: 0,
+        shutdown_timeout: 1,
+        db_path: "sqlite::memory:".to_string(),
+        openai_api_key: String::new(),
+        oidc: None,
+        proxy_auth: None,
+        webauthn: None,
+        frontend_dir: None,
+        tls: None,
+        metrics: None,
+        backup: None,
+        web_push: None,
+        smtp: None,
+        ntfy: None,
+    }
+}
+
+pub struct TestApp {
+    pub state: AppState,
+    pub llm: Arc<StubLlm>,
+    router: Router,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_config(test_config()).await
+    }
+
+    pub async fn with_config(config: Config) -> Self {
+        let db = crate::db::connect_in_memory()
+            .await
+            .expect("in-memory database should open");
+        let llm = Arc::new(StubLlm::new());
+        let mut state =
+            AppState::new(db, config, llm.clone()).expect("test config should be valid");
+        state.load_frontend().expect("test frontend should load");
+
+        Self {
+            router: create_app(state.clone()),
+            state,
+            llm,
+        }
+    }
+
+    /// Creates a user with password [`PASSWORD`].
+    pub async fn create_user(&self, username: &str, role: Role) -> i64 {
+        // Minimal argon2 parameters, default ones are slow in debug builds.
+        let params = Params::new(8, 1, 1, None).unwrap();
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = argon2.hash_password(PASSWORD.as_bytes(), &salt).unwrap();
+
+        store::user::create_user(&self.state.db, username, Some(&hash), role)
+            .await
+            .expect("user should be created")
+    }
+
+    /// Creates a user and logs them in through the login endpoint.
+    pub async fn login(&self, username: &str, role: Role) -> TestUser {
+        self.create_user(username, role).await;
+
+        let res = self
+            .post("/api/v1/auth/login")
+            .json(serde_json::json!({
+                "auth_type": "web",
+                "username": username,
+                "password": PASSWORD,
+            }))
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+
+        let cookie = res
+            .cookie("session")
+            .expect("login should set session cookie");
+        TestUser { cookie }
+    }
+
+    pub fn get(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::GET, uri)
+    }
+
+    pub fn post(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::POST, uri)
+    }
+
+    pub fn put(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::PUT, uri)
+    }
+
+    pub fn delete(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::DELETE, uri)
+    }
+
+    pub fn request(&self, method: Method, uri: &str) -> TestRequest<'_> {
+        TestRequest {
+            app: self,
+            builder: Request::builder().method(method).uri(uri),
+            body: Body::empty(),
+        }
+    }
+}
+
+pub struct TestUser {
+    pub cookie: String,
+}
+
+pub struct TestRequest<'a> {
+    app: &'a TestApp,
+    builder: axum::http::request::Builder,
+    body: Body,
+}
+
+impl TestRequest<'_> {
+    pub fn user(mut self, user: &TestUser) -> Self {
+        self.builder = self
+            .builder
+            .header(header::COOKIE, format!("session={}", user.cookie));
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.builder = self.builder.header(name, value);
+        self
+    }
+
+    /// Sets address of the connecting peer, as seen by the proxy authentication.
+    pub fn peer(mut self, addr: SocketAddr) -> Self {
+        self.builder = self.builder.extension(ConnectInfo(addr));
+        self
+    }
+
+    pub fn json(mut self, body: Value) -> Self {
+        self.builder = self
+            .builder
+            .header(header::CONTENT_TYPE, "application/json");
+        self.body = Body::from(body.to_string());
+        self
+    }
+
+    pub fn raw_json(mut self, body: &str) -> Self {
+        self.builder = self
+            .builder
+            .header(header::CONTENT_TYPE, "application/json");
+        self.body = Body::from(body.to_string());
+        self
+    }
+
+    pub fn body(mut self, content_type: &str, body: &str) -> Self {
+        self.builder = self.builder.header(header::CONTENT_TYPE, content_type);
+        self.body = Body::from(body.to_string());
+        self
+    }
+
+    pub async fn send(self) -> TestResponse {
+        let req = self.builder.body(self.body).expect("request should build");
+        let res = self
+            .app
+            .router
+            .clone()
+            .oneshot(req)
+            .await
+            .expect("router is infallible");
+
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body should be readable")
+            .to_vec();
+
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    #[track_caller]
+    pub fn assert_status(self, status: StatusCode) -> Self {
+        assert_eq!(
+            self.status,
+            status,
+            "unexpected status, body: {}",
+            String::from_utf8_lossy(&self.body)
+        );
+        self
+    }
+
+    /// Asserts the response is a problem with the given code.
+    #[track_caller]
+    pub fn assert_problem(self, status: StatusCode, code: &str) -> Self {
+        let res = self.assert_status(status);
+        assert_eq!(
+            res.headers[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        assert_eq!(res.json::<Value>()["code"], code);
+        res
+    }
+
+    #[track_caller]
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).expect("body should be valid json")
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| v.split(';').next())
+            .filter_map(|v| v.split_once('='))
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.to_string())
+    }
+}
+
+/// Helpers for creating data through the api.
+impl TestApp {
+    pub async fn create_store(&self, user: &TestUser, name: &str) -> i64 {
+        let res = self
+            .post("/api/v1/stores")
+            .user(user)
+            .json(serde_json::json!({ "name": name }))
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+        res.json::<Value>()["id"].as_i64().unwrap()
+    }
+
+    pub async fn create_section(&self, user: &TestUser, store_id: i64, name: &str) -> i64 {
+        let res = self
+            .post(&format!("/api/v1/stores/{store_id}/sections"))
+            .user(user)
+            .json(serde_json::json!({ "name": name }))
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+        res.json::<Value>()["id"].as_i64().unwrap()
+    }
+
+    pub async fn create_item(
+        &self,
+        user: &TestUser,
+        store_id: Option<i64>,
+        section_id: Option<i64>,
+        name: &str,
+    ) -> i64 {
+        let res = self
+            .post("/api/v1/items")
+            .user(user)
+            .json(serde_json::json!({
+                "store_id": store_id,
+                "section_id": section_id,
+                "name": name,
+            }))
+            .send()
+            .await
+            .assert_status(StatusCode::CREATED);
+        res.json::<Value>()["id"].as_i64().unwrap()
+    }
+
+    /// Returns all rows of the items table, including the checked items.
+    pub async fn all_items(&self) -> Vec<Item> {
+        sqlx::query_as("SELECT * FROM items ORDER BY id")
+            .fetch_all(&self.state.db)
+            .await
+            .unwrap()
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use axum::Router;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use jiff::tz::TimeZone;
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use time::macros::datetime;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::config::{Config, NtfyConfig, SmtpConfig, SmtpSecurity};
+use crate::notify::{self, cron::Cron};
+use crate::store::user::Role;
+use crate::tests::{TestApp, TestUser, test_config};
+
+/// SMTP server that accepts every message and keeps its data.
+struct SmtpSink {
+    port: u16,
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl SmtpSink {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+
+        let received = messages.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(Self::session(stream, received.clone()));
+            }
+        });
+
+        Self { port, messages }
+    }
+
+    async fn session(stream: tokio::net::TcpStream, messages: Arc<Mutex<Vec<String>>>) {
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let command = line.to_ascii_uppercase();
+            let reply: &[u8] = if command.starts_with("DATA") {
+                write.write_all(b"354 go ahead\r\n").await.unwrap();
+                let mut data = String::new();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "." {
+                        break;
+                    }
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                messages.lock().unwrap().push(data);
+                b"250 queued\r\n"
+            } else if command.starts_with("QUIT") {
+                write.write_all(b"221 bye\r\n").await.unwrap();
+                return;
+            } else {
+                b"250 ok\r\n"
+            };
+            write.write_all(reply).await.unwrap();
+        }
+    }
+}
+
+/// ntfy server that records the published messages and answers with the queued
+/// status codes, 200 once the queue is empty.
+struct Ntfy {
+    url: String,
+    messages: Arc<Mutex<Vec<(HeaderMap, Value)>>>,
+    statuses: Arc<Mutex<VecDeque<StatusCode>>>,
+}
+
+impl Ntfy {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(VecDeque::new()));
+
+        let (recorded, queued) = (messages.clone(), statuses.clone());
+        let router = Router::new().route(
+            "/",
+            post(
+                move |headers: HeaderMap, axum::Json(body): axum::Json<Value>| async move {
+                    recorded.lock().unwrap().push((headers, body));
+                    queued.lock().unwrap().pop_front().unwrap_or(StatusCode::OK)
+                },
+            ),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self {
+            url,
+            messages,
+            statuses,
+        }
+    }
+}
+
+fn notify_config(smtp: &SmtpSink, ntfy: &Ntfy) -> Config {
+    Config {
+        smtp: Some(SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(smtp.port),
+            username: None,
+            password: None,
+            from: "L Shop <shop@example.com>".to_string(),
+            security: SmtpSecurity::None,
+        }),
+        ntfy: Some(NtfyConfig {
+            url: ntfy.url.clone(),
+            token: Some("tk_secret".to_string()),
+        }),
+        ..test_config()
+    }
+}
+
+async fn create_schedule(app: &TestApp, user: &TestUser, body: Value) -> Value {
+    app.post("/api/v1/notifications/schedules")
+        .user(user)
+        .json(body)
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json()
+}
+
+async fn get_schedule(app: &TestApp, user: &TestUser) -> Value {
+    let list: Value = app
+        .get("/api/v1/notifications/schedules")
+        .user(user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    list[0].clone()
+}
+
+fn timestamp(value: &Value) -> OffsetDateTime {
+    OffsetDateTime::parse(value.as_str().unwrap(), &Rfc3339).unwrap()
+}
+
+#[tokio::test]
+async fn manage_schedules() {
+    let app = TestApp::new().await;
+    let user = app.login("user", Role::Shopper).await;
+    let schedule = json!({ "kind": "list", "transport": "email", "target": "me@example.com", "cron": "0 7 * * *" });
+
+    // Not configured
+    app.post("/api/v1/notifications/schedules")
+        .user(&user)
+        .json(schedule.clone())
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let versions: Value = app.get("/api/versions").send().await.json();
+    assert!(
+        !versions["capabilities"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("notifications"))
+    );
+
+    let (smtp, ntfy) = (SmtpSink::start().await, Ntfy::start().await);
+    let app = TestApp::with_config(notify_config(&smtp, &ntfy)).await;
+    let versions: Value = app.get("/api/versions").send().await.json();
+    assert!(
+        versions["capabilities"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("notifications"))
+    );
+    let admin = app.login("admin", Role::Admin).await;
+    let user = app.login("user", Role::Shopper).await;
+    let store = app.create_store(&admin, "Mart").await;
+
+    for invalid in [
+        json!({ "kind": "list", "transport": "email", "target": "not an email", "cron": "0 7 * * *" }),
+        json!({ "kind": "list", "transport": "ntfy", "target": "a/b", "cron": "0 7 * * *" }),
+        json!({ "kind": "list", "transport": "ntfy", "target": "shop", "cron": "0 7 * *" }),
+        json!({ "kind": "list", "transport": "ntfy", "target": "shop", "cron": "0 0 31 2 *" }),
+        json!({ "kind": "list", "transport": "ntfy", "target": "shop", "cron": "@daily", "timezone": "Europe/Nowhere" }),
+    ] {
+        app.post("/api/v1/notifications/schedules")
+            .user(&user)
+            .json(invalid)
+            .send()
+            .await
+            .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    }
+    app.post("/api/v1/notifications/schedules")
+        .user(&user)
+        .json(json!({ "kind": "digest", "store_id": 999, "transport": "ntfy", "target": "shop", "cron": "@daily" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "store_not_found");
+
+    let res = create_schedule(
+        &app,
+        &user,
+        json!({ "kind": "list", "store_id": store, "transport": "email", "target": " me@example.com ", "cron": "0 7 * * *" }),
+    )
+    .await;
+    let id = res["id"].as_i64().unwrap();
+    assert_eq!(res["target"], "me@example.com");
+    assert_eq!(res["enabled"], true);
+    assert_eq!(res["timezone"], "UTC");
+    assert!(res["last_sent_at"].is_null());
+    let next_run_at = timestamp(&res["next_run_at"]);
+    assert_eq!((next_run_at.hour(), next_run_at.minute()), (7, 0));
+    assert!(next_run_at > OffsetDateTime::now_utc());
+
+    // Schedules are private.
+    app.put(&format!("/api/v1/notifications/schedules/{id}"))
+        .user(&admin)
+        .json(json!({ "kind": "digest", "transport": "ntfy", "target": "shop", "cron": "@daily" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    let list: Value = app
+        .get("/api/v1/notifications/schedules")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(list, json!([]));
+
+    let res: Value = app
+        .put(&format!("/api/v1/notifications/schedules/{id}"))
+        .user(&user)
+        .json(json!({ "kind": "digest", "transport": "ntfy", "target": "shop", "cron": "@daily", "timezone": " europe/ljubljana ", "enabled": false }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(res["kind"], "digest");
+    assert_eq!(res["timezone"], "Europe/Ljubljana");
+    // Cron runs in the local time of the zone.
+    let local = jiff::Timestamp::from_second(timestamp(&res["next_run_at"]).unix_timestamp())
+        .unwrap()
+        .to_zoned(TimeZone::get("Europe/Ljubljana").unwrap());
+    assert_eq!((local.hour(), local.minute()), (0, 0));
+    assert!(res["store_id"].is_null());
+    assert_eq!(res["enabled"], false);
+
+    app.delete(&format!("/api/v1/notifications/schedules/{id}"))
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    app.delete(&format!("/api/v1/notifications/schedules/{id}"))
+        .user(&user)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn list_is_emailed_on_schedule() {
+    let (smtp, ntfy) = (SmtpSink::start().await, Ntfy::start().await);
+    let app = TestApp::with_config(notify_config(&smtp, &ntfy)).await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Mart").await;
+    let other = app.create_store(&admin, "Bakery").await;
+    let dairy = app.create_section(&admin, store, "Dairy").await;
+    app.create_item(&admin, Some(store), Some(dairy), "milk")
+        .await;
+    app.create_item(&admin, Some(store), None, "soap").await;
+    app.create_item(&admin, Some(other), None, "bread").await;
+
+    let res = create_schedule(
+        &app,
+        &admin,
+        json!({ "kind": "list", "store_id": store, "transport": "email", "target": "me@example.com", "cron": "0 7 * * *" }),
+    )
+    .await;
+    let next_run_at = timestamp(&res["next_run_at"]);
+
+    // Nothing is due yet.
+    let runs = notify::run_due(
+        &app.state.db,
+        &app.state.notifier,
+        OffsetDateTime::now_utc(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(runs, 0);
+
+    let runs = notify::run_due(&app.state.db, &app.state.notifier, next_run_at)
+        .await
+        .unwrap();
+    assert_eq!(runs, 1);
+
+    let messages = smtp.messages.lock().unwrap().clone();
+    assert_eq!(messages.len(), 1);
+    let message = &messages[0];
+    assert!(message.contains("From: \"L Shop\" <shop@example.com>"));
+    assert!(message.contains("To: me@example.com"));
+    assert!(message.contains("Subject: Shopping list for Mart: 2 items"));
+    assert!(message.contains("Mart:\n- soap\n\nMart / Dairy:\n- milk\n"));
+    assert!(!message.contains("bread"));
+
+    let schedule = get_schedule(&app, &admin).await;
+    assert_eq!(timestamp(&schedule["last_sent_at"]), next_run_at);
+    assert_eq!(
+        timestamp(&schedule["next_run_at"]),
+        next_run_at + time::Duration::days(1)
+    );
+    assert!(schedule["last_error"].is_null());
+}
+
+#[tokio::test]
+async fn digest_is_published_to_ntfy() {
+    let (smtp, ntfy) = (SmtpSink::start().await, Ntfy::start().await);
+    let app = TestApp::with_config(notify_config(&smtp, &ntfy)).await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Mart").await;
+    app.create_item(&admin, Some(store), None, "old").await;
+
+    let res = create_schedule(
+        &app,
+        &admin,
+        json!({ "kind": "digest", "transport": "ntfy", "target": "family-shop", "cron": "0 18 * * *" }),
+    )
+    .await;
+    let id = res["id"].as_i64().unwrap();
+    let next_run_at = timestamp(&res["next_run_at"]);
+
+    app.create_item(&admin, Some(store), None, "milk").await;
+    app.create_item(&admin, None, None, "soap").await;
+    let checked = app.create_item(&admin, None, None, "eggs").await;
+    app.put(&format!("/api/v1/items/{checked}/checked"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    // Sending now starts the next digest.
+    let res: Value = app
+        .post(&format!("/api/v1/notifications/schedules/{id}/send"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(res, json!({ "sent": true }));
+    {
+        let messages = ntfy.messages.lock().unwrap();
+        let (headers, body) = &messages[0];
+        assert_eq!(headers["authorization"], "Bearer tk_secret");
+        assert_eq!(body["topic"], "family-shop");
+        assert_eq!(body["title"], "2 items added to the list");
+        assert_eq!(body["message"], "- soap\n\nMart:\n- milk\n");
+    }
+
+    app.create_item(&admin, Some(store), None, "bread").await;
+    notify::run_due(&app.state.db, &app.state.notifier, next_run_at)
+        .await
+        .unwrap();
+    assert_eq!(ntfy.messages.lock().unwrap().len(), 2);
+    assert_eq!(
+        ntfy.messages.lock().unwrap()[1].1["title"],
+        "1 item added to the list"
+    );
+
+    // Nothing was added since.
+    let next_run_at = timestamp(&get_schedule(&app, &admin).await["next_run_at"]);
+    notify::run_due(&app.state.db, &app.state.notifier, next_run_at)
+        .await
+        .unwrap();
+    assert_eq!(ntfy.messages.lock().unwrap().len(), 2);
+    let schedule = get_schedule(&app, &admin).await;
+    assert_eq!(timestamp(&schedule["last_sent_at"]), next_run_at);
+    assert!(schedule["last_error"].is_null());
+}
+
+#[tokio::test]
+async fn failed_sends_are_recorded() {
+    let (smtp, ntfy) = (SmtpSink::start().await, Ntfy::start().await);
+    let app = TestApp::with_config(notify_config(&smtp, &ntfy)).await;
+    let admin = app.login("admin", Role::Admin).await;
+    let res = create_schedule(
+        &app,
+        &admin,
+        json!({ "kind": "digest", "transport": "ntfy", "target": "shop", "cron": "*/30 * * * *" }),
+    )
+    .await;
+    let id = res["id"].as_i64().unwrap();
+    let next_run_at = timestamp(&res["next_run_at"]);
+    app.create_item(&admin, None, None, "milk").await;
+
+    ntfy.statuses
+        .lock()
+        .unwrap()
+        .extend([StatusCode::FORBIDDEN, StatusCode::INTERNAL_SERVER_ERROR]);
+    app.post(&format!("/api/v1/notifications/schedules/{id}/send"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_problem(StatusCode::BAD_GATEWAY, "upstream_unavailable");
+    notify::run_due(&app.state.db, &app.state.notifier, next_run_at)
+        .await
+        .unwrap();
+
+    let schedule = get_schedule(&app, &admin).await;
+    assert_eq!(
+        schedule["last_error"],
+        "ntfy responded with 500 Internal Server Error"
+    );
+    assert!(schedule["last_sent_at"].is_null());
+    assert_eq!(
+        timestamp(&schedule["next_run_at"]),
+        next_run_at + time::Duration::minutes(30)
+    );
+
+    // Items of the failed digest are sent with the next one.
+    notify::run_due(
+        &app.state.db,
+        &app.state.notifier,
+        next_run_at + time::Duration::minutes(30),
+    )
+    .await
+    .unwrap();
+    let messages = ntfy.messages.lock().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[2].1["message"], "- milk\n");
+}
+
+#[test]
+fn cron_finds_next_run() {
+    let next = |cron: &str, after: OffsetDateTime| {
+        cron.parse::<Cron>()
+            .unwrap()
+            .next_after(after, &TimeZone::UTC)
+    };
+    // Saturday
+    let after = datetime!(2026-10-17 10:07:30 UTC);
+
+    assert_eq!(
+        next("*/15 * * * *", after),
+        Some(datetime!(2026-10-17 10:15 UTC))
+    );
+    assert_eq!(
+        next("0 8 * * mon-fri", after),
+        Some(datetime!(2026-10-19 08:00 UTC))
+    );
+    assert_eq!(
+        next("30 7 1,15 * *", after),
+        Some(datetime!(2026-11-01 07:30 UTC))
+    );
+    assert_eq!(next("@daily", after), Some(datetime!(2026-10-18 00:00 UTC)));
+    // Sunday is 0 and 7.
+    assert_eq!(
+        next("0 9 * * 7", after),
+        Some(datetime!(2026-10-18 09:00 UTC))
+    );
+    // Either day field matches when both are set.
+    assert_eq!(
+        next("0 0 13 * fri", after),
+        Some(datetime!(2026-10-23 00:00 UTC))
+    );
+    assert_eq!(
+        next("0 12 29 feb *", after),
+        Some(datetime!(2028-02-29 12:00 UTC))
+    );
+    // Runs are strictly after the time.
+    assert_eq!(
+        next("15 10 * * *", datetime!(2026-10-17 10:15 UTC)),
+        Some(datetime!(2026-10-18 10:15 UTC))
+    );
+    assert_eq!(next("0 0 31 2 *", after), None);
+
+    // Local time of the zone, with daylight saving changes.
+    let tz = TimeZone::get("Europe/Ljubljana").unwrap();
+    let next =
+        |cron: &str, after: OffsetDateTime| cron.parse::<Cron>().unwrap().next_after(after, &tz);
+    assert_eq!(
+        next("0 7 * * *", after),
+        Some(datetime!(2026-10-18 05:00 UTC))
+    );
+    // Winter time starts on 2026-10-25, 2:30 happens twice and runs once.
+    assert_eq!(
+        next("0 7 * * *", datetime!(2026-10-24 12:00 UTC)),
+        Some(datetime!(2026-10-25 06:00 UTC))
+    );
+    assert_eq!(
+        next("30 2 * * *", datetime!(2026-10-24 12:00 UTC)),
+        Some(datetime!(2026-10-25 00:30 UTC))
+    );
+    assert_eq!(
+        next("30 2 * * *", datetime!(2026-10-25 00:30 UTC)),
+        Some(datetime!(2026-10-26 01:30 UTC))
+    );
+    // Summer time starts on 2027-03-28, skipped 2:30 runs at 3:30.
+    assert_eq!(
+        next("30 2 * * *", datetime!(2027-03-27 12:00 UTC)),
+        Some(datetime!(2027-03-28 01:30 UTC))
+    );
+
+    for invalid in [
+        "60 * * * *",
+        "* * *",
+        "5-1 * * * *",
+        "*/0 * * * *",
+        "0 0 * foo *",
+    ] {
+        assert!(invalid.parse::<Cron>().is_err(), "{invalid}");
+    }
+}
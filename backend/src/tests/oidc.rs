@@ -0,0 +1,185 @@
+use std::sync::{Arc, Mutex};
+
+use axum::http::{StatusCode, header};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::prelude::*;
+use serde_json::{Value, json};
+use url::Url;
+
+use crate::config::OidcConfig;
+use crate::store::user::Role;
+use crate::tests::{TestApp, TestUser, test_config};
+
+/// Minimal identity provider that returns the configured claims in an unsigned id token.
+struct MockProvider {
+    issuer: String,
+    claims: Arc<Mutex<Value>>,
+}
+
+impl MockProvider {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let claims = Arc::new(Mutex::new(Value::Null));
+
+        let discovery = json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/authorize"),
+            "token_endpoint": format!("{issuer}/token"),
+        });
+        let token_claims = claims.clone();
+        let router = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(discovery) }),
+            )
+            .route(
+                "/token",
+                post(move || async move {
+                    let claims = token_claims.lock().unwrap().to_string();
+                    let id_token = format!(
+                        "{}.{}.",
+                        BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+                        BASE64_URL_SAFE_NO_PAD.encode(claims),
+                    );
+                    Json(json!({ "id_token": id_token, "token_type": "Bearer" }))
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self { issuer, claims }
+    }
+
+    fn config(&self) -> OidcConfig {
+        OidcConfig {
+            issuer_url: self.issuer.clone(),
+            client_id: "lshop".to_string(),
+            client_secret: Some("secret".to_string()),
+            redirect_url: "http://localhost:8000/api/auth/oidc/callback".to_string(),
+            scopes: vec!["openid".to_string()],
+            username_claim: "preferred_username".to_string(),
+            groups_claim: "groups".to_string(),
+            provision_groups: vec!["shoppers".to_string()],
+            link_existing_users: false,
+            default_role: Role::Member,
+        }
+    }
+}
+
+async fn oidc_app(provider: &MockProvider) -> TestApp {
+    let mut config = test_config();
+    config.oidc = Some(provider.config());
+    TestApp::with_config(config).await
+}
+
+/// Runs the login flow with an identity in the given groups.
+async fn login(
+    app: &TestApp,
+    provider: &MockProvider,
+    groups: &[&str],
+) -> (StatusCode, Option<TestUser>) {
+    let res = app
+        .get("/api/v1/auth/oidc/login?redirect=/lists")
+        .send()
+        .await
+        .assert_status(StatusCode::SEE_OTHER);
+    let state_cookie = res
+        .cookie("oidc_state")
+        .expect("login should set state cookie");
+    let location = Url::parse(res.headers[header::LOCATION].to_str().unwrap()).unwrap();
+    let param = |name| {
+        location
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+            .unwrap()
+    };
+    assert_eq!(param("code_challenge_method"), "S256");
+
+    *provider.claims.lock().unwrap() = json!({
+        "iss": provider.issuer,
+        "sub": "subject-1",
+        "aud": "lshop",
+        "exp": time::OffsetDateTime::now_utc().unix_timestamp() + 60,
+        "nonce": param("nonce"),
+        "preferred_username": "carol",
+        "groups": groups,
+    });
+
+    let callback = format!(
+        "/api/v1/auth/oidc/callback?code=abc&state={}",
+        param("state")
+    );
+    // Browser that didn't start the login, ie. one that was sent the callback url.
+    app.get(&callback)
+        .send()
+        .await
+        .assert_problem(StatusCode::BAD_REQUEST, "invalid_challenge");
+    app.get(&callback)
+        .header("cookie", "oidc_state=other")
+        .send()
+        .await
+        .assert_problem(StatusCode::BAD_REQUEST, "invalid_challenge");
+
+    let res = app
+        .get(&callback)
+        .header("cookie", &format!("oidc_state={state_cookie}"))
+        .send()
+        .await;
+    if res.status != StatusCode::SEE_OTHER {
+        return (res.status, None);
+    }
+
+    assert_eq!(res.headers[header::LOCATION], "/lists");
+    assert_eq!(res.cookie("oidc_state").as_deref(), Some(""));
+    let cookie = res.cookie("session").expect("callback should set session");
+    (res.status, Some(TestUser { cookie }))
+}
+
+#[tokio::test]
+async fn login_provisions_user() {
+    let provider = MockProvider::start().await;
+    let app = oidc_app(&provider).await;
+
+    let (_, user) = login(&app, &provider, &["shoppers"]).await;
+    let user = user.unwrap();
+
+    let me: Value = app.get("/api/v1/auth/me").user(&user).send().await.json();
+    assert_eq!(me["username"], "carol");
+    assert_eq!(me["role"], "member");
+}
+
+#[tokio::test]
+async fn login_without_group_is_rejected() {
+    let provider = MockProvider::start().await;
+    let app = oidc_app(&provider).await;
+
+    let (status, _) = login(&app, &provider, &["others"]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn callback_requires_valid_state() {
+    let provider = MockProvider::start().await;
+    let app = oidc_app(&provider).await;
+
+    app.get("/api/v1/auth/oidc/callback?code=abc&state=unknown")
+        .send()
+        .await
+        .assert_problem(StatusCode::BAD_REQUEST, "invalid_challenge");
+}
+
+#[tokio::test]
+async fn disabled_without_config() {
+    let app = TestApp::new().await;
+
+    app.get("/api/v1/auth/oidc/login")
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    app.get("/api/v1/auth/oidc/callback?code=abc&state=x")
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+}
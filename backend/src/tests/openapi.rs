@@ -0,0 +1,50 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::config::Environment;
+use crate::tests::{TestApp, test_config};
+
+#[tokio::test]
+async fn openapi_document_is_served() {
+    let app = TestApp::new().await;
+
+    let res = app
+        .get("/api/v1/openapi.json")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let doc: Value = res.json();
+
+    assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(doc["paths"]["/items"]["get"].is_object());
+    assert!(doc["components"]["schemas"]["Problem"].is_object());
+
+    // Descriptions of responses are required by the specification.
+    for (path, methods) in doc["paths"].as_object().unwrap() {
+        for (method, op) in methods.as_object().unwrap() {
+            for (status, response) in op["responses"].as_object().unwrap() {
+                assert!(
+                    response["description"].is_string(),
+                    "{method} {path} {status} has no description"
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn docs_ui_only_in_dev() {
+    let app = TestApp::new().await;
+    app.get("/api/docs")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    let mut config = test_config();
+    config.environment = Environment::Prod;
+    let app = TestApp::with_config(config).await;
+    app.get("/api/docs")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,262 @@
+//! Property tests for the ordering of items.
+//!
+//! Random sequences of operations are applied both through the api and to a simple model
+//! of the list. After every operation, items of each bucket (store and section) must have
+//! contiguous `ord` values starting at 1, in the same order as in the model.
+
+use std::collections::{BTreeMap, HashSet};
+
+use axum::http::StatusCode;
+use proptest::prelude::*;
+use serde_json::json;
+
+use crate::llm::Categorization;
+use crate::store::user::Role;
+use crate::tests::{TestApp, TestUser};
+
+type Bucket = (Option<i64>, Option<i64>);
+
+#[derive(Debug, Clone)]
+enum Op {
+    Create {
+        bucket: usize,
+    },
+    Move {
+        item: usize,
+        bucket: usize,
+        index: i64,
+    },
+    Check {
+        item: usize,
+    },
+    Organize {
+        store: usize,
+        picks: Vec<(usize, usize)>,
+    },
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0..6usize).prop_map(|bucket| Op::Create { bucket }),
+        3 => (any::<usize>(), 0..6usize, 0..8i64)
+            .prop_map(|(item, bucket, index)| Op::Move { item, bucket, index }),
+        1 => any::<usize>().prop_map(|item| Op::Check { item }),
+        1 => (0..2usize, prop::collection::vec((any::<usize>(), any::<usize>()), 0..6))
+            .prop_map(|(store, picks)| Op::Organize { store, picks }),
+    ]
+}
+
+struct Harness {
+    app: TestApp,
+    user: TestUser,
+    stores: Vec<i64>,
+    buckets: Vec<Bucket>,
+    /// Unchecked items of each bucket in the expected order.
+    model: BTreeMap<Bucket, Vec<i64>>,
+    /// All created items, including checked ones.
+    items: Vec<i64>,
+    next_name: usize,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        let app = TestApp::new().await;
+        let user = app.login("admin", Role::Admin).await;
+
+        let s1 = app.create_store(&user, "One").await;
+        let s2 = app.create_store(&user, "Two").await;
+        let a = app.create_section(&user, s1, "A").await;
+        let b = app.create_section(&user, s1, "B").await;
+        let c = app.create_section(&user, s2, "C").await;
+
+        let buckets = vec![
+            (None, None),
+            (Some(s1), None),
+            (Some(s1), Some(a)),
+            (Some(s1), Some(b)),
+            (Some(s2), None),
+            (Some(s2), Some(c)),
+        ];
+
+        Self {
+            app,
+            user,
+            stores: vec![s1, s2],
+            model: buckets.iter().map(|b| (*b, vec![])).collect(),
+            buckets,
+            items: vec![],
+            next_name: 0,
+        }
+    }
+
+    fn bucket_of(&self, item: i64) -> Option<Bucket> {
+        self.model
+            .iter()
+            .find(|(_, items)| items.contains(&item))
+            .map(|(bucket, _)| *bucket)
+    }
+
+    async fn apply(&mut self, op: &Op) {
+        match op {
+            Op::Create { bucket } => {
+                let (store_id, section_id) = self.buckets[*bucket];
+                self.next_name += 1;
+                let name = format!("item {}", self.next_name);
+                let id = self
+                    .app
+                    .create_item(&self.user, store_id, section_id, &name)
+                    .await;
+
+                self.items.push(id);
+                self.model
+                    .get_mut(&(store_id, section_id))
+                    .unwrap()
+                    .push(id);
+            }
+            Op::Move {
+                item,
+                bucket,
+                index,
+            } => {
+                if self.items.is_empty() {
+                    return;
+                }
+                let id = self.items[item % self.items.len()];
+                let (store_id, section_id) = self.buckets[*bucket];
+
+                let res = self
+                    .app
+                    .put(&format!("/api/v1/items/{id}/move"))
+                    .user(&self.user)
+                    .json(json!({ "store_id": store_id, "section_id": section_id, "index": index }))
+                    .send()
+                    .await;
+
+                let Some(source) = self.bucket_of(id) else {
+                    // Checked items can't be moved.
+                    res.assert_status(StatusCode::NOT_FOUND);
+                    return;
+                };
+                res.assert_status(StatusCode::OK);
+
+                self.model.get_mut(&source).unwrap().retain(|it| *it != id);
+                let target = self.model.get_mut(&(store_id, section_id)).unwrap();
+                let pos = (*index as usize).min(target.len());
+                target.insert(pos, id);
+            }
+            Op::Check { item } => {
+                if self.items.is_empty() {
+                    return;
+                }
+                let id = self.items[item % self.items.len()];
+
+                self.app
+                    .put(&format!("/api/v1/items/{id}/checked"))
+                    .user(&self.user)
+                    .send()
+                    .await
+                    .assert_status(StatusCode::OK);
+
+                for items in self.model.values_mut() {
+                    items.retain(|it| *it != id);
+                }
+            }
+            Op::Organize { store, picks } => {
+                let store_id = self.stores[*store];
+                let sections: Vec<i64> = self
+                    .buckets
+                    .iter()
+                    .filter(|(st, sec)| *st == Some(store_id) && sec.is_some())
+                    .map(|(_, sec)| sec.unwrap())
+                    .collect();
+                let unassigned = self.model[&(Some(store_id), None)].clone();
+
+                // Picks can repeat items, only the first assignment should be used.
+                let categorized: Vec<_> = picks
+                    .iter()
+                    .filter(|_| !unassigned.is_empty())
+                    .map(|(item, section)| Categorization {
+                        item_id: unassigned[item % unassigned.len()],
+                        section_id: sections[section % sections.len()],
+                    })
+                    .collect();
+                let stub_response = categorized.clone();
+                self.app.llm.set(move |_, _| Ok(stub_response.clone()));
+
+                self.app
+                    .post(&format!("/api/v1/stores/{store_id}/organize"))
+                    .user(&self.user)
+                    .send()
+                    .await
+                    .assert_status(StatusCode::NO_CONTENT);
+
+                let mut assigned = HashSet::new();
+                for cat in categorized {
+                    if !assigned.insert(cat.item_id) {
+                        continue;
+                    }
+                    self.model
+                        .get_mut(&(Some(store_id), None))
+                        .unwrap()
+                        .retain(|it| *it != cat.item_id);
+                    self.model
+                        .get_mut(&(Some(store_id), Some(cat.section_id)))
+                        .unwrap()
+                        .push(cat.item_id);
+                }
+            }
+        }
+    }
+
+    async fn check_invariants(&self) -> Result<(), TestCaseError> {
+        let mut actual: BTreeMap<Bucket, Vec<(i64, i64)>> =
+            self.buckets.iter().map(|b| (*b, vec![])).collect();
+        for item in self.app.all_items().await {
+            if item.checked {
+                continue;
+            }
+            actual
+                .get_mut(&(item.store_id, item.section_id))
+                .unwrap()
+                .push((item.ord, item.id));
+        }
+
+        for (bucket, mut items) in actual {
+            items.sort();
+            let ords: Vec<i64> = items.iter().map(|(ord, _)| *ord).collect();
+            let expected_ords: Vec<i64> = (1..=items.len() as i64).collect();
+            prop_assert_eq!(ords, expected_ords, "ord not contiguous in {:?}", bucket);
+
+            let ids: Vec<i64> = items.iter().map(|(_, id)| *id).collect();
+            prop_assert_eq!(
+                &ids,
+                &self.model[&bucket],
+                "unexpected order in {:?}",
+                bucket
+            );
+        }
+
+        Ok(())
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn ord_stays_contiguous(ops in prop::collection::vec(op_strategy(), 1..40)) {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let mut harness = Harness::new().await;
+            for op in &ops {
+                harness.apply(op).await;
+                harness.check_invariants().await?;
+            }
+            Ok::<_, TestCaseError>(())
+        })?;
+    }
+}
@@ -0,0 +1,185 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::llm::{Categorization, LlmError};
+use crate::store::{self, user::Role};
+use crate::tests::TestApp;
+
+#[tokio::test]
+async fn organize_assigns_unassigned_items() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let fruit = app.create_section(&admin, store_id, "fruit").await;
+    let dairy = app.create_section(&admin, store_id, "dairy").await;
+
+    let apple = app
+        .create_item(&admin, Some(store_id), None, "fruit apple")
+        .await;
+    let soap = app.create_item(&admin, Some(store_id), None, "soap").await;
+    let milk = app
+        .create_item(&admin, Some(store_id), None, "dairy milk")
+        .await;
+
+    app.post(&format!("/api/v1/stores/{store_id}/organize"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let items = app.all_items().await;
+    let find = |id| items.iter().find(|it| it.id == id).unwrap();
+    assert_eq!(find(apple).section_id, Some(fruit));
+    assert_eq!(find(milk).section_id, Some(dairy));
+    assert_eq!(find(soap).section_id, None);
+    assert_eq!(find(soap).ord, 1);
+}
+
+#[tokio::test]
+async fn organize_ignores_invalid_ids() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let other_id = app.create_store(&admin, "Other").await;
+    let fruit = app.create_section(&admin, store_id, "fruit").await;
+    let other_section = app.create_section(&admin, other_id, "other").await;
+    let apple = app.create_item(&admin, Some(store_id), None, "apple").await;
+    let other_item = app.create_item(&admin, Some(other_id), None, "pear").await;
+
+    app.llm.set(move |_, _| {
+        Ok(vec![
+            Categorization {
+                section_id: other_section,
+                item_id: apple,
+            },
+            Categorization {
+                section_id: fruit,
+                item_id: other_item,
+            },
+            Categorization {
+                section_id: 42,
+                item_id: apple,
+            },
+            Categorization {
+                section_id: fruit,
+                item_id: apple,
+            },
+            Categorization {
+                section_id: fruit,
+                item_id: apple,
+            },
+        ])
+    });
+
+    app.post(&format!("/api/v1/stores/{store_id}/organize"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let items = app.all_items().await;
+    let find = |id| items.iter().find(|it| it.id == id).unwrap();
+    assert_eq!(find(apple).section_id, Some(fruit));
+    assert_eq!(find(apple).ord, 1);
+    assert_eq!(find(other_item).section_id, None);
+}
+
+#[tokio::test]
+async fn organize_reports_llm_failure() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    app.create_section(&admin, store_id, "fruit").await;
+    app.create_item(&admin, Some(store_id), None, "apple").await;
+
+    app.llm.set(|_, _| {
+        let err = serde_json::from_str::<Value>("").unwrap_err();
+        Err(LlmError::InvalidResponse(err))
+    });
+
+    app.post(&format!("/api/v1/stores/{store_id}/organize"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_problem(StatusCode::BAD_GATEWAY, "upstream_unavailable");
+}
+
+#[tokio::test]
+async fn organize_requires_member() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+
+    app.post(&format!("/api/v1/stores/{store_id}/organize"))
+        .user(&shopper)
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+}
+
+// The stub blocks in place to change the items, so it needs another worker.
+#[tokio::test(flavor = "multi_thread")]
+async fn organize_skips_items_changed_meanwhile() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let other_id = app.create_store(&admin, "Other").await;
+    app.create_section(&admin, store_id, "fruit").await;
+    let dairy = app.create_section(&admin, store_id, "dairy").await;
+    let apple = app
+        .create_item(&admin, Some(store_id), None, "fruit apple")
+        .await;
+    let milk = app
+        .create_item(&admin, Some(store_id), None, "dairy milk")
+        .await;
+    let cheese = app
+        .create_item(&admin, Some(store_id), None, "dairy cheese")
+        .await;
+
+    // Apple is checked and milk moved to another store while the model runs.
+    let db = app.state.db.clone();
+    app.llm.set(move |items, sections| {
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                store::item::set_checked(&db, apple).await.unwrap();
+                sqlx::query("UPDATE items SET store_id = ? WHERE id = ?")
+                    .bind(other_id)
+                    .bind(milk)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            })
+        });
+
+        Ok(items
+            .iter()
+            .filter_map(|item| {
+                let section = sections.iter().find(|sec| item.name.contains(&sec.name))?;
+                Some(Categorization {
+                    section_id: section.id,
+                    item_id: item.id,
+                })
+            })
+            .collect())
+    });
+
+    app.post(&format!("/api/v1/stores/{store_id}/organize"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let items = app.all_items().await;
+    let find = |id| items.iter().find(|it| it.id == id).unwrap();
+    assert!(find(apple).checked);
+    assert_eq!(find(apple).section_id, None);
+    assert_eq!(
+        (find(milk).store_id, find(milk).section_id),
+        (Some(other_id), None)
+    );
+    assert_eq!(
+        (find(cheese).section_id, find(cheese).ord),
+        (Some(dairy), 1)
+    );
+}
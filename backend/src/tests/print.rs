@@ -0,0 +1,153 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+/// Lines of text on each page of the pdf, read back through the fonts' unicode maps.
+fn pdf_lines(pdf: &[u8]) -> Vec<Vec<String>> {
+    pdf_extract::extract_text_from_mem_by_pages(pdf)
+        .expect("text should extract")
+        .iter()
+        .map(|page| {
+            page.lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn print_as_html() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let store = app.create_store(&admin, "Mart").await;
+    let dairy = app.create_section(&admin, store, "Dairy").await;
+    app.create_section(&admin, store, "Empty").await;
+    let fruit = app.create_section(&admin, store, "Fruit").await;
+    app.create_item(&admin, Some(store), Some(fruit), "apples")
+        .await;
+    app.create_item(&admin, Some(store), Some(dairy), "<milk> & cream")
+        .await;
+    app.create_item(&admin, Some(store), None, "soap").await;
+    app.create_item(&admin, None, None, "stamps").await;
+
+    let res = app
+        .get(&format!(
+            "/api/v1/stores/{store}/print?font_size=14&columns=2"
+        ))
+        .user(&shopper)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.headers["content-type"], "text/html; charset=utf-8");
+    let html = String::from_utf8(res.body).unwrap();
+    assert!(html.contains("font: 14pt/1.3"), "{html}");
+    assert!(html.contains("columns: 2;"), "{html}");
+    assert!(html.contains("<li>&lt;milk&gt; &amp; cream</li>"), "{html}");
+    assert!(!html.contains("Empty"), "{html}");
+    assert!(!html.contains("stamps"), "{html}");
+
+    // Sections in store order, items without a section at the end.
+    let pos = |s: &str| html.find(s).unwrap();
+    assert!(pos("Dairy") < pos("Fruit"));
+    assert!(pos("Fruit") < pos("Other items"));
+    assert!(pos("Other items") < pos("soap"));
+}
+
+#[tokio::test]
+async fn print_as_pdf() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Big Mart").await;
+    let section = app.create_section(&admin, store, "Crème fraîche").await;
+    for i in 0..150 {
+        let name = format!("item {i} with a rather long name that needs to be wrapped (really)");
+        app.create_item(&admin, Some(store), Some(section), &name)
+            .await;
+    }
+
+    let res = app
+        .get(&format!(
+            "/api/v1/stores/{store}/print?format=pdf&columns=2"
+        ))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.headers["content-type"], "application/pdf");
+    assert_eq!(
+        res.headers["content-disposition"],
+        "inline; filename=\"big-mart.pdf\""
+    );
+    assert!(res.body.starts_with(b"%PDF-"));
+    let pages = pdf_lines(&res.body);
+    assert_eq!(pages.len(), 3, "items should flow onto more pages");
+    // Long names are wrapped.
+    assert_eq!(
+        pages[0][..4],
+        [
+            "Big Mart",
+            "Crème fraîche",
+            "item 0 with a rather long name that needs",
+            "to be wrapped (really)",
+        ]
+    );
+    assert!(pages[2].iter().any(|line| line.starts_with("item 149")));
+}
+
+#[tokio::test]
+async fn pdf_keeps_letters_outside_latin_1() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Tuš").await;
+    app.create_item(&admin, Some(store), None, "čebula, ćevapčići")
+        .await;
+    app.create_item(&admin, Some(store), None, "łosoś i żurek")
+        .await;
+    app.create_item(&admin, Some(store), None, "молоко").await;
+
+    let res = app
+        .get(&format!("/api/v1/stores/{store}/print?format=pdf"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(
+        pdf_lines(&res.body),
+        [[
+            "Tuš",
+            "Other items",
+            "čebula, ćevapčići",
+            "łosoś i żurek",
+            "молоко"
+        ]]
+    );
+}
+
+#[tokio::test]
+async fn print_rejects_invalid_options() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Mart").await;
+
+    let res = app
+        .get(&format!(
+            "/api/v1/stores/{store}/print?font_size=100&columns=3"
+        ))
+        .user(&admin)
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let errors = res.json::<Value>()["errors"].clone();
+    assert_eq!(errors[0]["field"], "font_size");
+    assert_eq!(errors[1]["field"], "columns");
+
+    app.get("/api/v1/stores/42/print")
+        .user(&admin)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "store_not_found");
+}
@@ -0,0 +1,524 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use base64::prelude::*;
+use hkdf::Hkdf;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::{PublicKey, SecretKey};
+use serde_json::{Value, json};
+use sha2::Sha256;
+
+use crate::config::{Config, WebPushConfig};
+use crate::push;
+use crate::store::{self, item::Item, user::Role};
+use crate::tests::{TestApp, TestUser, test_config};
+
+const AUTH_SECRET: [u8; 16] = *b"0123456789abcdef";
+
+fn push_config() -> Config {
+    let (vapid_private_key, _) = push::generate_keys();
+    Config {
+        web_push: Some(WebPushConfig {
+            vapid_private_key,
+            subject: "mailto:admin@example.com".to_string(),
+        }),
+        ..test_config()
+    }
+}
+
+/// Push service that records the requests per subscription and answers with the
+/// queued status codes, 201 once the queue is empty.
+struct PushService {
+    url: String,
+    requests: Arc<Mutex<Vec<(String, HeaderMap, Bytes)>>>,
+    statuses: Arc<Mutex<VecDeque<StatusCode>>>,
+}
+
+impl PushService {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(VecDeque::new()));
+
+        let (recorded, queued) = (requests.clone(), statuses.clone());
+        let router = Router::new().route(
+            "/push/{id}",
+            post(
+                move |Path(id): Path<String>, headers: HeaderMap, body: Bytes| async move {
+                    recorded.lock().unwrap().push((id, headers, body));
+                    queued
+                        .lock()
+                        .unwrap()
+                        .pop_front()
+                        .unwrap_or(StatusCode::CREATED)
+                },
+            ),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self {
+            url,
+            requests,
+            statuses,
+        }
+    }
+
+    fn received(&self) -> Vec<String> {
+        let requests = self.requests.lock().unwrap();
+        requests.iter().map(|(id, _, _)| id.clone()).collect()
+    }
+}
+
+/// Browser side of a subscription, which can decrypt the messages.
+struct Browser {
+    endpoint: String,
+    secret: SecretKey,
+    public_key: String,
+}
+
+impl Browser {
+    fn new(service: &PushService, name: &str) -> Self {
+        let (private_key, public_key) = push::generate_keys();
+        let secret =
+            SecretKey::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(private_key).unwrap()).unwrap();
+        Self {
+            endpoint: format!("{}/push/{name}", service.url),
+            secret,
+            public_key,
+        }
+    }
+
+    fn subscription(&self) -> Value {
+        json!({
+            "endpoint": self.endpoint,
+            "keys": {
+                "p256dh": self.public_key,
+                "auth": BASE64_URL_SAFE_NO_PAD.encode(AUTH_SECRET),
+            },
+        })
+    }
+
+    async fn subscribe(&self, app: &TestApp, user: &TestUser) -> i64 {
+        let res: Value = app
+            .post("/api/v1/push/subscriptions")
+            .user(user)
+            .json(self.subscription())
+            .send()
+            .await
+            .assert_status(StatusCode::CREATED)
+            .json();
+        res["id"].as_i64().unwrap()
+    }
+
+    /// Decrypts an aes128gcm message (RFC 8291).
+    fn decrypt(&self, body: &[u8]) -> Value {
+        let (salt, rest) = body.split_at(16);
+        assert_eq!(&rest[..4], 4096u32.to_be_bytes());
+        let key_len = rest[4] as usize;
+        let (as_public, ciphertext) = rest[5..].split_at(key_len);
+
+        let as_key = PublicKey::from_sec1_bytes(as_public).unwrap();
+        let shared =
+            p256::ecdh::diffie_hellman(self.secret.to_nonzero_scalar(), as_key.as_affine());
+        let mut key_info = b"WebPush: info\0".to_vec();
+        key_info.extend_from_slice(&BASE64_URL_SAFE_NO_PAD.decode(&self.public_key).unwrap());
+        key_info.extend_from_slice(as_public);
+        let mut ikm = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(&AUTH_SECRET), shared.raw_secret_bytes())
+            .expand(&key_info, &mut ikm)
+            .unwrap();
+
+        let hkdf = Hkdf::<Sha256>::new(Some(salt), &ikm);
+        let (mut cek, mut nonce) = ([0u8; 16], [0u8; 12]);
+        hkdf.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+            .unwrap();
+        hkdf.expand(b"Content-Encoding: nonce\0", &mut nonce)
+            .unwrap();
+        let mut plaintext = Aes128Gcm::new(&cek.into())
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .unwrap();
+
+        assert_eq!(plaintext.pop(), Some(2));
+        serde_json::from_slice(&plaintext).unwrap()
+    }
+}
+
+async fn user_id(app: &TestApp, username: &str) -> i64 {
+    sqlx::query_scalar("SELECT id FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap()
+}
+
+async fn notify(app: &TestApp, author: &str, items: &[Item]) {
+    let push = app.state.push.as_ref().unwrap();
+    let author_id = user_id(app, author).await;
+    push::notify_items_created(&app.state.db, push, author_id, author, items)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn push_capability() {
+    let app = TestApp::new().await;
+    let body: Value = app.get("/api/versions").send().await.json();
+    assert!(
+        !body["capabilities"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("push"))
+    );
+
+    let app = TestApp::with_config(push_config()).await;
+    let body: Value = app.get("/api/versions").send().await.json();
+    assert!(
+        body["capabilities"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("push"))
+    );
+}
+
+#[test]
+fn only_https_endpoints_are_allowed() {
+    let allowed = |url: &str| push::is_allowed_endpoint(&url::Url::parse(url).unwrap());
+    assert!(allowed("https://fcm.googleapis.com/fcm/send/abc"));
+    assert!(!allowed("http://push.example.com/abc"));
+    assert!(!allowed("http://localhost:8080/abc"));
+    assert!(!allowed("file:///push"));
+    // Hosts are names, addresses are checked when they're resolved.
+    assert!(!allowed("https://10.0.0.1/abc"));
+    assert!(!allowed("https://[::1]:8443/abc"));
+    // The local push service of the tests.
+    assert!(allowed("http://127.0.0.1:8080/push/a"));
+
+    let public = |ip: &str| push::is_public(ip.parse().unwrap());
+    assert!(public("142.250.180.10"));
+    assert!(public("2a00:1450:4001::200e"));
+    for local in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:10.0.0.1",
+    ] {
+        assert!(!public(local), "{local}");
+    }
+}
+
+#[tokio::test]
+async fn manage_subscriptions() {
+    let app = TestApp::new().await;
+    let alice = app.login("alice", Role::Shopper).await;
+    let service = PushService::start().await;
+    let browser = Browser::new(&service, "a");
+
+    // Not configured
+    app.get("/api/v1/push/key")
+        .user(&alice)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    app.post("/api/v1/push/subscriptions")
+        .user(&alice)
+        .json(browser.subscription())
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+
+    let app = TestApp::with_config(push_config()).await;
+    let alice = app.login("alice", Role::Shopper).await;
+    let bob = app.login("bob", Role::Admin).await;
+
+    let res: Value = app
+        .get("/api/v1/push/key")
+        .user(&alice)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(
+        res["public_key"],
+        app.state.push.as_ref().unwrap().public_key()
+    );
+
+    app.post("/api/v1/push/subscriptions")
+        .user(&alice)
+        .json(json!({
+            "endpoint": "file:///push",
+            "keys": { "p256dh": "not a key", "auth": "c2hvcnQ" },
+        }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    // Push services are only reached over https, and not in the local network.
+    for endpoint in [
+        "http://push.example.com/push/1",
+        "https://10.0.0.1/push/1",
+        "https://localhost:8443/push/1",
+    ] {
+        let mut subscription = browser.subscription();
+        subscription["endpoint"] = json!(endpoint);
+        app.post("/api/v1/push/subscriptions")
+            .user(&alice)
+            .json(subscription)
+            .send()
+            .await
+            .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    }
+
+    let id = browser.subscribe(&app, &alice).await;
+    // Subscribing again keeps the subscription.
+    assert_eq!(browser.subscribe(&app, &alice).await, id);
+
+    let list: Value = app
+        .get("/api/v1/push/subscriptions")
+        .user(&alice)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["endpoint"], browser.endpoint);
+    assert!(list[0].get("auth").is_none());
+
+    app.delete(&format!("/api/v1/push/subscriptions/{id}"))
+        .user(&bob)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    app.delete(&format!("/api/v1/push/subscriptions/{id}"))
+        .user(&alice)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let prefs: Value = app
+        .get("/api/v1/push/preferences")
+        .user(&alice)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(prefs, json!({ "item_created": true, "store_ids": [] }));
+
+    let store = app.create_store(&bob, "Mart").await;
+    let prefs: Value = app
+        .put("/api/v1/push/preferences")
+        .user(&alice)
+        .json(json!({ "item_created": true, "store_ids": [store] }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(prefs, json!({ "item_created": true, "store_ids": [store] }));
+    app.put("/api/v1/push/preferences")
+        .user(&alice)
+        .json(json!({ "item_created": true, "store_ids": [store, store] }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+}
+
+#[tokio::test]
+async fn added_items_are_pushed() {
+    let app = TestApp::with_config(push_config()).await;
+    let alice = app.login("alice", Role::Member).await;
+    let bob = app.login("bob", Role::Admin).await;
+    let service = PushService::start().await;
+    let browser = Browser::new(&service, "alice");
+    browser.subscribe(&app, &alice).await;
+    // The author isn't notified about their own items.
+    Browser::new(&service, "bob").subscribe(&app, &bob).await;
+
+    let store = app.create_store(&bob, "Mart").await;
+    app.create_item(&bob, Some(store), None, "milk").await;
+
+    // Notifications are sent in the background.
+    for _ in 0..100 {
+        if !service.received().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(service.received(), ["alice"]);
+
+    let (_, headers, body) = service.requests.lock().unwrap().remove(0);
+    assert_eq!(headers["content-encoding"], "aes128gcm");
+    assert_eq!(headers["ttl"], "86400");
+    assert_eq!(
+        browser.decrypt(&body),
+        json!({ "title": "bob added milk", "body": "Mart", "tag": "items", "url": "/" })
+    );
+
+    // VAPID token is signed by the server's key, for the push service's origin.
+    let authorization = headers["authorization"].to_str().unwrap();
+    let (token, key) = authorization
+        .strip_prefix("vapid t=")
+        .and_then(|rest| rest.split_once(", k="))
+        .unwrap();
+    assert_eq!(key, app.state.push.as_ref().unwrap().public_key());
+    let verifying_key =
+        VerifyingKey::from_sec1_bytes(&BASE64_URL_SAFE_NO_PAD.decode(key).unwrap()).unwrap();
+    let (signing_input, signature) = token.rsplit_once('.').unwrap();
+    let signature =
+        Signature::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(signature).unwrap()).unwrap();
+    verifying_key
+        .verify(signing_input.as_bytes(), &signature)
+        .unwrap();
+    let claims = signing_input.split('.').nth(1).unwrap();
+    let claims: Value =
+        serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap();
+    assert_eq!(claims["aud"], service.url);
+    assert_eq!(claims["sub"], "mailto:admin@example.com");
+}
+
+#[tokio::test]
+async fn preferences_filter_notifications() {
+    let app = TestApp::with_config(push_config()).await;
+    let alice = app.login("alice", Role::Member).await;
+    let bob = app.login("bob", Role::Member).await;
+    let carol = app.login("carol", Role::Admin).await;
+    let service = PushService::start().await;
+    let browser = Browser::new(&service, "alice");
+    browser.subscribe(&app, &alice).await;
+    Browser::new(&service, "bob").subscribe(&app, &bob).await;
+
+    let mart = app.create_store(&carol, "Mart").await;
+    let bakery = app.create_store(&carol, "Bakery").await;
+    app.put("/api/v1/push/preferences")
+        .user(&alice)
+        .json(json!({ "item_created": true, "store_ids": [bakery] }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    app.put("/api/v1/push/preferences")
+        .user(&bob)
+        .json(json!({ "item_created": false }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    let names: Vec<String> = ["milk", "bread", "buns", "rolls", "cake", "pie", "tart"]
+        .map(String::from)
+        .into();
+    let milk = store::item::create(&app.state.db, Some(mart), None, &names[0])
+        .await
+        .unwrap();
+    notify(&app, "carol", &[milk]).await;
+    assert!(service.received().is_empty());
+
+    // Only the items of the wanted store are listed.
+    let items = store::item::create_many(&app.state.db, Some(bakery), None, &names[1..])
+        .await
+        .unwrap();
+    let soap = store::item::create(&app.state.db, None, None, "soap")
+        .await
+        .unwrap();
+    notify(&app, "carol", &[items, vec![soap]].concat()).await;
+    assert_eq!(service.received(), ["alice"]);
+
+    let (_, _, body) = service.requests.lock().unwrap().remove(0);
+    let notification = browser.decrypt(&body);
+    assert_eq!(notification["title"], "carol added 6 items");
+    assert_eq!(notification["body"], "bread, buns, rolls, cake, pie, …");
+}
+
+#[tokio::test]
+async fn long_names_fit_a_message() {
+    let app = TestApp::with_config(push_config()).await;
+    let alice = app.login("alice", Role::Member).await;
+    app.login("bob", Role::Member).await;
+    let service = PushService::start().await;
+    let browser = Browser::new(&service, "alice");
+    browser.subscribe(&app, &alice).await;
+
+    // Names of 200 characters of 4 bytes each.
+    let names: Vec<String> = (0..6).map(|i| format!("{i}{}", "𝄞".repeat(199))).collect();
+    let store = store::shop::create(&app.state.db, &names[0]).await.unwrap();
+    let items = store::item::create_many(&app.state.db, Some(store.id), None, &names)
+        .await
+        .unwrap();
+    notify(&app, "bob", &items[..1]).await;
+    notify(&app, "bob", &items).await;
+
+    let requests = std::mem::take(&mut *service.requests.lock().unwrap());
+    assert_eq!(requests.len(), 2);
+    let short = format!("0{}…", "𝄞".repeat(38));
+    for (_, _, body) in &requests {
+        assert!(body.len() <= 4096);
+    }
+    let single = browser.decrypt(&requests[0].2);
+    assert_eq!(single["title"], format!("bob added {short}"));
+    assert_eq!(single["body"], short);
+    let many = browser.decrypt(&requests[1].2);
+    let listed: Vec<&str> = many["body"].as_str().unwrap().split(", ").collect();
+    assert_eq!(listed.len(), 6);
+    assert_eq!(listed[0], short);
+    assert!(listed[..5].iter().all(|name| name.chars().count() == 40));
+
+    // Anything else that's too long is cut to fit.
+    let notification = push::Notification {
+        title: "é".repeat(2000),
+        body: "𝄞".repeat(2000),
+        tag: "items".to_string(),
+        url: "/".to_string(),
+    };
+    let payload = push::payload(&notification);
+    // Header, tag and padding delimiter take the rest of the 4096 bytes.
+    assert!(payload.len() < 4096 - 86 - 16);
+    let payload: Value = serde_json::from_slice(&payload).unwrap();
+    assert_eq!(payload["body"], "");
+    assert!(payload["title"].as_str().unwrap().ends_with('…'));
+}
+
+#[tokio::test]
+async fn expired_subscriptions_are_deleted() {
+    let app = TestApp::with_config(push_config()).await;
+    let alice = app.login("alice", Role::Member).await;
+    app.login("bob", Role::Member).await;
+    let service = PushService::start().await;
+    Browser::new(&service, "gone").subscribe(&app, &alice).await;
+    Browser::new(&service, "broken")
+        .subscribe(&app, &alice)
+        .await;
+    service
+        .statuses
+        .lock()
+        .unwrap()
+        .extend([StatusCode::GONE, StatusCode::INTERNAL_SERVER_ERROR]);
+
+    let item = store::item::create(&app.state.db, None, None, "milk")
+        .await
+        .unwrap();
+    notify(&app, "bob", &[item]).await;
+    assert_eq!(service.received(), ["gone", "broken"]);
+
+    // Other failures keep the subscription.
+    let list: Value = app
+        .get("/api/v1/push/subscriptions")
+        .user(&alice)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert!(list[0]["endpoint"].as_str().unwrap().ends_with("/broken"));
+}
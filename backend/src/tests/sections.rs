@@ -0,0 +1,201 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+fn ids(sections: &Value) -> Vec<i64> {
+    sections
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["id"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn create_list_update_delete() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+
+    let fruit = app.create_section(&admin, store_id, "Fruit").await;
+    let dairy = app.create_section(&admin, store_id, "Dairy").await;
+
+    let res = app
+        .get(&format!("/api/v1/stores/{store_id}/sections"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(ids(&res.json()), [fruit, dairy]);
+
+    let res = app
+        .put(&format!("/api/v1/sections/{fruit}"))
+        .user(&admin)
+        .json(json!({ "name": "Vegetables" }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Value>()["name"], "Vegetables");
+
+    app.delete(&format!("/api/v1/sections/{fruit}"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let res = app
+        .get(&format!("/api/v1/stores/{store_id}/sections"))
+        .user(&admin)
+        .send()
+        .await;
+    assert_eq!(ids(&res.json()), [dairy]);
+}
+
+#[tokio::test]
+async fn missing_store_or_section() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+
+    app.get("/api/v1/stores/42/sections")
+        .user(&admin)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "store_not_found");
+    app.post("/api/v1/stores/42/sections")
+        .user(&admin)
+        .json(json!({ "name": "Fruit" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "store_not_found");
+    app.put("/api/v1/sections/42")
+        .user(&admin)
+        .json(json!({ "name": "Fruit" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "section_not_found");
+}
+
+#[tokio::test]
+async fn reorder() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let a = app.create_section(&admin, store_id, "A").await;
+    let b = app.create_section(&admin, store_id, "B").await;
+    let c = app.create_section(&admin, store_id, "C").await;
+
+    let res = app
+        .put(&format!("/api/v1/stores/{store_id}/sections/reorder"))
+        .user(&admin)
+        .json(json!({ "ids": [c, a, b] }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(ids(&res.json()), [c, a, b]);
+}
+
+#[tokio::test]
+async fn reorder_requires_exactly_store_sections() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let other_id = app.create_store(&admin, "Other").await;
+    let a = app.create_section(&admin, store_id, "A").await;
+    let b = app.create_section(&admin, store_id, "B").await;
+    let other = app.create_section(&admin, other_id, "X").await;
+
+    let uri = format!("/api/v1/stores/{store_id}/sections/reorder");
+    for ids in [vec![a], vec![a, b, other], vec![a, other], vec![]] {
+        app.put(&uri)
+            .user(&admin)
+            .json(json!({ "ids": ids }))
+            .send()
+            .await
+            .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "invalid_section_ids");
+    }
+
+    app.put(&uri)
+        .user(&admin)
+        .json(json!({ "ids": [a, b, a] }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    // Sections of the other store are untouched.
+    let res = app
+        .get(&format!("/api/v1/stores/{other_id}/sections"))
+        .user(&admin)
+        .send()
+        .await;
+    assert_eq!(ids(&res.json()), [other]);
+}
+
+#[tokio::test]
+async fn only_admins_can_modify_sections() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let a = app.create_section(&admin, store_id, "A").await;
+
+    app.post(&format!("/api/v1/stores/{store_id}/sections"))
+        .user(&member)
+        .json(json!({ "name": "B" }))
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    app.put(&format!("/api/v1/sections/{a}"))
+        .user(&member)
+        .json(json!({ "name": "B" }))
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    app.delete(&format!("/api/v1/sections/{a}"))
+        .user(&member)
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    app.put(&format!("/api/v1/stores/{store_id}/sections/reorder"))
+        .user(&member)
+        .json(json!({ "ids": [a] }))
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    app.get(&format!("/api/v1/stores/{store_id}/sections"))
+        .user(&member)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+}
+
+#[tokio::test]
+async fn delete_moves_items_to_store_unassigned() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let fruit = app.create_section(&admin, store_id, "Fruit").await;
+
+    let bread = app.create_item(&admin, Some(store_id), None, "bread").await;
+    let apple = app.create_item(&admin, None, Some(fruit), "apple").await;
+    let pear = app.create_item(&admin, None, Some(fruit), "pear").await;
+
+    app.delete(&format!("/api/v1/sections/{fruit}"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let list: Value = app.get("/api/v1/items").user(&admin).send().await.json();
+    let store = &list["stores"][0];
+    let unassigned: Vec<_> = store["unassigned"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|it| it["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(unassigned, [bread, apple, pear]);
+    assert!(store["sections"].as_array().unwrap().is_empty());
+}
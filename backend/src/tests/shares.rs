@@ -0,0 +1,152 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::store::{self, user::Role};
+use crate::tests::{TestApp, TestUser};
+
+async fn create_share(app: &TestApp, user: &TestUser, store_id: i64, permission: &str) -> Value {
+    app.post(&format!("/api/v1/stores/{store_id}/shares"))
+        .user(user)
+        .json(json!({ "permission": permission, "expires_in_hours": 24 }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json()
+}
+
+#[tokio::test]
+async fn create_list_delete() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+
+    let link = create_share(&app, &member, store_id, "view").await;
+    assert!(link["token"].as_str().is_some_and(|t| !t.is_empty()));
+
+    let res = app
+        .get(&format!("/api/v1/stores/{store_id}/shares"))
+        .user(&member)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let links: Vec<Value> = res.json();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0]["id"], link["id"]);
+    // Token is returned only on creation.
+    assert!(links[0].get("token").is_none());
+
+    app.delete(&format!("/api/v1/shares/{}", link["id"]))
+        .user(&member)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    app.delete(&format!("/api/v1/shares/{}", link["id"]))
+        .user(&member)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+
+    app.get(&format!(
+        "/api/v1/share/{}",
+        link["token"].as_str().unwrap()
+    ))
+    .send()
+    .await
+    .assert_problem(StatusCode::UNAUTHORIZED, "invalid_credentials");
+}
+
+#[tokio::test]
+async fn create_validates_request() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+
+    let uri = format!("/api/v1/stores/{store_id}/shares");
+    app.post(&uri)
+        .user(&member)
+        .json(json!({ "permission": "view", "expires_in_hours": 0 }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    app.post("/api/v1/stores/42/shares")
+        .user(&member)
+        .json(json!({ "permission": "view", "expires_in_hours": 1 }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    app.post(&uri)
+        .user(&shopper)
+        .json(json!({ "permission": "view", "expires_in_hours": 1 }))
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+}
+
+#[tokio::test]
+async fn guest_sees_only_shared_store() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let other_id = app.create_store(&admin, "Other").await;
+    let apple = app
+        .create_item(&member, Some(store_id), None, "apple")
+        .await;
+    let pear = app.create_item(&member, Some(other_id), None, "pear").await;
+
+    let link = create_share(&app, &member, store_id, "view").await;
+    let token = link["token"].as_str().unwrap();
+
+    let res = app
+        .get(&format!("/api/v1/share/{token}"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let list: Value = res.json();
+    assert_eq!(list["permission"], "view");
+    assert_eq!(list["id"], store_id);
+    assert_eq!(list["unassigned"][0]["id"], apple);
+
+    // View links can't check items.
+    app.put(&format!("/api/v1/share/{token}/items/{apple}/checked"))
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+
+    let link = create_share(&app, &member, store_id, "check").await;
+    let token = link["token"].as_str().unwrap();
+
+    app.put(&format!("/api/v1/share/{token}/items/{pear}/checked"))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    // Store is checked with the write, an item moved away can't be checked.
+    let soap = app.create_item(&member, None, None, "soap").await;
+    for id in [pear, soap] {
+        let checked = store::item::set_checked_in_store(&app.state.db, id, store_id)
+            .await
+            .unwrap();
+        assert!(checked.is_none());
+    }
+    assert!(app.all_items().await.iter().all(|item| !item.checked));
+
+    let res = app
+        .put(&format!("/api/v1/share/{token}/items/{apple}/checked"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Value>()["checked"], true);
+}
+
+#[tokio::test]
+async fn invalid_token_is_rejected() {
+    let app = TestApp::new().await;
+
+    app.get("/api/v1/share/not-a-token")
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "invalid_credentials");
+}
@@ -0,0 +1,147 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+#[tokio::test]
+async fn create_list_update_delete() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+
+    let id = app.create_store(&admin, "  Big   Mart ").await;
+    app.create_store(&admin, "Alpha").await;
+
+    let res = app
+        .get("/api/v1/stores")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let stores: Vec<Value> = res.json();
+    let names: Vec<_> = stores.iter().map(|s| s["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Alpha", "Big Mart"]);
+
+    let res = app
+        .put(&format!("/api/v1/stores/{id}"))
+        .user(&admin)
+        .json(json!({ "name": "Mart" }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Value>()["name"], "Mart");
+
+    app.delete(&format!("/api/v1/stores/{id}"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let stores: Vec<Value> = app.get("/api/v1/stores").user(&admin).send().await.json();
+    assert_eq!(stores.len(), 1);
+}
+
+#[tokio::test]
+async fn invalid_names_are_rejected() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+
+    let res = app
+        .post("/api/v1/stores")
+        .user(&admin)
+        .json(json!({ "name": " \n " }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    assert_eq!(res.json::<Value>()["errors"][0]["code"], "empty");
+
+    let res = app
+        .post("/api/v1/stores")
+        .user(&admin)
+        .json(json!({ "name": "a".repeat(101) }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    assert_eq!(res.json::<Value>()["errors"][0]["code"], "too_long");
+}
+
+#[tokio::test]
+async fn update_missing_store() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+
+    app.put("/api/v1/stores/42")
+        .user(&admin)
+        .json(json!({ "name": "Mart" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "store_not_found");
+}
+
+#[tokio::test]
+async fn only_admins_can_modify_stores() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+    let id = app.create_store(&admin, "Mart").await;
+
+    app.post("/api/v1/stores")
+        .user(&member)
+        .json(json!({ "name": "Other" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+    app.put(&format!("/api/v1/stores/{id}"))
+        .user(&member)
+        .json(json!({ "name": "Other" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+    app.delete(&format!("/api/v1/stores/{id}"))
+        .user(&member)
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+
+    // Everyone can see the stores.
+    app.get("/api/v1/stores")
+        .user(&member)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    app.get("/api/v1/stores")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn delete_moves_items_to_unassigned() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store_id = app.create_store(&admin, "Mart").await;
+    let section_id = app.create_section(&admin, store_id, "Fruit").await;
+
+    app.create_item(&admin, None, None, "soap").await;
+    let apple = app
+        .create_item(&admin, None, Some(section_id), "apple")
+        .await;
+    let bread = app.create_item(&admin, Some(store_id), None, "bread").await;
+
+    app.delete(&format!("/api/v1/stores/{store_id}"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let list: Value = app.get("/api/v1/items").user(&admin).send().await.json();
+    let ids: Vec<_> = list["unassigned"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|it| it["id"].as_i64().unwrap())
+        .collect();
+    // Store's unassigned items come before sections.
+    assert_eq!(ids[1..], [bread, apple]);
+    assert!(list["stores"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,219 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::handler::text::parse_items;
+use crate::llm::LlmError;
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+#[test]
+fn parse_strips_list_markers() {
+    let text = "
+        ## Groceries
+        Fruit:
+        - apples
+        * [ ] bananas
+        - [x] bread
+        ✅ eggs
+        1. 2 apples
+        2) 1.5 kg   flour
+        (3) milk
+        • ☐ soap
+
+        plain item
+    ";
+    assert_eq!(
+        parse_items(text),
+        [
+            "apples",
+            "bananas",
+            "2 apples",
+            "1.5 kg flour",
+            "milk",
+            "soap",
+            "plain item"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn import_appends_items_in_order() {
+    let app = TestApp::new().await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let existing = app.create_item(&shopper, None, None, "tea").await;
+
+    let res = app
+        .post("/api/v1/items/text")
+        .user(&shopper)
+        .json(json!({ "text": "- milk\n- [ ] bread\n\n3. eggs" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+    let body: Value = res.json();
+    assert_eq!(body["organized"], false);
+
+    let items = app.all_items().await;
+    let names: Vec<_> = items.iter().map(|i| (i.name.as_str(), i.ord)).collect();
+    assert_eq!(names, [("tea", 1), ("milk", 2), ("bread", 3), ("eggs", 4)]);
+    assert_eq!(items[0].id, existing);
+}
+
+#[tokio::test]
+async fn import_and_organize() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let store = app.create_store(&admin, "Mart").await;
+    let dairy = app.create_section(&admin, store, "dairy").await;
+    let req = json!({ "text": "dairy milk\nsoap", "store_id": store, "organize": true });
+
+    app.post("/api/v1/items/text")
+        .user(&shopper)
+        .json(req.clone())
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+
+    let body: Value = app
+        .post("/api/v1/items/text")
+        .user(&admin)
+        .json(req.clone())
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(body["organized"], true);
+    assert_eq!(body["items"][0]["section_id"], dairy);
+    assert_eq!(body["items"][1]["section_id"], Value::Null);
+
+    // Items are kept when the model fails.
+    app.llm.set(|_, _| {
+        let err = serde_json::from_str::<Value>("").unwrap_err();
+        Err(LlmError::InvalidResponse(err))
+    });
+    let body: Value = app
+        .post("/api/v1/items/text")
+        .user(&admin)
+        .json(req)
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(body["organized"], false);
+    assert_eq!(app.all_items().await.len(), 4);
+}
+
+#[tokio::test]
+async fn import_rejects_invalid_text() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+
+    let res = app
+        .post("/api/v1/items/text")
+        .user(&admin)
+        .json(json!({ "text": "# Only a heading\n- [x] done", "organize": true }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let errors = res.json::<Value>()["errors"].clone();
+    assert_eq!(errors[0]["field"], "text");
+    assert_eq!(errors[1]["field"], "organize");
+
+    app.post("/api/v1/items/text")
+        .user(&admin)
+        .json(json!({ "text": "milk", "section_id": 42 }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "section_not_found");
+}
+
+#[tokio::test]
+async fn export_as_markdown_and_plain_text() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Mart").await;
+    let dairy = app.create_section(&admin, store, "Dairy").await;
+    app.create_section(&admin, store, "Empty").await;
+    app.create_item(&admin, None, None, "soap").await;
+    app.create_item(&admin, Some(store), None, "bread").await;
+    app.create_item(&admin, Some(store), Some(dairy), "milk")
+        .await;
+    app.create_store(&admin, "Other").await;
+
+    let res = app
+        .get("/api/v1/items/text")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.headers["content-type"], "text/markdown; charset=utf-8");
+    let markdown = String::from_utf8(res.body).unwrap();
+    assert_eq!(
+        markdown,
+        "- [ ] soap\n\n## Mart\n- [ ] bread\n\n### Dairy\n- [ ] milk\n"
+    );
+
+    let res = app
+        .get(&format!("/api/v1/items/text?format=plain&store_id={store}"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let plain = String::from_utf8(res.body).unwrap();
+    assert_eq!(plain, "Mart:\n- bread\n\nMart / Dairy:\n- milk\n");
+
+    // Exported text can be pasted back.
+    assert_eq!(parse_items(&markdown), ["soap", "bread", "milk"]);
+    assert_eq!(parse_items(&plain), ["bread", "milk"]);
+}
+
+#[tokio::test]
+async fn exported_names_are_escaped() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "#1 Mart").await;
+    let section = app.create_section(&admin, store, "[x] Deals:").await;
+    let names = ["# 2", "[x] done", "- dash", "1. first", "Note:", "\\back"];
+    for name in names {
+        app.create_item(&admin, Some(store), Some(section), name)
+            .await;
+    }
+
+    let markdown = app
+        .get("/api/v1/items/text")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .body;
+    let markdown = String::from_utf8(markdown).unwrap();
+    assert_eq!(
+        markdown,
+        "## \\#1 Mart\n\n### \\[x] Deals\\:\n- [ ] \\# 2\n- [ ] \\[x] done\n- [ ] \\- dash\n\
+         - [ ] \\1. first\n- [ ] Note\\:\n- [ ] \\\\back\n"
+    );
+
+    let plain = app
+        .get("/api/v1/items/text?format=plain")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .body;
+    let plain = String::from_utf8(plain).unwrap();
+
+    for text in [markdown, plain] {
+        app.post("/api/v1/items/text")
+            .user(&admin)
+            .json(json!({ "text": text }))
+            .send()
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+    let items = app.all_items().await;
+    let imported: Vec<_> = items[names.len()..]
+        .iter()
+        .map(|i| i.name.as_str())
+        .collect();
+    assert_eq!(imported, [names, names].concat());
+}
@@ -0,0 +1,152 @@
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::time::Duration;
+
+use axum_server::Handle;
+
+use crate::config::{AcmeConfig, TlsConfig};
+use crate::tests::TestApp;
+use crate::tls;
+
+/// Writes a new self-signed certificate for localhost, returns it in DER.
+fn write_cert(dir: &Path) -> Vec<u8> {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    std::fs::write(dir.join("cert.pem"), cert.cert.pem()).unwrap();
+    std::fs::write(dir.join("key.pem"), cert.key_pair.serialize_pem()).unwrap();
+    cert.cert.der().to_vec()
+}
+
+fn start(app: &TestApp, conf: TlsConfig) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = app.router.clone();
+    tokio::spawn(async move { tls::serve(listener, router, &conf, Handle::new()).await });
+    addr
+}
+
+/// Returns the certificate the server presents.
+async fn peer_certificate(addr: SocketAddr, roots: &[Vec<u8>]) -> reqwest::Result<Vec<u8>> {
+    let mut client = reqwest::Client::builder()
+        .tls_info(true)
+        .resolve("localhost", addr);
+    for root in roots {
+        client = client.add_root_certificate(reqwest::Certificate::from_der(root).unwrap());
+    }
+
+    let res = client
+        .build()?
+        .get(format!("https://localhost:{}/api/versions", addr.port()))
+        .send()
+        .await?
+        .error_for_status()?;
+    let info = res.extensions().get::<reqwest::tls::TlsInfo>().unwrap();
+    Ok(info.peer_certificate().unwrap().to_vec())
+}
+
+#[tokio::test]
+async fn reloads_certificate_files() {
+    let app = TestApp::new().await;
+    let dir = tempfile::tempdir().unwrap();
+    let first = write_cert(dir.path());
+
+    let addr = start(
+        &app,
+        TlsConfig {
+            cert_path: Some(dir.path().join("cert.pem").to_string_lossy().into()),
+            key_path: Some(dir.path().join("key.pem").to_string_lossy().into()),
+            reload_interval: 1,
+            acme: None,
+        },
+    );
+
+    let mut served = Err(None);
+    for _ in 0..50 {
+        match peer_certificate(addr, std::slice::from_ref(&first)).await {
+            Ok(cert) => {
+                served = Ok(cert);
+                break;
+            }
+            Err(err) => served = Err(Some(err)),
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(served.unwrap(), first);
+
+    let second = write_cert(dir.path());
+    let roots = [first, second.clone()];
+    let mut served = Vec::new();
+    for _ in 0..50 {
+        served = peer_certificate(addr, &roots).await.unwrap();
+        if served == second {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(served, second, "certificate should be reloaded");
+}
+
+#[tokio::test]
+async fn rejects_incomplete_config() {
+    let app = TestApp::new().await;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let conf = TlsConfig {
+        cert_path: Some("cert.pem".to_string()),
+        key_path: None,
+        reload_interval: 1,
+        acme: None,
+    };
+
+    assert!(
+        tls::serve(listener, app.router.clone(), &conf, Handle::new())
+            .await
+            .is_err()
+    );
+}
+
+/// Obtains a certificate from a local Pebble instance, see the readme. Run with
+/// `PEBBLE_CA=<path to pebble.minica.pem> cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "requires a running Pebble instance"]
+async fn obtains_certificate_from_pebble() {
+    let ca_cert_path = std::env::var("PEBBLE_CA").expect("PEBBLE_CA should be set");
+    let app = TestApp::new().await;
+    let cache = tempfile::tempdir().unwrap();
+
+    // Pebble validates tls-alpn-01 challenges on port 5001.
+    let listener = TcpListener::bind("127.0.0.1:5001").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let conf = TlsConfig {
+        cert_path: None,
+        key_path: None,
+        reload_interval: 1,
+        acme: Some(AcmeConfig {
+            domains: vec!["localhost".to_string()],
+            contact: vec![],
+            directory_url: "https://localhost:14000/dir".to_string(),
+            cache_dir: cache.path().to_string_lossy().into(),
+            challenge: Default::default(),
+            http_port: None,
+            ca_cert_path: Some(ca_cert_path),
+        }),
+    };
+    let router = app.router.clone();
+    tokio::spawn(async move { tls::serve(listener, router, &conf, Handle::new()).await });
+
+    for _ in 0..60 {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .resolve("localhost", addr)
+            .build()
+            .unwrap();
+        let res = client
+            .get(format!("https://localhost:{}/api/versions", addr.port()))
+            .send()
+            .await;
+        if res.is_ok_and(|res| res.status().is_success()) {
+            assert!(std::fs::read_dir(cache.path()).unwrap().count() > 0);
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    panic!("certificate wasn't obtained");
+}
@@ -0,0 +1,74 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+#[tokio::test]
+async fn versions_lists_current_and_capabilities() {
+    let app = TestApp::new().await;
+
+    let res = app
+        .get("/api/versions")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let body: Value = res.json();
+
+    assert_eq!(body["current"], "v1");
+    assert_eq!(body["versions"][0]["path"], "/api/v1");
+    assert_eq!(body["versions"][0]["deprecated_at"], Value::Null);
+    assert_eq!(body["versions"][1]["path"], "/api");
+    assert!(body["versions"][1]["sunset_at"].is_string());
+    assert_eq!(
+        body["capabilities"],
+        serde_json::json!(["organize", "share_links", "caldav"])
+    );
+
+    // Organizing needs the language model.
+    app.llm.set_configured(false);
+    let body: Value = app.get("/api/versions").send().await.json();
+    assert_eq!(
+        body["capabilities"],
+        serde_json::json!(["share_links", "caldav"])
+    );
+}
+
+#[tokio::test]
+async fn current_version_is_not_deprecated() {
+    let app = TestApp::new().await;
+    let user = app.login("ana", Role::Member).await;
+
+    let res = app
+        .get("/api/v1/items")
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert!(res.headers.get("deprecation").is_none());
+    assert!(res.headers.get("sunset").is_none());
+}
+
+#[tokio::test]
+async fn legacy_routes_are_deprecated() {
+    let app = TestApp::new().await;
+    let user = app.login("ana", Role::Member).await;
+
+    let res = app
+        .get("/api/items")
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.headers["deprecation"], "@1792368000");
+    assert_eq!(res.headers["sunset"], "Mon, 19 Apr 2027 00:00:00 GMT");
+    assert_eq!(res.headers["link"], "</api/v1>; rel=\"successor-version\"");
+
+    // Errors are marked too, old clients should see the headers on every response.
+    let res = app
+        .get("/api/items")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+    assert!(res.headers.get("deprecation").is_some());
+}
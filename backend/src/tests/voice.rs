@@ -0,0 +1,170 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::handler::voice::parse_command;
+use crate::llm::{LlmError, ParsedCommand};
+use crate::store::user::Role;
+use crate::tests::TestApp;
+
+fn stores() -> Vec<String> {
+    vec!["Mercator".to_string(), "Spar".to_string()]
+}
+
+#[test]
+fn parse_splits_items_and_store() {
+    let command = parse_command("Add milk and two loaves of bread to Mercator.", &stores());
+    assert_eq!(command.items, ["milk", "2 loaves of bread"]);
+    assert_eq!(command.store.as_deref(), Some("Mercator"));
+
+    let command = parse_command(
+        "please put eggs, a bottle of wine & soap on the Spar list",
+        &stores(),
+    );
+    assert_eq!(command.items, ["eggs", "bottle of wine", "soap"]);
+    assert_eq!(command.store.as_deref(), Some("Spar"));
+
+    let command = parse_command("we need tomatoes on my shopping list please", &stores());
+    assert_eq!(command.items, ["tomatoes"]);
+    assert_eq!(command.store, None);
+
+    // Only known stores are split off after "for".
+    let command = parse_command("add food for the cat", &stores());
+    assert_eq!(command.items, ["food for the cat"]);
+    assert_eq!(command.store, None);
+
+    let command = parse_command("add bread to Lidl", &stores());
+    assert_eq!(command.items, ["bread"]);
+    assert_eq!(command.store.as_deref(), Some("Lidl"));
+}
+
+#[tokio::test]
+async fn add_with_rules() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    let store = app.create_store(&admin, "Mercator").await;
+    app.llm.set_configured(false);
+
+    let body: Value = app
+        .post("/api/v1/items/voice")
+        .user(&shopper)
+        .json(json!({ "text": "add milk and two loaves of bread to mercator" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(
+        body["speech"],
+        "Added milk and 2 loaves of bread to Mercator."
+    );
+    assert_eq!(body["store_id"], store);
+    assert_eq!(body["items"].as_array().unwrap().len(), 2);
+
+    let items = app.all_items().await;
+    let names: Vec<_> = items
+        .iter()
+        .map(|i| (i.name.as_str(), i.store_id))
+        .collect();
+    assert_eq!(
+        names,
+        [("milk", Some(store)), ("2 loaves of bread", Some(store))]
+    );
+
+    let body: Value = app
+        .post("/api/v1/items/voice")
+        .user(&shopper)
+        .json(json!({ "text": "Add soap to the list." }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(body["speech"], "Added soap to the list.");
+    assert_eq!(body["store_id"], Value::Null);
+}
+
+#[tokio::test]
+async fn add_to_unknown_store() {
+    let app = TestApp::new().await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    app.llm.set_configured(false);
+
+    let body: Value = app
+        .post("/api/v1/items/voice")
+        .user(&shopper)
+        .json(json!({ "text": "add eggs to Lidl" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(
+        body["speech"],
+        "Added eggs to the list. There's no store called Lidl."
+    );
+    assert_eq!(body["store_id"], Value::Null);
+    assert_eq!(app.all_items().await[0].store_id, None);
+}
+
+#[tokio::test]
+async fn add_with_llm() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let store = app.create_store(&admin, "Interspar").await;
+    app.llm.set_parse(|text, stores| {
+        assert_eq!(text, "grab six eggs and some butter at spar");
+        assert_eq!(stores, ["Interspar"]);
+        Ok(ParsedCommand {
+            items: vec!["6 eggs".to_string(), "butter".to_string()],
+            store: Some("spar".to_string()),
+            usage: None,
+        })
+    });
+
+    let body: Value = app
+        .post("/api/v1/items/voice")
+        .user(&admin)
+        .json(json!({ "text": "  grab six eggs and some butter at spar " }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(body["speech"], "Added 6 eggs and butter to Interspar.");
+    assert_eq!(body["store_id"], store);
+
+    // Rules are used when the model fails.
+    app.llm.set_parse(|_, _| {
+        let err = serde_json::from_str::<Value>("").unwrap_err();
+        Err(LlmError::InvalidResponse(err))
+    });
+    let body: Value = app
+        .post("/api/v1/items/voice")
+        .user(&admin)
+        .json(json!({ "text": "add tea" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    assert_eq!(body["speech"], "Added tea to the list.");
+}
+
+#[tokio::test]
+async fn add_without_items() {
+    let app = TestApp::new().await;
+    let shopper = app.login("shopper", Role::Shopper).await;
+    app.llm.set_configured(false);
+
+    app.post("/api/v1/items/voice")
+        .user(&shopper)
+        .json(json!({ "text": "add to the list" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    app.post("/api/v1/items/voice")
+        .user(&shopper)
+        .json(json!({ "text": "   " }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    assert!(app.all_items().await.is_empty());
+}
@@ -0,0 +1,121 @@
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+use crate::config::WebauthnConfig;
+use crate::store::user::Role;
+use crate::tests::{TestApp, test_config};
+
+async fn webauthn_app() -> TestApp {
+    let mut config = test_config();
+    config.webauthn = Some(WebauthnConfig {
+        rp_id: "localhost".to_string(),
+        rp_origin: "http://localhost:8000".to_string(),
+        rp_name: "L Shop".to_string(),
+    });
+    TestApp::with_config(config).await
+}
+
+fn fake_registration(challenge_id: &str) -> Value {
+    json!({
+        "challenge_id": challenge_id,
+        "name": "Laptop",
+        "credential": {
+            "id": "AAAA",
+            "rawId": "AAAA",
+            "response": {
+                "attestationObject": "AAAA",
+                "clientDataJSON": "AAAA",
+            },
+            "type": "public-key",
+            "extensions": {},
+        },
+    })
+}
+
+#[tokio::test]
+async fn disabled_without_config() {
+    let app = TestApp::new().await;
+    let user = app.login("alice", Role::Member).await;
+
+    app.post("/api/v1/auth/webauthn/register/start")
+        .user(&user)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    app.post("/api/v1/auth/webauthn/login/start")
+        .json(json!({ "username": "alice" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn registration_challenge_is_single_use() {
+    let app = webauthn_app().await;
+    let user = app.login("alice", Role::Member).await;
+
+    let res = app
+        .post("/api/v1/auth/webauthn/register/start")
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let start: Value = res.json();
+    let challenge_id = start["challenge_id"].as_str().unwrap();
+    assert_eq!(start["options"]["publicKey"]["rp"]["id"], "localhost");
+
+    // Invalid attestation consumes the challenge.
+    app.post("/api/v1/auth/webauthn/register/finish")
+        .user(&user)
+        .json(fake_registration(challenge_id))
+        .send()
+        .await
+        .assert_problem(StatusCode::BAD_REQUEST, "bad_request");
+    app.post("/api/v1/auth/webauthn/register/finish")
+        .user(&user)
+        .json(fake_registration(challenge_id))
+        .send()
+        .await
+        .assert_problem(StatusCode::BAD_REQUEST, "invalid_challenge");
+}
+
+#[tokio::test]
+async fn login_requires_registered_passkey() {
+    let app = webauthn_app().await;
+    app.create_user("alice", Role::Member).await;
+
+    app.post("/api/v1/auth/webauthn/login/start")
+        .json(json!({ "username": "alice" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "invalid_credentials");
+    app.post("/api/v1/auth/webauthn/login/start")
+        .json(json!({ "username": "bob" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNAUTHORIZED, "invalid_credentials");
+}
+
+#[tokio::test]
+async fn list_and_delete_credentials() {
+    let app = webauthn_app().await;
+    let user = app.login("alice", Role::Member).await;
+
+    let res = app
+        .get("/api/v1/auth/webauthn/credentials")
+        .user(&user)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(res.json::<Vec<Value>>().len(), 0);
+
+    app.delete("/api/v1/auth/webauthn/credentials/42")
+        .user(&user)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+    app.get("/api/v1/auth/webauthn/credentials")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,456 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use axum::Router;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+
+use crate::store::user::Role;
+use crate::tests::{TestApp, TestUser};
+use crate::webhook::{self, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+const SECRET: &str = "0123456789abcdef";
+
+/// Receiver that records the requests and answers with the queued status codes,
+/// 204 once the queue is empty.
+struct Receiver {
+    url: String,
+    requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    statuses: Arc<Mutex<VecDeque<StatusCode>>>,
+}
+
+impl Receiver {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(VecDeque::new()));
+
+        let (recorded, queued) = (requests.clone(), statuses.clone());
+        let router = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| async move {
+                recorded.lock().unwrap().push((headers, body));
+                queued
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .unwrap_or(StatusCode::NO_CONTENT)
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self {
+            url,
+            requests,
+            statuses,
+        }
+    }
+}
+
+async fn create_webhook(app: &TestApp, admin: &TestUser, body: Value) -> i64 {
+    let res: Value = app
+        .post("/api/v1/webhooks")
+        .user(admin)
+        .json(body)
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    res["id"].as_i64().unwrap()
+}
+
+async fn deliveries(app: &TestApp, admin: &TestUser, id: i64) -> Vec<Value> {
+    app.get(&format!("/api/v1/webhooks/{id}/deliveries"))
+        .user(admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json()
+}
+
+fn events(deliveries: &[Value]) -> Vec<&str> {
+    // Log is newest first.
+    deliveries
+        .iter()
+        .rev()
+        .map(|d| d["event"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn manage_webhooks() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let member = app.login("member", Role::Member).await;
+
+    let res: Value = app
+        .post("/api/v1/webhooks")
+        .user(&admin)
+        .json(json!({
+            "url": " https://example.com/hook ",
+            "events": ["item.created", "item.created", "store.completed"],
+            "secret": SECRET,
+        }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    let id = res["id"].as_i64().unwrap();
+    assert_eq!(res["url"], "https://example.com/hook");
+    assert_eq!(res["events"], json!(["item.created", "store.completed"]));
+    assert_eq!(res["enabled"], true);
+    assert!(res.get("secret").is_none());
+
+    app.post("/api/v1/webhooks")
+        .user(&admin)
+        .json(json!({ "url": "ftp://example.com", "secret": "short" }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    app.post("/api/v1/webhooks")
+        .user(&admin)
+        .json(json!({ "url": "https://example.com", "events": ["item.eaten"], "secret": SECRET }))
+        .send()
+        .await
+        .assert_problem(StatusCode::UNPROCESSABLE_ENTITY, "malformed_body");
+    app.get("/api/v1/webhooks")
+        .user(&member)
+        .send()
+        .await
+        .assert_problem(StatusCode::FORBIDDEN, "forbidden");
+
+    // Secret is kept when it's not sent.
+    let res: Value = app
+        .put(&format!("/api/v1/webhooks/{id}"))
+        .user(&admin)
+        .json(json!({ "url": "http://localhost:8123/api/webhook/shop", "enabled": false }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(res["events"], json!([]));
+    assert_eq!(res["enabled"], false);
+    let secret: String = sqlx::query_scalar("SELECT secret FROM webhooks WHERE id = ?")
+        .bind(id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+    assert_eq!(secret, SECRET);
+
+    let list: Value = app
+        .get("/api/v1/webhooks")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+
+    app.delete(&format!("/api/v1/webhooks/{id}"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    app.get(&format!("/api/v1/webhooks/{id}/deliveries"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_problem(StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn mutations_queue_events() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let all = create_webhook(
+        &app,
+        &admin,
+        json!({ "url": "https://example.com/all", "secret": SECRET }),
+    )
+    .await;
+    let trips = create_webhook(
+        &app,
+        &admin,
+        json!({
+            "url": "https://example.com/trips",
+            "events": ["item.checked", "store.completed"],
+            "secret": SECRET,
+        }),
+    )
+    .await;
+    let disabled = create_webhook(
+        &app,
+        &admin,
+        json!({ "url": "https://example.com/off", "secret": SECRET, "enabled": false }),
+    )
+    .await;
+
+    let store = app.create_store(&admin, "Mart").await;
+    let milk = app.create_item(&admin, Some(store), None, "milk").await;
+    let eggs = app.create_item(&admin, Some(store), None, "eggs").await;
+    let soap = app.create_item(&admin, None, None, "soap").await;
+    app.put(&format!("/api/v1/items/{milk}/rename"))
+        .user(&admin)
+        .json(json!({ "name": "oat milk" }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    for id in [milk, eggs, soap] {
+        app.put(&format!("/api/v1/items/{id}/checked"))
+            .user(&admin)
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+    }
+    app.delete(&format!("/api/v1/stores/{store}"))
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let log = deliveries(&app, &admin, all).await;
+    assert_eq!(
+        events(&log),
+        [
+            "store.created",
+            "item.created",
+            "item.created",
+            "item.created",
+            "item.updated",
+            "item.checked",
+            "item.checked",
+            "store.completed",
+            "item.checked",
+            "store.deleted",
+        ]
+    );
+    assert!(log.iter().all(|d| d["status"] == "pending"));
+
+    let log = deliveries(&app, &admin, trips).await;
+    assert_eq!(
+        events(&log),
+        [
+            "item.checked",
+            "item.checked",
+            "store.completed",
+            "item.checked"
+        ]
+    );
+    let payload: Value = serde_json::from_str(log[1]["payload"].as_str().unwrap()).unwrap();
+    assert_eq!(payload["event"], "store.completed");
+    assert_eq!(payload["data"]["id"], store);
+    assert_eq!(payload["data"]["name"], "Mart");
+    let payload: Value = serde_json::from_str(log[3]["payload"].as_str().unwrap()).unwrap();
+    assert_eq!(payload["data"]["name"], "oat milk");
+    assert_eq!(payload["data"]["checked"], true);
+
+    assert!(deliveries(&app, &admin, disabled).await.is_empty());
+}
+
+#[tokio::test]
+async fn deliveries_are_signed_and_retried() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let receiver = Receiver::start().await;
+    let id = create_webhook(
+        &app,
+        &admin,
+        json!({ "url": receiver.url, "events": ["store.created"], "secret": SECRET }),
+    )
+    .await;
+    app.create_store(&admin, "Mart").await;
+
+    let client = webhook::client();
+    let now = OffsetDateTime::now_utc();
+    receiver
+        .statuses
+        .lock()
+        .unwrap()
+        .push_back(StatusCode::INTERNAL_SERVER_ERROR);
+
+    // First attempt fails and is retried after the backoff.
+    let sent = webhook::deliver_due(&app.state.db, &client, now)
+        .await
+        .unwrap();
+    assert_eq!(sent, 1);
+    let log = deliveries(&app, &admin, id).await;
+    assert_eq!(log[0]["status"], "pending");
+    assert_eq!(log[0]["attempts"], 1);
+    assert_eq!(log[0]["last_status_code"], 500);
+    let sent = webhook::deliver_due(&app.state.db, &client, now + time::Duration::seconds(29))
+        .await
+        .unwrap();
+    assert_eq!(sent, 0);
+
+    let later = now + time::Duration::seconds(30);
+    let sent = webhook::deliver_due(&app.state.db, &client, later)
+        .await
+        .unwrap();
+    assert_eq!(sent, 1);
+    let log = deliveries(&app, &admin, id).await;
+    assert_eq!(log[0]["status"], "delivered");
+    assert_eq!(log[0]["attempts"], 2);
+    assert_eq!(log[0]["last_status_code"], 204);
+    assert!(log[0]["last_error"].is_null());
+
+    let requests = receiver.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    let (headers, body) = &requests[1];
+    assert_eq!(headers["x-lshop-event"], "store.created");
+    assert_eq!(headers["content-type"], "application/json");
+    let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+    assert_eq!(timestamp, later.unix_timestamp().to_string());
+    assert_eq!(
+        headers[SIGNATURE_HEADER].to_str().unwrap(),
+        webhook::sign(SECRET, timestamp, body)
+    );
+    let body: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["data"]["name"], "Mart");
+}
+
+#[tokio::test]
+async fn imports_queue_created_events() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let id = create_webhook(
+        &app,
+        &admin,
+        json!({ "url": "https://example.com/all", "secret": SECRET }),
+    )
+    .await;
+
+    let store: Value = app
+        .post("/api/v1/stores/import")
+        .user(&admin)
+        .body(
+            "application/toml",
+            "version = 1\nname = \"Mart\"\nsections = [\"Dairy\"]\n",
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    app.create_item(&admin, store["id"].as_i64(), None, "milk")
+        .await;
+
+    // Store of the dataset is new, its items are created with it.
+    let mut dataset: Value = app
+        .get("/api/v1/export")
+        .user(&admin)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    dataset["stores"][0]["name"] = json!("Spar");
+    app.post("/api/v1/import?dry_run=true")
+        .user(&admin)
+        .json(dataset.clone())
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(deliveries(&app, &admin, id).await.len(), 2);
+    app.post("/api/v1/import")
+        .user(&admin)
+        .json(dataset)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    let log = deliveries(&app, &admin, id).await;
+    assert_eq!(
+        events(&log),
+        [
+            "store.created",
+            "item.created",
+            "store.created",
+            "item.created"
+        ]
+    );
+    let payload: Value = serde_json::from_str(log[1]["payload"].as_str().unwrap()).unwrap();
+    assert_eq!(payload["data"]["name"], "Spar");
+}
+
+#[tokio::test]
+async fn deliveries_are_given_up() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    // Nothing listens on the port.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    drop(listener);
+    let id = create_webhook(&app, &admin, json!({ "url": url, "secret": SECRET })).await;
+    app.create_store(&admin, "Mart").await;
+
+    sqlx::query("UPDATE webhook_deliveries SET attempts = 7")
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+    let client = webhook::client();
+    webhook::deliver_due(&app.state.db, &client, OffsetDateTime::now_utc())
+        .await
+        .unwrap();
+
+    let log = deliveries(&app, &admin, id).await;
+    assert_eq!(log[0]["status"], "failed");
+    assert_eq!(log[0]["attempts"], 8);
+    assert!(log[0]["last_status_code"].is_null());
+    assert!(log[0]["last_error"].is_string());
+}
+
+#[tokio::test]
+async fn disabling_gives_up_pending_deliveries() {
+    let app = TestApp::new().await;
+    let admin = app.login("admin", Role::Admin).await;
+    let receiver = Receiver::start().await;
+    receiver
+        .statuses
+        .lock()
+        .unwrap()
+        .push_back(StatusCode::INTERNAL_SERVER_ERROR);
+    let id = create_webhook(
+        &app,
+        &admin,
+        json!({ "url": receiver.url, "secret": SECRET }),
+    )
+    .await;
+    app.create_store(&admin, "Mart").await;
+
+    let client = webhook::client();
+    webhook::deliver_due(&app.state.db, &client, OffsetDateTime::now_utc())
+        .await
+        .unwrap();
+    assert_eq!(deliveries(&app, &admin, id).await[0]["status"], "pending");
+
+    app.put(&format!("/api/v1/webhooks/{id}"))
+        .user(&admin)
+        .json(json!({ "url": receiver.url, "enabled": false }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let log = deliveries(&app, &admin, id).await;
+    assert_eq!(log[0]["status"], "failed");
+    assert_eq!(log[0]["last_error"], "webhook was disabled");
+
+    // Deliveries that are still pending aren't sent while the webhook is disabled.
+    sqlx::query("UPDATE webhook_deliveries SET status = 'pending'")
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+    let later = OffsetDateTime::now_utc() + time::Duration::hours(1);
+    let sent = webhook::deliver_due(&app.state.db, &client, later)
+        .await
+        .unwrap();
+    assert_eq!(sent, 0);
+    assert_eq!(receiver.requests.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn backoff_doubles_up_to_the_limit() {
+    assert_eq!(webhook::backoff(1), time::Duration::seconds(30));
+    assert_eq!(webhook::backoff(2), time::Duration::minutes(1));
+    assert_eq!(webhook::backoff(5), time::Duration::minutes(8));
+    assert_eq!(webhook::backoff(20), time::Duration::hours(6));
+}
@@ -0,0 +1,212 @@
+//! TLS termination, with certificates either read from files or obtained with ACME.
+
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use axum::Router;
+use axum::extract::Request;
+use axum::http::{StatusCode, Uri, header};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_server::Handle;
+use axum_server::tls_rustls::RustlsConfig;
+use futures_util::StreamExt;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+use rustls_acme::caches::DirCache;
+use rustls_acme::{AcmeState, UseChallenge};
+
+use crate::config::{AcmeChallenge, AcmeConfig, TlsConfig};
+
+const DEFAULT_HTTP_PORT: u16 = 80;
+
+/// Serves the app over https on the listener, until shut down through the handle.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    conf: &TlsConfig,
+    handle: Handle,
+) -> anyhow::Result<()> {
+    // Explicitly, so that it doesn't depend on crypto providers enabled by other dependencies.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    listener.set_nonblocking(true)?;
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    match (&conf.cert_path, &conf.key_path, &conf.acme) {
+        (Some(cert_path), Some(key_path), None) => {
+            let config = RustlsConfig::from_pem_file(cert_path, key_path)
+                .await
+                .context("failed to load tls certificate")?;
+            tokio::spawn(watch_files(
+                config.clone(),
+                cert_path.into(),
+                key_path.into(),
+                Duration::from_secs(conf.reload_interval),
+            ));
+
+            axum_server::from_tcp_rustls(listener, config)
+                .handle(handle)
+                .serve(make_service)
+                .await?;
+        }
+        (None, None, Some(acme)) => {
+            let mut state = acme_state(acme)?;
+            let acceptor = state.axum_acceptor(state.default_rustls_config());
+
+            // Plain http is needed for http-01 challenges, otherwise it only redirects.
+            let mut http = Router::new();
+            if acme.challenge == AcmeChallenge::Http01 {
+                http = http.route_service(
+                    "/.well-known/acme-challenge/{token}",
+                    state.http01_challenge_tower_service(),
+                );
+            }
+            let http = http.fallback(redirect_to_https);
+            let http_listener = match http_port(acme) {
+                Some(port) => {
+                    let http_listener = TcpListener::bind((listener.local_addr()?.ip(), port))
+                        .with_context(|| format!("failed to bind http port {port}"))?;
+                    http_listener.set_nonblocking(true)?;
+                    Some(http_listener)
+                }
+                None => None,
+            };
+
+            tokio::spawn(async move {
+                while let Some(event) = state.next().await {
+                    match event {
+                        Ok(ok) => tracing::info!("acme event: {ok:?}"),
+                        Err(err) => tracing::error!(error = err.to_string(), "acme error: {err}"),
+                    }
+                }
+            });
+
+            let https = axum_server::from_tcp(listener)
+                .acceptor(acceptor)
+                .handle(handle.clone())
+                .serve(make_service);
+            match http_listener {
+                Some(http_listener) => {
+                    tokio::try_join!(
+                        https,
+                        axum_server::from_tcp(http_listener)
+                            .handle(handle)
+                            .serve(http.into_make_service()),
+                    )?;
+                }
+                None => https.await?,
+            }
+        }
+        _ => anyhow::bail!("tls requires either cert_path and key_path, or acme to be set"),
+    }
+
+    Ok(())
+}
+
+/// Port of the plain http listener, `http_01` challenges always need one.
+fn http_port(conf: &AcmeConfig) -> Option<u16> {
+    match conf.challenge {
+        AcmeChallenge::Http01 => Some(conf.http_port.unwrap_or(DEFAULT_HTTP_PORT)),
+        AcmeChallenge::TlsAlpn01 => conf.http_port,
+    }
+}
+
+fn acme_state(conf: &AcmeConfig) -> anyhow::Result<AcmeState<std::io::Error>> {
+    if conf.domains.is_empty() {
+        anyhow::bail!("acme requires at least one domain");
+    }
+
+    let challenge = match conf.challenge {
+        AcmeChallenge::TlsAlpn01 => UseChallenge::TlsAlpn01,
+        AcmeChallenge::Http01 => UseChallenge::Http01,
+    };
+
+    let mut acme = rustls_acme::AcmeConfig::new(&conf.domains)
+        .contact(&conf.contact)
+        .directory(&conf.directory_url)
+        .challenge_type(challenge)
+        .cache(DirCache::new(PathBuf::from(&conf.cache_dir)));
+
+    if let Some(ca_cert_path) = &conf.ca_cert_path {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(ca_cert_path)
+            .context("failed to read acme ca certificate")?
+        {
+            roots.add(cert?)?;
+        }
+        let client_config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        acme = acme.client_tls_config(Arc::new(client_config));
+    }
+
+    Ok(acme.state())
+}
+
+/// Reloads the certificate when the files change, ie. after a renewal by certbot.
+/// On error the current certificate is kept.
+async fn watch_files(
+    config: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    interval: Duration,
+) {
+    let mut last_modified = modified(&cert_path, &key_path);
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let modified = modified(&cert_path, &key_path);
+        if modified == last_modified {
+            continue;
+        }
+
+        // Files can be caught in the middle of an update, the reload is retried until it succeeds.
+        match config.reload_from_pem_file(&cert_path, &key_path).await {
+            Ok(()) => {
+                last_modified = modified;
+                tracing::info!("tls certificate reloaded");
+            }
+            Err(err) => tracing::error!(
+                error = err.to_string(),
+                "failed to reload tls certificate: {err}"
+            ),
+        }
+    }
+}
+
+fn modified(cert_path: &Path, key_path: &Path) -> [Option<SystemTime>; 2] {
+    [cert_path, key_path].map(|path| path.metadata().and_then(|m| m.modified()).ok())
+}
+
+async fn redirect_to_https(req: Request) -> Response {
+    let Some(host) = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    // Drop the http port, https is served on the default one.
+    let host = host.split(':').next().unwrap_or(host);
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+
+    match Uri::builder()
+        .scheme("https")
+        .authority(host)
+        .path_and_query(path)
+        .build()
+    {
+        Ok(uri) => Redirect::permanent(&uri.to_string()).into_response(),
+        Err(_) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
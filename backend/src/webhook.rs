@@ -0,0 +1,146 @@
+//! Delivery of the webhook events queued by the store. Deliveries are persisted, so
+//! the ones that failed or weren't sent before a restart are retried.
+
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use time::OffsetDateTime;
+
+use crate::db::Db;
+use crate::shutdown::Shutdown;
+use crate::store::webhook::{self, Delivery, MAX_ATTEMPTS, Webhook};
+use crate::util::to_hex;
+
+pub const EVENT_HEADER: &str = "x-lshop-event";
+pub const DELIVERY_HEADER: &str = "x-lshop-delivery";
+pub const TIMESTAMP_HEADER: &str = "x-lshop-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-lshop-signature";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 20;
+/// Delay before the first retry, it doubles with each attempt.
+const FIRST_RETRY: time::Duration = time::Duration::seconds(30);
+const MAX_RETRY: time::Duration = time::Duration::hours(6);
+/// Finished deliveries are kept in the log this long.
+const RETENTION: time::Duration = time::Duration::days(30);
+const MAX_ERROR_LEN: usize = 500;
+
+pub fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        // Redirect would be followed without the receiver checking the signature.
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(concat!("lshop-webhooks/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("http client should build")
+}
+
+/// Delivers due events until shutdown.
+pub async fn run(db: Db, shutdown: Shutdown) {
+    let client = client();
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = shutdown.clone().requested() => return,
+        }
+
+        let now = OffsetDateTime::now_utc();
+        if let Err(err) = deliver_due(&db, &client, now).await {
+            tracing::error!(error = err.to_string(), "webhook delivery failed: {err}");
+        }
+        if let Err(err) = webhook::prune_deliveries(&db, now - RETENTION).await {
+            tracing::error!(
+                error = err.to_string(),
+                "pruning webhook deliveries failed: {err}"
+            );
+        }
+    }
+}
+
+/// Sends the deliveries that are due at `now`. Returns the number of attempts.
+pub async fn deliver_due(
+    db: &Db,
+    client: &reqwest::Client,
+    now: OffsetDateTime,
+) -> Result<usize, sqlx::Error> {
+    let due = webhook::due_deliveries(db, now, BATCH_SIZE).await?;
+    for (delivery, hook) in &due {
+        match send(client, hook, delivery, now).await {
+            Ok(status) => {
+                webhook::mark_delivered(db, delivery.id, status, OffsetDateTime::now_utc()).await?
+            }
+            Err((status, error)) => {
+                let attempts = delivery.attempts + 1;
+                let retry_at = (attempts < MAX_ATTEMPTS).then(|| now + backoff(attempts));
+                tracing::warn!(
+                    webhook_id = hook.id,
+                    delivery_id = delivery.id,
+                    attempts,
+                    "webhook delivery failed: {error}"
+                );
+                webhook::mark_failed(db, delivery.id, status, &error, retry_at).await?;
+            }
+        }
+    }
+    Ok(due.len())
+}
+
+/// Posts the payload. Returns the status code, or the status code and the error if
+/// the receiver didn't accept it.
+async fn send(
+    client: &reqwest::Client,
+    hook: &Webhook,
+    delivery: &Delivery,
+    now: OffsetDateTime,
+) -> Result<i64, (Option<i64>, String)> {
+    let timestamp = now.unix_timestamp().to_string();
+    let signature = sign(&hook.secret, &timestamp, &delivery.payload);
+
+    let res = client
+        .post(&hook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, &timestamp)
+        .header(SIGNATURE_HEADER, signature)
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|err| (None, truncate(&err.to_string())))?;
+
+    let status = i64::from(res.status().as_u16());
+    if res.status().is_success() {
+        Ok(status)
+    } else {
+        Err((Some(status), format!("unexpected status {}", res.status())))
+    }
+}
+
+/// Signature of the request, `sha256=` followed by the hex HMAC-SHA256 of
+/// `<timestamp>.<body>` keyed with the webhook's secret.
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", to_hex(&mac.finalize().into_bytes()))
+}
+
+/// Delay after the failed attempt, doubling from 30 seconds up to 6 hours.
+pub fn backoff(attempts: i64) -> time::Duration {
+    let exp = attempts.clamp(1, 16) - 1;
+    (FIRST_RETRY * (1i32 << exp)).min(MAX_RETRY)
+}
+
+fn truncate(error: &str) -> String {
+    match error.char_indices().nth(MAX_ERROR_LEN) {
+        Some((idx, _)) => format!("{}…", &error[..idx]),
+        None => error.to_string(),
+    }
+}
@@ -1,8 +1,19 @@
+export type Problem = {
+  type: string;
+  title: string;
+  status: number;
+  detail: string;
+  code: string;
+  request_id?: string;
+  errors?: { field: string; code: string; message: string }[];
+};
+
 export class ApiError extends Error {
   constructor(
     message: string,
     public status: number,
-    public response?: Response
+    public response?: Response,
+    public problem?: Problem
   ) {
     super(message);
     this.name = "ApiError";
@@ -20,7 +31,7 @@ export async function apiFetch<T = unknown>(
   input: RequestInfo | URL,
   init?: RequestInit
 ): Promise<T | null> {
-  const url = typeof input === "string" ? `/api${input}` : input;
+  const url = typeof input === "string" ? `/api/v1${input}` : input;
   const response = await fetch(url, { ...init, credentials: "include" });
 
   if (!response.ok) {
@@ -28,8 +39,18 @@ export async function apiFetch<T = unknown>(
       throw new UnauthorizedError("Unauthorized", response);
     }
 
-    const message = `HTTP ${response.status}: ${response.statusText}`;
-    throw new ApiError(message, response.status, response);
+    let problem: Problem | undefined;
+    if (
+      response.headers
+        .get("content-type")
+        ?.startsWith("application/problem+json")
+    ) {
+      problem = await response.json();
+    }
+
+    const message =
+      problem?.detail ?? `HTTP ${response.status}: ${response.statusText}`;
+    throw new ApiError(message, response.status, response, problem);
   }
 
   if (response.headers.get("content-type")?.startsWith("application/json")) {